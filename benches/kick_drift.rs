@@ -0,0 +1,56 @@
+//! Compares the fused kernel against split kick/drift kernels.
+//! Run with `cargo bench --bench kick_drift`.
+use std::time::Instant;
+
+use parabody::{
+    pipeline::{PassGraph, Pipeline},
+    structures::{Body, StaticConfig},
+};
+use wgpu::PowerPreference;
+
+const STEPS: usize = 100;
+
+async fn bench(name: &str, pass_graph: PassGraph, num_bodies: usize) {
+    let mut pipeline = Pipeline::create(
+        include_str!("../shaders/dynamics.wgsl"),
+        pass_graph,
+        StaticConfig {
+            max_bodies: num_bodies as u32,
+            ..Default::default()
+        },
+        PowerPreference::HighPerformance,
+    )
+    .await
+    .expect("Could not create the pipeline");
+    pipeline.set_dt(0.001);
+
+    let input: Vec<Body> = (0..num_bodies)
+        .map(|i| Body {
+            position: [i as f32, (i % 7) as f32, (i % 13) as f32],
+            mu: 1.0,
+            ..Default::default()
+        })
+        .collect();
+    pipeline.write_bodies(&input).expect("Sized for the bodies");
+
+    // Warm up so that pipeline creation and first-use costs aren't measured
+    pipeline.submit_and_block(1);
+    let start = Instant::now();
+    pipeline.submit_and_block(STEPS);
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "{:>6} n={:>6}: {:>10.1} steps/s",
+        name,
+        num_bodies,
+        STEPS as f64 / elapsed
+    );
+}
+
+fn main() {
+    pollster::block_on(async {
+        for num_bodies in [256, 1024, 4096] {
+            bench("fused", PassGraph::fused(), num_bodies).await;
+            bench("split", PassGraph::split(), num_bodies).await;
+        }
+    });
+}
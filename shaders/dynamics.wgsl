@@ -1,7 +1,27 @@
+{#- Index of the thread along the dispatch, returning from threads past `bound`.
+    Dispatches are rounded up to whole workgroups, so every kernel indexing a
+    buffer by thread must start with this. -#}
+{%- macro thread_index(name, bound) -%}
+let {{name}} = gid[0];
+    if !({{name}} < {{bound}}) { return; }
+{%- endmacro thread_index %}
+
 struct Config {
     num_bodies: u32,
     dt: f32,
-    _pad: vec2<u32>,
+    luminous_body: u32,
+    radiation_pressure: f32,
+    external_params: array<vec4<f32>, 2>,
+    force_params: vec4<f32>,
+    species: array<vec4<f32>, 8>, // Must match MAX_SPECIES
+    hydro_params: vec4<f32>, // Smoothing length, adiabatic index, alpha, beta
+    box_size: f32, // Periodic box edge length, zero for open boundaries
+    pm_mass_scale: f32, // Fixed-point scale of the particle-mesh deposit
+    cell_size: f32, // Neighbor grid cell edge length
+    softening: f32, // Plummer softening length of the direct-sum gravity
+    post_newtonian_bodies: vec4<u32>, // Must match MAX_POST_NEWTONIAN_BODIES
+    post_newtonian_params: vec4<f32>, // Speed of light, radiation reaction
+    num_systems: u32, // Systems of the ensemble, zero for one system
 }
 
 struct Body {
@@ -11,27 +31,1092 @@ struct Body {
     mu: f32, // Size: 4, Align: 4, Upto: 36
 }
 
+struct BodyProperties {
+    zonal: vec3<f32>, // J2, J3, J4
+    radius: f32,
+    area_to_mass: f32,
+    charge: f32,
+    species: u32,
+    gas: u32,
+}
+
+struct GasState {
+    internal_energy: f32,
+    density: f32,
+    pressure: f32,
+    heating_rate: f32,
+}
+
+struct EphemerisState {
+    bodies: array<u32, 32>, // Must match MAX_EPHEMERIS_BODIES
+    num_bodies: u32,
+    num_samples: u32,
+    interval: f32,
+    sample_index: i32, // Sample at or before the current time
+    fraction: f32, // Position between that sample and the next
+}
+
+struct ThrustState {
+    acceleration: vec3<f32>, // Constant, or along the velocity in x
+    kind: u32, // Must match the THRUST_* kinds
+    first: u32,
+    num_samples: u32,
+    sample_index: i32,
+    fraction: f32,
+}
+
+struct System {
+    first: u32,
+    count: u32,
+    softening: f32,
+}
+
+struct Tangent {
+    position: vec3<f32>,
+    log_growth: f32, // Logarithms of the norms taken out by renormalizing
+    velocity: vec3<f32>,
+    weighted_growth: f32, // Integral of the growth rate times the time
+    megno_integral: f32,
+    time: f32,
+}
+{%- if events %}
+
+struct EventRecord {
+    step: u32,
+    body: u32,
+    predicate: u32,
+    before: f32,
+    after: f32,
+}
+
+struct EventLog {
+    // Records appended, counting those past the capacity
+    count: atomic<u32>,
+    steps: u32,
+    records: array<EventRecord, 4096>, // Must match MAX_EVENT_RECORDS
+}
+{%- endif %}
+
 @group(0) @binding(0) var<uniform> config: Config;
+{%- if push_constants %}
+
+// The body count and the bits of the step size, pushed with every dispatch over the
+// bodies so that they change between passes without remapping the config. One
+// signed vector, as the GL backend can only push a uniform every kernel reads and
+// can't push unsigned integers.
+var<push_constant> step: vec2<i32>;
+
+fn num_bodies() -> u32 { return u32(step.x); }
+fn dt() -> f32 { return bitcast<f32>(step.y); }
+{%- else %}
+
+fn num_bodies() -> u32 { return config.num_bodies; }
+fn dt() -> f32 { return config.dt; }
+{%- endif %}
 @group(1) @binding(0) var<storage, read> input : array<Body, {{static_config.max_bodies}}>;
 @group(1) @binding(1) var<storage, read_write> output : array<Body, {{static_config.max_bodies}}>;
+// Written by force kernels, read by integrators. Persists between steps.
+@group(1) @binding(2) var<storage, read_write> accelerations : array<vec4<f32>, {{static_config.max_bodies}}>;
+@group(1) @binding(3) var<storage, read> properties : array<BodyProperties, {{static_config.max_bodies}}>;
+{%- if static_config.neighbor_grid %}
+{%- set table_size = static_config.neighbor_grid.table_size %}
+// Bodies per hash table entry, reused as the insertion cursor once scanned
+@group(1) @binding(4) var<storage, read_write> cell_counts : array<atomic<u32>, {{table_size}}>;
+// Offset of each entry's bodies in `cell_bodies`, with the total at the end
+@group(1) @binding(5) var<storage, read_write> cell_start : array<u32, {{table_size + 1}}>;
+@group(1) @binding(6) var<storage, read_write> cell_bodies : array<u32, {{static_config.max_bodies}}>;
+{%- endif %}
+{%- if static_config.hydrodynamics %}
+@group(1) @binding(7) var<storage, read_write> gas : array<GasState, {{static_config.max_bodies}}>;
+{%- endif %}
+{%- if static_config.ephemeris %}
+@group(1) @binding(8) var<storage, read_write> ephemeris_state : EphemerisState;
+// Position and velocity of every sample of the prescribed bodies, body after body
+@group(1) @binding(9) var<storage, read> ephemeris_samples : array<vec4<f32>>;
+{%- endif %}
+{%- if static_config.thrust %}
+@group(1) @binding(10) var<storage, read_write> thrust : array<ThrustState, {{static_config.max_bodies}}>;
+// Accelerations of the tables, with the interval to the next sample in `w`
+@group(1) @binding(11) var<storage, read> thrust_samples : array<vec4<f32>>;
+{%- endif %}
+{%- if static_config.com_correction %}
+// Sums of `mu` times the positions with the total `mu` in `w` and of `mu` times
+// the velocities
+@group(1) @binding(12) var<storage, read_write> com_sums : array<vec4<f32>>;
+{%- endif %}
+{%- if static_config.variational %}
+@group(1) @binding(13) var<storage, read_write> tangents : array<Tangent, {{static_config.max_bodies}}>;
+{%- endif %}
+{%- if static_config.ensemble %}
+@group(1) @binding(14) var<storage, read> systems : array<System, {{static_config.max_bodies}}>;
+{%- endif %}
+{%- if events %}
+// The value of every predicate of every body after the last step, predicate after
+// predicate within a body, followed by those evaluated by `events_probe`
+@group(1) @binding(15) var<storage, read_write> event_values : array<f32>;
+@group(1) @binding(16) var<storage, read_write> event_log : EventLog;
+{%- endif %}
+
+// Shortest separation between periodic images
+fn minimum_image(separation: vec3<f32>) -> vec3<f32> {
+    if (config.box_size > 0.0) {
+        return separation - config.box_size * round(separation / config.box_size);
+    }
+    return separation;
+}
+
+// Plummer softened distance for the gravity between point masses
+fn softened(distance: f32, softening: f32) -> f32 {
+    return sqrt(distance * distance + softening * softening);
+}
+
+// The bodies the body's direct sum runs over, every body outside of an ensemble
+fn system_of(idx: u32) -> System {
+{%- if static_config.ensemble %}
+    if (config.num_systems > 0u) {
+        // The last system starting at or before the body
+        var low = 0u;
+        var high = config.num_systems;
+        while (high - low > 1u) {
+            let middle = (low + high) / 2u;
+            if (systems[middle].first <= idx) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        return systems[low];
+    }
+{%- endif %}
+    return System(0u, num_bodies(), config.softening);
+}
+
+// Wrap a position back into the periodic box
+fn wrap(position: vec3<f32>) -> vec3<f32> {
+    if (config.box_size > 0.0) {
+        return position - config.box_size * floor(position / config.box_size);
+    }
+    return position;
+}
+
+fn external_acceleration(position: vec3<f32>) -> vec3<f32> {
+{%- if static_config.external_potential %}
+{%- set kind = static_config.external_potential.kind %}
+    let r = position - config.external_params[0].xyz;
+    let mu = config.external_params[0].w;
+{%- if kind == "Kepler" %}
+    return -mu / pow(length(r), 3.0) * r;
+{%- elif kind == "Harmonic" %}
+    return -mu * mu * r;
+{%- elif kind == "MiyamotoNagai" %}
+    let a = config.external_params[1].x;
+    let b = config.external_params[1].y;
+    let zeta = sqrt(r.z * r.z + b * b);
+    let d = sqrt(r.x * r.x + r.y * r.y + (a + zeta) * (a + zeta));
+    return -mu / pow(d, 3.0) * vec3<f32>(r.x, r.y, r.z * (a + zeta) / zeta);
+{%- elif kind == "Nfw" %}
+    let distance = length(r);
+    let x = distance / config.external_params[1].x;
+    let enclosed = log(1.0 + x) - x / (1.0 + x);
+    return -mu * enclosed / pow(distance, 3.0) * r;
+{%- elif kind == "Cr3bp" %}
+    // Primaries on the x axis of the frame rotating with them, and the centrifugal
+    // term. `mu` is the mass parameter.
+    let r1 = r + vec3<f32>(mu, 0.0, 0.0);
+    let r2 = r - vec3<f32>(1.0 - mu, 0.0, 0.0);
+    return -(1.0 - mu) / pow(length(r1), 3.0) * r1 - mu / pow(length(r2), 3.0) * r2
+        + vec3<f32>(r.x, r.y, 0.0);
+{%- endif %}
+{%- else %}
+    return vec3<f32>(0.0, 0.0, 0.0);
+{%- endif %}
+}
+
+{%- if static_config.zonal_harmonics %}
+// Acceleration at `r` relative to an oblate body with gravitational parameter `mu`
+fn zonal_acceleration(r: vec3<f32>, mu: f32, body: BodyProperties) -> vec3<f32> {
+    let r2 = dot(r, r);
+    let distance = sqrt(r2);
+    let z2 = r.z * r.z / r2;
+    let j2 = -1.5 * body.zonal.x * mu * pow(body.radius, 2.0) / pow(distance, 5.0)
+        * vec3<f32>(r.x * (1.0 - 5.0 * z2), r.y * (1.0 - 5.0 * z2), r.z * (3.0 - 5.0 * z2));
+    let j3_xy = r.z * (3.0 - 7.0 * z2);
+    let j3 = -2.5 * body.zonal.y * mu * pow(body.radius, 3.0) / pow(distance, 7.0)
+        * vec3<f32>(r.x * j3_xy, r.y * j3_xy, r2 * (6.0 * z2 - 7.0 * z2 * z2 - 0.6));
+    let j4_xy = 1.0 - 14.0 * z2 + 21.0 * z2 * z2;
+    let j4 = 1.875 * body.zonal.z * mu * pow(body.radius, 4.0) / pow(distance, 7.0)
+        * vec3<f32>(r.x * j4_xy, r.y * j4_xy, r.z * (5.0 - 70.0 / 3.0 * z2 + 21.0 * z2 * z2));
+    return j2 + j3 + j4;
+}
+{%- endif %}
+
+{%- if static_config.radiation_pressure %}
+fn radiation_acceleration(idx: u32) -> vec3<f32> {
+    let source = config.luminous_body;
+    let area_to_mass = properties[idx].area_to_mass;
+    if (idx == source || area_to_mass == 0.0) { return vec3<f32>(0.0, 0.0, 0.0); }
+    let r = minimum_image(input[idx].position - input[source].position);
+    // Cylindrical shadow behind every body with a radius
+    for(var other_idx: u32 = u32(0); other_idx < num_bodies(); other_idx++) {
+        let radius = properties[other_idx].radius;
+        if (other_idx == idx || other_idx == source || radius == 0.0) { continue; }
+        let axis = normalize(minimum_image(input[other_idx].position - input[source].position));
+        let offset = minimum_image(input[idx].position - input[other_idx].position);
+        let along = dot(offset, axis);
+        if (along > 0.0 && length(offset - along * axis) < radius) {
+            return vec3<f32>(0.0, 0.0, 0.0);
+        }
+    }
+    return config.radiation_pressure * area_to_mass / pow(length(r), 3.0) * r;
+}
+{%- endif %}
+
+{%- if static_config.post_newtonian %}
+
+// First post-Newtonian correction in the field of each body of the table, with
+// the body as a test particle. Between two bodies of the table, optionally the
+// 2.5PN radiation reaction on their relative motion, of which each takes the
+// share of the other's mass.
+fn post_newtonian_acceleration(idx: u32) -> vec3<f32> {
+    var acceleration = vec3<f32>(0.0, 0.0, 0.0);
+    let c = config.post_newtonian_params.x;
+    let c2 = c * c;
+    let radiating = config.post_newtonian_params.y != 0.0
+        && any(config.post_newtonian_bodies == vec4<u32>(idx));
+    for(var i: u32 = 0u; i < 4u; i++) {
+        let source = config.post_newtonian_bodies[i];
+        if (source >= num_bodies() || source == idx) { continue; }
+        let r = minimum_image(input[idx].position - input[source].position);
+        let v = input[idx].velocity - input[source].velocity;
+        let distance = length(r);
+        if (distance == 0.0) { continue; }
+        let mu = input[source].mu;
+        let v2 = dot(v, v);
+        acceleration += mu / (c2 * pow(distance, 3.0))
+            * ((4.0 * mu / distance - v2) * r + 4.0 * dot(r, v) * v);
+        let total = input[idx].mu + mu;
+        if (radiating && total > 0.0) {
+            let n = r / distance;
+            let radial_velocity = dot(n, v);
+            let eta = input[idx].mu * mu / (total * total);
+            // Grouped so that c^5 doesn't overflow in SI units
+            let compactness = total / (c2 * distance);
+            let relative = 1.6 * eta * compactness * compactness / (c * distance)
+                * ((3.0 * v2 + 17.0 / 3.0 * total / distance) * radial_velocity * n
+                    - (v2 + 3.0 * total / distance) * v);
+            acceleration += mu / total * relative;
+        }
+    }
+    return acceleration;
+}
+{%- endif %}
+
+{%- set law = static_config.force_law %}
+{%- if law.kind == "Mond" %}
+
+// The Newtonian gravity of the bodies strengthened by the interpolating function
+fn mond_acceleration(newtonian: vec3<f32>) -> vec3<f32> {
+    let y = length(newtonian) / config.force_params.x;
+    if (y == 0.0) { return newtonian; }
+{%- if law.interpolation == "Standard" %}
+    let nu = sqrt(0.5 + sqrt(0.25 + 1.0 / (y * y)));
+{%- else %}
+    let nu = 0.5 + sqrt(0.25 + 1.0 / y);
+{%- endif %}
+    return nu * newtonian;
+}
+{%- endif %}
+{%- if law.kind == "LennardJones" %}
+fn lennard_jones(idx: u32, other_idx: u32, separation: vec3<f32>, distance: f32) -> vec3<f32> {
+    if (input[idx].mass > 0.0 && distance < config.force_params.x) {
+        let a = config.species[properties[idx].species];
+        let b = config.species[properties[other_idx].species];
+        let sigma = 0.5 * (a.x + b.x);
+        let epsilon = sqrt(a.y * b.y);
+        let s6 = pow(sigma / distance, 6.0);
+        // Positive is repulsive
+        let force = 24.0 * epsilon / distance * (2.0 * s6 * s6 - s6);
+        return -force / (input[idx].mass * distance) * separation;
+    }
+    return vec3<f32>(0.0, 0.0, 0.0);
+}
+{%- endif %}
+
+{%- if static_config.neighbor_grid %}
+fn neighbor_cells_per_axis() -> i32 {
+    return max(i32(config.box_size / config.cell_size), 1);
+}
+
+fn neighbor_cell(position: vec3<f32>) -> vec3<i32> {
+    if (config.box_size > 0.0) {
+        return vec3<i32>(floor(wrap(position) / config.box_size * f32(neighbor_cells_per_axis())));
+    }
+    return vec3<i32>(floor(position / config.cell_size));
+}
+
+fn neighbor_hash(cell: vec3<i32>) -> u32 {
+    var wrapped = cell;
+    if (config.box_size > 0.0) {
+        let n = neighbor_cells_per_axis();
+        let size = vec3<i32>(n, n, n);
+        wrapped = ((cell % size) + size) % size;
+    }
+    let hash = (bitcast<u32>(wrapped.x) * 73856093u)
+        ^ (bitcast<u32>(wrapped.y) * 19349663u)
+        ^ (bitcast<u32>(wrapped.z) * 83492791u);
+    return hash % {{table_size}}u;
+}
+
+// The distinct hash table entries covering the 27 cells around a position
+struct NeighborCells {
+    entries: array<u32, 27>,
+    count: u32,
+}
+
+fn neighbor_cells(position: vec3<f32>) -> NeighborCells {
+    var cells: NeighborCells;
+    let center = neighbor_cell(position);
+    for(var i: i32 = 0; i < 27; i++) {
+        let entry = neighbor_hash(center + vec3<i32>(i % 3 - 1, i / 3 % 3 - 1, i / 9 - 1));
+        var seen = false;
+        for(var j: u32 = 0u; j < cells.count; j++) {
+            seen = seen || cells.entries[j] == entry;
+        }
+        if (!seen) {
+            cells.entries[cells.count] = entry;
+            cells.count++;
+        }
+    }
+    return cells;
+}
+
+{%- if law.kind == "LennardJones" %}
+
+// Forces which vanish beyond a cell, from the bodies in the neighboring cells only
+fn short_range_acceleration(idx: u32) -> vec3<f32> {
+    var acceleration: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    var cells = neighbor_cells(input[idx].position);
+    for(var cell: u32 = 0u; cell < cells.count; cell++) {
+        let entry = cells.entries[cell];
+        for(var slot: u32 = cell_start[entry]; slot < cell_start[entry + 1u]; slot++) {
+            let other_idx = cell_bodies[slot];
+            if (idx == other_idx) { continue; }
+            let separation = minimum_image(input[other_idx].position - input[idx].position);
+            let distance = length(separation);
+            if (distance == 0.0) { continue; }
+            acceleration += lennard_jones(idx, other_idx, separation, distance);
+        }
+    }
+    return acceleration;
+}
+{%- endif %}
+{%- endif %}
+
+{%- if static_config.hydrodynamics %}
+// Cubic spline kernel with support `2 * h`
+fn sph_kernel(distance: f32, h: f32) -> f32 {
+    let q = distance / h;
+    let norm = 1.0 / (3.14159265 * h * h * h);
+    if (q < 1.0) {
+        return norm * (1.0 - 1.5 * q * q + 0.75 * q * q * q);
+    } else if (q < 2.0) {
+        return norm * 0.25 * pow(2.0 - q, 3.0);
+    }
+    return 0.0;
+}
 
-@compute @workgroup_size(64)
+// Derivative of the kernel with respect to the distance
+fn sph_kernel_derivative(distance: f32, h: f32) -> f32 {
+    let q = distance / h;
+    let norm = 1.0 / (3.14159265 * h * h * h * h);
+    if (q < 1.0) {
+        return norm * (-3.0 * q + 2.25 * q * q);
+    } else if (q < 2.0) {
+        return norm * -0.75 * (2.0 - q) * (2.0 - q);
+    }
+    return 0.0;
+}
+
+// Pressure and viscous acceleration from the neighboring gas, also
+// stores the heating rate for the kick to integrate
+fn hydro_acceleration(idx: u32) -> vec3<f32> {
+    var acceleration: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    if (properties[idx].gas == 0u) { return acceleration; }
+    let h = config.hydro_params.x;
+    let gamma = config.hydro_params.y;
+    let state = gas[idx];
+    let sound_speed = sqrt(gamma * state.pressure / state.density);
+    var heating = 0.0;
+    var cells = neighbor_cells(input[idx].position);
+    for(var cell: u32 = 0u; cell < cells.count; cell++) {
+        let entry = cells.entries[cell];
+        for(var slot: u32 = cell_start[entry]; slot < cell_start[entry + 1u]; slot++) {
+            let other_idx = cell_bodies[slot];
+            if (idx == other_idx || properties[other_idx].gas == 0u) { continue; }
+            let separation = minimum_image(input[idx].position - input[other_idx].position);
+            let distance = length(separation);
+            if (distance == 0.0 || distance >= 2.0 * h) { continue; }
+            let other = gas[other_idx];
+            let relative_velocity = input[idx].velocity - input[other_idx].velocity;
+            let approach = dot(relative_velocity, separation);
+            // Monaghan viscosity, only between approaching bodies
+            var viscosity = 0.0;
+            if (approach < 0.0) {
+                let mu = h * approach / (distance * distance + 0.01 * h * h);
+                let other_sound_speed = sqrt(gamma * other.pressure / other.density);
+                let mean_sound_speed = 0.5 * (sound_speed + other_sound_speed);
+                let mean_density = 0.5 * (state.density + other.density);
+                viscosity = (-config.hydro_params.z * mean_sound_speed * mu
+                    + config.hydro_params.w * mu * mu) / mean_density;
+            }
+            let gradient = sph_kernel_derivative(distance, h) / distance * separation;
+            let factor = input[other_idx].mass * (state.pressure / (state.density * state.density)
+                + other.pressure / (other.density * other.density) + viscosity);
+            acceleration -= factor * gradient;
+            heating += 0.5 * factor * dot(relative_velocity, gradient);
+        }
+    }
+    gas[idx].heating_rate = heating;
+    return acceleration;
+}
+{%- endif %}
+
+{%- if custom_force %}
+
+// Supplied with `PipelineBuilder::custom_force`, defines `custom_acceleration`
+{{ custom_force }}
+{%- endif %}
+
+{%- if static_config.thrust %}
+
+// The body's own thrust at the time of the input state
+fn thrust_acceleration(idx: u32) -> vec3<f32> {
+    let state = thrust[idx];
+    if (state.kind == 1u) {
+        return state.acceleration;
+    } else if (state.kind == 2u) {
+        let speed = length(input[idx].velocity);
+        if (speed > 0.0) {
+            return state.acceleration.x / speed * input[idx].velocity;
+        }
+    } else if (state.kind == 3u) {
+        // Linear between the samples around the clock, held at the ends
+        var index = state.sample_index;
+        var s = state.fraction;
+        let last = i32(state.num_samples) - 1;
+        if (index < 0) {
+            index = 0;
+            s = 0.0;
+        }
+        if (index >= last) {
+            index = last - 1;
+            s = 1.0;
+        }
+        let first = state.first + u32(index);
+        return mix(thrust_samples[first].xyz, thrust_samples[first + 1u].xyz, s);
+    }
+    return vec3<f32>(0.0, 0.0, 0.0);
+}
+{%- endif %}
+
+// `velocity` kicked by `acceleration` over the step. In the rotating frame of the
+// restricted three-body problem, the kick is between two exact rotations for the
+// Coriolis term, which keeps the step reversible.
+fn kicked(velocity: vec3<f32>, acceleration: vec3<f32>) -> vec3<f32> {
+{%- if static_config.external_potential and static_config.external_potential.kind == "Cr3bp" %}
+    let c = cos(dt());
+    let s = sin(dt());
+    let half = vec3<f32>(c * velocity.x + s * velocity.y, c * velocity.y - s * velocity.x, velocity.z)
+        + acceleration * dt();
+    return vec3<f32>(c * half.x + s * half.y, c * half.y - s * half.x, half.z);
+{%- else %}
+    return velocity + acceleration * dt();
+{%- endif %}
+}
+
+fn acceleration(idx: u32) -> vec3<f32> {
+    var acceleration: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+{%- if law.kind == "Mond" %}
+    var newtonian: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+{%- endif %}
+    let system = system_of(idx);
+    for(var other_idx: u32 = system.first; other_idx < system.first + system.count; other_idx++) {
+        // TODO: Ensure there isn't a faster way to do this
+        if (idx == other_idx) { continue; }
+        let separation = minimum_image(input[other_idx].position - input[idx].position);
+        let distance = length(separation);
+        // Close encounters are left to the softening, only coincident bodies have no direction
+        if (distance == 0.0) { continue; }
+{%- if law.kind == "Mond" %}
+        newtonian += input[other_idx].mu / pow(softened(distance, system.softening), 3.0) * separation;
+{%- elif law.kind == "Newtonian" or law.gravity %}
+        acceleration += input[other_idx].mu / pow(softened(distance, system.softening), 3.0)
+            * separation;
+{%- endif %}
+{%- if law.kind == "Coulomb" %}
+        if (input[idx].mass > 0.0) {
+            acceleration -= config.force_params.x * properties[idx].charge * properties[other_idx].charge
+                / (input[idx].mass * pow(distance, 3.0)) * separation;
+        }
+{%- elif law.kind == "LennardJones" and not static_config.neighbor_grid %}
+        acceleration += lennard_jones(idx, other_idx, separation, distance);
+{%- endif %}
+{%- if static_config.zonal_harmonics %}
+        // Perturbation from the other body's oblateness, and the reaction to our own
+        acceleration += zonal_acceleration(-separation, input[other_idx].mu, properties[other_idx]);
+        if (input[idx].mu > 0.0) {
+            acceleration -= input[other_idx].mu / input[idx].mu
+                * zonal_acceleration(separation, input[idx].mu, properties[idx]);
+        }
+{%- endif %}
+    }
+{%- if law.kind == "Mond" %}
+    acceleration += mond_acceleration(newtonian);
+{%- endif %}
+{%- if static_config.neighbor_grid and law.kind == "LennardJones" %}
+    acceleration += short_range_acceleration(idx);
+{%- endif %}
+{%- if static_config.radiation_pressure %}
+    acceleration += radiation_acceleration(idx);
+{%- endif %}
+{%- if static_config.post_newtonian %}
+    acceleration += post_newtonian_acceleration(idx);
+{%- endif %}
+{%- if static_config.hydrodynamics %}
+    acceleration += hydro_acceleration(idx);
+{%- endif %}
+{%- if custom_force %}
+    acceleration += custom_acceleration(idx);
+{%- endif %}
+{%- if static_config.thrust %}
+    acceleration += thrust_acceleration(idx);
+{%- endif %}
+    return acceleration + external_acceleration(input[idx].position);
+}
+
+// Fused kick-drift, one dispatch per step
+@compute @workgroup_size({{workgroup_size}})
 fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
-    let idx = gid[0];
-    if !(idx < config.num_bodies) { return; }
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    let a = acceleration(idx);
+    accelerations[idx] = vec4<f32>(a, 0.0);
     // Create mutable copy of previous state
     output[idx] = input[idx];
     // Propagate dynamics
-    output[idx].position += input[idx].velocity * config.dt;
-    var acceleration: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
-    for(var other_idx: u32 = u32(0); other_idx < config.num_bodies; other_idx++) {
-        // TODO: Ensure there isn't a faster way to do this
-        if (idx == other_idx) { return; }
-        let separation = input[other_idx].position - input[idx].position;
+    output[idx].velocity = kicked(output[idx].velocity, a);
+{%- if static_config.hydrodynamics %}
+    gas[idx].internal_energy += gas[idx].heating_rate * dt();
+{%- endif %}
+    output[idx].position = wrap(output[idx].position + output[idx].velocity * dt());
+}
+
+// Split force accumulation from the input buffer into the acceleration buffer
+@compute @workgroup_size({{workgroup_size}})
+fn accelerate(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    accelerations[idx] = vec4<f32>(acceleration(idx), 0.0);
+}
+
+// Split kick, updates velocities from the acceleration buffer
+@compute @workgroup_size({{workgroup_size}})
+fn kick(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    output[idx] = input[idx];
+    output[idx].velocity = kicked(output[idx].velocity, accelerations[idx].xyz);
+{%- if static_config.hydrodynamics %}
+    gas[idx].internal_energy += gas[idx].heating_rate * dt();
+{%- endif %}
+}
+
+// Split drift, updates positions in place in the output buffer
+@compute @workgroup_size({{workgroup_size}})
+fn drift(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    output[idx].position = wrap(output[idx].position + output[idx].velocity * dt());
+}
+
+// Drift from the input into the output, which the following passes read as their
+// input. Starts a reversed step, which kicks last.
+@compute @workgroup_size({{workgroup_size}})
+fn drift_first(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    output[idx] = input[idx];
+    output[idx].position = wrap(input[idx].position + input[idx].velocity * dt());
+}
+
+// Gravitational potential of each body, skipping coincident bodies like the direct sum, into
+// the otherwise unused `w` of its acceleration. Not part of a step, only for display.
+@compute @workgroup_size({{workgroup_size}})
+fn potential(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    var potential = 0.0;
+    let system = system_of(idx);
+    for(var other_idx: u32 = system.first; other_idx < system.first + system.count; other_idx++) {
+        if (idx == other_idx) { continue; }
+        let distance = length(minimum_image(input[other_idx].position - input[idx].position));
+        if (distance == 0.0) { continue; }
+        potential -= input[other_idx].mu / softened(distance, system.softening);
+    }
+    accelerations[idx].w = potential;
+}
+{%- if static_config.neighbor_grid %}
+
+// Neighbor grid construction, a counting sort of the bodies by hash table entry
+@compute @workgroup_size({{workgroup_size}})
+fn neighbor_clear(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="i", bound=table_size ~ "u") }}
+    atomicStore(&cell_counts[i], 0u);
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn neighbor_count(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    atomicAdd(&cell_counts[neighbor_hash(neighbor_cell(input[idx].position))], 1u);
+}
+
+var<workgroup> chunk_offsets : array<u32, {{workgroup_size}}>;
+
+// Exclusive prefix sum of the counts, dispatched as a single workgroup
+// with each thread scanning a contiguous chunk of the table
+@compute @workgroup_size({{workgroup_size}})
+fn neighbor_scan(@builtin(local_invocation_id) lid: vec3<u32>) {
+    let thread = lid[0];
+    let chunk = ({{table_size}}u + {{workgroup_size}}u - 1u) / {{workgroup_size}}u;
+    let begin = min(thread * chunk, {{table_size}}u);
+    let end = min(begin + chunk, {{table_size}}u);
+    var total = 0u;
+    for(var i: u32 = begin; i < end; i++) {
+        total += atomicLoad(&cell_counts[i]);
+    }
+    chunk_offsets[thread] = total;
+    workgroupBarrier();
+    if (thread == 0u) {
+        var sum = 0u;
+        for(var i: u32 = 0u; i < {{workgroup_size}}u; i++) {
+            let count = chunk_offsets[i];
+            chunk_offsets[i] = sum;
+            sum += count;
+        }
+        cell_start[{{table_size}}u] = sum;
+    }
+    workgroupBarrier();
+    var offset = chunk_offsets[thread];
+    for(var i: u32 = begin; i < end; i++) {
+        cell_start[i] = offset;
+        offset += atomicLoad(&cell_counts[i]);
+        atomicStore(&cell_counts[i], 0u);
+    }
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn neighbor_scatter(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    let entry = neighbor_hash(neighbor_cell(input[idx].position));
+    cell_bodies[cell_start[entry] + atomicAdd(&cell_counts[entry], 1u)] = idx;
+}
+{%- if static_config.deterministic %}
+
+// Insertion sort of the bodies of every entry, which the scatter leaves in the
+// order the threads happened to run in
+@compute @workgroup_size({{workgroup_size}})
+fn neighbor_sort(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="i", bound=table_size ~ "u") }}
+    let begin = cell_start[i];
+    let end = cell_start[i + 1u];
+    for(var slot: u32 = begin + 1u; slot < end; slot++) {
+        let body = cell_bodies[slot];
+        var j = slot;
+        while (j > begin && cell_bodies[j - 1u] > body) {
+            cell_bodies[j] = cell_bodies[j - 1u];
+            j--;
+        }
+        cell_bodies[j] = body;
+    }
+}
+{%- endif %}
+{%- endif %}
+{%- if static_config.hydrodynamics %}
+
+// Density and pressure of every gas body, before the forces which use them
+@compute @workgroup_size({{workgroup_size}})
+fn sph_density(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    if (properties[idx].gas == 0u) { return; }
+    let h = config.hydro_params.x;
+    var density = input[idx].mass * sph_kernel(0.0, h);
+    var cells = neighbor_cells(input[idx].position);
+    for(var cell: u32 = 0u; cell < cells.count; cell++) {
+        let entry = cells.entries[cell];
+        for(var slot: u32 = cell_start[entry]; slot < cell_start[entry + 1u]; slot++) {
+            let other_idx = cell_bodies[slot];
+            if (idx == other_idx || properties[other_idx].gas == 0u) { continue; }
+            let distance = length(minimum_image(input[idx].position - input[other_idx].position));
+            density += input[other_idx].mass * sph_kernel(distance, h);
+        }
+    }
+    gas[idx].density = density;
+    gas[idx].pressure = (config.hydro_params.y - 1.0) * density * gas[idx].internal_energy;
+}
+{%- endif %}
+{%- if static_config.ephemeris %}
+
+// The ephemeris clock advanced by the step, as the sample and the fraction of the
+// interval past it. Kept apart so that the fraction doesn't lose precision.
+fn ephemeris_clock() -> vec2<f32> {
+    let time = ephemeris_state.fraction + dt() / ephemeris_state.interval;
+    let whole = floor(time);
+    return vec2<f32>(f32(ephemeris_state.sample_index) + whole, time - whole);
+}
+
+// Overwrite the prescribed bodies in the output with their state at the end of the
+// step, the cubic Hermite interpolation of the samples around it. Held at the
+// first and last sample outside of the ephemeris.
+@compute @workgroup_size({{workgroup_size}})
+fn ephemeris(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="ephemeris_state.num_bodies") }}
+    let clock = ephemeris_clock();
+    var index = i32(clock.x);
+    var s = clock.y;
+    let last = i32(ephemeris_state.num_samples) - 1;
+    if (index < 0) {
+        index = 0;
+        s = 0.0;
+    }
+    if (index >= last) {
+        index = last - 1;
+        s = 1.0;
+    }
+    let h = ephemeris_state.interval;
+    let first = 2u * (idx * ephemeris_state.num_samples + u32(index));
+    let p0 = ephemeris_samples[first].xyz;
+    let v0 = ephemeris_samples[first + 1u].xyz;
+    let p1 = ephemeris_samples[first + 2u].xyz;
+    let v1 = ephemeris_samples[first + 3u].xyz;
+    let body = ephemeris_state.bodies[idx];
+    output[body].position = (2.0 * s * s * s - 3.0 * s * s + 1.0) * p0
+        + (s * s * s - 2.0 * s * s + s) * h * v0
+        + (3.0 * s * s - 2.0 * s * s * s) * p1
+        + (s * s * s - s * s) * h * v1;
+    output[body].velocity = 6.0 * (s * s - s) * (p0 - p1) / h
+        + (3.0 * s * s - 4.0 * s + 1.0) * v0
+        + (3.0 * s * s - 2.0 * s) * v1;
+}
+
+// Advance the ephemeris clock once the prescribed bodies were placed
+@compute @workgroup_size({{workgroup_size}})
+fn ephemeris_tick(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="1u") }}
+    let clock = ephemeris_clock();
+    ephemeris_state.sample_index = i32(clock.x);
+    ephemeris_state.fraction = clock.y;
+}
+{%- endif %}
+{%- if static_config.thrust %}
+
+// Advance the clocks of the thrust tables by the step
+@compute @workgroup_size({{workgroup_size}})
+fn thrust_tick(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    if (thrust[idx].kind != 3u) { return; }
+    let time = thrust[idx].fraction + dt() / thrust_samples[thrust[idx].first].w;
+    let whole = floor(time);
+    thrust[idx].sample_index += i32(whole);
+    thrust[idx].fraction = time - whole;
+}
+{%- endif %}
+{%- if static_config.com_correction %}
+
+var<workgroup> com_positions : array<vec4<f32>, {{workgroup_size}}>;
+var<workgroup> com_velocities : array<vec4<f32>, {{workgroup_size}}>;
+
+// Sum the mu-weighted positions and velocities, dispatched as a single workgroup
+// with each thread summing every workgroup size'th body. Not a kernel over the
+// bodies, so the count comes from the config.
+@compute @workgroup_size({{workgroup_size}})
+fn com_reduce(@builtin(local_invocation_id) lid: vec3<u32>) {
+    let thread = lid[0];
+    var position = vec4<f32>(0.0);
+    var velocity = vec4<f32>(0.0);
+    for(var i: u32 = thread; i < config.num_bodies; i += {{workgroup_size}}u) {
+        let mu = input[i].mu;
+        position += vec4<f32>(mu * input[i].position, mu);
+        velocity += vec4<f32>(mu * input[i].velocity, 0.0);
+    }
+    com_positions[thread] = position;
+    com_velocities[thread] = velocity;
+    workgroupBarrier();
+    if (thread == 0u) {
+        for(var i: u32 = 1u; i < {{workgroup_size}}u; i++) {
+            position += com_positions[i];
+            velocity += com_velocities[i];
+        }
+        com_sums[0] = position;
+        com_sums[1] = velocity;
+    }
+}
+
+// Move the bodies into the frame of their center of mass, unless none has a mu
+@compute @workgroup_size({{workgroup_size}})
+fn com_shift(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    output[idx] = input[idx];
+    let total = com_sums[0].w;
+    if (total > 0.0) {
+        output[idx].position = wrap(input[idx].position - com_sums[0].xyz / total);
+        output[idx].velocity = input[idx].velocity - com_sums[1].xyz / total;
+    }
+}
+{%- endif %}
+{%- if static_config.variational %}
+
+// Advance the body's tangent vector over the step just taken, by the tidal
+// tensor of the other bodies' gravity and the gradient of the external
+// potential, and integrate its growth rate. Renormalized once it grows or
+// shrinks far enough to lose precision.
+@compute @workgroup_size({{workgroup_size}})
+fn variational(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    var tangent = tangents[idx];
+    let position = input[idx].position;
+    let displacement = tangent.position;
+    var tidal = vec3<f32>(0.0, 0.0, 0.0);
+    let system = system_of(idx);
+    for(var other_idx: u32 = system.first; other_idx < system.first + system.count; other_idx++) {
+        if (idx == other_idx) { continue; }
+        let separation = minimum_image(input[other_idx].position - position);
         let distance = length(separation);
-        if (distance < 0.1) { return; }
-        acceleration += input[other_idx].mu / pow(distance, 3.0) * separation;
+        if (distance == 0.0) { continue; }
+        let d = softened(distance, system.softening);
+        tidal += input[other_idx].mu / pow(d, 3.0)
+            * (3.0 * dot(separation, displacement) / (d * d) * separation - displacement);
+    }
+{%- if static_config.external_potential %}
+    // Central differences over a small fraction of the distance to the center
+    let size = length(displacement);
+    if (size > 0.0) {
+        let h = 1e-3 * max(length(position - config.external_params[0].xyz), 1e-3);
+        let direction = displacement * (1.0 / size);
+        tidal += size / (2.0 * h) * (external_acceleration(position + h * direction)
+            - external_acceleration(position - h * direction));
+    }
+{%- endif %}
+    // The growth rate of the norm in phase space at the start of the step
+    let norm2 = dot(displacement, displacement) + dot(tangent.velocity, tangent.velocity);
+    let rate = (dot(displacement, tangent.velocity) + dot(tangent.velocity, tidal)) / norm2;
+    tangent.weighted_growth += rate * (tangent.time + 0.5 * dt()) * dt();
+    tangent.time += dt();
+    tangent.megno_integral += 2.0 * tangent.weighted_growth / tangent.time * dt();
+    tangent.velocity = kicked(tangent.velocity, tidal);
+    tangent.position += tangent.velocity * dt();
+    let norm = sqrt(dot(tangent.position, tangent.position) + dot(tangent.velocity, tangent.velocity));
+    if (norm > 1e8 || norm < 1e-8) {
+        tangent.log_growth += log(norm);
+        tangent.position *= 1.0 / norm;
+        tangent.velocity *= 1.0 / norm;
+    }
+    tangents[idx] = tangent;
+}
+{%- endif %}
+{%- if events %}
+{%- set num_events = events | length %}
+
+// Distance between two bodies, for the predicates
+fn event_separation(idx: u32, other: u32) -> f32 {
+    return length(minimum_image(input[other].position - input[idx].position));
+}
+
+// Distance to the nearest other body of the same system, for the predicates
+fn event_nearest(idx: u32) -> f32 {
+    var nearest = 3.4e38;
+    let system = system_of(idx);
+    for(var other_idx: u32 = system.first; other_idx < system.first + system.count; other_idx++) {
+        if (idx == other_idx) { continue; }
+        nearest = min(nearest, event_separation(idx, other_idx));
+    }
+    return nearest;
+}
+{%- for event in events %}
+
+fn event_predicate_{{loop.index0}}(idx: u32) -> f32 {
+    return f32({{event.expression}});
+}
+{%- endfor %}
+
+// Log the predicates of the body which crossed zero over the step just taken
+@compute @workgroup_size({{workgroup_size}})
+fn events(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+{%- for event in events %}
+    {
+        let slot = idx * {{num_events}}u + {{loop.index0}}u;
+        let before = event_values[slot];
+        let after = event_predicate_{{loop.index0}}(idx);
+{%- if event.direction == "Rising" %}
+        let crossed = before < 0.0 && after >= 0.0;
+{%- elif event.direction == "Falling" %}
+        let crossed = before >= 0.0 && after < 0.0;
+{%- else %}
+        let crossed = (before < 0.0) != (after < 0.0);
+{%- endif %}
+        if (crossed) {
+            let record = atomicAdd(&event_log.count, 1u);
+            if (record < 4096u) {
+                event_log.records[record] = EventRecord(event_log.steps, idx, {{loop.index0}}u, before, after);
+            }
+        }
+        event_values[slot] = after;
     }
-    output[idx].velocity += acceleration * config.dt;
-    output[idx].mass += 1.0;
-}
\ No newline at end of file
+{%- endfor %}
+}
+
+// Count the step the events were logged for
+@compute @workgroup_size({{workgroup_size}})
+fn events_tick(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="1u") }}
+    event_log.steps += 1u;
+}
+
+// Take the predicates of the latest state as the values before the next step
+@compute @workgroup_size({{workgroup_size}})
+fn events_prime(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+{%- for event in events %}
+    event_values[idx * {{num_events}}u + {{loop.index0}}u] = event_predicate_{{loop.index0}}(idx);
+{%- endfor %}
+}
+
+// Evaluate the predicates of the latest state after the values of the steps
+@compute @workgroup_size({{workgroup_size}})
+fn events_probe(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    let offset = {{num_events * static_config.max_bodies}}u;
+{%- for event in events %}
+    event_values[offset + idx * {{num_events}}u + {{loop.index0}}u] = event_predicate_{{loop.index0}}(idx);
+{%- endfor %}
+}
+{%- endif %}
+{%- if static_config.force_solver.kind == "PM" %}
+{%- set g = static_config.force_solver.grid_size %}
+{%- set cells = g * g * g %}
+
+// Particle-mesh gravity. The mass is deposited in fixed point since there are no
+// float atomics, then transformed on a pair of complex grids which swap after every write.
+@group(2) @binding(0) var<storage, read_write> density : array<atomic<i32>, {{cells}}>;
+@group(2) @binding(1) var<storage, read> grid_source : array<vec2<f32>, {{cells}}>;
+@group(2) @binding(2) var<storage, read_write> grid_destination : array<vec2<f32>, {{cells}}>;
+
+fn pm_spacing() -> f32 {
+    return config.box_size / f32({{g}});
+}
+
+// Index of a grid node, wrapping periodically
+fn pm_index(node: vec3<i32>) -> u32 {
+    let n = vec3<i32>({{g}}, {{g}}, {{g}});
+    let wrapped = ((node % n) + n) % n;
+    return u32(wrapped.x + {{g}} * (wrapped.y + {{g}} * wrapped.z));
+}
+
+// Offset of one of the 8 nodes surrounding a position
+fn pm_corner(corner: u32) -> vec3<i32> {
+    return vec3<i32>(i32(corner & 1u), i32((corner >> 1u) & 1u), i32(corner >> 2u));
+}
+
+// Cloud-in-cell weight of a corner, `fraction` is the position within the cell
+fn pm_weight(fraction: vec3<f32>, corner: vec3<i32>) -> f32 {
+    let weights = select(1.0 - fraction, fraction, corner == vec3<i32>(1, 1, 1));
+    return weights.x * weights.y * weights.z;
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn pm_clear(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="i", bound=cells ~ "u") }}
+    atomicStore(&density[i], 0);
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn pm_deposit(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    let scaled = wrap(input[idx].position) / pm_spacing();
+    let base = floor(scaled);
+    let node = vec3<i32>(base);
+    for(var corner: u32 = 0u; corner < 8u; corner++) {
+        let offset = pm_corner(corner);
+        let mass = input[idx].mu * pm_weight(scaled - base, offset) * config.pm_mass_scale;
+        atomicAdd(&density[pm_index(node + offset)], i32(round(mass)));
+    }
+}
+
+// Convert the deposited mass into a complex density
+@compute @workgroup_size({{workgroup_size}})
+fn pm_load(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="i", bound=cells ~ "u") }}
+    let volume = pow(pm_spacing(), 3.0);
+    grid_destination[i] = vec2<f32>(f32(atomicLoad(&density[i])) / (config.pm_mass_scale * volume), 0.0);
+}
+
+// One radix-2 Stockham pass along the axis with the given stride, combining
+// transforms of length `span` into transforms of length `2 * span`
+fn pm_fft(thread: u32, stride: u32, span: u32, sign: f32) {
+    let half = {{g}}u / 2u;
+    if !(thread < {{cells}}u / 2u) { return; }
+    let row = thread / half;
+    let i = thread % half;
+    let base = row % stride + row / stride * stride * {{g}}u;
+    let k = i & (span - 1u);
+    let u0 = grid_source[base + i * stride];
+    let u1 = grid_source[base + (i + half) * stride];
+    let angle = sign * 3.14159265 * f32(k) / f32(span);
+    let twiddle = vec2<f32>(cos(angle), sin(angle));
+    let v = vec2<f32>(u1.x * twiddle.x - u1.y * twiddle.y, u1.x * twiddle.y + u1.y * twiddle.x);
+    let j = (i << 1u) - k;
+    grid_destination[base + j * stride] = u0 + v;
+    grid_destination[base + (j + span) * stride] = u0 - v;
+}
+{% for pass in fft_passes %}
+@compute @workgroup_size({{workgroup_size}})
+fn {{pass.entry_point}}(@builtin(global_invocation_id) gid: vec3<u32>) {
+    pm_fft(gid[0], {{pass.stride}}u, {{pass.span}}u, f32({{pass.sign}}));
+}
+{% endfor %}
+// Multiply the transformed density by the Green's function of the Laplacian,
+// including the normalization of the inverse transform
+@compute @workgroup_size({{workgroup_size}})
+fn pm_green(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="i", bound=cells ~ "u") }}
+    let n = {{g}};
+    let node = vec3<i32>(i32(i) % n, i32(i) / n % n, i32(i) / (n * n));
+    let frequency = select(node, node - vec3<i32>(n, n, n), node >= vec3<i32>(n / 2, n / 2, n / 2));
+    let k = 2.0 * 3.14159265 / config.box_size * vec3<f32>(frequency);
+    let k2 = dot(k, k);
+    if (k2 == 0.0) {
+        grid_destination[i] = vec2<f32>(0.0, 0.0);
+        return;
+    }
+    grid_destination[i] = grid_source[i] * (-4.0 * 3.14159265 / (k2 * f32({{cells}})));
+}
+
+// Interpolate the centered-difference potential gradient back to the bodies
+@compute @workgroup_size({{workgroup_size}})
+fn pm_accelerate(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="num_bodies()") }}
+    let spacing = pm_spacing();
+    let scaled = wrap(input[idx].position) / spacing;
+    let base = floor(scaled);
+    let node = vec3<i32>(base);
+    var acceleration: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    for(var corner: u32 = 0u; corner < 8u; corner++) {
+        let offset = pm_corner(corner);
+        let n = node + offset;
+        let gradient = vec3<f32>(
+            grid_source[pm_index(n + vec3<i32>(1, 0, 0))].x - grid_source[pm_index(n - vec3<i32>(1, 0, 0))].x,
+            grid_source[pm_index(n + vec3<i32>(0, 1, 0))].x - grid_source[pm_index(n - vec3<i32>(0, 1, 0))].x,
+            grid_source[pm_index(n + vec3<i32>(0, 0, 1))].x - grid_source[pm_index(n - vec3<i32>(0, 0, 1))].x
+        ) / (2.0 * spacing);
+        acceleration -= pm_weight(scaled - base, offset) * gradient;
+    }
+{%- if custom_force %}
+    acceleration += custom_acceleration(idx);
+{%- endif %}
+    accelerations[idx] = vec4<f32>(acceleration + external_acceleration(input[idx].position), 0.0);
+}
+{%- endif %}
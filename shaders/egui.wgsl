@@ -0,0 +1,57 @@
+struct Screen {
+    size: vec2<f32>, // In points
+}
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(1) @binding(0) var egui_texture: texture_2d<f32>;
+@group(1) @binding(1) var egui_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>, // Linear, premultiplied alpha
+}
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    let lower = srgb / 12.92;
+    let higher = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4, 2.4, 2.4));
+    return select(higher, lower, srgb < vec3<f32>(0.04045, 0.04045, 0.04045));
+}
+
+fn srgb_from_linear(rgb: vec3<f32>) -> vec3<f32> {
+    let lower = rgb * 12.92;
+    let higher = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4, 1.0 / 2.4, 1.0 / 2.4)) - 0.055;
+    return select(higher, lower, rgb < vec3<f32>(0.0031308, 0.0031308, 0.0031308));
+}
+
+// egui vertices are in points from the top left, with sRGB colors
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        2.0 * position.x / screen.size.x - 1.0,
+        1.0 - 2.0 * position.y / screen.size.y,
+        0.0,
+        1.0
+    );
+    out.uv = uv;
+    out.color = vec4<f32>(linear_from_srgb(color.rgb), color.a);
+    return out;
+}
+
+// For sRGB targets, which encode the output themselves
+@fragment
+fn fs_linear(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color * textureSample(egui_texture, egui_sampler, in.uv);
+}
+
+// For targets storing the values as they are
+@fragment
+fn fs_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = in.color * textureSample(egui_texture, egui_sampler, in.uv);
+    return vec4<f32>(srgb_from_linear(color.rgb), color.a);
+}
@@ -0,0 +1,43 @@
+struct Body {
+    position: vec3<f32>,
+    mass: f32,
+    velocity: vec3<f32>,
+    mu: f32,
+}
+
+@group(0) @binding(0) var<storage, read> bodies : array<Body>;
+// Bound to exactly the bodies being read, packed without padding
+@group(0) @binding(1) var<storage, read_write> gathered : array<f32>;
+
+// Must match BodyField::entry_point
+@compute @workgroup_size({{workgroup_size}})
+fn gather_position(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid[0];
+    if !(idx < arrayLength(&gathered) / 3u) { return; }
+    gathered[3u * idx] = bodies[idx].position.x;
+    gathered[3u * idx + 1u] = bodies[idx].position.y;
+    gathered[3u * idx + 2u] = bodies[idx].position.z;
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn gather_velocity(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid[0];
+    if !(idx < arrayLength(&gathered) / 3u) { return; }
+    gathered[3u * idx] = bodies[idx].velocity.x;
+    gathered[3u * idx + 1u] = bodies[idx].velocity.y;
+    gathered[3u * idx + 2u] = bodies[idx].velocity.z;
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn gather_mass(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid[0];
+    if !(idx < arrayLength(&gathered)) { return; }
+    gathered[idx] = bodies[idx].mass;
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn gather_mu(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid[0];
+    if !(idx < arrayLength(&gathered)) { return; }
+    gathered[idx] = bodies[idx].mu;
+}
@@ -0,0 +1,203 @@
+{#- Index of the thread along the dispatch, returning from threads past `bound`,
+    as in the dynamics -#}
+{%- macro thread_index(name, bound) -%}
+let {{name}} = gid[0];
+    if !({{name}} < {{bound}}) { return; }
+{%- endmacro thread_index %}
+
+struct Body {
+    position: vec3<f32>,
+    mass: f32,
+    velocity: vec3<f32>,
+    mu: f32,
+}
+
+struct NeighborQuery {
+    num_bodies: u32,
+    k: u32,
+    cell_size: f32,
+    box_size: f32,
+    cells_per_axis: u32,
+    rings: u32,
+    table_size: u32,
+}
+
+struct Neighbor {
+    index: u32,
+    distance: f32,
+}
+
+@group(0) @binding(0) var<storage, read> bodies : array<Body>;
+@group(0) @binding(1) var<storage, read> query : NeighborQuery;
+@group(0) @binding(2) var<storage, read_write> cell_counts : array<atomic<u32>>;
+// One past the table, the end of the last entry
+@group(0) @binding(3) var<storage, read_write> cell_start : array<u32>;
+@group(0) @binding(4) var<storage, read_write> cell_bodies : array<u32>;
+// `query.k` per body, nearest first
+@group(0) @binding(5) var<storage, read_write> neighbors : array<Neighbor>;
+@group(0) @binding(6) var<storage, read_write> densities : array<f32>;
+
+fn periodic() -> bool {
+    return query.cells_per_axis > 0u;
+}
+
+fn search_cell(position: vec3<f32>) -> vec3<i32> {
+    if (periodic()) {
+        let wrapped = position - query.box_size * floor(position / query.box_size);
+        let last = i32(query.cells_per_axis) - 1;
+        return clamp(vec3<i32>(floor(wrapped / query.cell_size)), vec3<i32>(0), vec3<i32>(last));
+    }
+    return vec3<i32>(floor(position / query.cell_size));
+}
+
+// A cell at most the searched rings outside the box, back inside it
+fn wrap_cell(cell: vec3<i32>) -> vec3<i32> {
+    if (periodic()) {
+        let n = vec3<i32>(i32(query.cells_per_axis));
+        // Keep the operands of % positive, its result for negative operands
+        // differs between backends
+        return (cell + n) % n;
+    }
+    return cell;
+}
+
+fn search_hash(cell: vec3<i32>) -> u32 {
+    let hash = (bitcast<u32>(cell.x) * 73856093u)
+        ^ (bitcast<u32>(cell.y) * 19349663u)
+        ^ (bitcast<u32>(cell.z) * 83492791u);
+    return hash % query.table_size;
+}
+
+fn search_distance(a: vec3<f32>, b: vec3<f32>) -> f32 {
+    var separation = b - a;
+    if (periodic()) {
+        separation -= query.box_size * round(separation / query.box_size);
+    }
+    return length(separation);
+}
+
+// Cubic spline kernel with support `h`
+fn density_kernel(distance: f32, h: f32) -> f32 {
+    let q = 2.0 * distance / h;
+    let norm = 8.0 / (3.14159265 * h * h * h);
+    if (q < 1.0) {
+        return norm * (1.0 - 1.5 * q * q + 0.75 * q * q * q);
+    } else if (q < 2.0) {
+        return norm * 0.25 * pow(2.0 - q, 3.0);
+    }
+    return 0.0;
+}
+
+// The hash table, a counting sort of the bodies by entry as for the neighbor grid
+@compute @workgroup_size({{workgroup_size}})
+fn search_clear(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="i", bound="query.table_size") }}
+    atomicStore(&cell_counts[i], 0u);
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn search_count(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="query.num_bodies") }}
+    atomicAdd(&cell_counts[search_hash(search_cell(bodies[idx].position))], 1u);
+}
+
+var<workgroup> chunk_offsets : array<u32, {{workgroup_size}}>;
+
+// Exclusive prefix sum of the counts, dispatched as a single workgroup
+@compute @workgroup_size({{workgroup_size}})
+fn search_scan(@builtin(local_invocation_id) lid: vec3<u32>) {
+    let thread = lid[0];
+    let chunk = (query.table_size + {{workgroup_size}}u - 1u) / {{workgroup_size}}u;
+    let begin = min(thread * chunk, query.table_size);
+    let end = min(begin + chunk, query.table_size);
+    var total = 0u;
+    for(var i: u32 = begin; i < end; i++) {
+        total += atomicLoad(&cell_counts[i]);
+    }
+    chunk_offsets[thread] = total;
+    workgroupBarrier();
+    if (thread == 0u) {
+        var sum = 0u;
+        for(var i: u32 = 0u; i < {{workgroup_size}}u; i++) {
+            let count = chunk_offsets[i];
+            chunk_offsets[i] = sum;
+            sum += count;
+        }
+        cell_start[query.table_size] = sum;
+    }
+    workgroupBarrier();
+    var offset = chunk_offsets[thread];
+    for(var i: u32 = begin; i < end; i++) {
+        cell_start[i] = offset;
+        offset += atomicLoad(&cell_counts[i]);
+        atomicStore(&cell_counts[i], 0u);
+    }
+}
+
+@compute @workgroup_size({{workgroup_size}})
+fn search_scatter(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="query.num_bodies") }}
+    let entry = search_hash(search_cell(bodies[idx].position));
+    cell_bodies[cell_start[entry] + atomicAdd(&cell_counts[entry], 1u)] = idx;
+}
+
+// The nearest neighbors of the body, visiting the shells of cells around its own
+// until the farthest of them is closer than any body left unvisited. Bodies of
+// other cells sharing a hash table entry are skipped, so every body is visited
+// once. Then the density from the neighbors, with the kernel reaching to the
+// farthest.
+@compute @workgroup_size({{workgroup_size}})
+fn search_neighbors(@builtin(global_invocation_id) gid: vec3<u32>) {
+    {{ self::thread_index(name="idx", bound="query.num_bodies") }}
+    let position = bodies[idx].position;
+    let center = search_cell(position);
+    let k = query.k;
+    var found: array<Neighbor, {{max_neighbors}}>;
+    var count = 0u;
+    for(var ring: i32 = 0; ring <= i32(query.rings); ring++) {
+        for(var z: i32 = -ring; z <= ring; z++) {
+            for(var y: i32 = -ring; y <= ring; y++) {
+                // Inside the shell only its two faces along x
+                let face = ring == 0 || abs(y) == ring || abs(z) == ring;
+                for(var x: i32 = -ring; x <= ring; x += select(2 * ring, 1, face)) {
+                    let cell = wrap_cell(center + vec3<i32>(x, y, z));
+                    let entry = search_hash(cell);
+                    for(var slot: u32 = cell_start[entry]; slot < cell_start[entry + 1u]; slot++) {
+                        let other_idx = cell_bodies[slot];
+                        let other = bodies[other_idx].position;
+                        if (other_idx == idx || any(search_cell(other) != cell)) { continue; }
+                        let distance = search_distance(position, other);
+                        if (count == k && distance >= found[k - 1u].distance) { continue; }
+                        // Insert in order of distance, dropping the farthest once full
+                        var i = min(count, k - 1u);
+                        count = min(count + 1u, k);
+                        while (i > 0u && found[i - 1u].distance > distance) {
+                            found[i] = found[i - 1u];
+                            i--;
+                        }
+                        found[i] = Neighbor(other_idx, distance);
+                    }
+                }
+            }
+        }
+        if (count == k && found[k - 1u].distance <= f32(ring) * query.cell_size) {
+            break;
+        }
+    }
+    var density = 0.0;
+    let h = found[max(count, 1u) - 1u].distance;
+    if (count > 0u && h > 0.0) {
+        density = bodies[idx].mass * density_kernel(0.0, h);
+        for(var i: u32 = 0u; i < count; i++) {
+            density += bodies[found[i].index].mass * density_kernel(found[i].distance, h);
+        }
+    }
+    for(var i: u32 = 0u; i < k; i++) {
+        if (i < count) {
+            neighbors[idx * k + i] = found[i];
+        } else {
+            neighbors[idx * k + i] = Neighbor(0xffffffffu, 0.0);
+        }
+    }
+    densities[idx] = density;
+}
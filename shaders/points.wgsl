@@ -0,0 +1,196 @@
+struct Camera {
+    view_projection: mat4x4<f32>,
+    viewport: vec2<f32>, // Surface size in pixels
+    point_size: f32, // Sprite diameter in pixels
+    color_mode: u32, // ColorQuantity::mode, see body_color
+    color_range: vec2<f32>, // Values at the ends of the color map
+    highlight: u32, // Index of the highlighted body, 0xffffffff for none
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) @interpolate(flat) highlighted: u32,
+}
+
+// Must match PALETTE in structures.rs
+fn palette(index: u32) -> vec3<f32> {
+    var colors = array<vec3<f32>, 8>(
+        vec3<f32>(1.0, 1.0, 1.0),
+        vec3<f32>(1.0, 0.8, 0.3),
+        vec3<f32>(0.3, 0.6, 1.0),
+        vec3<f32>(1.0, 0.35, 0.3),
+        vec3<f32>(0.4, 0.9, 0.4),
+        vec3<f32>(0.8, 0.5, 1.0),
+        vec3<f32>(0.3, 0.9, 0.9),
+        vec3<f32>(0.6, 0.6, 0.6)
+    );
+    return colors[index % 8u];
+}
+
+// Polynomial fit of viridis for t in [0, 1]
+fn color_map(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777, 0.0054, 0.3341);
+    let c1 = vec3<f32>(0.1051, 1.4046, 1.3846);
+    let c2 = vec3<f32>(-0.3309, 0.2148, 0.0951);
+    let c3 = vec3<f32>(-4.6342, -5.7991, -19.3324);
+    let c4 = vec3<f32>(6.2283, 14.1799, 56.6906);
+    let c5 = vec3<f32>(4.7764, -13.7451, -65.3530);
+    let c6 = vec3<f32>(-5.4355, 4.6459, 26.3124);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+// Position of `value` along the color map
+fn color_map_position(value: f32) -> f32 {
+    let span = camera.color_range.y - camera.color_range.x;
+    if (span <= 0.0) {
+        return 0.5;
+    }
+    return clamp((value - camera.color_range.x) / span, 0.0, 1.0);
+}
+
+// `indices` holds the palette color and the group, the mode selects the quantity shown
+fn body_color(indices: vec2<u32>, mass: f32, velocity: vec3<f32>, potential: f32) -> vec3<f32> {
+    var value: f32;
+    if (camera.color_mode == 0u) {
+        return palette(indices.x);
+    } else if (camera.color_mode == 1u) {
+        return palette(indices.y);
+    } else if (camera.color_mode == 2u) {
+        value = mass;
+    } else if (camera.color_mode == 3u) {
+        value = length(velocity);
+    } else if (camera.color_mode == 4u) {
+        value = potential;
+    } else {
+        value = 0.5 * dot(velocity, velocity) + potential;
+    }
+    return color_map(color_map_position(value));
+}
+
+// One quad per body, the positions are read straight from the simulation's body buffer
+// and the potentials from the `w` of the accelerations
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex: u32,
+    @builtin(instance_index) instance: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) indices: vec2<u32>,
+    @location(2) mass: f32,
+    @location(3) velocity: vec3<f32>,
+    @location(4) potential: f32
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0)
+    );
+    let corner = corners[vertex];
+    let center = camera.view_projection * vec4<f32>(position, 1.0);
+    // Offset in normalized device coordinates, scaled by w to undo the perspective divide
+    let highlighted = u32(instance == camera.highlight);
+    let size = camera.point_size * (1.0 + 2.0 * f32(highlighted));
+    let offset = corner * size / camera.viewport * center.w;
+
+    var out: VertexOutput;
+    out.clip_position = center + vec4<f32>(offset, 0.0, 0.0);
+    out.corner = corner;
+    out.color = body_color(indices, mass, velocity, potential);
+    out.highlighted = highlighted;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let r2 = dot(in.corner, in.corner);
+    if (r2 > 1.0) {
+        discard;
+    }
+    if (in.highlighted != 0u && r2 > 0.6) {
+        return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    }
+    return vec4<f32>(in.color * (1.0 - 0.5 * r2), 1.0);
+}
+
+struct Trail {
+    length: u32,
+    head: u32, // Slot the next positions are recorded to
+    num_bodies: u32,
+}
+
+@group(0) @binding(1) var<uniform> trail: Trail;
+
+struct TrailOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+// The ring holds `length` slots of `num_bodies` positions each, faded by the age of their slot.
+// No potentials are recorded, so the trails keep the palette when the bodies show them.
+@vertex
+fn vs_trail(
+    @builtin(vertex_index) vertex: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) indices: vec2<u32>,
+    @location(2) mass: f32,
+    @location(3) velocity: vec3<f32>
+) -> TrailOutput {
+    let slot = vertex / trail.num_bodies;
+    let age = (trail.head + trail.length - 1u - slot) % trail.length;
+    let fade = 1.0 - f32(age) / f32(trail.length);
+
+    var out: TrailOutput;
+    out.clip_position = camera.view_projection * vec4<f32>(position, 1.0);
+    var color = palette(indices.x);
+    if (camera.color_mode < 4u) {
+        color = body_color(indices, mass, velocity, 0.0);
+    }
+    out.color = 0.6 * fade * color;
+    return out;
+}
+
+@fragment
+fn fs_trail(in: TrailOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
+
+struct LegendOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) t: f32,
+}
+
+// A vertical bar in the lower left corner, `t` runs from 0 at the bottom to 1 at the top
+@vertex
+fn vs_legend(@builtin(vertex_index) vertex: u32) -> LegendOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0)
+    );
+    let corner = corners[vertex];
+    // Margin, width and height in pixels
+    let pixels = vec2<f32>(16.0, 16.0) + corner * vec2<f32>(12.0, 160.0);
+
+    var out: LegendOutput;
+    out.clip_position = vec4<f32>(2.0 * pixels / camera.viewport - 1.0, 0.0, 1.0);
+    out.t = corner.y;
+    return out;
+}
+
+// The palette in equal bands for the discrete modes, the color map otherwise
+@fragment
+fn fs_legend(in: LegendOutput) -> @location(0) vec4<f32> {
+    if (camera.color_mode < 2u) {
+        return vec4<f32>(palette(min(u32(in.t * 8.0), 7u)), 1.0);
+    }
+    return vec4<f32>(color_map(in.t), 1.0);
+}
@@ -0,0 +1,173 @@
+//! Accuracy against the analytic solution of the two-body problem.
+//!
+//! A body orbits a much heavier one on a Kepler ellipse for many periods. The
+//! osculating elements of the integrated orbit are compared with the fixed
+//! ones of the analytic orbit: the mean longitude drifts where the period is
+//! off, and the longitude of periapsis where the ellipse precesses, which the
+//! Kepler problem never does.
+use std::f64::consts::PI;
+
+use crate::analysis;
+use crate::ic::kepler::{self, Anomaly, OrbitalElements};
+use crate::structures::Body;
+
+/// Gravitational parameters of the central body and the orbiting one
+const CENTRAL_MU: f32 = 1.0;
+const ORBITING_MU: f32 = 1e-3;
+/// Below this eccentricity the periapsis is too ill-defined to follow
+const MIN_PRECESSION_ECCENTRICITY: f64 = 0.05;
+
+/// A test body on the orbit of semi-major axis one and `eccentricity` about a
+/// central body, starting at the periapsis in the frame of their center of
+/// mass
+pub fn two_body(eccentricity: f64) -> Vec<Body> {
+    let elements = OrbitalElements {
+        semi_major_axis: 1.0,
+        eccentricity,
+        inclination: 0.0,
+        longitude_of_ascending_node: 0.0,
+        argument_of_periapsis: 0.0,
+        anomaly: Anomaly::True(0.0),
+    };
+    let central = Body {
+        mu: CENTRAL_MU,
+        mass: CENTRAL_MU,
+        ..Default::default()
+    };
+    let orbiting = kepler::orbiting(&central, &elements, ORBITING_MU, ORBITING_MU);
+    let total = (CENTRAL_MU + ORBITING_MU) as f64;
+    let shift = |vector: fn(&Body) -> [f32; 3]| {
+        vector(&orbiting).map(|x| x as f64 * ORBITING_MU as f64 / total)
+    };
+    let (position, velocity) = (shift(|body| body.position), shift(|body| body.velocity));
+    [central, orbiting]
+        .map(|body| Body {
+            position: [0, 1, 2].map(|i| (body.position[i] as f64 - position[i]) as f32),
+            velocity: [0, 1, 2].map(|i| (body.velocity[i] as f64 - velocity[i]) as f32),
+            ..body
+        })
+        .to_vec()
+}
+
+/// Period of the orbits of `two_body`
+pub fn two_body_period() -> f64 {
+    2.0 * PI / ((CENTRAL_MU + ORBITING_MU) as f64).sqrt()
+}
+
+/// How far an integrated two-body orbit strayed from the analytic one
+#[derive(Debug, Clone, Copy)]
+pub struct KeplerAccuracy {
+    /// Periods of the analytic orbit covered
+    pub periods: f64,
+    /// Period of the integrated orbit relative to the analytic one, minus one
+    pub period_error: f64,
+    /// Largest change of the total energy relative to the initial
+    pub energy_drift: f64,
+    /// Turn of the periapsis per period in radians, `None` for orbits too
+    /// circular to have a periapsis to follow
+    pub perihelion_drift: Option<f64>,
+    /// Distance from the analytic position at the end, relative to the
+    /// semi-major axis
+    pub position_error: f64,
+}
+
+/// Follows a two-body orbit through snapshots of its bodies, the first the
+/// central body and the second the orbiting one. The snapshots must be less
+/// than half a period apart for the turns to be counted.
+#[derive(Debug, Clone)]
+pub struct KeplerTracker {
+    mu: f64,
+    initial: OrbitalElements,
+    initial_energy: f64,
+    mean_motion: f64,
+    /// Unwrapped mean longitude and longitude of periapsis of the last snapshot
+    mean_longitude: f64,
+    periapsis: f64,
+    energy_drift: f64,
+    last: Option<(f64, [f64; 3])>,
+    /// Whether the orbit stopped being an ellipse
+    lost: bool,
+}
+
+impl KeplerTracker {
+    pub fn new(bodies: &[Body]) -> Self {
+        let mu = (bodies[0].mu + bodies[1].mu) as f64;
+        let initial = analysis::osculating_elements(bodies, 0)[1]
+            .expect("The two-body orbit needs to be elliptic");
+        Self {
+            mu,
+            initial,
+            initial_energy: analysis::total_energy(bodies),
+            mean_motion: (mu / initial.semi_major_axis.powi(3)).sqrt(),
+            mean_longitude: mean_longitude(&initial),
+            periapsis: periapsis_longitude(&initial),
+            energy_drift: 0.0,
+            last: None,
+            lost: false,
+        }
+    }
+
+    /// Take in the bodies at `time`
+    pub fn record(&mut self, time: f64, bodies: &[Body]) {
+        let energy = analysis::total_energy(bodies);
+        let drift = ((energy - self.initial_energy) / self.initial_energy).abs();
+        self.energy_drift = self.energy_drift.max(drift);
+        let elements = match analysis::osculating_elements(bodies, 0)[1] {
+            Some(elements) if elements.eccentricity < 1.0 => elements,
+            _ => {
+                self.lost = true;
+                return;
+            }
+        };
+        self.mean_longitude += wrap(mean_longitude(&elements) - self.mean_longitude);
+        self.periapsis += wrap(periapsis_longitude(&elements) - self.periapsis);
+        let relative =
+            [0, 1, 2].map(|i| bodies[1].position[i] as f64 - bodies[0].position[i] as f64);
+        self.last = Some((time, relative));
+    }
+
+    /// The errors as of the last snapshot, `None` before the first or once the
+    /// orbit is no longer an ellipse
+    pub fn accuracy(&self) -> Option<KeplerAccuracy> {
+        let (time, position) = match self.last {
+            Some(last) if !self.lost => last,
+            _ => return None,
+        };
+        let initial = &self.initial;
+        let expected_turn = self.mean_motion * time;
+        let phase_error = self.mean_longitude - mean_longitude(initial) - expected_turn;
+        let periods = expected_turn / (2.0 * PI);
+        let mean_anomaly = kepler::mean_from_true(initial.true_anomaly(), initial.eccentricity);
+        let analytic = OrbitalElements {
+            anomaly: Anomaly::Mean(mean_anomaly + expected_turn),
+            ..*initial
+        };
+        let (expected, _) = analytic.state(self.mu);
+        let distance = (0..3)
+            .map(|i| (position[i] - expected[i]).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        Some(KeplerAccuracy {
+            periods,
+            period_error: -phase_error / (expected_turn + phase_error),
+            energy_drift: self.energy_drift,
+            perihelion_drift: (initial.eccentricity >= MIN_PRECESSION_ECCENTRICITY)
+                .then(|| (self.periapsis - periapsis_longitude(initial)) / periods),
+            position_error: distance / initial.semi_major_axis,
+        })
+    }
+}
+
+fn periapsis_longitude(elements: &OrbitalElements) -> f64 {
+    elements.longitude_of_ascending_node + elements.argument_of_periapsis
+}
+
+fn mean_longitude(elements: &OrbitalElements) -> f64 {
+    periapsis_longitude(elements)
+        + kepler::mean_from_true(elements.true_anomaly(), elements.eccentricity)
+}
+
+/// `angle` brought into `[-π, π)`
+fn wrap(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
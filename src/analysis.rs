@@ -0,0 +1,191 @@
+//! Diagnostics computed from body state on the host, and those searching the
+//! bodies on the GPU from the latest state of a pipeline.
+//!
+//! Bodies only carry `mu = G * m`, so energies are reported multiplied by `G`
+//! and weighted by `mu` rather than mass.
+use crate::ic::{cr3bp, kepler::OrbitalElements};
+use crate::neighbors::NeighborSearch;
+use crate::pipeline::Pipeline;
+use crate::structures::Body;
+use crate::vector::{cross, dot, norm};
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f64; 3] {
+    [
+        a[0] as f64 - b[0] as f64,
+        a[1] as f64 - b[1] as f64,
+        a[2] as f64 - b[2] as f64,
+    ]
+}
+
+/// The `k` nearest neighbors of every body of `pipeline` and the mass density
+/// about it, for finding clusters, cores and binaries, see
+/// `Pipeline::search_neighbors`
+pub fn knn(pipeline: &mut Pipeline, k: usize) -> NeighborSearch {
+    pipeline.search_neighbors(k)
+}
+
+/// `G` times the kinetic energy
+pub fn kinetic_energy(bodies: &[Body]) -> f64 {
+    bodies
+        .iter()
+        .map(|body| 0.5 * body.mu as f64 * norm(sub(body.velocity, [0.0; 3])).powi(2))
+        .sum()
+}
+
+/// `G` times the pairwise gravitational potential energy
+pub fn potential_energy(bodies: &[Body]) -> f64 {
+    let mut energy = 0.0;
+    for (i, a) in bodies.iter().enumerate() {
+        for b in &bodies[i + 1..] {
+            let distance = norm(sub(a.position, b.position));
+            if distance > 0.0 {
+                energy -= a.mu as f64 * b.mu as f64 / distance;
+            }
+        }
+    }
+    energy
+}
+
+/// `G` times the total energy
+pub fn total_energy(bodies: &[Body]) -> f64 {
+    kinetic_energy(bodies) + potential_energy(bodies)
+}
+
+/// `G` times the total linear momentum
+pub fn momentum(bodies: &[Body]) -> [f64; 3] {
+    let mut momentum = [0.0; 3];
+    for body in bodies {
+        for (total, v) in momentum.iter_mut().zip(body.velocity) {
+            *total += body.mu as f64 * v as f64;
+        }
+    }
+    momentum
+}
+
+/// `mu`-weighted mean position, `None` if no body has a `mu`
+pub fn center_of_mass(bodies: &[Body]) -> Option<[f64; 3]> {
+    let total: f64 = bodies.iter().map(|body| body.mu as f64).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut center = [0.0; 3];
+    for body in bodies {
+        for (c, p) in center.iter_mut().zip(body.position) {
+            *c += body.mu as f64 * p as f64 / total;
+        }
+    }
+    Some(center)
+}
+
+/// The Tisserand parameter `a_p / a + 2 cos(i) sqrt(a (1 - e^2) / a_p)` of the
+/// orbit at `r` and `v` about a central `mu`, relative to a perturber's orbit of
+/// semi-major axis `perturber_axis` and unit angular momentum `perturber_normal`.
+/// `None` on radial paths.
+pub(crate) fn tisserand(
+    r: [f64; 3],
+    v: [f64; 3],
+    mu: f64,
+    perturber_axis: f64,
+    perturber_normal: [f64; 3],
+) -> Option<f64> {
+    let h = cross(r, v);
+    let h_norm = norm(h);
+    if h_norm == 0.0 || mu <= 0.0 {
+        return None;
+    }
+    let inverse_axis = 2.0 / norm(r) - dot(v, v) / mu;
+    let semi_latus_rectum = h_norm * h_norm / mu;
+    let cos_inclination = dot(h, perturber_normal) / h_norm;
+    Some(
+        perturber_axis * inverse_axis
+            + 2.0 * cos_inclination * (semi_latus_rectum / perturber_axis).sqrt(),
+    )
+}
+
+/// The Jacobi constant of every body in the rotating frame of the restricted
+/// three-body problem about `center`
+pub fn jacobi_constants(bodies: &[Body], center: [f64; 3], mass_parameter: f64) -> Vec<f64> {
+    bodies
+        .iter()
+        .map(|body| {
+            let position = [0, 1, 2].map(|i| body.position[i] as f64 - center[i]);
+            cr3bp::jacobi_constant(mass_parameter, position, body.velocity.map(f64::from))
+        })
+        .collect()
+}
+
+/// The Tisserand parameter of every body's orbit about the primary of the
+/// restricted three-body problem about `center`, relative to the secondary's.
+/// `None` for bodies on radial paths.
+pub fn synodic_tisserand_parameters(
+    bodies: &[Body],
+    center: [f64; 3],
+    mass_parameter: f64,
+) -> Vec<Option<f64>> {
+    bodies
+        .iter()
+        .map(|body| {
+            let position = [0, 1, 2].map(|i| body.position[i] as f64 - center[i]);
+            cr3bp::tisserand_parameter(mass_parameter, position, body.velocity.map(f64::from))
+        })
+        .collect()
+}
+
+/// The Tisserand parameter of every body's orbit about the body at
+/// `central_idx`, relative to the orbit of the body at `perturber_idx`. `None`
+/// for those two, for bodies on radial paths and all bodies when the perturber
+/// isn't on a bound orbit.
+pub fn tisserand_parameters(
+    bodies: &[Body],
+    central_idx: usize,
+    perturber_idx: usize,
+) -> Vec<Option<f64>> {
+    let central = &bodies[central_idx];
+    let perturber = &bodies[perturber_idx];
+    let r = sub(perturber.position, central.position);
+    let v = sub(perturber.velocity, central.velocity);
+    let mu = central.mu as f64 + perturber.mu as f64;
+    let h = cross(r, v);
+    let inverse_axis = 2.0 / norm(r) - dot(v, v) / mu;
+    if norm(h) == 0.0 || inverse_axis <= 0.0 {
+        return vec![None; bodies.len()];
+    }
+    let normal = h.map(|x| x / norm(h));
+    bodies
+        .iter()
+        .enumerate()
+        .map(|(index, body)| {
+            if index == central_idx || index == perturber_idx {
+                return None;
+            }
+            tisserand(
+                sub(body.position, central.position),
+                sub(body.velocity, central.velocity),
+                central.mu as f64 + body.mu as f64,
+                1.0 / inverse_axis,
+                normal,
+            )
+        })
+        .collect()
+}
+
+/// The osculating orbit of every body about the body at `central_idx`, for the
+/// sum of their `mu`. `None` for the central body itself and for bodies on
+/// radial or parabolic paths.
+pub fn osculating_elements(bodies: &[Body], central_idx: usize) -> Vec<Option<OrbitalElements>> {
+    let central = &bodies[central_idx];
+    bodies
+        .iter()
+        .enumerate()
+        .map(|(index, body)| {
+            if index == central_idx {
+                return None;
+            }
+            OrbitalElements::from_state(
+                sub(body.position, central.position),
+                sub(body.velocity, central.velocity),
+                central.mu as f64 + body.mu as f64,
+            )
+        })
+        .collect()
+}
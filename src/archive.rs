@@ -0,0 +1,344 @@
+//! Simulation archives, the snapshots of a run in one compressed file which can
+//! be played back to render or export them again without recomputing the run,
+//! in the spirit of REBOUND's SimulationArchive.
+//!
+//! Files start with the same preamble as checkpoints, with their own magic:
+//!
+//! | bytes | contents                                            |
+//! |-------|-----------------------------------------------------|
+//! | 4     | magic `PBAR`                                        |
+//! | 1     | byte order of everything that follows, `L` or `B`   |
+//! | 1     | reserved, zero                                      |
+//! | 2     | format version                                      |
+//!
+//! The rest is a zstd stream of the `RunManifest`, holding the `StaticConfig`,
+//! as a length-prefixed JSON string, whether the run had maneuvers as a `u32`,
+//! and the number of bodies as a `u64` followed by their `VisualAttributes` as
+//! `u32`s. Then come the frames up to the end of the stream, each the step as a
+//! `u64` followed by the state of a checkpoint: the `DynamicConfig` as a
+//! length-prefixed JSON string, the time direction as a `u32`, the
+//! simulated time as an `f64`, the number of bodies as a `u64`, fewer than at
+//! the start once bodies are removed, and the bodies, their properties and
+//! their gas states as 32-bit words.
+//!
+//! So a run restarted from any frame repeats the frames after it, bit for bit
+//! with `StaticConfig::deterministic` or the direct solver, as long as it has
+//! no state on the host: maneuvers, thrust, the ephemeris and the correction of
+//! the center of mass aren't archived. `parabody validate restart` checks this,
+//! refusing the archives of runs with any of them.
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::checkpoint::{
+    read_dynamic_config, read_word_vec, write_dynamic_config, write_words, Checkpoint,
+};
+use crate::format::{invalid_data, Endianness, OrderedReader, OrderedWriter};
+use crate::manifest::RunManifest;
+use crate::pipeline::TimeDirection;
+use crate::structures::VisualAttributes;
+
+pub const MAGIC: [u8; 4] = *b"PBAR";
+pub const VERSION: u16 = 4;
+
+/// What an archive records once, ahead of the frames
+#[derive(Debug, Clone)]
+pub struct ArchiveHeader {
+    /// With the static config of every frame
+    pub manifest: RunManifest,
+    /// Whether the run applied maneuvers, which the frames don't replay
+    pub maneuvers: bool,
+    /// Of the bodies at the start of the run
+    pub attributes: Vec<VisualAttributes>,
+}
+
+/// The state of the simulation at one snapshot, from which the run can be
+/// restarted
+#[derive(Debug, Clone)]
+pub struct ArchiveFrame {
+    pub step: usize,
+    /// With the static config of the header
+    pub state: Checkpoint,
+}
+
+/// Compresses the frames of a run into an archive as they're written
+pub struct ArchiveWriter<W: Write = BufWriter<File>> {
+    /// Taken when the stream is finished
+    writer: Option<OrderedWriter<zstd::Encoder<'static, W>>>,
+    snapshots: usize,
+}
+
+impl ArchiveWriter {
+    pub fn create(path: &Path, header: &ArchiveHeader) -> io::Result<Self> {
+        Self::new(
+            BufWriter::new(File::create(path)?),
+            header,
+            Endianness::native(),
+        )
+    }
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Write the preamble and the header to `writer`
+    pub fn new(writer: W, header: &ArchiveHeader, endianness: Endianness) -> io::Result<Self> {
+        let mut writer = OrderedWriter::new(writer, endianness);
+        writer.write_bytes(&MAGIC)?;
+        writer.write_bytes(&[endianness.marker(), 0])?;
+        writer.write_u16(VERSION)?;
+        let encoder = zstd::Encoder::new(writer.into_inner(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        let mut writer = OrderedWriter::new(encoder, endianness);
+        let manifest = serde_json::to_vec(&header.manifest)?;
+        writer.write_u64(manifest.len() as u64)?;
+        writer.write_bytes(&manifest)?;
+        writer.write_u32(header.maneuvers as u32)?;
+        writer.write_u64(header.attributes.len() as u64)?;
+        for attributes in &header.attributes {
+            writer.write_u32(attributes.group)?;
+            writer.write_u32(attributes.color)?;
+            writer.write_u32(attributes.label)?;
+        }
+        Ok(Self {
+            writer: Some(writer),
+            snapshots: 0,
+        })
+    }
+
+    fn writer(&mut self) -> io::Result<&mut OrderedWriter<zstd::Encoder<'static, W>>> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| io::Error::other("The archive is already finished"))
+    }
+
+    /// Append the `state` after `step` steps, such as one of
+    /// `Pipeline::capture`. Its static config is the one of the header.
+    pub fn write_frame(&mut self, step: usize, state: &Checkpoint) -> io::Result<()> {
+        assert_eq!(state.bodies.len(), state.properties.len());
+        assert_eq!(state.bodies.len(), state.gas.len());
+        let writer = self.writer()?;
+        writer.write_u64(step as u64)?;
+        write_dynamic_config(writer, &state.dynamic_config)?;
+        writer.write_u32(match state.time_direction {
+            TimeDirection::Forward => 0,
+            TimeDirection::Backward => 1,
+        })?;
+        writer.write_f64(state.time)?;
+        writer.write_u64(state.bodies.len() as u64)?;
+        write_words(writer, &state.bodies)?;
+        write_words(writer, &state.properties)?;
+        write_words(writer, &state.gas)?;
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    pub fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    /// End the compressed stream, without which the last frames can't be read
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer()?;
+        let encoder = self.writer.take().expect("Checked above").into_inner();
+        encoder.finish()?.flush()
+    }
+}
+
+/// Reads the header of an archive and then its frames one by one, as an
+/// iterator
+pub struct ArchiveReader<R: Read = BufReader<File>> {
+    reader: OrderedReader<zstd::Decoder<'static, BufReader<R>>>,
+    header: ArchiveHeader,
+}
+
+impl ArchiveReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> ArchiveReader<R> {
+    /// Read the preamble and the header from `reader`
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut preamble = [0; 6];
+        reader.read_exact(&mut preamble)?;
+        if preamble[..4] != MAGIC {
+            return Err(invalid_data("Not a parabody archive".to_string()));
+        }
+        let endianness = Endianness::from_marker(preamble[4])?;
+        let mut reader = OrderedReader::new(reader, endianness);
+        let version = reader.read_u16()?;
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported archive version {} (expected {})",
+                version, VERSION
+            )));
+        }
+        let decoder = zstd::Decoder::new(reader.into_inner())?;
+        let mut reader = OrderedReader::new(decoder, endianness);
+        let length = reader.read_u64()?;
+        let manifest = reader.read_vec(length)?;
+        let manifest: RunManifest = serde_json::from_slice(&manifest)?;
+        let maneuvers = reader.read_u32()? != 0;
+        let num_bodies = reader.read_u64()?;
+        let mut attributes = Vec::with_capacity(num_bodies.min(1 << 20) as usize);
+        for _ in 0..num_bodies {
+            attributes.push(VisualAttributes {
+                group: reader.read_u32()?,
+                color: reader.read_u32()?,
+                label: reader.read_u32()?,
+            });
+        }
+        Ok(Self {
+            reader,
+            header: ArchiveHeader {
+                manifest,
+                maneuvers,
+                attributes,
+            },
+        })
+    }
+
+    pub fn header(&self) -> &ArchiveHeader {
+        &self.header
+    }
+
+    /// The next frame, `None` at the end of the archive
+    pub fn read_frame(&mut self) -> io::Result<Option<ArchiveFrame>> {
+        let step = match self.reader.read_u64() {
+            Ok(step) => step as usize,
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let reader = &mut self.reader;
+        let dynamic_config = read_dynamic_config(reader)?;
+        let time_direction = match reader.read_u32()? {
+            0 => TimeDirection::Forward,
+            1 => TimeDirection::Backward,
+            direction => {
+                return Err(invalid_data(format!(
+                    "Unknown time direction {}",
+                    direction
+                )))
+            }
+        };
+        let time = reader.read_f64()?;
+        let num_bodies = reader.read_u64()?;
+        let static_config = self.header.manifest.static_config;
+        if num_bodies > static_config.max_bodies as u64 {
+            return Err(invalid_data(format!(
+                "{} bodies exceed the maximum of {}",
+                num_bodies, static_config.max_bodies
+            )));
+        }
+        let num_bodies = num_bodies as usize;
+        let bodies = read_word_vec(reader, num_bodies)?;
+        let properties = read_word_vec(reader, num_bodies)?;
+        let gas = read_word_vec(reader, num_bodies)?;
+        Ok(Some(ArchiveFrame {
+            step,
+            state: Checkpoint {
+                static_config,
+                dynamic_config,
+                time_direction,
+                time,
+                bodies,
+                properties,
+                gas,
+            },
+        }))
+    }
+}
+
+impl<R: Read> Iterator for ArchiveReader<R> {
+    type Item = io::Result<ArchiveFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::PowerPreference;
+
+    use super::*;
+    use crate::pipeline::{PassGraph, Pipeline};
+    use crate::scenario::Scenario;
+    use crate::structures::Body;
+
+    /// Steps between the recorded frames
+    const STRIDE: usize = 8;
+
+    #[test]
+    fn restart_repeats_the_archived_frames() {
+        let (scenario, _) = Scenario::from_toml(
+            r#"
+            [config]
+            dt = 0.01
+            steps = 24
+            softening = 0.05
+
+            [generator]
+            kind = "Plummer"
+            count = 100
+            scale_radius = 1.0
+            seed = 7
+            "#,
+        )
+        .expect("The scenario is valid");
+        let mut static_config = scenario.static_config();
+        static_config.deterministic = true;
+        let create = || {
+            let mut pipeline = pollster::block_on(Pipeline::create(
+                include_str!("../shaders/dynamics.wgsl"),
+                PassGraph::split(),
+                static_config,
+                PowerPreference::HighPerformance,
+            ))
+            .expect("Could not create the pipeline");
+            pipeline.set_dt(scenario.config.dt);
+            pipeline.set_softening(scenario.config.softening);
+            pipeline
+        };
+
+        let mut pipeline = create();
+        pipeline.write_bodies(&scenario.bodies()).unwrap();
+        let header = ArchiveHeader {
+            manifest: RunManifest::new(&scenario, &pipeline),
+            maneuvers: false,
+            attributes: scenario.visual_attributes(),
+        };
+        let mut archive = Vec::new();
+        let mut writer = ArchiveWriter::new(&mut archive, &header, Endianness::native()).unwrap();
+        for frame in 0..4 {
+            writer
+                .write_frame(frame * STRIDE, &pipeline.capture())
+                .unwrap();
+            pipeline.submit_and_block(STRIDE);
+        }
+        writer.finish().unwrap();
+        // The GL backend can't open a second device on a thread still holding one
+        drop(pipeline);
+
+        let mut reader = ArchiveReader::new(archive.as_slice()).unwrap();
+        assert_eq!(reader.header().manifest.static_config.max_bodies, 100);
+        let mut frames = reader.by_ref().skip(1).map(Result::unwrap);
+        let start = frames.next().expect("The archive has a second frame");
+        let mut restarted = create();
+        restarted.load(&start.state).unwrap();
+        let mut step = start.step;
+        for frame in frames {
+            restarted.submit_and_block(frame.step - step);
+            step = frame.step;
+            assert_eq!(restarted.time(), frame.state.time);
+            assert_eq!(
+                bytemuck::cast_slice::<Body, u32>(&restarted.read_bodies()),
+                bytemuck::cast_slice::<Body, u32>(&frame.state.bodies),
+                "The restart differs from the archive at step {}",
+                step
+            );
+        }
+        assert_eq!(step, 3 * STRIDE);
+    }
+}
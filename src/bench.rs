@@ -0,0 +1,72 @@
+//! Throughput of the pipeline on an adapter, for choosing the settings of a run
+//! and for following the speed of the kernels from one version to the next.
+use std::{fs, io, path::Path, time::Duration};
+
+use serde::Serialize;
+
+/// The measurements of a benchmark on one adapter
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    /// Version of parabody which ran the benchmark
+    pub version: String,
+    pub adapter: String,
+    pub backend: String,
+    pub device_type: String,
+    pub results: Vec<BenchResult>,
+}
+
+/// Steps of one solver on one number of bodies
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    /// Name of the solver, as given to `parabody bench`
+    pub solver: String,
+    pub bodies: usize,
+    pub workgroup_size: u32,
+    pub steps: usize,
+    pub seconds: f64,
+    pub steps_per_second: f64,
+    /// Pairs of bodies per second a direct sum would have to evaluate to keep
+    /// up, so that solvers which don't visit every pair compare with those
+    /// which do
+    pub interactions_per_second: f64,
+}
+
+impl BenchResult {
+    pub fn new(
+        solver: String,
+        bodies: usize,
+        workgroup_size: u32,
+        steps: usize,
+        elapsed: Duration,
+    ) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        let interactions = steps as f64 * bodies as f64 * bodies.saturating_sub(1) as f64;
+        let per_second = |count: f64| if seconds > 0.0 { count / seconds } else { 0.0 };
+        Self {
+            solver,
+            bodies,
+            workgroup_size,
+            steps,
+            seconds,
+            steps_per_second: per_second(steps as f64),
+            interactions_per_second: per_second(interactions),
+        }
+    }
+}
+
+impl BenchReport {
+    pub fn new(adapter: &wgpu::AdapterInfo) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            adapter: adapter.name.clone(),
+            backend: format!("{:?}", adapter.backend),
+            device_type: format!("{:?}", adapter.device_type),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+}
@@ -0,0 +1,88 @@
+//! Binaries, the pairs of bodies bound to each other, which in star clusters
+//! store and release the energy driving the evolution of the core.
+//!
+//! Two bodies are a binary when each is the other's nearest neighbor, the
+//! energy of their relative orbit is negative in the field of the pair alone,
+//! and the orbit stays well clear of the other bodies, see `ISOLATION`. The
+//! nearest neighbors are searched on the GPU, see `Pipeline::search_neighbors`.
+use crate::ic::kepler::OrbitalElements;
+use crate::neighbors::NeighborSearch;
+use crate::pipeline::Pipeline;
+use crate::structures::Body;
+
+/// Largest apocenter of a binary, as a fraction of the distance from either
+/// body to the nearest other. Keeps out the pairs that only happen to be
+/// slow relative to each other, whose orbits the other bodies would disrupt.
+pub const ISOLATION: f64 = 0.5;
+
+/// A pair of bodies bound to each other, with their relative orbit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binary {
+    /// Indices of the bodies, the lower first
+    pub bodies: [usize; 2],
+    pub separation: f64,
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    /// `G` times the energy of the relative orbit, `-mu_1 mu_2 / (2 a)`
+    pub binding_energy: f64,
+}
+
+/// The binaries among the bodies in the latest state of `pipeline`, in the
+/// order of their lower index
+pub fn find_binaries(pipeline: &mut Pipeline) -> Vec<Binary> {
+    let bodies = pipeline.read_bodies();
+    let search = pipeline.search_neighbors(2);
+    binaries(&bodies, &search, pipeline.dynamic_config().box_size)
+}
+
+/// The binaries among `bodies` with their two nearest neighbors in `search`,
+/// the separations to the nearest image in a periodic box of `box_size`
+pub fn binaries(bodies: &[Body], search: &NeighborSearch, box_size: f32) -> Vec<Binary> {
+    let nearest = |body: usize| {
+        search
+            .neighbors(body)
+            .first()
+            .map(|neighbor| neighbor.index as usize)
+    };
+    // Distance to the nearest body but the companion, the first neighbor
+    let perturber = |body: usize| {
+        search
+            .neighbors(body)
+            .get(1)
+            .map_or(f64::INFINITY, |neighbor| neighbor.distance as f64)
+    };
+    let mut binaries = Vec::new();
+    for (index, body) in bodies.iter().enumerate() {
+        let other = match nearest(index) {
+            Some(other) if other > index && nearest(other) == Some(index) => other,
+            _ => continue,
+        };
+        let companion = &bodies[other];
+        let mu = body.mu as f64 + companion.mu as f64;
+        let position = [0, 1, 2].map(|axis| {
+            let separation = companion.position[axis] as f64 - body.position[axis] as f64;
+            match box_size > 0.0 {
+                true => separation - box_size as f64 * (separation / box_size as f64).round(),
+                false => separation,
+            }
+        });
+        let velocity =
+            [0, 1, 2].map(|axis| companion.velocity[axis] as f64 - body.velocity[axis] as f64);
+        let orbit = match OrbitalElements::from_state(position, velocity, mu) {
+            Some(orbit) if mu > 0.0 && orbit.semi_major_axis > 0.0 => orbit,
+            _ => continue,
+        };
+        let apocenter = orbit.semi_major_axis * (1.0 + orbit.eccentricity);
+        if apocenter > ISOLATION * perturber(index).min(perturber(other)) {
+            continue;
+        }
+        binaries.push(Binary {
+            bodies: [index, other],
+            separation: position.iter().map(|x| x * x).sum::<f64>().sqrt(),
+            semi_major_axis: orbit.semi_major_axis,
+            eccentricity: orbit.eccentricity,
+            binding_energy: -(body.mu as f64) * companion.mu as f64 / (2.0 * orbit.semi_major_axis),
+        });
+    }
+    binaries
+}
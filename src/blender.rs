@@ -0,0 +1,80 @@
+//! Export of a recorded trajectory for offline rendering in Blender. The frames are
+//! written as snapshots in the `format` layout, along with an `import.py` which
+//! loads them as animated point clouds.
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::format::{self, Endianness};
+use crate::manifest::RunManifest;
+use crate::structures::{Body, VisualAttributes};
+
+/// Writes the frames of a trajectory into a directory as they are produced
+pub struct BlenderExport {
+    directory: PathBuf,
+    attributes: Vec<VisualAttributes>,
+    /// Manifest of the run as JSON, kept in the import script
+    manifest: String,
+    frames: usize,
+}
+
+impl BlenderExport {
+    /// Create `directory` and its `frames` subdirectory. Every frame carries
+    /// `attributes`, which the import script uses to color the bodies.
+    pub fn create(
+        directory: &Path,
+        attributes: &[VisualAttributes],
+        manifest: &RunManifest,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(directory.join("frames"))?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            attributes: attributes.to_vec(),
+            manifest: manifest.to_json()?,
+            frames: 0,
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Append the next frame
+    pub fn write_frame(&mut self, bodies: &[Body]) -> io::Result<()> {
+        let path = self
+            .directory
+            .join("frames")
+            .join(format!("frame_{:05}.pbdy", self.frames));
+        format::write_attributed_bodies(
+            BufWriter::new(File::create(path)?),
+            bodies,
+            &self.attributes,
+            Endianness::native(),
+        )?
+        .flush()?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Write the import script for the frames so far, `frame_interval` steps of
+    /// `dt` apart, and return its path
+    pub fn finish(self, frame_interval: usize, dt: f32) -> io::Result<PathBuf> {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_template("import", include_str!("../templates/blender_import.py"))
+            .unwrap();
+        let mut context = tera::Context::new();
+        context.insert("frame_count", &self.frames);
+        context.insert("frame_interval", &frame_interval);
+        context.insert("dt", &dt.to_string());
+        // As a string literal, which JSON escapes the same way as Python
+        context.insert("manifest", &serde_json::to_string(&self.manifest)?);
+        let script = tera
+            .render("import", &context)
+            .expect("Failed to render Blender import script from template");
+        let path = self.directory.join("import.py");
+        fs::write(&path, script)?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,177 @@
+//! Bodies with stable IDs and optional names, so that analysis can refer to a
+//! body by what it is rather than by where it sits in the buffers
+use std::{collections::HashMap, fmt};
+
+use crate::structures::Body;
+
+/// Identifies a body of a `BodySet` for as long as it's in the set. IDs of
+/// removed bodies aren't reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BodyId(pub u32);
+
+impl fmt::Display for BodyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// The bodies uploaded to a pipeline, in buffer order, with an ID for each and
+/// a name for some
+#[derive(Debug, Clone, Default)]
+pub struct BodySet {
+    bodies: Vec<Body>,
+    ids: Vec<BodyId>,
+    names: Vec<Option<String>>,
+    /// Position of each ID and each name in `bodies`
+    indices: HashMap<BodyId, usize>,
+    by_name: HashMap<String, usize>,
+    next_id: u32,
+}
+
+impl BodySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Unnamed bodies with IDs counting up from zero in order
+    pub fn from_bodies(bodies: impl IntoIterator<Item = Body>) -> Self {
+        let mut set = Self::new();
+        for body in bodies {
+            set.push(body);
+        }
+        set
+    }
+
+    /// Append an unnamed body
+    pub fn push(&mut self, body: Body) -> BodyId {
+        self.insert(body, None)
+    }
+
+    /// Append a body which `get` finds by `name`. Panics if the name is taken.
+    pub fn push_named(&mut self, name: impl Into<String>, body: Body) -> BodyId {
+        self.insert(body, Some(name.into()))
+    }
+
+    fn insert(&mut self, body: Body, name: Option<String>) -> BodyId {
+        let id = BodyId(self.next_id);
+        self.next_id += 1;
+        let index = self.bodies.len();
+        if let Some(name) = &name {
+            assert!(
+                !self.by_name.contains_key(name),
+                "A body named {} is already in the set",
+                name
+            );
+            self.by_name.insert(name.clone(), index);
+        }
+        self.bodies.push(body);
+        self.ids.push(id);
+        self.names.push(name);
+        self.indices.insert(id, index);
+        id
+    }
+
+    /// Remove the body `id`, moving the bodies after it down by one
+    pub fn remove(&mut self, id: BodyId) -> Option<Body> {
+        let index = self.index_of(id)?;
+        let body = self.bodies.remove(index);
+        self.ids.remove(index);
+        self.names.remove(index);
+        self.reindex();
+        Some(body)
+    }
+
+    fn reindex(&mut self) {
+        self.indices = self
+            .ids
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| (id, index))
+            .collect();
+        self.by_name = self
+            .names
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| Some((name.clone()?, index)))
+            .collect();
+    }
+
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+
+    /// The bodies in buffer order, as uploaded with `Pipeline::write_bodies`
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    pub fn ids(&self) -> &[BodyId] {
+        &self.ids
+    }
+
+    /// Position of the body `id` in the buffers
+    pub fn index_of(&self, id: BodyId) -> Option<usize> {
+        self.indices.get(&id).copied()
+    }
+
+    /// ID of the body named `name`
+    pub fn id_of(&self, name: &str) -> Option<BodyId> {
+        self.by_name.get(name).map(|&index| self.ids[index])
+    }
+
+    pub fn name(&self, id: BodyId) -> Option<&str> {
+        self.names[self.index_of(id)?].as_deref()
+    }
+
+    /// The body named `name`
+    pub fn get(&self, name: &str) -> Option<&Body> {
+        self.by_name.get(name).map(|&index| &self.bodies[index])
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Body> {
+        let index = *self.by_name.get(name)?;
+        Some(&mut self.bodies[index])
+    }
+
+    pub fn get_by_id(&self, id: BodyId) -> Option<&Body> {
+        self.index_of(id).map(|index| &self.bodies[index])
+    }
+
+    /// Replace the states with `readback`, the bodies read back in buffer order
+    /// after uploading `bodies`
+    pub fn update(&mut self, readback: &[Body]) {
+        assert_eq!(
+            readback.len(),
+            self.bodies.len(),
+            "The readback doesn't hold the bodies of the set"
+        );
+        self.bodies.copy_from_slice(readback);
+    }
+
+    /// The ID, name and state of every body, in buffer order
+    pub fn iter(&self) -> impl Iterator<Item = (BodyId, Option<&str>, &Body)> {
+        self.ids
+            .iter()
+            .zip(&self.names)
+            .zip(&self.bodies)
+            .map(|((&id, name), body)| (id, name.as_deref(), body))
+    }
+
+    /// The named bodies of `readback`, read back in buffer order after
+    /// uploading `bodies`, by name
+    pub fn named<'a>(&'a self, readback: &'a [Body]) -> HashMap<&'a str, &'a Body> {
+        assert_eq!(
+            readback.len(),
+            self.bodies.len(),
+            "The readback doesn't hold the bodies of the set"
+        );
+        self.by_name
+            .iter()
+            .map(|(name, &index)| (name.as_str(), &readback[index]))
+            .collect()
+    }
+}
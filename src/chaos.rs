@@ -0,0 +1,67 @@
+//! Chaos indicators from the tangent vectors the kernels integrate alongside the
+//! bodies.
+//!
+//! The tangent vector of a body is the displacement of a shadow body in phase
+//! space, following the motion linearized about the body in the field of the
+//! others: the tidal tensor of their gravity and the gradient of the external
+//! potential. Other forces are left out, and the shadows don't pull on anything.
+//! From the growth rate `w = (d/dt delta . delta) / |delta|^2` of its norm,
+//!
+//! - the largest Lyapunov exponent is `1/t * int w dt`, tending to zero on
+//!   regular orbits
+//! - MEGNO is `Y = 2/t * int w s ds` and its mean `<Y> = 1/t * int Y ds` tends to
+//!   2 on quasi-periodic orbits, to 0 on stable periodic ones and grows like
+//!   `lambda * t / 2` on chaotic ones
+use crate::ic::Rng;
+use crate::structures::Tangent;
+
+/// Seed of the directions the tangent vectors start in, the same on every run
+const SEED: u64 = 0x7a6e_6765_6e74;
+
+/// The chaos indicators of a body
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ChaosIndicators {
+    /// Estimate of the largest Lyapunov exponent, per unit of time
+    pub lyapunov: f64,
+    pub megno: f64,
+    /// Mean of the MEGNO over the time so far, the indicator usually quoted
+    pub mean_megno: f64,
+}
+
+impl ChaosIndicators {
+    /// All zero until the first step
+    pub(crate) fn from_tangent(tangent: &Tangent) -> Self {
+        let time = tangent.time as f64;
+        if time <= 0.0 {
+            return Self::default();
+        }
+        let norm = tangent
+            .position
+            .iter()
+            .chain(&tangent.velocity)
+            .map(|&x| (x as f64).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        Self {
+            lyapunov: (tangent.log_growth as f64 + norm.ln()) / time,
+            megno: 2.0 * tangent.weighted_growth as f64 / time,
+            mean_megno: tangent.megno_integral as f64 / time,
+        }
+    }
+}
+
+/// Unit tangent vectors in phase space for `num_bodies` bodies, in directions
+/// drawn from a fixed sequence
+pub(crate) fn initial_tangents(num_bodies: usize) -> Vec<Tangent> {
+    let mut rng = Rng::new(SEED);
+    (0..num_bodies)
+        .map(|_| {
+            let scale = std::f64::consts::FRAC_1_SQRT_2;
+            Tangent {
+                position: rng.unit_vector().map(|x| (scale * x) as f32),
+                velocity: rng.unit_vector().map(|x| (scale * x) as f32),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
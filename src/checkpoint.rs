@@ -0,0 +1,216 @@
+//! Binary snapshots of the full simulation state, from which a run can be resumed.
+//!
+//! Files start with the same preamble as body files, with their own magic:
+//!
+//! | bytes | contents                                            |
+//! |-------|-----------------------------------------------------|
+//! | 4     | magic `PBCK`                                        |
+//! | 1     | byte order of everything that follows, `L` or `B`   |
+//! | 1     | reserved, zero                                      |
+//! | 2     | format version                                      |
+//!
+//! followed by the `StaticConfig` and the `DynamicConfig` as length-prefixed JSON
+//! strings, the time direction as a `u32`, the simulation time as an `f64` and the
+//! number of bodies as a `u64`. Then come the bodies, their properties and their
+//! gas states, each a run of 32-bit words. Every GPU structure is made of 32-bit
+//! fields without padding, so swapping the words converts between byte orders.
+//! The configs are stored by field name so that they can't be read into a
+//! build whose fields differ; version 1 stored the `DynamicConfig` as words.
+//!
+//! The accelerations are recomputed from the bodies at the start of every step
+//! and the initial conditions draw no random numbers, so neither is stored.
+//! Thrust profiles, ephemerides, tangent vectors and ensembles keep state on the
+//! device which isn't either, so `Pipeline::checkpoint` refuses to save them.
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bytemuck::Pod;
+
+use crate::format::{invalid_data, Endianness, OrderedReader, OrderedWriter};
+use crate::pipeline::TimeDirection;
+use crate::structures::{Body, BodyProperties, DynamicConfig, GasState, StaticConfig};
+
+pub const MAGIC: [u8; 4] = *b"PBCK";
+pub const VERSION: u16 = 2;
+
+/// Everything a pipeline needs to continue a run where it left off
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub static_config: StaticConfig,
+    pub dynamic_config: DynamicConfig,
+    pub time_direction: TimeDirection,
+    /// Simulated time accumulated over the steps so far
+    pub time: f64,
+    pub bodies: Vec<Body>,
+    pub properties: Vec<BodyProperties>,
+    pub gas: Vec<GasState>,
+}
+
+impl Checkpoint {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = self.write(BufWriter::new(File::create(path)?), Endianness::native())?;
+        writer.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn write<W: Write>(&self, writer: W, endianness: Endianness) -> io::Result<W> {
+        assert_eq!(self.bodies.len(), self.properties.len());
+        assert_eq!(self.bodies.len(), self.gas.len());
+        let mut writer = OrderedWriter::new(writer, endianness);
+        writer.write_bytes(&MAGIC)?;
+        writer.write_bytes(&[endianness.marker(), 0])?;
+        writer.write_u16(VERSION)?;
+        let static_config = serde_json::to_vec(&self.static_config)?;
+        writer.write_u64(static_config.len() as u64)?;
+        writer.write_bytes(&static_config)?;
+        write_dynamic_config(&mut writer, &self.dynamic_config)?;
+        writer.write_u32(match self.time_direction {
+            TimeDirection::Forward => 0,
+            TimeDirection::Backward => 1,
+        })?;
+        writer.write_f64(self.time)?;
+        writer.write_u64(self.bodies.len() as u64)?;
+        write_words(&mut writer, &self.bodies)?;
+        write_words(&mut writer, &self.properties)?;
+        write_words(&mut writer, &self.gas)?;
+        Ok(writer.into_inner())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut preamble = [0; 6];
+        reader.read_exact(&mut preamble)?;
+        if preamble[..4] != MAGIC {
+            return Err(invalid_data("Not a parabody checkpoint".to_string()));
+        }
+        let mut reader = OrderedReader::new(reader, Endianness::from_marker(preamble[4])?);
+        let version = reader.read_u16()?;
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported checkpoint version {} (expected {})",
+                version, VERSION
+            )));
+        }
+        let length = reader.read_u64()?;
+        let static_config = reader.read_vec(length)?;
+        let static_config: StaticConfig = serde_json::from_slice(&static_config)?;
+        let dynamic_config = read_dynamic_config(&mut reader)?;
+        let time_direction = match reader.read_u32()? {
+            0 => TimeDirection::Forward,
+            1 => TimeDirection::Backward,
+            direction => {
+                return Err(invalid_data(format!(
+                    "Unknown time direction {}",
+                    direction
+                )))
+            }
+        };
+        let time = reader.read_f64()?;
+        let num_bodies = reader.read_u64()?;
+        if num_bodies > static_config.max_bodies as u64 {
+            return Err(invalid_data(format!(
+                "{} bodies exceed the maximum of {}",
+                num_bodies, static_config.max_bodies
+            )));
+        }
+        let num_bodies = num_bodies as usize;
+        let bodies = read_word_vec(&mut reader, num_bodies)?;
+        let properties = read_word_vec(&mut reader, num_bodies)?;
+        let gas = read_word_vec(&mut reader, num_bodies)?;
+        Ok(Self {
+            static_config,
+            dynamic_config,
+            time_direction,
+            time,
+            bodies,
+            properties,
+            gas,
+        })
+    }
+}
+
+pub(crate) fn write_dynamic_config<W: Write>(
+    writer: &mut OrderedWriter<W>,
+    dynamic_config: &DynamicConfig,
+) -> io::Result<()> {
+    let dynamic_config = serde_json::to_vec(dynamic_config)?;
+    writer.write_u64(dynamic_config.len() as u64)?;
+    writer.write_bytes(&dynamic_config)
+}
+
+/// Fails on any missing or unknown field, as from a build with other fields
+pub(crate) fn read_dynamic_config<R: Read>(
+    reader: &mut OrderedReader<R>,
+) -> io::Result<DynamicConfig> {
+    let length = reader.read_u64()?;
+    let dynamic_config = reader.read_vec(length)?;
+    serde_json::from_slice(&dynamic_config).map_err(|error| {
+        invalid_data(format!(
+            "Dynamic config does not match this build: {}",
+            error
+        ))
+    })
+}
+
+pub(crate) fn write_words<W: Write, T: Pod>(
+    writer: &mut OrderedWriter<W>,
+    values: &[T],
+) -> io::Result<()> {
+    for &word in bytemuck::cast_slice::<T, u32>(values) {
+        writer.write_u32(word)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_words<R: Read, T: Pod>(
+    reader: &mut OrderedReader<R>,
+    values: &mut [T],
+) -> io::Result<()> {
+    for word in bytemuck::cast_slice_mut::<T, u32>(values) {
+        *word = reader.read_u32()?;
+    }
+    Ok(())
+}
+
+/// `count` values read with `read_words`, allocated a chunk at a time so that a
+/// corrupt count fails at the end of the file instead of allocating all of it
+pub(crate) fn read_word_vec<R: Read, T: Pod>(
+    reader: &mut OrderedReader<R>,
+    count: usize,
+) -> io::Result<Vec<T>> {
+    const CHUNK: usize = 1 << 16;
+    let mut values = Vec::new();
+    while values.len() < count {
+        let start = values.len();
+        values.resize(start + CHUNK.min(count - start), T::zeroed());
+        read_words(reader, &mut values[start..])?;
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dynamic_config_of_another_build_is_rejected() {
+        let mut dynamic_config = serde_json::to_value(DynamicConfig::default()).unwrap();
+        dynamic_config["drag"] = 0.5.into();
+        let dynamic_config = serde_json::to_vec(&dynamic_config).unwrap();
+        let mut file = OrderedWriter::new(Vec::new(), Endianness::Little);
+        file.write_u64(dynamic_config.len() as u64).unwrap();
+        file.write_bytes(&dynamic_config).unwrap();
+        let file = file.into_inner();
+
+        let error =
+            read_dynamic_config(&mut OrderedReader::new(file.as_slice(), Endianness::Little))
+                .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("unknown field `drag`"));
+    }
+}
@@ -0,0 +1,94 @@
+//! Diagnostics of the structure of a star cluster for following its core
+//! collapse, after Casertano & Hut (1985).
+//!
+//! The density center is the mean position of the bodies weighted by the
+//! density about them, and the core radius the density-weighted spread of the
+//! bodies about it. The density about a body is the `mu` of its nearest
+//! neighbors but the farthest over the volume they're in, so `G` times the
+//! mass density. The neighbors are searched on the GPU, see
+//! `Pipeline::search_neighbors`.
+use crate::analysis;
+use crate::groups::{self, LAGRANGIAN_FRACTIONS};
+use crate::neighbors::NeighborSearch;
+use crate::pipeline::Pipeline;
+use crate::structures::Body;
+
+/// Neighbors the density about every body is estimated from
+pub const DENSITY_NEIGHBORS: usize = 6;
+
+/// The structure of the bodies at one time
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterStats {
+    pub density_center: [f64; 3],
+    pub core_radius: f64,
+    /// Density-weighted mean of `G` times the density about the bodies
+    pub core_density: f64,
+    /// Radii about the density center enclosing the `LAGRANGIAN_FRACTIONS` of
+    /// the mass
+    pub lagrangian_radii: [f64; 3],
+}
+
+/// The structure of the bodies in the latest state of `pipeline`
+pub fn cluster_stats(pipeline: &mut Pipeline) -> ClusterStats {
+    let bodies = pipeline.read_bodies();
+    let neighbors = DENSITY_NEIGHBORS.min(bodies.len().saturating_sub(1)).max(1);
+    let search = pipeline.search_neighbors(neighbors);
+    stats(&bodies, &search)
+}
+
+/// The structure of `bodies` with their nearest neighbors in `search`. The
+/// center of mass stands in for the density center if no body has a density.
+pub fn stats(bodies: &[Body], search: &NeighborSearch) -> ClusterStats {
+    let densities: Vec<f64> = (0..bodies.len())
+        .map(|body| density(bodies, search, body))
+        .collect();
+    let mut weight = 0.0;
+    let mut squared_weight = 0.0;
+    let mut center = [0.0; 3];
+    for (body, &density) in bodies.iter().zip(&densities) {
+        weight += density;
+        squared_weight += density * density;
+        for (c, p) in center.iter_mut().zip(body.position) {
+            *c += density * p as f64;
+        }
+    }
+    let density_center = match weight > 0.0 {
+        true => center.map(|x| x / weight),
+        false => analysis::center_of_mass(bodies).unwrap_or_default(),
+    };
+    let mut spread = 0.0;
+    for (body, density) in bodies.iter().zip(&densities) {
+        let distance: f64 = (0..3)
+            .map(|axis| (body.position[axis] as f64 - density_center[axis]).powi(2))
+            .sum();
+        spread += density * density * distance;
+    }
+    let (core_radius, core_density) = match squared_weight > 0.0 {
+        true => ((spread / squared_weight).sqrt(), squared_weight / weight),
+        false => (0.0, 0.0),
+    };
+    ClusterStats {
+        density_center,
+        core_radius,
+        core_density,
+        lagrangian_radii: groups::lagrangian_radii(bodies, density_center, &LAGRANGIAN_FRACTIONS),
+    }
+}
+
+/// `G` times the density about `body`, zero without neighbors
+fn density(bodies: &[Body], search: &NeighborSearch, body: usize) -> f64 {
+    let neighbors = search.neighbors(body);
+    let (farthest, inner) = match neighbors.split_last() {
+        Some(split) => split,
+        None => return 0.0,
+    };
+    let radius = farthest.distance as f64;
+    if radius <= 0.0 {
+        return 0.0;
+    }
+    let mu: f64 = inner
+        .iter()
+        .map(|neighbor| bodies[neighbor.index as usize].mu as f64)
+        .sum();
+    mu / (4.0 / 3.0 * std::f64::consts::PI * radius.powi(3))
+}
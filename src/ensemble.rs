@@ -0,0 +1,105 @@
+//! Ensembles, many independent systems integrated side by side in one pipeline.
+//!
+//! A few bodies leave most of a GPU idle, so small systems such as the members
+//! of a parameter sweep are packed one after the other into the body buffer and
+//! stepped by the same dispatches. The direct sum only adds up the forces
+//! between bodies of the same system, each with the system's own softening.
+//! Everything else the kernels do to a body, the external potential, thrust or
+//! the post-Newtonian table, is still shared by every system.
+use std::ops::Range;
+
+use crate::analysis;
+use crate::structures::{Body, System};
+
+/// Systems of bodies, in the order they were added
+#[derive(Debug, Clone, Default)]
+pub struct Ensemble {
+    systems: Vec<System>,
+    bodies: Vec<Body>,
+}
+
+impl Ensemble {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a system of `bodies` with gravity softened by `softening`, returning
+    /// its index
+    pub fn push(&mut self, bodies: &[Body], softening: f32) -> usize {
+        assert!(!bodies.is_empty(), "A system needs at least one body");
+        self.systems.push(System {
+            first: self.bodies.len() as u32,
+            count: bodies.len() as u32,
+            softening,
+        });
+        self.bodies.extend_from_slice(bodies);
+        self.systems.len() - 1
+    }
+
+    /// Number of systems
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    /// The bodies of every system, packed as the pipeline holds them
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    pub fn systems(&self) -> &[System] {
+        &self.systems
+    }
+
+    /// Indices of the bodies of `system` among the packed bodies
+    pub fn range(&self, system: usize) -> Range<usize> {
+        range(&self.systems[system])
+    }
+
+    /// `packed`, one entry per body such as the bodies read back from the
+    /// pipeline, cut into the systems
+    pub fn split<'a, T>(&self, packed: &'a [T]) -> Vec<&'a [T]> {
+        split(&self.systems, packed)
+    }
+
+    /// Total energy of each system of the `packed` bodies
+    pub fn energies(&self, packed: &[Body]) -> Vec<f64> {
+        self.split(packed)
+            .into_iter()
+            .map(analysis::total_energy)
+            .collect()
+    }
+}
+
+fn range(system: &System) -> Range<usize> {
+    system.first as usize..(system.first + system.count) as usize
+}
+
+pub(crate) fn split<'a, T>(systems: &[System], packed: &'a [T]) -> Vec<&'a [T]> {
+    systems
+        .iter()
+        .map(|system| &packed[range(system)])
+        .collect()
+}
+
+/// `systems` after removing the bodies not `kept`, dropping the systems left
+/// without bodies
+pub(crate) fn compact(systems: &[System], kept: &[bool]) -> Vec<System> {
+    let mut first = 0;
+    systems
+        .iter()
+        .filter_map(|system| {
+            let count = kept[range(system)].iter().filter(|&&keep| keep).count() as u32;
+            let compacted = System {
+                first,
+                count,
+                ..*system
+            };
+            first += count;
+            (count > 0).then_some(compacted)
+        })
+        .collect()
+}
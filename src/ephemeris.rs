@@ -0,0 +1,204 @@
+//! Bodies whose states are prescribed by an ephemeris instead of integrated.
+//!
+//! The states are sampled at a fixed interval. After every step the kernels
+//! interpolate the samples with cubic Hermite polynomials and overwrite the
+//! prescribed bodies, so that the other bodies feel their gravity where the
+//! ephemeris has them. Tables are read from CSV files in the layout
+//! `output::CsvWriter` writes, or with the `spk` feature sampled from SPK kernels.
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use crate::format::invalid_data;
+
+#[cfg(feature = "spk")]
+pub mod spk;
+
+/// Evenly spaced samples of the states of the prescribed bodies
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ephemeris {
+    /// Simulated time of the first sample
+    pub start: f64,
+    /// Time between samples
+    pub interval: f64,
+    /// One per prescribed body, all with the same number of samples
+    pub tracks: Vec<Track>,
+}
+
+/// The samples of one body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    /// Index of the prescribed body
+    pub body: usize,
+    /// Position and velocity at every sample
+    pub states: Vec<([f64; 3], [f64; 3])>,
+}
+
+/// Columns a CSV table must have, any others are ignored
+const COLUMNS: [&str; 8] = ["time", "id", "x", "y", "z", "vx", "vy", "vz"];
+
+impl Ephemeris {
+    pub fn num_samples(&self) -> usize {
+        self.tracks.first().map_or(0, |track| track.states.len())
+    }
+
+    /// Simulated time of the last sample
+    pub fn end(&self) -> f64 {
+        self.start + self.interval * (self.num_samples().max(1) - 1) as f64
+    }
+
+    /// A table with a row per body and sample under a header naming the columns,
+    /// which include `time`, `id` and the state from `x` to `vz`. The tracks are
+    /// of the bodies with the index of their id. Every id needs a row at every
+    /// time and the times must be evenly spaced.
+    pub fn parse_csv(text: &str) -> io::Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let header: Vec<&str> = match lines.next() {
+            Some((_, header)) => header.split(',').map(str::trim).collect(),
+            None => return Err(invalid_data("Empty ephemeris table".to_string())),
+        };
+        let mut columns = [0; COLUMNS.len()];
+        for (column, name) in columns.iter_mut().zip(COLUMNS) {
+            *column = header
+                .iter()
+                .position(|&field| field == name)
+                .ok_or_else(|| invalid_data(format!("No {} column in the header", name)))?;
+        }
+        let mut samples: BTreeMap<usize, Vec<(f64, [f64; 6])>> = BTreeMap::new();
+        for (i, line) in lines {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let value = |column: usize| {
+                fields
+                    .get(columns[column])
+                    .and_then(|field| field.parse::<f64>().ok())
+                    .ok_or_else(|| {
+                        invalid_data(format!(
+                            "Line {}: {} is not a number",
+                            i + 1,
+                            COLUMNS[column]
+                        ))
+                    })
+            };
+            let id = fields
+                .get(columns[1])
+                .and_then(|field| field.parse::<usize>().ok())
+                .ok_or_else(|| invalid_data(format!("Line {}: id is not an index", i + 1)))?;
+            let mut state = [0.0; 6];
+            for (axis, component) in state.iter_mut().enumerate() {
+                *component = value(axis + 2)?;
+            }
+            samples.entry(id).or_default().push((value(0)?, state));
+        }
+        let (first_id, times): (usize, Vec<f64>) = match samples.iter().next() {
+            Some((&id, first)) => (id, first.iter().map(|(time, _)| *time).collect()),
+            None => {
+                return Err(invalid_data(
+                    "No samples in the ephemeris table".to_string(),
+                ))
+            }
+        };
+        if times.len() < 2 {
+            return Err(invalid_data(
+                "An ephemeris needs at least two samples per body".to_string(),
+            ));
+        }
+        let start = times[0];
+        let interval = (times[times.len() - 1] - start) / (times.len() - 1) as f64;
+        let evenly_spaced = interval > 0.0
+            && times
+                .iter()
+                .enumerate()
+                .all(|(i, time)| (time - start - i as f64 * interval).abs() <= 1e-6 * interval);
+        if !evenly_spaced {
+            return Err(invalid_data(
+                "The samples of an ephemeris must be evenly spaced in time".to_string(),
+            ));
+        }
+        let mut tracks = Vec::new();
+        for (id, rows) in samples {
+            let same_times = rows.len() == times.len()
+                && rows
+                    .iter()
+                    .zip(&times)
+                    .all(|((time, _), expected)| (time - expected).abs() <= 1e-6 * interval);
+            if !same_times {
+                return Err(invalid_data(format!(
+                    "Body {} isn't sampled at the times of body {}",
+                    id, first_id
+                )));
+            }
+            tracks.push(Track {
+                body: id,
+                states: rows
+                    .into_iter()
+                    .map(|(_, [x, y, z, vx, vy, vz])| ([x, y, z], [vx, vy, vz]))
+                    .collect(),
+            });
+        }
+        Ok(Self {
+            start,
+            interval,
+            tracks,
+        })
+    }
+
+    pub fn read_csv(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse_csv(&fs::read_to_string(path)?)
+    }
+
+    /// Keep the tracks of the ids in `bodies`, each paired with the index of the
+    /// body it prescribes
+    pub fn assign(self, bodies: &[(usize, usize)]) -> io::Result<Self> {
+        let tracks = bodies
+            .iter()
+            .map(|&(body, id)| {
+                self.tracks
+                    .iter()
+                    .find(|track| track.body == id)
+                    .map(|track| Track {
+                        body,
+                        states: track.states.clone(),
+                    })
+                    .ok_or_else(|| invalid_data(format!("No body {} in the ephemeris", id)))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { tracks, ..self })
+    }
+
+    /// Convert from the units of the samples, with `length_unit` and `time_unit`
+    /// the lengths and times of the samples in the simulation's units
+    pub fn scale(&mut self, length_unit: f64, time_unit: f64) {
+        self.start *= time_unit;
+        self.interval *= time_unit;
+        for track in &mut self.tracks {
+            for (position, velocity) in &mut track.states {
+                *position = position.map(|x| x * length_unit);
+                *velocity = velocity.map(|v| v * length_unit / time_unit);
+            }
+        }
+    }
+
+    /// The state of the body of `track` at `time`, interpolated as the kernels
+    /// do and held at the ends of the samples
+    pub fn state_at(&self, track: usize, time: f64) -> ([f64; 3], [f64; 3]) {
+        let states = &self.tracks[track].states;
+        let last = states.len() - 1;
+        let u = ((time - self.start) / self.interval).clamp(0.0, last as f64);
+        let sample = (u.floor() as usize).min(last - 1);
+        let (s, h) = (u - sample as f64, self.interval);
+        let ((p0, v0), (p1, v1)) = (states[sample], states[sample + 1]);
+        let mut position = [0.0; 3];
+        let mut velocity = [0.0; 3];
+        for axis in 0..3 {
+            position[axis] = (2.0 * s * s * s - 3.0 * s * s + 1.0) * p0[axis]
+                + (s * s * s - 2.0 * s * s + s) * h * v0[axis]
+                + (3.0 * s * s - 2.0 * s * s * s) * p1[axis]
+                + (s * s * s - s * s) * h * v1[axis];
+            velocity[axis] = 6.0 * (s * s - s) * (p0[axis] - p1[axis]) / h
+                + (3.0 * s * s - 4.0 * s + 1.0) * v0[axis]
+                + (3.0 * s * s - 2.0 * s) * v1[axis];
+        }
+        (position, velocity)
+    }
+}
@@ -0,0 +1,323 @@
+//! SPK kernels, the binary ephemerides of the SPICE toolkit, read without the
+//! toolkit. Only the Chebyshev segments of types 2 and 3 are supported, which
+//! is what the planetary ephemerides such as DE440 are made of.
+//!
+//! Bodies are named by their NAIF ids, such as 0 for the solar system barycenter,
+//! 10 for the Sun and 399 for the Earth. Epochs are in seconds of TDB past J2000,
+//! states in kilometers and kilometers per second in the frame of the kernel,
+//! which must be the equatorial J2000 frame.
+use std::{fs, io, mem, path::Path};
+
+use super::{Ephemeris, Track};
+use crate::format::invalid_data;
+
+/// Bytes in a record of the file
+const RECORD: usize = 1024;
+/// NAIF id of the inertial J2000 frame
+const J2000_FRAME: i32 = 1;
+/// Longest chain of centers followed to the barycenter
+const MAX_CHAIN: usize = 16;
+
+/// A segment's summary, giving the state of `target` relative to `center`
+/// over the epochs from `start` to `end`
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    target: i32,
+    center: i32,
+    frame: i32,
+    kind: i32,
+    start: f64,
+    end: f64,
+    /// Index of the first double of the segment's data
+    first: usize,
+    /// Index one past the last double
+    last: usize,
+}
+
+/// The segments of an SPK file, with the file in memory
+pub struct Kernel {
+    data: Vec<u8>,
+    little_endian: bool,
+    segments: Vec<Segment>,
+}
+
+impl Kernel {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(fs::read(path)?)
+    }
+
+    pub fn parse(data: Vec<u8>) -> io::Result<Self> {
+        if data.len() < RECORD || &data[..7] != b"DAF/SPK" {
+            return Err(invalid_data("Not an SPK file".to_string()));
+        }
+        let little_endian = match &data[88..96] {
+            b"LTL-IEEE" => true,
+            b"BIG-IEEE" => false,
+            // Files older than the format marker, which were written natively
+            _ => cfg!(target_endian = "little"),
+        };
+        let mut kernel = Self {
+            data,
+            little_endian,
+            segments: Vec::new(),
+        };
+        let (doubles, integers) = (kernel.integer_at(8), kernel.integer_at(12));
+        if doubles != 2 || integers != 6 {
+            return Err(invalid_data(format!(
+                "Summaries of {} doubles and {} integers, expected 2 and 6",
+                doubles, integers
+            )));
+        }
+        // Five doubles per summary, the two epochs and the six integers in pairs.
+        // Each record names the next, so a corrupt file could chain them in a cycle.
+        let records = kernel.data.len() / RECORD;
+        let mut visited = vec![false; records];
+        let mut record = kernel.integer_at(76) as usize;
+        while record > 0 {
+            if record > records {
+                return Err(invalid_data(format!(
+                    "Summary record {} is missing",
+                    record
+                )));
+            }
+            if mem::replace(&mut visited[record - 1], true) {
+                return Err(invalid_data(format!(
+                    "Summary record {} is reached twice, the records form a cycle",
+                    record
+                )));
+            }
+            let offset = (record - 1) * RECORD;
+            let count = kernel.double_at(offset + 16) as usize;
+            for i in 0..count.min((RECORD - 24) / 40) {
+                let summary = offset + 24 + 40 * i;
+                let address = |j: usize| kernel.integer_at(summary + 16 + 4 * j);
+                let segment = Segment {
+                    target: address(0),
+                    center: address(1),
+                    frame: address(2),
+                    kind: address(3),
+                    start: kernel.double_at(summary),
+                    end: kernel.double_at(summary + 8),
+                    first: (address(4) - 1).max(0) as usize,
+                    last: address(5).max(0) as usize,
+                };
+                if segment.last * 8 > kernel.data.len() || segment.first + 4 > segment.last {
+                    return Err(invalid_data(format!(
+                        "Segment of body {} is out of the file",
+                        segment.target
+                    )));
+                }
+                kernel.segments.push(segment);
+            }
+            record = kernel.double_at(offset) as usize;
+        }
+        Ok(kernel)
+    }
+
+    /// Position and velocity of `target` relative to `center` at `epoch`
+    pub fn state(&self, target: i32, center: i32, epoch: f64) -> io::Result<([f64; 3], [f64; 3])> {
+        let (target, center) = (
+            self.barycentric(target, epoch)?,
+            self.barycentric(center, epoch)?,
+        );
+        Ok((
+            [0, 1, 2].map(|i| target.0[i] - center.0[i]),
+            [0, 1, 2].map(|i| target.1[i] - center.1[i]),
+        ))
+    }
+
+    /// Samples of the bodies with the NAIF ids of `bodies`, each paired with the
+    /// index of the body it prescribes, relative to `center`. The samples are
+    /// `interval` seconds apart and cover `duration` seconds from `epoch`, which
+    /// is the ephemeris' time zero.
+    pub fn ephemeris(
+        &self,
+        bodies: &[(usize, i32)],
+        center: i32,
+        epoch: f64,
+        duration: f64,
+        interval: f64,
+    ) -> io::Result<Ephemeris> {
+        assert!(interval > 0.0, "The sampling interval must be positive");
+        let num_samples = (duration / interval).ceil().max(1.0) as usize + 1;
+        let tracks = bodies
+            .iter()
+            .map(|&(body, id)| {
+                let states = (0..num_samples)
+                    .map(|i| self.state(id, center, epoch + i as f64 * interval))
+                    .collect::<io::Result<_>>()?;
+                Ok(Track { body, states })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Ephemeris {
+            start: 0.0,
+            interval,
+            tracks,
+        })
+    }
+
+    /// State of `body` relative to the solar system barycenter, following the
+    /// centers of the segments
+    fn barycentric(&self, mut body: i32, epoch: f64) -> io::Result<([f64; 3], [f64; 3])> {
+        let mut position = [0.0; 3];
+        let mut velocity = [0.0; 3];
+        for _ in 0..MAX_CHAIN {
+            if body == 0 {
+                return Ok((position, velocity));
+            }
+            // Later segments take precedence over earlier ones
+            let segment = self
+                .segments
+                .iter()
+                .rev()
+                .find(|segment| {
+                    segment.target == body && (segment.start..=segment.end).contains(&epoch)
+                })
+                .ok_or_else(|| {
+                    invalid_data(format!("No segment of body {} at epoch {}", body, epoch))
+                })?;
+            let (p, v) = self.evaluate(segment, epoch)?;
+            for i in 0..3 {
+                position[i] += p[i];
+                velocity[i] += v[i];
+            }
+            body = segment.center;
+        }
+        Err(invalid_data(format!(
+            "The centers of body {} don't lead to the barycenter",
+            body
+        )))
+    }
+
+    /// State of the segment's target relative to its center
+    fn evaluate(&self, segment: &Segment, epoch: f64) -> io::Result<([f64; 3], [f64; 3])> {
+        if segment.frame != J2000_FRAME {
+            return Err(invalid_data(format!(
+                "Segment of body {} is in frame {}, only J2000 is supported",
+                segment.target, segment.frame
+            )));
+        }
+        let components = match segment.kind {
+            2 => 3,
+            3 => 6,
+            kind => {
+                return Err(invalid_data(format!(
+                    "Segment of body {} is of type {}, only types 2 and 3 are supported",
+                    segment.target, kind
+                )))
+            }
+        };
+        // The directory at the end: the first epoch, the span and size of the
+        // records and their number
+        let double = |index: usize| self.double_at(8 * index);
+        let init = double(segment.last - 4);
+        let length = double(segment.last - 3);
+        let size = double(segment.last - 2) as usize;
+        let count = double(segment.last - 1) as usize;
+        let degree = size.saturating_sub(2) / components;
+        if degree == 0 || count == 0 || segment.first + count * size > segment.last - 4 {
+            return Err(invalid_data(format!(
+                "Segment of body {} is malformed",
+                segment.target
+            )));
+        }
+        let index = (((epoch - init) / length).floor().max(0.0) as usize).min(count - 1);
+        let record = segment.first + index * size;
+        let (middle, radius) = (double(record), double(record + 1));
+        let t = (epoch - middle) / radius;
+        // Chebyshev polynomials and their derivatives at `t`
+        let mut polynomials = vec![1.0, t];
+        let mut derivatives = vec![0.0, 1.0];
+        for k in 2..degree {
+            polynomials.push(2.0 * t * polynomials[k - 1] - polynomials[k - 2]);
+            derivatives
+                .push(2.0 * polynomials[k - 1] + 2.0 * t * derivatives[k - 1] - derivatives[k - 2]);
+        }
+        let series = |component: usize, basis: &[f64]| {
+            let coefficients = record + 2 + component * degree;
+            (0..degree)
+                .map(|k| double(coefficients + k) * basis[k])
+                .sum::<f64>()
+        };
+        let position = [0, 1, 2].map(|i| series(i, &polynomials));
+        let velocity = match components {
+            3 => [0, 1, 2].map(|i| series(i, &derivatives) / radius),
+            _ => [3, 4, 5].map(|i| series(i, &polynomials)),
+        };
+        Ok((position, velocity))
+    }
+
+    fn double_at(&self, offset: usize) -> f64 {
+        let bytes = self.data[offset..offset + 8].try_into().unwrap();
+        match self.little_endian {
+            true => f64::from_le_bytes(bytes),
+            false => f64::from_be_bytes(bytes),
+        }
+    }
+
+    fn integer_at(&self, offset: usize) -> i32 {
+        let bytes = self.data[offset..offset + 4].try_into().unwrap();
+        match self.little_endian {
+            true => i32::from_le_bytes(bytes),
+            false => i32::from_be_bytes(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A little-endian file record followed by an empty summary record
+    fn empty_kernel() -> Vec<u8> {
+        let mut data = vec![0; 2 * RECORD];
+        data[..7].copy_from_slice(b"DAF/SPK");
+        data[8..12].copy_from_slice(&2i32.to_le_bytes());
+        data[12..16].copy_from_slice(&6i32.to_le_bytes());
+        data[76..80].copy_from_slice(&2i32.to_le_bytes());
+        data[88..96].copy_from_slice(b"LTL-IEEE");
+        data
+    }
+
+    fn error(data: Vec<u8>) -> String {
+        match Kernel::parse(data) {
+            Ok(_) => panic!("The kernel should be rejected"),
+            Err(error) => {
+                assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+                error.to_string()
+            }
+        }
+    }
+
+    #[test]
+    fn empty_kernel_has_no_segments() {
+        assert!(Kernel::parse(empty_kernel()).unwrap().segments.is_empty());
+    }
+
+    #[test]
+    fn truncated_file() {
+        let mut data = empty_kernel();
+        data.truncate(RECORD);
+        assert_eq!(error(data.clone()), "Summary record 2 is missing");
+        data.truncate(RECORD / 2);
+        assert_eq!(error(data), "Not an SPK file");
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut data = empty_kernel();
+        data[..7].copy_from_slice(b"DAF/PCK");
+        assert_eq!(error(data), "Not an SPK file");
+    }
+
+    #[test]
+    fn cyclic_summary_chain() {
+        let mut data = empty_kernel();
+        // The summary record names itself as the next
+        data[RECORD..RECORD + 8].copy_from_slice(&2f64.to_le_bytes());
+        assert_eq!(
+            error(data),
+            "Summary record 2 is reached twice, the records form a cycle"
+        );
+    }
+}
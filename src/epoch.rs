@@ -0,0 +1,351 @@
+//! Calendar epochs and the conversions between the time scales UTC, TAI, TT and
+//! TDB.
+//!
+//! Epochs are kept as seconds of TDB past J2000, the time argument of SPK
+//! kernels. UTC follows the leap seconds from 1972 to the latest at the start of
+//! 2017 and is taken as ten seconds behind TAI before. TDB differs from TT by the
+//! two largest periodic terms, good to about thirty microseconds.
+use std::fmt;
+
+/// Julian date of J2000, 2000 January 1.5
+const J2000: f64 = 2_451_545.0;
+/// Julian day number of the day J2000 falls on
+const J2000_DAY: i64 = 2_451_545;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+/// TT minus TAI in seconds
+const TT_MINUS_TAI: f64 = 32.184;
+/// Year and month from whose first day on TAI was ahead of UTC by the seconds
+const LEAP_SECONDS: [(i32, u32, f64); 28] = [
+    (1972, 1, 10.0),
+    (1972, 7, 11.0),
+    (1973, 1, 12.0),
+    (1974, 1, 13.0),
+    (1975, 1, 14.0),
+    (1976, 1, 15.0),
+    (1977, 1, 16.0),
+    (1978, 1, 17.0),
+    (1979, 1, 18.0),
+    (1980, 1, 19.0),
+    (1981, 7, 20.0),
+    (1982, 7, 21.0),
+    (1983, 7, 22.0),
+    (1985, 7, 23.0),
+    (1988, 1, 24.0),
+    (1990, 1, 25.0),
+    (1991, 1, 26.0),
+    (1992, 7, 27.0),
+    (1993, 7, 28.0),
+    (1994, 7, 29.0),
+    (1996, 1, 30.0),
+    (1997, 7, 31.0),
+    (1999, 1, 32.0),
+    (2006, 1, 33.0),
+    (2009, 1, 34.0),
+    (2012, 7, 35.0),
+    (2015, 7, 36.0),
+    (2017, 1, 37.0),
+];
+
+/// A time scale epochs can be given and read in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeScale {
+    Utc,
+    Tai,
+    Tt,
+    Tdb,
+}
+
+impl TimeScale {
+    pub const ALL: [Self; 4] = [Self::Utc, Self::Tai, Self::Tt, Self::Tdb];
+
+    /// Name in scenario files and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utc => "UTC",
+            Self::Tai => "TAI",
+            Self::Tt => "TT",
+            Self::Tdb => "TDB",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scale| scale.name() == name)
+    }
+}
+
+/// An instant, as seconds of TDB past J2000
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Epoch {
+    tdb: f64,
+}
+
+impl Epoch {
+    pub const J2000: Self = Self { tdb: 0.0 };
+
+    pub fn from_tdb_seconds(seconds: f64) -> Self {
+        Self { tdb: seconds }
+    }
+
+    pub fn tdb_seconds(self) -> f64 {
+        self.tdb
+    }
+
+    /// The epoch `seconds` of TDB later, earlier if negative
+    pub fn after(self, seconds: f64) -> Self {
+        Self {
+            tdb: self.tdb + seconds,
+        }
+    }
+
+    /// From the seconds past J2000 of `scale`, for UTC counting days of 86400
+    /// seconds
+    pub fn from_seconds(seconds: f64, scale: TimeScale) -> Self {
+        let tt = match scale {
+            TimeScale::Tdb => return Self { tdb: seconds },
+            TimeScale::Tt => seconds,
+            TimeScale::Tai => seconds + TT_MINUS_TAI,
+            TimeScale::Utc => seconds + tai_minus_utc(seconds) + TT_MINUS_TAI,
+        };
+        Self {
+            tdb: tt + tdb_minus_tt(tt),
+        }
+    }
+
+    /// Seconds past J2000 of `scale`
+    pub fn seconds(self, scale: TimeScale) -> f64 {
+        let tt = self.tdb - tdb_minus_tt(self.tdb);
+        match scale {
+            TimeScale::Tdb => self.tdb,
+            TimeScale::Tt => tt,
+            TimeScale::Tai => tt - TT_MINUS_TAI,
+            TimeScale::Utc => {
+                let tai = tt - TT_MINUS_TAI;
+                tai - tai_minus_utc(tai - tai_minus_utc(tai))
+            }
+        }
+    }
+
+    pub fn from_julian_date(julian_date: f64, scale: TimeScale) -> Self {
+        Self::from_seconds((julian_date - J2000) * SECONDS_PER_DAY, scale)
+    }
+
+    pub fn julian_date(self, scale: TimeScale) -> f64 {
+        J2000 + self.seconds(scale) / SECONDS_PER_DAY
+    }
+
+    /// From a date of the Gregorian calendar and the time of day in `scale`,
+    /// `None` if there's no such date
+    pub fn from_calendar(
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: f64,
+        scale: TimeScale,
+    ) -> Option<Self> {
+        let valid = (1..=12).contains(&month)
+            && day >= 1
+            && day <= days_in_month(year, month)
+            && hour < 24
+            && minute < 60
+            && (0.0..61.0).contains(&second);
+        if !valid {
+            return None;
+        }
+        let days = day_number(year, month, day) - J2000_DAY;
+        let seconds = days as f64 * SECONDS_PER_DAY - 0.5 * SECONDS_PER_DAY
+            + (hour * 3600 + minute * 60) as f64
+            + second;
+        Some(Self::from_seconds(seconds, scale))
+    }
+
+    /// The date and the time of day in `scale` to the millisecond, as the year,
+    /// month, day, hour, minute and millisecond of the minute
+    pub fn calendar(self, scale: TimeScale) -> (i32, u32, u32, u32, u32, u32) {
+        // Milliseconds since midnight at the start of the day of J2000
+        let total = ((self.seconds(scale) + 0.5 * SECONDS_PER_DAY) * 1000.0).round() as i64;
+        let days = total.div_euclid(86_400_000);
+        let milliseconds = total.rem_euclid(86_400_000) as u32;
+        let (year, month, day) = civil_date(J2000_DAY + days);
+        (
+            year,
+            month,
+            day,
+            milliseconds / 3_600_000,
+            milliseconds / 60_000 % 60,
+            milliseconds % 60_000,
+        )
+    }
+
+    /// An ISO 8601 date and time such as `2024-03-01T12:00:00.5`, or a Julian
+    /// date such as `JD 2460371.0`, followed by the name of the time scale, UTC
+    /// if none is given
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (text, scale) = match text.rsplit_once(char::is_whitespace) {
+            Some((rest, name)) => match TimeScale::from_name(name) {
+                Some(scale) => (rest.trim_end(), scale),
+                None => (text, TimeScale::Utc),
+            },
+            None => (text, TimeScale::Utc),
+        };
+        if let Some(number) = text.strip_prefix("JD") {
+            let julian_date: f64 = number.trim().parse().ok()?;
+            return julian_date
+                .is_finite()
+                .then(|| Self::from_julian_date(julian_date, scale));
+        }
+        let text = text.strip_suffix('Z').unwrap_or(text);
+        let (date, time) = match text.split_once(['T', ' ']) {
+            Some((date, time)) => (date, time.trim()),
+            None => (text, "00:00"),
+        };
+        // A leading minus for years before 1 BC
+        let (sign, date) = match date.strip_prefix('-') {
+            Some(date) => (-1, date),
+            None => (1, date),
+        };
+        let mut date = date.split('-');
+        let year: i32 = date.next()?.parse().ok()?;
+        let month = date.next()?.parse().ok()?;
+        let day = date.next()?.parse().ok()?;
+        let mut time = time.split(':');
+        let hour = time.next()?.parse().ok()?;
+        let minute = time.next()?.parse().ok()?;
+        let second = match time.next() {
+            Some(second) => second.parse().ok()?,
+            None => 0.0,
+        };
+        if date.next().is_some() || time.next().is_some() {
+            return None;
+        }
+        Self::from_calendar(sign * year, month, day, hour, minute, second, scale)
+    }
+}
+
+impl fmt::Display for Epoch {
+    /// The ISO 8601 date and time in UTC to the millisecond
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day, hour, minute, milliseconds) = self.calendar(TimeScale::Utc);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03} UTC",
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            milliseconds / 1000,
+            milliseconds % 1000
+        )
+    }
+}
+
+/// TAI minus UTC at `seconds` past J2000 of either
+fn tai_minus_utc(seconds: f64) -> f64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|&&(year, month, _)| {
+            let days = day_number(year, month, 1) - J2000_DAY;
+            seconds >= days as f64 * SECONDS_PER_DAY - 0.5 * SECONDS_PER_DAY
+        })
+        .map_or(LEAP_SECONDS[0].2, |&(_, _, leap)| leap)
+}
+
+/// TDB minus TT at `seconds` past J2000 of either, from the mean anomaly of the
+/// Earth
+fn tdb_minus_tt(seconds: f64) -> f64 {
+    let g = (357.53 + 0.985_600_28 * seconds / SECONDS_PER_DAY).to_radians();
+    0.001_657 * g.sin() + 0.000_014 * (2.0 * g).sin()
+}
+
+fn is_leap_year(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Julian day number of a date of the proleptic Gregorian calendar
+fn day_number(year: i32, month: u32, day: u32) -> i64 {
+    let a = (14 - month as i64) / 12;
+    let y = year as i64 + 4800 - a;
+    let m = month as i64 + 12 * a - 3;
+    day as i64 + (153 * m + 2) / 5 + 365 * y + y.div_euclid(4) - y.div_euclid(100)
+        + y.div_euclid(400)
+        - 32045
+}
+
+/// The date of the proleptic Gregorian calendar of a Julian day number
+fn civil_date(day_number: i64) -> (i32, u32, u32) {
+    let a = day_number + 32044;
+    let b = (4 * a + 3).div_euclid(146_097);
+    let c = a - 146_097 * b / 4;
+    let d = (4 * c + 3) / 1461;
+    let e = c - 1461 * d / 4;
+    let m = (5 * e + 2) / 153;
+    let day = e - (153 * m + 2) / 5 + 1;
+    let month = m + 3 - 12 * (m / 10);
+    let year = 100 * b + d - 4800 + m / 10;
+    (year as i32, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn j2000_is_the_zero_point() {
+        let epoch = Epoch::from_calendar(2000, 1, 1, 12, 0, 0.0, TimeScale::Tdb).unwrap();
+        assert_eq!(epoch, Epoch::J2000);
+        assert_eq!(Epoch::J2000.julian_date(TimeScale::Tdb), J2000);
+        // TT is within the periodic terms of TDB, and UTC 64.184 seconds behind
+        let tt = Epoch::from_calendar(2000, 1, 1, 12, 0, 0.0, TimeScale::Tt).unwrap();
+        assert!(tt.tdb_seconds().abs() < 1e-3);
+        assert_eq!(
+            Epoch::J2000.calendar(TimeScale::Utc),
+            (2000, 1, 1, 11, 58, 55_816)
+        );
+    }
+
+    #[test]
+    fn julian_date_and_calendar_round_trip() {
+        let noon = Epoch::from_calendar(2024, 3, 1, 12, 0, 0.0, TimeScale::Utc).unwrap();
+        assert!((noon.julian_date(TimeScale::Utc) - 2_460_371.0).abs() < 1e-9);
+        let dates = [
+            (1969, 7, 20, 20, 17, 40_000),
+            (1999, 12, 31, 23, 59, 59_500),
+            (2017, 1, 1, 0, 0, 0),
+            (2024, 2, 29, 6, 30, 15_250),
+        ];
+        for (year, month, day, hour, minute, milliseconds) in dates {
+            for scale in TimeScale::ALL {
+                let epoch = Epoch::from_calendar(
+                    year,
+                    month,
+                    day,
+                    hour,
+                    minute,
+                    milliseconds as f64 / 1000.0,
+                    scale,
+                )
+                .unwrap();
+                let epoch = Epoch::from_julian_date(epoch.julian_date(scale), scale);
+                assert_eq!(
+                    epoch.calendar(scale),
+                    (year, month, day, hour, minute, milliseconds),
+                    "in {}",
+                    scale.name()
+                );
+            }
+        }
+    }
+}
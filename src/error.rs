@@ -0,0 +1,111 @@
+//! Errors of the pipeline, with the GPU calls they came from
+use std::{error::Error, fmt, io};
+
+use wgpu::ErrorFilter;
+
+use crate::shader::ShaderDiagnostic;
+
+#[derive(Debug)]
+pub enum ParabodyError {
+    Io(io::Error),
+    /// A shader template failed to render
+    Template(String),
+    /// A rendered shader failed to parse or validate
+    Shader(Box<ShaderDiagnostic>),
+    /// A struct of a shader doesn't match the Rust struct it shares buffers with
+    Layout(String),
+    /// A pass takes its entry point from a module the pipeline doesn't have
+    UnknownModule(String),
+    /// The adapter can't run the static configuration, even reduced
+    Unsupported(String),
+    /// More bodies were written than the pipeline was created for
+    TooManyBodies {
+        bodies: usize,
+        max_bodies: u32,
+    },
+    /// Data written to the pipeline doesn't fit its bodies or configuration
+    InvalidInput(String),
+    /// wgpu rejected a call, `context` names the buffer or kernel it was for
+    Gpu {
+        context: String,
+        source: wgpu::Error,
+    },
+}
+
+impl fmt::Display for ParabodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParabodyError::Io(error) => write!(f, "{}", error),
+            ParabodyError::Template(error) => write!(f, "Could not render shader: {}", error),
+            ParabodyError::Shader(diagnostic) => write!(f, "Invalid shader: {}", diagnostic),
+            ParabodyError::Layout(error) => {
+                write!(f, "Shader struct doesn't match Rust: {}", error)
+            }
+            ParabodyError::UnknownModule(name) => write!(f, "No shader module named {}", name),
+            ParabodyError::Unsupported(error) => write!(f, "Unsupported by the adapter: {}", error),
+            ParabodyError::TooManyBodies { bodies, max_bodies } => write!(
+                f,
+                "{} bodies exceed the maximum of {} the pipeline was created for",
+                bodies, max_bodies
+            ),
+            ParabodyError::InvalidInput(error) => write!(f, "{}", error),
+            ParabodyError::Gpu { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl Error for ParabodyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParabodyError::Io(error) => Some(error),
+            ParabodyError::Template(_)
+            | ParabodyError::Shader(_)
+            | ParabodyError::Layout(_)
+            | ParabodyError::UnknownModule(_)
+            | ParabodyError::Unsupported(_)
+            | ParabodyError::TooManyBodies { .. }
+            | ParabodyError::InvalidInput(_) => None,
+            ParabodyError::Gpu { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<io::Error> for ParabodyError {
+    fn from(error: io::Error) -> Self {
+        ParabodyError::Io(error)
+    }
+}
+
+/// `error` followed by its sources, which tera and naga keep the details in
+pub fn describe(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message = format!("{}: {}", message, cause);
+        source = cause.source();
+    }
+    message
+}
+
+/// Call `f` inside error scopes of `device`, so that validation errors and
+/// running out of memory are returned with `context` instead of reaching the
+/// device's handler, which panics
+pub fn scoped<T>(
+    device: &wgpu::Device,
+    context: impl FnOnce() -> String,
+    f: impl FnOnce() -> T,
+) -> Result<T, ParabodyError> {
+    device.push_error_scope(ErrorFilter::OutOfMemory);
+    device.push_error_scope(ErrorFilter::Validation);
+    let value = f();
+    // Native scopes resolve immediately
+    let validation = pollster::block_on(device.pop_error_scope());
+    let out_of_memory = pollster::block_on(device.pop_error_scope());
+    match validation.or(out_of_memory) {
+        Some(source) => Err(ParabodyError::Gpu {
+            context: context(),
+            source,
+        }),
+        None => Ok(value),
+    }
+}
@@ -0,0 +1,87 @@
+//! Bodies escaping the system, found on the host from a snapshot of the bodies.
+//!
+//! Distances are measured from the `mu`-weighted barycenter of all bodies, or
+//! the origin if no body has a `mu`. The energy of a body is that of its orbit
+//! about the barycenter of the other bodies, as if their whole `mu` sat there.
+use crate::structures::Body;
+use crate::vector::norm;
+
+/// When a body counts as escaped, either criterion is enough
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EscapeCriteria {
+    /// Distance from the barycenter beyond which bodies have escaped
+    pub max_distance: Option<f64>,
+    /// Whether bodies on unbound orbits about the other bodies have escaped
+    pub unbound: bool,
+}
+
+/// A body found to have escaped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ejection {
+    /// Index of the body
+    pub body: usize,
+    /// Distance from the barycenter
+    pub distance: f64,
+    /// Specific energy of the orbit about the other bodies, `None` if they have
+    /// no `mu`
+    pub energy: Option<f64>,
+}
+
+impl EscapeCriteria {
+    pub fn is_enabled(&self) -> bool {
+        self.max_distance.is_some() || self.unbound
+    }
+
+    /// The bodies which have escaped, in the order of their indices
+    pub fn escapers(&self, bodies: &[Body]) -> Vec<Ejection> {
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+        // mu-weighted sums of the positions and velocities
+        let mut total = 0.0;
+        let mut position = [0.0; 3];
+        let mut velocity = [0.0; 3];
+        for body in bodies {
+            let mu = body.mu as f64;
+            total += mu;
+            for axis in 0..3 {
+                position[axis] += mu * body.position[axis] as f64;
+                velocity[axis] += mu * body.velocity[axis] as f64;
+            }
+        }
+        let barycenter = match total > 0.0 {
+            true => position.map(|p| p / total),
+            false => [0.0; 3],
+        };
+        let mut ejections = Vec::new();
+        for (i, body) in bodies.iter().enumerate() {
+            let mu = body.mu as f64;
+            let r = [0, 1, 2].map(|axis| body.position[axis] as f64);
+            let v = [0, 1, 2].map(|axis| body.velocity[axis] as f64);
+            let distance = norm([0, 1, 2].map(|axis| r[axis] - barycenter[axis]));
+            let others = total - mu;
+            let energy = match others > 0.0 {
+                true => {
+                    let separation = norm(
+                        [0, 1, 2].map(|axis| r[axis] - (position[axis] - mu * r[axis]) / others),
+                    );
+                    let speed = norm(
+                        [0, 1, 2].map(|axis| v[axis] - (velocity[axis] - mu * v[axis]) / others),
+                    );
+                    Some(0.5 * speed * speed - total / separation)
+                }
+                false => None,
+            };
+            let far = self.max_distance.is_some_and(|max| distance > max);
+            let unbound = self.unbound && energy.is_some_and(|energy| energy > 0.0);
+            if far || unbound {
+                ejections.push(Ejection {
+                    body: i,
+                    distance,
+                    energy,
+                });
+            }
+        }
+        ejections
+    }
+}
@@ -0,0 +1,399 @@
+//! Events, the moments a quantity of a body crosses zero, such as a body
+//! crossing a plane, approaching another or colliding.
+//!
+//! Each predicate is a WGSL expression of the body index `idx` rendered into
+//! the shader, see `PipelineBuilder::events`. A kernel after every step
+//! evaluates the predicates on every body and appends the sign changes to an
+//! event log, so the GPU only narrows an event down to its step. The host
+//! finds its time by bisection, evaluating the predicates on the states
+//! interpolated within the step, see `EventMonitor`.
+//!
+//! ```toml
+//! [[events]]
+//! name = "ascending node"
+//! kind = "Plane"
+//! axis = "z"
+//! direction = "Rising"
+//!
+//! [[events]]
+//! name = "impact"
+//! kind = "Approach"
+//! body = 0
+//! distance = 0.01
+//! terminal = true
+//! ```
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::Checkpoint;
+use crate::maneuver::ManeuverSchedule;
+use crate::manifest::RunManifest;
+use crate::pipeline::{Pipeline, TimeDirection};
+use crate::structures::Body;
+use crate::trajectory::{self, Trajectory};
+
+/// Halvings of the step bracketing an event, as far as the `f32` fraction of
+/// the step interpolated at resolves
+const BISECTIONS: usize = 24;
+
+/// Which sign changes of a predicate are events, in the direction of integration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventDirection {
+    /// From negative to zero or positive
+    Rising,
+    /// From zero or positive to negative
+    Falling,
+    Any,
+}
+
+/// A predicate compiled into the kernels, an event whenever its value crosses
+/// zero in `direction`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct EventPredicate {
+    /// WGSL expression of the body index `idx`. Besides what the forces can
+    /// read, such as `input[idx]`, the shader has `event_separation(idx, other)`,
+    /// the distance between two bodies, and `event_nearest(idx)`, the distance
+    /// to the nearest other body of the same system.
+    pub expression: String,
+    pub direction: EventDirection,
+    /// Body whose state the events capture too, such as the center of a plane
+    pub reference: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// Name of the axis as a component of a WGSL vector
+    pub fn component(self) -> &'static str {
+        match self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        }
+    }
+}
+
+/// What an event of a scenario watches for
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind")]
+pub enum EventCondition {
+    /// A body crossing the plane where its coordinate along `axis` is `offset`,
+    /// relative to the body at index `center` if there is one
+    Plane {
+        axis: Axis,
+        offset: f64,
+        center: Option<usize>,
+    },
+    /// A body coming within `distance` of the body at index `body`
+    Approach { body: usize, distance: f64 },
+    /// A body coming within `distance` of any other body of its system
+    Collision { distance: f64 },
+    /// A WGSL expression, see `EventPredicate::expression`
+    Predicate { predicate: String },
+}
+
+/// An event of a scenario
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EventDefinition {
+    pub name: String,
+    #[serde(flatten)]
+    pub condition: EventCondition,
+    /// By default any crossing of a plane or predicate, and bodies getting
+    /// closer for approaches and collisions
+    pub direction: Option<EventDirection>,
+    /// Stop the run at the end of the submission the event happens in
+    pub terminal: bool,
+}
+
+impl EventDefinition {
+    pub fn predicate(&self) -> EventPredicate {
+        let (expression, direction, reference) = match &self.condition {
+            EventCondition::Plane {
+                axis,
+                offset,
+                center,
+            } => {
+                let component = axis.component();
+                let origin = match center {
+                    Some(center) => format!(" - input[{}u].position.{}", center, component),
+                    None => String::new(),
+                };
+                (
+                    format!(
+                        "input[idx].position.{}{} - ({:?})",
+                        component, origin, *offset as f32
+                    ),
+                    EventDirection::Any,
+                    *center,
+                )
+            }
+            EventCondition::Approach { body, distance } => (
+                format!(
+                    "event_separation(idx, {}u) - ({:?})",
+                    body, *distance as f32
+                ),
+                EventDirection::Falling,
+                Some(*body),
+            ),
+            EventCondition::Collision { distance } => (
+                format!("event_nearest(idx) - ({:?})", *distance as f32),
+                EventDirection::Falling,
+                None,
+            ),
+            EventCondition::Predicate { predicate } => {
+                (predicate.clone(), EventDirection::Any, None)
+            }
+        };
+        EventPredicate {
+            expression,
+            direction: self.direction.unwrap_or(direction),
+            reference,
+        }
+    }
+}
+
+/// A step in which a predicate of a body crossed zero, as the GPU logged it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepCrossing {
+    /// Index of the predicate among those the pipeline was built with
+    pub predicate: usize,
+    pub body: usize,
+    /// Step since the log was last read, counting from zero
+    pub step: usize,
+    /// Simulated times at the start and the end of the step
+    pub times: [f64; 2],
+    /// Values of the predicate at the start and the end of the step
+    pub values: [f32; 2],
+}
+
+/// The crossings logged since the log was last read, see `Pipeline::read_events`
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    /// Steps logged
+    pub steps: usize,
+    /// In the order of the steps, then the predicates, then the bodies
+    pub crossings: Vec<StepCrossing>,
+    /// Crossings past the capacity of the log which were lost, none of them in
+    /// a step before the last kept
+    pub dropped: usize,
+}
+
+/// An event with its time found
+#[derive(Debug, Clone, Copy)]
+pub struct Event {
+    /// Index of the predicate among those the pipeline was built with
+    pub predicate: usize,
+    pub body: usize,
+    pub time: f64,
+    /// The body at the time of the event, interpolated
+    pub state: Body,
+    /// The reference body of the predicate at the time of the event
+    pub reference: Option<Body>,
+}
+
+/// Finds the times of the events a pipeline logs, by going back to the start
+/// of the steps checked and running up to each step with a crossing again
+pub struct EventMonitor {
+    /// The state the steps checked next start from, with its maneuvers
+    start: (Checkpoint, ManeuverSchedule),
+}
+
+impl EventMonitor {
+    /// Start checking from the current state of `pipeline`
+    pub fn new(pipeline: &mut Pipeline) -> Self {
+        pipeline.read_events();
+        Self {
+            start: (pipeline.capture(), pipeline.maneuvers().clone()),
+        }
+    }
+
+    /// Check the steps from the current state of `pipeline` on, dropping those
+    /// logged so far. Must be called whenever the bodies change other than by
+    /// the steps, such as when they're removed.
+    pub fn restart(&mut self, pipeline: &mut Pipeline) {
+        *self = Self::new(pipeline);
+    }
+
+    /// The events of the steps since the start or the last check, in order of
+    /// time. Leaves `pipeline` in the state it was in, the steps with events are
+    /// run again to find their times, and checks the steps after it next.
+    pub fn check(&mut self, pipeline: &mut Pipeline) -> Vec<Event> {
+        let mut events = pipeline.without_progress(|pipeline| self.replay(pipeline));
+        self.restart(pipeline);
+        events.sort_by(|a, b| {
+            let order = match pipeline.time_direction() {
+                TimeDirection::Forward => a.time.total_cmp(&b.time),
+                TimeDirection::Backward => b.time.total_cmp(&a.time),
+            };
+            order.then((a.predicate, a.body).cmp(&(b.predicate, b.body)))
+        });
+        events
+    }
+
+    /// Run the steps logged again from the start, stopping at those with
+    /// crossings to find the times of their events
+    fn replay(&self, pipeline: &mut Pipeline) -> Vec<Event> {
+        let mut events = Vec::new();
+        let mut log = pipeline.read_events();
+        let (mut checkpoint, mut maneuvers) = self.start.clone();
+        let mut remaining = log.steps;
+        while let Some(first) = log.crossings.first().map(|crossing| crossing.step) {
+            pipeline
+                .load(&checkpoint)
+                .expect("Captured from the same pipeline");
+            pipeline.set_maneuvers(maneuvers);
+            pipeline.submit_and_block(first);
+            pipeline.read_events();
+            let mut bracket = Trajectory::new(pipeline.dynamic_config().box_size);
+            let before = frame(pipeline);
+            pipeline.submit_and_block(1);
+            let step = pipeline.read_events();
+            if step.dropped > 0 {
+                log::warn!(
+                    "Lost {} events of the step at t = {}, the event log is full",
+                    step.dropped,
+                    pipeline.time()
+                );
+            }
+            let after = frame(pipeline);
+            let end = (pipeline.capture(), pipeline.maneuvers().clone());
+            match pipeline.time_direction() {
+                TimeDirection::Forward => {
+                    before.push_to(&mut bracket);
+                    after.push_to(&mut bracket);
+                }
+                TimeDirection::Backward => {
+                    after.push_to(&mut bracket);
+                    before.push_to(&mut bracket);
+                }
+            }
+            for crossing in &step.crossings {
+                events.push(refine(pipeline, &bracket, crossing));
+            }
+            (checkpoint, maneuvers) = end;
+            pipeline
+                .load(&checkpoint)
+                .expect("Captured from the same pipeline");
+            pipeline.set_maneuvers(maneuvers.clone());
+            remaining -= first + 1;
+            pipeline.submit_and_block(remaining);
+            log = pipeline.read_events();
+        }
+        events
+    }
+}
+
+/// The state at one end of a step, with its accelerations where the dense
+/// output has them
+struct Frame {
+    time: f64,
+    bodies: Vec<Body>,
+    accelerations: Option<Vec<[f32; 3]>>,
+}
+
+impl Frame {
+    fn push_to(self, trajectory: &mut Trajectory) {
+        match &self.accelerations {
+            Some(accelerations) => trajectory.push_dense(self.time, &self.bodies, accelerations),
+            None => trajectory.push(self.time, &self.bodies),
+        }
+    }
+}
+
+/// The latest state of `pipeline`, with the accelerations only forwards in
+/// time, as the velocities are only brought level with the positions then
+fn frame(pipeline: &mut Pipeline) -> Frame {
+    let time = pipeline.time();
+    match pipeline.time_direction() {
+        TimeDirection::Forward => {
+            let (bodies, accelerations) = trajectory::dense_state(pipeline);
+            Frame {
+                time,
+                bodies,
+                accelerations: Some(accelerations),
+            }
+        }
+        TimeDirection::Backward => Frame {
+            time,
+            bodies: pipeline.read_bodies(),
+            accelerations: None,
+        },
+    }
+}
+
+/// The event of `crossing`, bisecting the step in `bracket` on the sign of its
+/// predicate
+fn refine(pipeline: &mut Pipeline, bracket: &Trajectory, crossing: &StepCrossing) -> Event {
+    let predicates = pipeline.event_predicates().len();
+    let reference = pipeline.event_predicates()[crossing.predicate].reference;
+    let value = |pipeline: &mut Pipeline, time: f64| {
+        let bodies = bracket.sample(time).expect("Sampled within the step");
+        pipeline.evaluate_events(&bodies)[crossing.body * predicates + crossing.predicate]
+    };
+    let [mut low, mut high] = crossing.times;
+    let low_negative = crossing.values[0] < 0.0;
+    for _ in 0..BISECTIONS {
+        let middle = 0.5 * (low + high);
+        match (value(pipeline, middle) < 0.0) == low_negative {
+            true => low = middle,
+            false => high = middle,
+        }
+    }
+    let time = 0.5 * (low + high);
+    let state = |body: usize| {
+        bracket
+            .body(body)
+            .at(time)
+            .expect("Sampled within the step")
+    };
+    Event {
+        predicate: crossing.predicate,
+        body: crossing.body,
+        time,
+        state: state(crossing.body),
+        reference: reference.map(state),
+    }
+}
+
+/// Write one row per event with its name and the state of the body at its time,
+/// after the manifest of the run as a comment
+pub fn write_csv(
+    path: &Path,
+    definitions: &[EventDefinition],
+    events: &[Event],
+    manifest: &RunManifest,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    manifest.write_comment(&mut writer)?;
+    writeln!(writer, "event,body,time,x,y,z,vx,vy,vz")?;
+    for event in events {
+        let name = &definitions[event.predicate].name;
+        let state: Vec<String> = event
+            .state
+            .position
+            .into_iter()
+            .chain(event.state.velocity)
+            .map(|value| value.to_string())
+            .collect();
+        writeln!(
+            writer,
+            "\"{}\",{},{},{}",
+            name.replace('"', "\"\""),
+            event.body,
+            event.time,
+            state.join(",")
+        )?;
+    }
+    writer.flush()
+}
@@ -0,0 +1,347 @@
+//! Platform independent on-disk layout for body state.
+//!
+//! Files start with a fixed header:
+//!
+//! | bytes | contents                                            |
+//! |-------|-----------------------------------------------------|
+//! | 4     | magic `PBDY`                                        |
+//! | 1     | byte order of everything that follows, `L` or `B`   |
+//! | 1     | reserved, zero                                      |
+//! | 2     | format version                                      |
+//! | 4     | size of one body record in bytes                    |
+//! | 8     | number of body records                              |
+//!
+//! followed by the body records. Each record is the fields of `Body` written
+//! individually as `f32`s in declaration order with no padding, then since version 2
+//! the fields of `VisualAttributes` as `u32`s. Readers skip any trailing bytes in
+//! a record, so newer versions may only append fields.
+use std::io::{self, Read, Write};
+
+use crate::structures::{Body, VisualAttributes};
+
+pub const MAGIC: [u8; 4] = *b"PBDY";
+pub const VERSION: u16 = 2;
+const BODY_RECORD_SIZE: u32 = 8 * 4;
+/// Size of a record including the visual attributes
+const ATTRIBUTED_RECORD_SIZE: u32 = BODY_RECORD_SIZE + 3 * 4;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endianness {
+    pub fn native() -> Self {
+        if cfg!(target_endian = "big") {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        }
+    }
+
+    pub(crate) fn marker(self) -> u8 {
+        match self {
+            Endianness::Little => b'L',
+            Endianness::Big => b'B',
+        }
+    }
+
+    pub(crate) fn from_marker(marker: u8) -> io::Result<Self> {
+        match marker {
+            b'L' => Ok(Endianness::Little),
+            b'B' => Ok(Endianness::Big),
+            _ => Err(invalid_data(format!(
+                "Unknown byte order marker {:#x}",
+                marker
+            ))),
+        }
+    }
+}
+
+pub(crate) fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Writes primitives in a fixed byte order
+pub struct OrderedWriter<W: Write> {
+    inner: W,
+    endianness: Endianness,
+}
+
+macro_rules! write_primitive {
+    ($name:ident, $ty:ty) => {
+        pub fn $name(&mut self, value: $ty) -> io::Result<()> {
+            match self.endianness {
+                Endianness::Little => self.inner.write_all(&value.to_le_bytes()),
+                Endianness::Big => self.inner.write_all(&value.to_be_bytes()),
+            }
+        }
+    };
+}
+
+impl<W: Write> OrderedWriter<W> {
+    pub fn new(inner: W, endianness: Endianness) -> Self {
+        Self { inner, endianness }
+    }
+
+    write_primitive!(write_u16, u16);
+    write_primitive!(write_u32, u32);
+    write_primitive!(write_u64, u64);
+    write_primitive!(write_f32, f32);
+    write_primitive!(write_f64, f64);
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_all(bytes)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Reads primitives in a fixed byte order
+pub struct OrderedReader<R: Read> {
+    inner: R,
+    endianness: Endianness,
+}
+
+macro_rules! read_primitive {
+    ($name:ident, $ty:ty) => {
+        pub fn $name(&mut self) -> io::Result<$ty> {
+            let mut bytes = [0; std::mem::size_of::<$ty>()];
+            self.inner.read_exact(&mut bytes)?;
+            Ok(match self.endianness {
+                Endianness::Little => <$ty>::from_le_bytes(bytes),
+                Endianness::Big => <$ty>::from_be_bytes(bytes),
+            })
+        }
+    };
+}
+
+impl<R: Read> OrderedReader<R> {
+    pub fn new(inner: R, endianness: Endianness) -> Self {
+        Self { inner, endianness }
+    }
+
+    read_primitive!(read_u16, u16);
+    read_primitive!(read_u32, u32);
+    read_primitive!(read_u64, u64);
+    read_primitive!(read_f32, f32);
+    read_primitive!(read_f64, f64);
+
+    pub fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        self.inner.read_exact(bytes)
+    }
+
+    /// `count` bytes, allocated as they're read so that a corrupt length fails at
+    /// the end of the data instead of allocating all of it
+    pub fn read_vec(&mut self, count: u64) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        (&mut self.inner).take(count).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < count {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+        Ok(bytes)
+    }
+
+    /// Skip `count` bytes, failing like `read_exact` if the data ends first
+    pub fn skip(&mut self, count: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut (&mut self.inner).take(count), &mut io::sink())?;
+        if skipped < count {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to skip the whole record",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Write the header followed by the bodies, with default visual attributes
+pub fn write_bodies<W: Write>(writer: W, bodies: &[Body], endianness: Endianness) -> io::Result<W> {
+    let attributes = vec![VisualAttributes::default(); bodies.len()];
+    write_attributed_bodies(writer, bodies, &attributes, endianness)
+}
+
+/// Write the header followed by the bodies, each with its visual attributes
+pub fn write_attributed_bodies<W: Write>(
+    writer: W,
+    bodies: &[Body],
+    attributes: &[VisualAttributes],
+    endianness: Endianness,
+) -> io::Result<W> {
+    assert_eq!(bodies.len(), attributes.len());
+    let mut writer = OrderedWriter::new(writer, endianness);
+    writer.write_bytes(&MAGIC)?;
+    writer.write_bytes(&[endianness.marker(), 0])?;
+    writer.write_u16(VERSION)?;
+    writer.write_u32(ATTRIBUTED_RECORD_SIZE)?;
+    writer.write_u64(bodies.len() as u64)?;
+    for (body, attributes) in bodies.iter().zip(attributes) {
+        write_body(&mut writer, body)?;
+        writer.write_u32(attributes.group)?;
+        writer.write_u32(attributes.color)?;
+        writer.write_u32(attributes.label)?;
+    }
+    Ok(writer.into_inner())
+}
+
+fn write_body<W: Write>(writer: &mut OrderedWriter<W>, body: &Body) -> io::Result<()> {
+    for value in body.position {
+        writer.write_f32(value)?;
+    }
+    writer.write_f32(body.mass)?;
+    for value in body.velocity {
+        writer.write_f32(value)?;
+    }
+    writer.write_f32(body.mu)
+}
+
+/// Read bodies written by `write_bodies` on any platform
+pub fn read_bodies<R: Read>(reader: R) -> io::Result<Vec<Body>> {
+    Ok(read_attributed_bodies(reader)?.0)
+}
+
+/// Read the bodies and their visual attributes, which are defaulted for files
+/// written before version 2
+pub fn read_attributed_bodies<R: Read>(
+    mut reader: R,
+) -> io::Result<(Vec<Body>, Vec<VisualAttributes>)> {
+    let mut preamble = [0; 6];
+    reader.read_exact(&mut preamble)?;
+    if preamble[..4] != MAGIC {
+        return Err(invalid_data("Not a parabody file".to_string()));
+    }
+    let mut reader = OrderedReader::new(reader, Endianness::from_marker(preamble[4])?);
+    let version = reader.read_u16()?;
+    if version == 0 || version > VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported format version {} (newest supported is {})",
+            version, VERSION
+        )));
+    }
+    let record_size = reader.read_u32()?;
+    if record_size < BODY_RECORD_SIZE {
+        return Err(invalid_data(format!(
+            "Body records of {} bytes are too small",
+            record_size
+        )));
+    }
+    let num_bodies = reader.read_u64()?;
+    let capacity = num_bodies.min(1 << 20) as usize;
+    let mut bodies = Vec::with_capacity(capacity);
+    let mut attributes = Vec::with_capacity(capacity);
+    for _ in 0..num_bodies {
+        bodies.push(read_body(&mut reader)?);
+        let mut read = BODY_RECORD_SIZE;
+        if version >= 2 && record_size >= ATTRIBUTED_RECORD_SIZE {
+            attributes.push(VisualAttributes {
+                group: reader.read_u32()?,
+                color: reader.read_u32()?,
+                label: reader.read_u32()?,
+            });
+            read = ATTRIBUTED_RECORD_SIZE;
+        } else {
+            attributes.push(VisualAttributes::default());
+        }
+        reader.skip((record_size - read) as u64)?;
+    }
+    Ok((bodies, attributes))
+}
+
+fn read_body<R: Read>(reader: &mut OrderedReader<R>) -> io::Result<Body> {
+    let mut body = Body::default();
+    for value in &mut body.position {
+        *value = reader.read_f32()?;
+    }
+    body.mass = reader.read_f32()?;
+    for value in &mut body.velocity {
+        *value = reader.read_f32()?;
+    }
+    body.mu = reader.read_f32()?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bodies() -> (Vec<Body>, Vec<VisualAttributes>) {
+        let bodies = (0..3)
+            .map(|i| Body {
+                position: [i as f32, -0.5, 1e-7],
+                mass: 1.5 + i as f32,
+                velocity: [0.25, i as f32 * -3.0, f32::MAX],
+                mu: 39.478,
+            })
+            .collect();
+        let attributes = (0..3)
+            .map(|i| VisualAttributes {
+                group: i,
+                color: 2 * i,
+                label: 100 + i,
+            })
+            .collect();
+        (bodies, attributes)
+    }
+
+    fn write(endianness: Endianness) -> Vec<u8> {
+        let (bodies, attributes) = bodies();
+        write_attributed_bodies(Vec::new(), &bodies, &attributes, endianness).unwrap()
+    }
+
+    fn assert_same_bodies(read: &[Body], written: &[Body]) {
+        assert_eq!(
+            bytemuck::cast_slice::<Body, u32>(read),
+            bytemuck::cast_slice::<Body, u32>(written)
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let (read, read_attributes) = read_attributed_bodies(write(Endianness::Little).as_slice())
+            .expect("The file is valid");
+        let (bodies, attributes) = bodies();
+        assert_same_bodies(&read, &bodies);
+        assert_eq!(read_attributes, attributes);
+    }
+
+    #[test]
+    fn big_endian_header() {
+        let file = write(Endianness::Big);
+        assert_eq!(file[..4], MAGIC);
+        assert_eq!(file[4], b'B');
+        assert_eq!(file[6..8], VERSION.to_be_bytes());
+        assert_eq!(file[8..12], ATTRIBUTED_RECORD_SIZE.to_be_bytes());
+        assert_eq!(file[12..20], 3u64.to_be_bytes());
+        assert_same_bodies(&read_bodies(file.as_slice()).unwrap(), &bodies().0);
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let mut file = write(Endianness::Little);
+        file[6..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let error = read_bodies(file.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("Unsupported format version"));
+    }
+
+    #[test]
+    fn truncated_file_is_rejected() {
+        let file = write(Endianness::Little);
+        // Short of the last attribute, and of the whole last record
+        for length in [file.len() - 1, file.len() - ATTRIBUTED_RECORD_SIZE as usize] {
+            let error = read_bodies(&file[..length]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Statistics of the groups of bodies, such as the galaxies of a merger or the
+//! clusters of a larger run, by the `group` of their visual attributes.
+//!
+//! The statistics weigh the bodies by their mass. A body is bound to its group
+//! when its energy relative to the group's center of mass is negative, in the
+//! potential of the group alone. Those potentials, the only part quadratic in
+//! the bodies, are evaluated on the GPU.
+use std::collections::BTreeMap;
+
+use crate::pipeline::Pipeline;
+use crate::structures::Body;
+
+/// Fractions of a group's mass the Lagrangian radii enclose
+pub const LAGRANGIAN_FRACTIONS: [f64; 3] = [0.1, 0.5, 0.9];
+
+/// The statistics of one group
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupStats {
+    pub group: u32,
+    pub bodies: usize,
+    pub mass: f64,
+    pub center_of_mass: [f64; 3],
+    /// Velocity of the center of mass
+    pub velocity: [f64; 3],
+    /// Mass of the bodies bound to the group
+    pub bound_mass: f64,
+    /// One-dimensional velocity dispersion about the center of mass
+    pub velocity_dispersion: f64,
+    /// Radii about the center of mass enclosing the `LAGRANGIAN_FRACTIONS` of
+    /// the mass
+    pub lagrangian_radii: [f64; 3],
+}
+
+/// The statistics of every group of `bodies`, in the order of the groups.
+/// `groups` has the group of every body.
+pub fn group_stats(pipeline: &mut Pipeline, bodies: &[Body], groups: &[u32]) -> Vec<GroupStats> {
+    let mut members: BTreeMap<u32, Vec<Body>> = BTreeMap::new();
+    for (body, group) in bodies.iter().zip(groups) {
+        members.entry(*group).or_default().push(*body);
+    }
+    members
+        .into_iter()
+        .map(|(group, bodies)| {
+            let potentials = pipeline.evaluate_potentials(&bodies);
+            stats(group, &bodies, &potentials)
+        })
+        .collect()
+}
+
+/// The statistics of the `bodies` of a group, with their `potentials` in the
+/// field of the group
+pub fn stats(group: u32, bodies: &[Body], potentials: &[f32]) -> GroupStats {
+    let mass: f64 = bodies.iter().map(|body| body.mass as f64).sum();
+    let center_of_mass = weighted_mean(bodies, |body| body.position);
+    let velocity = weighted_mean(bodies, |body| body.velocity);
+    let mut bound_mass = 0.0;
+    let mut kinetic = 0.0;
+    for (body, potential) in bodies.iter().zip(potentials) {
+        let speed_squared = squared_distance(body.velocity, velocity);
+        if 0.5 * speed_squared + (*potential as f64) < 0.0 {
+            bound_mass += body.mass as f64;
+        }
+        kinetic += body.mass as f64 * speed_squared;
+    }
+    GroupStats {
+        group,
+        bodies: bodies.len(),
+        mass,
+        center_of_mass,
+        velocity,
+        bound_mass,
+        velocity_dispersion: match mass > 0.0 {
+            true => (kinetic / (3.0 * mass)).sqrt(),
+            false => 0.0,
+        },
+        lagrangian_radii: lagrangian_radii(bodies, center_of_mass, &LAGRANGIAN_FRACTIONS),
+    }
+}
+
+/// Radii about `center` enclosing the `fractions` of the mass of `bodies`, each
+/// the distance of the body which brings the enclosed mass up to it. Of the
+/// number of bodies if they have no mass.
+pub fn lagrangian_radii<const N: usize>(
+    bodies: &[Body],
+    center: [f64; 3],
+    fractions: &[f64; N],
+) -> [f64; N] {
+    let massless = bodies.iter().all(|body| body.mass <= 0.0);
+    let mut shells: Vec<(f64, f64)> = bodies
+        .iter()
+        .map(|body| {
+            let distance = squared_distance(body.position, center).sqrt();
+            (distance, if massless { 1.0 } else { body.mass as f64 })
+        })
+        .collect();
+    shells.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let total: f64 = shells.iter().map(|(_, mass)| mass).sum();
+    fractions.map(|fraction| {
+        let mut enclosed = 0.0;
+        for &(distance, mass) in &shells {
+            enclosed += mass;
+            if enclosed >= fraction * total {
+                return distance;
+            }
+        }
+        shells.last().map_or(0.0, |(distance, _)| *distance)
+    })
+}
+
+/// Mass-weighted mean of `vector` over `bodies`, the plain mean if they have
+/// no mass
+fn weighted_mean(bodies: &[Body], vector: impl Fn(&Body) -> [f32; 3]) -> [f64; 3] {
+    let mass: f64 = bodies.iter().map(|body| body.mass as f64).sum();
+    let weight = |body: &Body| match mass > 0.0 {
+        true => body.mass as f64 / mass,
+        false => 1.0 / bodies.len() as f64,
+    };
+    let mut mean = [0.0; 3];
+    for body in bodies {
+        let vector = vector(body);
+        for axis in 0..3 {
+            mean[axis] += weight(body) * vector[axis] as f64;
+        }
+    }
+    mean
+}
+
+fn squared_distance(a: [f32; 3], b: [f64; 3]) -> f64 {
+    (0..3).map(|axis| (a[axis] as f64 - b[axis]).powi(2)).sum()
+}
@@ -0,0 +1,490 @@
+//! Immediate mode controls drawn over the viewer with egui. Window events are
+//! translated into egui input here and the tessellated output is painted with
+//! a small render pipeline of its own, on the simulation's device.
+use std::{collections::HashMap, mem::size_of, num::NonZeroU32, time::Instant};
+
+use bytemuck::{Pod, Zeroable};
+use egui::{epaint::ImageDelta, ClippedPrimitive, ImageData, Pos2, TextureId};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, BufferBindingType,
+    BufferUsages, ColorTargetState, ColorWrites, CommandEncoder, Extent3d, FilterMode,
+    FragmentState, ImageCopyTexture, ImageDataLayout, IndexFormat, LoadOp, MultisampleState,
+    Operations, Origin3d, PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipelineDescriptor, SamplerBindingType, SamplerDescriptor,
+    ShaderStages, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+
+use crate::pipeline::Pipeline;
+
+/// Points scrolled per line of a mouse wheel
+const POINTS_PER_LINE: f32 = 50.0;
+
+/// The egui context with its pending input and the output of the last run
+pub struct Gui {
+    context: egui::Context,
+    input: egui::RawInput,
+    start: Instant,
+    /// Physical pixels per point
+    scale: f32,
+    pointer: Pos2,
+    modifiers: egui::Modifiers,
+    textures_delta: egui::TexturesDelta,
+    primitives: Vec<ClippedPrimitive>,
+    painter: Painter,
+}
+
+impl Gui {
+    /// Set up the painting into targets of `format`, at `scale` pixels per point
+    pub fn new(pipeline: &Pipeline, format: TextureFormat, scale: f32) -> Self {
+        Self {
+            context: egui::Context::default(),
+            input: egui::RawInput::default(),
+            start: Instant::now(),
+            scale,
+            pointer: Pos2::ZERO,
+            modifiers: egui::Modifiers::default(),
+            textures_delta: egui::TexturesDelta::default(),
+            primitives: Vec::new(),
+            painter: Painter::new(pipeline, format),
+        }
+    }
+
+    /// Queue `event` as input for the next run. Returns whether egui wants the
+    /// event for itself, in which case the viewer should not act on it.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        let events = &mut self.input.events;
+        match event {
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.scale = *scale_factor as f32;
+            }
+            WindowEvent::ModifiersChanged(state) => self.modifiers = modifiers(*state),
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer = Pos2::new(
+                    position.x as f32 / self.scale,
+                    position.y as f32 / self.scale,
+                );
+                events.push(egui::Event::PointerMoved(self.pointer));
+            }
+            WindowEvent::CursorLeft { .. } => events.push(egui::Event::PointerGone),
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    MouseButton::Other(_) => return false,
+                };
+                events.push(egui::Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                });
+                return self.context.wants_pointer_input();
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => egui::vec2(*x, *y) * POINTS_PER_LINE,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        egui::vec2(position.x as f32, position.y as f32) / self.scale
+                    }
+                };
+                events.push(egui::Event::Scroll(delta));
+                return self.context.wants_pointer_input();
+            }
+            WindowEvent::ReceivedCharacter(character) => {
+                if !character.is_control() {
+                    events.push(egui::Event::Text(character.to_string()));
+                }
+                return self.context.wants_keyboard_input();
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(keycode),
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(key) = key(*keycode) {
+                    events.push(egui::Event::Key {
+                        key,
+                        pressed: *state == ElementState::Pressed,
+                        modifiers: self.modifiers,
+                    });
+                }
+                return self.context.wants_keyboard_input();
+            }
+            _ => (),
+        }
+        false
+    }
+
+    /// Run `ui` on the queued input for a frame of `size` pixels, keeping the
+    /// output for `paint`. Returns whether egui asks for another frame soon.
+    pub fn run(&mut self, size: (u32, u32), ui: impl FnOnce(&egui::Context)) -> bool {
+        let mut input = std::mem::take(&mut self.input);
+        input.screen_rect = Some(egui::Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(size.0 as f32, size.1 as f32) / self.scale,
+        ));
+        input.pixels_per_point = Some(self.scale);
+        input.time = Some(self.start.elapsed().as_secs_f64());
+        input.modifiers = self.modifiers;
+        let output = self.context.run(input, ui);
+        self.textures_delta.append(output.textures_delta);
+        self.primitives = self.context.tessellate(output.shapes);
+        output.needs_repaint
+    }
+
+    /// Record drawing the output of the last run over `view` of `size` pixels
+    pub fn paint(
+        &mut self,
+        pipeline: &Pipeline,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        size: (u32, u32),
+    ) {
+        let textures_delta = std::mem::take(&mut self.textures_delta);
+        for (id, delta) in &textures_delta.set {
+            self.painter.update_texture(pipeline, *id, delta);
+        }
+        self.painter
+            .paint(pipeline, encoder, view, size, self.scale, &self.primitives);
+        for id in &textures_delta.free {
+            self.painter.textures.remove(id);
+        }
+    }
+}
+
+/// Must match `Screen` in `egui.wgsl`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+struct ScreenUniform {
+    size: [f32; 2],
+    _pad: [f32; 2],
+}
+
+/// Draws egui meshes, each texture with its own bind group
+struct Painter {
+    render_pipeline: wgpu::RenderPipeline,
+    screen_buffer: wgpu::Buffer,
+    screen_bindgroup: wgpu::BindGroup,
+    texture_bindgroup_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    textures: HashMap<TextureId, (wgpu::Texture, wgpu::BindGroup)>,
+}
+
+impl Painter {
+    fn new(pipeline: &Pipeline, format: TextureFormat) -> Self {
+        let device = pipeline.device();
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/egui.wgsl"));
+        let screen_bindgroup_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("egui screen"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("egui screen"),
+            size: size_of::<ScreenUniform>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_bindgroup = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("egui screen"),
+            layout: &screen_bindgroup_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+        let texture_bindgroup_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("egui texture"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("egui"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("egui"),
+            bind_group_layouts: &[&screen_bindgroup_layout, &texture_bindgroup_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("egui"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<egui::epaint::Vertex>() as u64,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Unorm8x4,
+                    ],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: if format.describe().srgb {
+                    "fs_linear"
+                } else {
+                    "fs_gamma"
+                },
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            render_pipeline,
+            screen_buffer,
+            screen_bindgroup,
+            texture_bindgroup_layout,
+            sampler,
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Create or patch a texture, egui's textures are all sRGB with premultiplied alpha
+    fn update_texture(&mut self, pipeline: &Pipeline, id: TextureId, delta: &ImageDelta) {
+        let (pixels, [width, height]) = match &delta.image {
+            ImageData::Color(image) => (image.pixels.clone(), image.size),
+            ImageData::Font(image) => (image.srgba_pixels(1.0).collect(), image.size),
+        };
+        let extent = Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        };
+        let origin = match delta.pos {
+            Some([x, y]) => Origin3d {
+                x: x as u32,
+                y: y as u32,
+                z: 0,
+            },
+            None => {
+                let device = pipeline.device();
+                let texture = device.create_texture(&TextureDescriptor {
+                    label: Some("egui"),
+                    size: extent,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8UnormSrgb,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                });
+                let view = texture.create_view(&TextureViewDescriptor::default());
+                let bindgroup = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("egui texture"),
+                    layout: &self.texture_bindgroup_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(&view),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::Sampler(&self.sampler),
+                        },
+                    ],
+                });
+                self.textures.insert(id, (texture, bindgroup));
+                Origin3d::ZERO
+            }
+        };
+        let texture = match self.textures.get(&id) {
+            Some((texture, _)) => texture,
+            None => {
+                log::warn!("egui patched the unknown texture {:?}", id);
+                return;
+            }
+        };
+        pipeline.queue().write_texture(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect: TextureAspect::All,
+            },
+            bytemuck::cast_slice(&pixels),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4 * width as u32),
+                rows_per_image: None,
+            },
+            extent,
+        );
+    }
+
+    fn paint(
+        &self,
+        pipeline: &Pipeline,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        (width, height): (u32, u32),
+        scale: f32,
+        primitives: &[ClippedPrimitive],
+    ) {
+        // All meshes share one vertex and one index buffer
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut draws = Vec::new();
+        for primitive in primitives {
+            if let egui::epaint::Primitive::Mesh(mesh) = &primitive.primitive {
+                let start = indices.len() as u32;
+                draws.push((
+                    primitive.clip_rect,
+                    mesh.texture_id,
+                    start..start + mesh.indices.len() as u32,
+                    vertices.len() as i32,
+                ));
+                vertices.extend_from_slice(&mesh.vertices);
+                indices.extend_from_slice(&mesh.indices);
+            }
+        }
+        if indices.is_empty() {
+            return;
+        }
+        let device = pipeline.device();
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("egui vertices"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("egui indices"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: BufferUsages::INDEX,
+        });
+        let uniform = ScreenUniform {
+            size: [width as f32 / scale, height as f32 / scale],
+            _pad: [0.0; 2],
+        };
+        pipeline
+            .queue()
+            .write_buffer(&self.screen_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("egui"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.screen_bindgroup, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+        for (clip_rect, texture_id, indices, base_vertex) in draws {
+            // The clip rectangle in pixels, within the target
+            let min_x = ((clip_rect.min.x * scale).round() as u32).min(width);
+            let min_y = ((clip_rect.min.y * scale).round() as u32).min(height);
+            let max_x = ((clip_rect.max.x * scale).round() as u32).clamp(min_x, width);
+            let max_y = ((clip_rect.max.y * scale).round() as u32).clamp(min_y, height);
+            if max_x == min_x || max_y == min_y {
+                continue;
+            }
+            let bindgroup = match self.textures.get(&texture_id) {
+                Some((_, bindgroup)) => bindgroup,
+                None => continue,
+            };
+            render_pass.set_scissor_rect(min_x, min_y, max_x - min_x, max_y - min_y);
+            render_pass.set_bind_group(1, bindgroup, &[]);
+            render_pass.draw_indexed(indices, base_vertex, 0..1);
+        }
+    }
+}
+
+fn modifiers(state: ModifiersState) -> egui::Modifiers {
+    egui::Modifiers {
+        alt: state.alt(),
+        ctrl: state.ctrl(),
+        shift: state.shift(),
+        mac_cmd: cfg!(target_os = "macos") && state.logo(),
+        command: if cfg!(target_os = "macos") {
+            state.logo()
+        } else {
+            state.ctrl()
+        },
+    }
+}
+
+/// The keys egui's widgets respond to, text arrives as characters
+fn key(keycode: VirtualKeyCode) -> Option<egui::Key> {
+    use egui::Key;
+    Some(match keycode {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Z => Key::Z,
+        _ => return None,
+    })
+}
@@ -0,0 +1,148 @@
+//! Offscreen rendering of the bodies to numbered PNG files, so that animations
+//! can be produced on machines without a display.
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+use wgpu::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+    ImageCopyTexture, ImageDataLayout, MapMode, Origin3d, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::pipeline::Pipeline;
+use crate::render::{ColorMap, OrbitCamera, PointRenderer};
+use crate::structures::{Body, VisualAttributes};
+
+/// PNG stores sRGB, so the render target applies the same encoding as a window surface
+const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Renders frames into a texture on the pipeline's device and writes them to
+/// `frame_00000.png`, `frame_00001.png` and so on
+pub struct FrameWriter {
+    renderer: PointRenderer,
+    texture: wgpu::Texture,
+    /// Rows are padded to the copy alignment on the way out of the texture
+    readback_buffer: wgpu::Buffer,
+    padded_row: u32,
+    size: (u32, u32),
+    camera: OrbitCamera,
+    directory: PathBuf,
+    frames: usize,
+}
+
+impl FrameWriter {
+    /// Create `directory` and the render target of `size` pixels. The camera
+    /// is framed around `bodies` and, like the color map, stays fixed for the
+    /// whole animation.
+    pub fn create(
+        pipeline: &Pipeline,
+        directory: &Path,
+        size: (u32, u32),
+        bodies: &[Body],
+        attributes: &[VisualAttributes],
+        color_map: ColorMap,
+    ) -> io::Result<Self> {
+        assert!(size.0 > 0 && size.1 > 0, "Frames must not be empty");
+        fs::create_dir_all(directory)?;
+        let device = pipeline.device();
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Frame"),
+            size: Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        });
+        let padded_row = (size.0 * BYTES_PER_PIXEL).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Frame readback"),
+            size: padded_row as u64 * size.1 as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut renderer = PointRenderer::new(pipeline, FORMAT, attributes, 0);
+        renderer.set_color_map(color_map);
+        Ok(Self {
+            renderer,
+            texture,
+            readback_buffer,
+            padded_row,
+            size,
+            camera: OrbitCamera::framing(bodies),
+            directory: directory.to_path_buf(),
+            frames: 0,
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn camera_mut(&mut self) -> &mut OrbitCamera {
+        &mut self.camera
+    }
+
+    /// Render the latest body state and write it as the next frame, returning its path
+    pub fn write_frame(&mut self, pipeline: &mut Pipeline) -> io::Result<PathBuf> {
+        let (width, height) = self.size;
+        let view = self.texture.create_view(&TextureViewDescriptor::default());
+        let mut encoder = pipeline
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        self.renderer
+            .draw(pipeline, &mut encoder, &view, &self.camera, self.size);
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &self.readback_buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(self.padded_row),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        pipeline.queue().submit(Some(encoder.finish()));
+
+        let slice = self.readback_buffer.slice(..);
+        pipeline.map_slice_blocking(MapMode::Read, slice);
+        let row = (width * BYTES_PER_PIXEL) as usize;
+        let pixels: Vec<u8> = slice
+            .get_mapped_range()
+            .chunks(self.padded_row as usize)
+            .flat_map(|padded| &padded[..row])
+            .copied()
+            .collect();
+        self.readback_buffer.unmap();
+
+        let path = self.directory.join(format!("frame_{:05}.png", self.frames));
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(&path)?), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        self.frames += 1;
+        Ok(path)
+    }
+}
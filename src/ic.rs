@@ -0,0 +1,38 @@
+//! Generated initial conditions.
+//!
+//! Positions come from low-discrepancy sequences rather than random numbers, so
+//! the same arguments always give the same bodies without a seed to carry around.
+//! Models sampled from distribution functions take a seed for `Rng` instead.
+
+pub mod cr3bp;
+mod disk;
+pub mod kepler;
+mod random;
+pub mod solar_system;
+mod spherical;
+pub mod tle;
+
+pub use disk::{merger, Bulge, ExponentialDisk};
+pub use random::Rng;
+pub use spherical::{hernquist, king, plummer};
+
+/// Generalized golden ratio for three dimensions, the root of `x^4 = x + 1`
+const PHI_3: f64 = 1.220_744_084_605_759;
+
+/// `count` positions spread uniformly over the ball of `radius` about `center`
+pub fn uniform_sphere(count: usize, radius: f32, center: [f32; 3]) -> Vec<[f32; 3]> {
+    // Additive recurrence in the unit cube, keeping the points inside the ball
+    let alpha = [1.0 / PHI_3, 1.0 / PHI_3.powi(2), 1.0 / PHI_3.powi(3)];
+    (0..)
+        .map(|i| alpha.map(|a| 2.0 * (0.5 + a * i as f64).fract() - 1.0))
+        .filter(|point| point.iter().map(|x| x * x).sum::<f64>() <= 1.0)
+        .take(count)
+        .map(|point| {
+            [
+                center[0] + radius * point[0] as f32,
+                center[1] + radius * point[1] as f32,
+                center[2] + radius * point[2] as f32,
+            ]
+        })
+        .collect()
+}
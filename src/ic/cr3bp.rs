@@ -0,0 +1,108 @@
+//! The circular restricted three-body problem, in the synodic frame rotating
+//! with the primaries.
+//!
+//! Units are those of `ExternalPotential::Cr3bp`: the primaries are a unit
+//! distance apart, `G` times their total mass is one and the frame turns once
+//! every `2 pi` about the z axis. The primary of mass `1 - mu` sits at
+//! `(-mu, 0, 0)` and the secondary of mass `mu` at `(1 - mu, 0, 0)`, relative
+//! to the potential's center.
+use crate::structures::Body;
+
+/// Mass parameter of the Earth and the Moon
+pub const EARTH_MOON: f64 = 0.012_150_585;
+/// Mass parameter of the Sun and Jupiter
+pub const SUN_JUPITER: f64 = 9.538_754e-4;
+
+/// Halvings of the bracket of a collinear Lagrange point, down to the precision
+/// of the doubles
+const BISECTIONS: usize = 200;
+
+/// Distances to the primary and the secondary
+fn distances(mass_parameter: f64, [x, y, z]: [f64; 3]) -> (f64, f64) {
+    let mu = mass_parameter;
+    (
+        ((x + mu).powi(2) + y * y + z * z).sqrt(),
+        ((x - 1.0 + mu).powi(2) + y * y + z * z).sqrt(),
+    )
+}
+
+/// Gravity of the primaries and the centrifugal term at `position`, the
+/// acceleration of a body at rest in the rotating frame
+pub fn acceleration(mass_parameter: f64, position: [f64; 3]) -> [f64; 3] {
+    let mu = mass_parameter;
+    let (r1, r2) = distances(mu, position);
+    let [x, y, z] = position;
+    [
+        -(1.0 - mu) * (x + mu) / r1.powi(3) - mu * (x - 1.0 + mu) / r2.powi(3) + x,
+        -(1.0 - mu) * y / r1.powi(3) - mu * y / r2.powi(3) + y,
+        -(1.0 - mu) * z / r1.powi(3) - mu * z / r2.powi(3),
+    ]
+}
+
+/// The pseudo-potential `Omega`, whose gradient is the acceleration of a body
+/// at rest in the rotating frame
+pub fn effective_potential(mass_parameter: f64, position: [f64; 3]) -> f64 {
+    let mu = mass_parameter;
+    let (r1, r2) = distances(mu, position);
+    0.5 * (position[0].powi(2) + position[1].powi(2)) + (1.0 - mu) / r1 + mu / r2
+}
+
+/// The Jacobi constant `2 Omega - v^2`, the one integral of the motion. Bodies
+/// can't reach the regions where `2 Omega` is below it.
+pub fn jacobi_constant(mass_parameter: f64, position: [f64; 3], velocity: [f64; 3]) -> f64 {
+    let speed_squared = velocity.iter().map(|v| v * v).sum::<f64>();
+    2.0 * effective_potential(mass_parameter, position) - speed_squared
+}
+
+/// The Tisserand parameter of the orbit about the primary relative to that of
+/// the secondary, from the state in the rotating frame. `None` on radial paths.
+pub fn tisserand_parameter(
+    mass_parameter: f64,
+    position: [f64; 3],
+    velocity: [f64; 3],
+) -> Option<f64> {
+    let mu = mass_parameter;
+    // The inertial state relative to the primary, in the frame's orientation
+    let r = [position[0] + mu, position[1], position[2]];
+    let v = [velocity[0] - r[1], velocity[1] + r[0], velocity[2]];
+    crate::analysis::tisserand(r, v, 1.0 - mu, 1.0, [0.0, 0.0, 1.0])
+}
+
+/// The five Lagrange points, where a body is at rest in the rotating frame. L1
+/// lies between the primaries, L2 beyond the secondary and L3 beyond the primary.
+/// L4 leads the secondary by 60 degrees and L5 trails it.
+pub fn lagrange_points(mass_parameter: f64) -> [[f64; 3]; 5] {
+    let mu = mass_parameter;
+    let collinear = |mut below: f64, mut above: f64| {
+        // The acceleration along the x axis rises through zero in each bracket
+        for _ in 0..BISECTIONS {
+            let middle = 0.5 * (below + above);
+            match acceleration(mu, [middle, 0.0, 0.0])[0] < 0.0 {
+                true => below = middle,
+                false => above = middle,
+            }
+        }
+        [0.5 * (below + above), 0.0, 0.0]
+    };
+    let near = f64::EPSILON.sqrt();
+    let height = 0.75f64.sqrt();
+    [
+        collinear(-mu + near, 1.0 - mu - near),
+        collinear(1.0 - mu + near, 2.0),
+        collinear(-2.0, -mu - near),
+        [0.5 - mu, height, 0.0],
+        [0.5 - mu, -height, 0.0],
+    ]
+}
+
+/// A massless body at `position` with `velocity` in the rotating frame, offset
+/// by the potential's `center`
+pub fn body(center: [f64; 3], position: [f64; 3], velocity: [f64; 3]) -> Body {
+    let position = [0, 1, 2].map(|i| (center[i] + position[i]) as f32);
+    Body {
+        position,
+        mass: 0.0,
+        velocity: velocity.map(|v| v as f32),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,377 @@
+//! Rotating disk galaxies with an exponential surface density.
+//!
+//! Like the spherical models, galaxies are in N-body units with `G = 1` and
+//! shifted into their center of mass frame. The disk lies in the xy plane and
+//! rotates counterclockwise about z.
+use std::f64::consts::PI;
+
+use serde::Deserialize;
+
+use super::{
+    spherical::{body, center},
+    Rng,
+};
+use crate::structures::{Body, ExternalPotential};
+
+/// Radii beyond this many scale lengths and heights beyond this many scale
+/// heights are resampled, the disk holds less than a thousandth of its mass
+/// out there
+const MAX_RADIUS: f64 = 10.0;
+const MAX_HEIGHT: f64 = 10.0;
+
+/// A Hernquist bulge at the center of a disk
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Bulge {
+    pub count: usize,
+    pub mass: f32,
+    pub scale_radius: f32,
+}
+
+/// A disk with surface density `exp(-R / scale_length)` and vertical density
+/// `sech^2(z / scale_height)`, supported by rotation against its own gravity,
+/// that of the bulge and that of the halo. The disk is warm, with a radial
+/// velocity dispersion falling off as the square root of the surface density,
+/// the azimuthal dispersion and asymmetric drift of the epicyclic approximation
+/// and the vertical dispersion of an isothermal sheet.
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialDisk {
+    pub count: usize,
+    /// Total mass of the disk
+    pub mass: f32,
+    pub scale_length: f32,
+    pub scale_height: f32,
+    /// Radial velocity dispersion at the center
+    pub dispersion: f32,
+    pub bulge: Option<Bulge>,
+    /// Analytic potential of a dark matter halo, centered on the disk whatever
+    /// its `center`. It adds to the rotation curve but isn't sampled, so the
+    /// run needs the same `external_potential`.
+    pub halo: Option<ExternalPotential>,
+}
+
+impl ExponentialDisk {
+    /// The bodies of the disk followed by those of the bulge. The bulge is
+    /// sampled in equilibrium with its own potential only.
+    pub fn generate(&self, seed: u64) -> Vec<Body> {
+        let mut rng = Rng::new(seed);
+        let mass = self.mass as f64 / self.count as f64;
+        let (length, height) = (self.scale_length as f64, self.scale_height as f64);
+        let mut bodies: Vec<Body> = (0..self.count)
+            .map(|_| {
+                let radius = loop {
+                    let radius = disk_radius(rng.uniform());
+                    if radius < MAX_RADIUS {
+                        break radius * length;
+                    }
+                };
+                let z = loop {
+                    let z = (2.0 * rng.uniform() - 1.0).atanh();
+                    if z.abs() < MAX_HEIGHT {
+                        break z * height;
+                    }
+                };
+                let angle = 2.0 * PI * rng.uniform();
+                let (sin, cos) = angle.sin_cos();
+                // Mean rotation and dispersions in cylindrical coordinates
+                let (circular, epicyclic) = self.rotation(radius);
+                let surface_density = self.surface_density(radius);
+                let radial_dispersion = self.dispersion as f64 * (-0.5 * radius / length).exp();
+                let azimuthal_dispersion = radial_dispersion * epicyclic.sqrt();
+                let vertical_dispersion = (PI * surface_density * height).sqrt();
+                let drift = radial_dispersion.powi(2) * (1.0 - epicyclic - 2.0 * radius / length);
+                let rotation = (circular + drift).max(0.0).sqrt();
+                let radial = radial_dispersion * rng.normal();
+                let azimuthal = rotation + azimuthal_dispersion * rng.normal();
+                body(
+                    [radius * cos, radius * sin, z],
+                    [
+                        radial * cos - azimuthal * sin,
+                        radial * sin + azimuthal * cos,
+                        vertical_dispersion * rng.normal(),
+                    ],
+                    mass,
+                )
+            })
+            .collect();
+        if let Some(bulge) = self.bulge {
+            let seed = rng.next_u64();
+            bodies.extend(super::hernquist(
+                bulge.count,
+                bulge.mass,
+                bulge.scale_radius,
+                seed,
+            ));
+        }
+        center(&mut bodies);
+        bodies
+    }
+
+    fn surface_density(&self, radius: f64) -> f64 {
+        let length = self.scale_length as f64;
+        self.mass as f64 / (2.0 * PI * length * length) * (-radius / length).exp()
+    }
+
+    /// Circular speed squared at `radius` in the midplane, and the epicyclic
+    /// frequency squared over four times the angular frequency squared
+    fn rotation(&self, radius: f64) -> (f64, f64) {
+        let step = 1e-3 * radius;
+        let inner = self.circular_speed(radius - step);
+        let circular = self.circular_speed(radius);
+        let outer = self.circular_speed(radius + step);
+        // kappa^2 = dv^2/dR / R + 2 v^2 / R^2, Omega^2 = v^2 / R^2
+        let slope = (outer - inner) / (2.0 * step);
+        let epicyclic = if circular > 0.0 {
+            (slope * radius / circular + 2.0) / 4.0
+        } else {
+            1.0
+        };
+        (circular, epicyclic)
+    }
+
+    /// Circular speed squared at `radius` in the midplane
+    fn circular_speed(&self, radius: f64) -> f64 {
+        // Freeman's razor thin exponential disk
+        let length = self.scale_length as f64;
+        let y = radius / (2.0 * length);
+        let mut speed = 2.0 * self.mass as f64 / length
+            * y
+            * y
+            * (bessel_i0(y) * bessel_k0(y) - bessel_i1(y) * bessel_k1(y));
+        if let Some(bulge) = self.bulge {
+            let scale_radius = bulge.scale_radius as f64;
+            speed += bulge.mass as f64 * radius / (radius + scale_radius).powi(2);
+        }
+        if let Some(halo) = self.halo {
+            speed -= radius * halo.acceleration([radius, 0.0, 0.0])[0];
+        }
+        speed
+    }
+}
+
+/// Two disk galaxies falling towards each other on a parabolic orbit, closest at
+/// `pericenter` between their centers and starting `separation` apart. The
+/// second galaxy has `mass_ratio` times the mass and bodies of `primary`, with
+/// lengths scaled by its square root to keep the surface density. The primary
+/// rotates in the orbital plane, prograde, and the secondary is tilted by
+/// `inclination` about the line joining their starting positions. Halos aren't
+/// included since external potentials stay put.
+pub fn merger(
+    primary: &ExponentialDisk,
+    mass_ratio: f32,
+    pericenter: f32,
+    separation: f32,
+    inclination: f32,
+    seed: u64,
+) -> [Vec<Body>; 2] {
+    let primary = ExponentialDisk {
+        halo: None,
+        ..*primary
+    };
+    let (ratio, length) = (mass_ratio as f64, (mass_ratio as f64).sqrt());
+    let scale = |count: usize| ((count as f64 * ratio).round() as usize).max(1);
+    let secondary = ExponentialDisk {
+        count: scale(primary.count),
+        mass: primary.mass * mass_ratio,
+        scale_length: primary.scale_length * length as f32,
+        scale_height: primary.scale_height * length as f32,
+        // Speeds go as the square root of mass over length
+        dispersion: primary.dispersion * ratio.powf(0.25) as f32,
+        bulge: primary.bulge.map(|bulge| Bulge {
+            count: scale(bulge.count),
+            mass: bulge.mass * mass_ratio,
+            scale_radius: bulge.scale_radius * length as f32,
+        }),
+        halo: None,
+    };
+    let galaxy_mass = |disk: &ExponentialDisk| {
+        disk.mass as f64 + disk.bulge.map_or(0.0, |bulge| bulge.mass as f64)
+    };
+    let (m1, m2) = (galaxy_mass(&primary), galaxy_mass(&secondary));
+    // Incoming on the parabola r = 2 q / (1 + cos(nu)), with the pericenter on
+    // the x axis
+    let p = 2.0 * pericenter as f64;
+    let r = (separation as f64).max(pericenter as f64);
+    let anomaly = -(p / r - 1.0).clamp(-1.0, 1.0).acos();
+    let (sin, cos) = anomaly.sin_cos();
+    let speed = ((m1 + m2) / p).sqrt();
+    let position = [r * cos, r * sin, 0.0];
+    let velocity = [-speed * sin, speed * (1.0 + cos), 0.0];
+    // Tilt the secondary about the line of centers
+    let axis = [cos, sin, 0.0];
+    let tilt = |v: [f64; 3]| rotate(v, axis, inclination as f64);
+    let mut rng = Rng::new(seed);
+    let galaxies = [
+        (primary.generate(rng.next_u64()), -m2 / (m1 + m2), false),
+        (secondary.generate(rng.next_u64()), m1 / (m1 + m2), true),
+    ];
+    galaxies.map(|(mut bodies, share, tilted)| {
+        for body in &mut bodies {
+            let (mut p, mut v) = (body.position.map(f64::from), body.velocity.map(f64::from));
+            if tilted {
+                (p, v) = (tilt(p), tilt(v));
+            }
+            body.position = [0, 1, 2].map(|i| (p[i] + share * position[i]) as f32);
+            body.velocity = [0, 1, 2].map(|i| (v[i] + share * velocity[i]) as f32);
+        }
+        bodies
+    })
+}
+
+/// Rotate `v` by `angle` about the unit vector `axis`, with Rodrigues' formula
+fn rotate(v: [f64; 3], axis: [f64; 3], angle: f64) -> [f64; 3] {
+    let (sin, cos) = angle.sin_cos();
+    let dot = axis[0] * v[0] + axis[1] * v[1] + axis[2] * v[2];
+    let cross = [
+        axis[1] * v[2] - axis[2] * v[1],
+        axis[2] * v[0] - axis[0] * v[2],
+        axis[0] * v[1] - axis[1] * v[0],
+    ];
+    [0, 1, 2].map(|i| v[i] * cos + cross[i] * sin + axis[i] * dot * (1.0 - cos))
+}
+
+/// The radius in scale lengths within which the disk holds the `fraction` of
+/// its mass, inverting `1 - (1 + x) exp(-x)` with Newton's method
+fn disk_radius(fraction: f64) -> f64 {
+    let mut x: f64 = 1.0;
+    for _ in 0..50 {
+        let step = (1.0 - (1.0 + x) * (-x).exp() - fraction) / (x * (-x).exp());
+        x = (x - step).max(0.5 * x);
+        if step.abs() < 1e-12 {
+            break;
+        }
+    }
+    x
+}
+
+// Modified Bessel functions from the polynomial approximations of Abramowitz
+// and Stegun 9.8, accurate to about 1e-7
+
+fn polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |sum, c| sum * x + c)
+}
+
+fn bessel_i0(x: f64) -> f64 {
+    if x <= 3.75 {
+        let t = (x / 3.75).powi(2);
+        polynomial(
+            &[
+                1.0, 3.5156229, 3.0899424, 1.2067492, 0.2659732, 0.0360768, 0.0045813,
+            ],
+            t,
+        )
+    } else {
+        let t = 3.75 / x;
+        x.exp() / x.sqrt()
+            * polynomial(
+                &[
+                    0.39894228,
+                    0.01328592,
+                    0.00225319,
+                    -0.00157565,
+                    0.00916281,
+                    -0.02057706,
+                    0.02635537,
+                    -0.01647633,
+                    0.00392377,
+                ],
+                t,
+            )
+    }
+}
+
+fn bessel_i1(x: f64) -> f64 {
+    if x <= 3.75 {
+        let t = (x / 3.75).powi(2);
+        x * polynomial(
+            &[
+                0.5, 0.87890594, 0.51498869, 0.15084934, 0.02658733, 0.00301532, 0.00032411,
+            ],
+            t,
+        )
+    } else {
+        let t = 3.75 / x;
+        x.exp() / x.sqrt()
+            * polynomial(
+                &[
+                    0.39894228,
+                    -0.03988024,
+                    -0.00362018,
+                    0.00163801,
+                    -0.01031555,
+                    0.02282967,
+                    -0.02895312,
+                    0.01787654,
+                    -0.00420059,
+                ],
+                t,
+            )
+    }
+}
+
+fn bessel_k0(x: f64) -> f64 {
+    if x <= 2.0 {
+        let t = (x / 2.0).powi(2);
+        -(x / 2.0).ln() * bessel_i0(x)
+            + polynomial(
+                &[
+                    -0.57721566,
+                    0.42278420,
+                    0.23069756,
+                    0.03488590,
+                    0.00262698,
+                    0.00010750,
+                    0.00000740,
+                ],
+                t,
+            )
+    } else {
+        let t = 2.0 / x;
+        (-x).exp() / x.sqrt()
+            * polynomial(
+                &[
+                    1.25331414,
+                    -0.07832358,
+                    0.02189568,
+                    -0.01062446,
+                    0.00587872,
+                    -0.00251540,
+                    0.00053208,
+                ],
+                t,
+            )
+    }
+}
+
+fn bessel_k1(x: f64) -> f64 {
+    if x <= 2.0 {
+        let t = (x / 2.0).powi(2);
+        (x / 2.0).ln() * bessel_i1(x)
+            + polynomial(
+                &[
+                    1.0,
+                    0.15443144,
+                    -0.67278579,
+                    -0.18156897,
+                    -0.01919402,
+                    -0.00110404,
+                    -0.00004686,
+                ],
+                t,
+            ) / x
+    } else {
+        let t = 2.0 / x;
+        (-x).exp() / x.sqrt()
+            * polynomial(
+                &[
+                    1.25331414,
+                    0.23498619,
+                    -0.03655620,
+                    0.01504268,
+                    -0.00780353,
+                    0.00325614,
+                    -0.00068245,
+                ],
+                t,
+            )
+    }
+}
@@ -0,0 +1,224 @@
+//! Bodies placed on Keplerian orbits given by their orbital elements.
+//!
+//! Angles are in radians. The reference plane is the xy plane of the frame and
+//! the reference direction its x axis.
+use std::f64::consts::PI;
+
+use crate::structures::Body;
+use crate::vector::{cross, dot, norm, scale, sub};
+
+/// Iterations of Newton's method solving Kepler's equation, which converges
+/// within a few for all but the most eccentric orbits
+const KEPLER_ITERATIONS: usize = 50;
+
+/// Where along the orbit the body is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anomaly {
+    True(f64),
+    Mean(f64),
+}
+
+/// A Keplerian orbit, elliptic or hyperbolic. Parabolic orbits, with an
+/// eccentricity of exactly one, have no finite semi-major axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitalElements {
+    /// Negative for hyperbolic orbits
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    pub inclination: f64,
+    pub longitude_of_ascending_node: f64,
+    pub argument_of_periapsis: f64,
+    pub anomaly: Anomaly,
+}
+
+impl OrbitalElements {
+    pub fn true_anomaly(&self) -> f64 {
+        match self.anomaly {
+            Anomaly::True(anomaly) => anomaly,
+            Anomaly::Mean(anomaly) => true_from_mean(anomaly, self.eccentricity),
+        }
+    }
+
+    /// Position and velocity relative to the central body, for the sum `mu` of
+    /// the gravitational parameters of both bodies
+    pub fn state(&self, mu: f64) -> ([f64; 3], [f64; 3]) {
+        let e = self.eccentricity;
+        assert!(
+            (e - 1.0).abs() > f64::EPSILON,
+            "Parabolic orbits have no semi-major axis"
+        );
+        let nu = self.true_anomaly();
+        // Semi-latus rectum, positive for both conic sections
+        let p = self.semi_major_axis * (1.0 - e * e);
+        let r = p / (1.0 + e * nu.cos());
+        let speed = (mu / p).sqrt();
+        let position = [r * nu.cos(), r * nu.sin(), 0.0];
+        let velocity = [-speed * nu.sin(), speed * (e + nu.cos()), 0.0];
+        (self.rotate(position), self.rotate(velocity))
+    }
+
+    /// The osculating orbit of a body at `position` moving with `velocity`
+    /// relative to the central body, for the sum `mu` of the gravitational
+    /// parameters of both bodies. The anomaly is the true anomaly. The node is
+    /// taken on the x axis for orbits in the reference plane and the periapsis
+    /// at the node for circular orbits. `None` for radial and parabolic orbits.
+    pub fn from_state(position: [f64; 3], velocity: [f64; 3], mu: f64) -> Option<Self> {
+        let r = norm(position);
+        let h = cross(position, velocity);
+        let energy = 0.5 * dot(velocity, velocity) - mu / r;
+        if r == 0.0
+            || norm(h) <= 1e-12 * r * norm(velocity)
+            || energy.abs() <= f64::EPSILON * mu / r
+        {
+            return None;
+        }
+        let h_unit = scale(h, 1.0 / norm(h));
+        let radial = dot(velocity, velocity) - mu / r;
+        let e_vector = scale(
+            sub(
+                scale(position, radial),
+                scale(velocity, dot(position, velocity)),
+            ),
+            1.0 / mu,
+        );
+        let eccentricity = norm(e_vector);
+        let node = [-h[1], h[0], 0.0];
+        let node = if norm(node) > 1e-12 * norm(h) {
+            scale(node, 1.0 / norm(node))
+        } else {
+            [1.0, 0.0, 0.0]
+        };
+        let periapsis = if eccentricity > 1e-12 {
+            scale(e_vector, 1.0 / eccentricity)
+        } else {
+            node
+        };
+        // Angle from `a` to `b` about the orbit normal
+        let angle = |a, b| {
+            dot(h_unit, cross(a, b))
+                .atan2(dot(a, b))
+                .rem_euclid(2.0 * PI)
+        };
+        Some(Self {
+            semi_major_axis: -mu / (2.0 * energy),
+            eccentricity,
+            inclination: h_unit[2].clamp(-1.0, 1.0).acos(),
+            longitude_of_ascending_node: node[1].atan2(node[0]).rem_euclid(2.0 * PI),
+            argument_of_periapsis: angle(node, periapsis),
+            anomaly: Anomaly::True(angle(periapsis, position)),
+        })
+    }
+
+    /// Rotate `v` from the perifocal frame of the orbit into the reference frame
+    fn rotate(&self, v: [f64; 3]) -> [f64; 3] {
+        let (sin_o, cos_o) = self.longitude_of_ascending_node.sin_cos();
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        let (sin_w, cos_w) = self.argument_of_periapsis.sin_cos();
+        // Argument of periapsis about z, inclination about x, node about z
+        let x = cos_w * v[0] - sin_w * v[1];
+        let y = sin_w * v[0] + cos_w * v[1];
+        let (y, z) = (cos_i * y - sin_i * v[2], sin_i * y + cos_i * v[2]);
+        [cos_o * x - sin_o * y, sin_o * x + cos_o * y, z]
+    }
+}
+
+/// The true anomaly at `mean` anomaly on an orbit of eccentricity `e`
+pub fn true_from_mean(mean: f64, e: f64) -> f64 {
+    if e < 1.0 {
+        // Kepler's equation M = E - e sin E for the eccentric anomaly
+        let mean = mean.rem_euclid(2.0 * PI);
+        let mut anomaly = if e < 0.8 { mean } else { PI };
+        for _ in 0..KEPLER_ITERATIONS {
+            let step = (anomaly - e * anomaly.sin() - mean) / (1.0 - e * anomaly.cos());
+            anomaly -= step;
+            if step.abs() < 1e-15 {
+                break;
+            }
+        }
+        2.0 * ((1.0 + e).sqrt() * (anomaly / 2.0).sin())
+            .atan2((1.0 - e).sqrt() * (anomaly / 2.0).cos())
+    } else {
+        // M = e sinh H - H for the hyperbolic anomaly
+        let mut anomaly = (mean / e).asinh();
+        for _ in 0..KEPLER_ITERATIONS {
+            let step = (e * anomaly.sinh() - anomaly - mean) / (e * anomaly.cosh() - 1.0);
+            anomaly -= step;
+            if step.abs() < 1e-15 {
+                break;
+            }
+        }
+        2.0 * ((e + 1.0).sqrt() * (anomaly / 2.0).sinh())
+            .atan2((e - 1.0).sqrt() * (anomaly / 2.0).cosh())
+    }
+}
+
+/// The mean anomaly at `true_anomaly` on an orbit of eccentricity `e`
+pub fn mean_from_true(true_anomaly: f64, e: f64) -> f64 {
+    let (sin, cos) = (true_anomaly / 2.0).sin_cos();
+    if e < 1.0 {
+        let anomaly = 2.0 * ((1.0 - e).sqrt() * sin).atan2((1.0 + e).sqrt() * cos);
+        (anomaly - e * anomaly.sin()).rem_euclid(2.0 * PI)
+    } else {
+        let anomaly = 2.0 * ((e - 1.0).sqrt() / (e + 1.0).sqrt() * sin / cos).atanh();
+        e * anomaly.sinh() - anomaly
+    }
+}
+
+/// A body of gravitational parameter `mu` on the orbit `elements` about
+/// `central`. The orbit is relative, so its period follows from the sum of the
+/// two `mu`, and `central` is left where it is.
+pub fn orbiting(central: &Body, elements: &OrbitalElements, mu: f32, mass: f32) -> Body {
+    let (position, velocity) = elements.state(central.mu as f64 + mu as f64);
+    Body {
+        position: [0, 1, 2].map(|i| (central.position[i] as f64 + position[i]) as f32),
+        mass,
+        velocity: [0, 1, 2].map(|i| (central.velocity[i] as f64 + velocity[i]) as f32),
+        mu,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64, what: &str) {
+        assert!((a - b).abs() < 1e-9, "{}: {} != {}", what, a, b);
+    }
+
+    #[test]
+    fn elements_survive_a_round_trip_through_the_state() {
+        let mu = 4.0 * PI * PI;
+        let orbits = [
+            (1.5, 0.3, Anomaly::True(2.0)),
+            (2.0, 0.9, Anomaly::Mean(0.4)),
+            (-1.0, 1.8, Anomaly::True(-0.5)),
+        ];
+        for (semi_major_axis, eccentricity, anomaly) in orbits {
+            let elements = OrbitalElements {
+                semi_major_axis,
+                eccentricity,
+                inclination: 0.7,
+                longitude_of_ascending_node: 1.2,
+                argument_of_periapsis: 4.0,
+                anomaly,
+            };
+            let (position, velocity) = elements.state(mu);
+            let recovered = OrbitalElements::from_state(position, velocity, mu)
+                .expect("The orbit is neither radial nor parabolic");
+            assert_close(
+                recovered.semi_major_axis,
+                semi_major_axis,
+                "semi-major axis",
+            );
+            assert_close(recovered.eccentricity, eccentricity, "eccentricity");
+            assert_close(recovered.inclination, 0.7, "inclination");
+            assert_close(recovered.longitude_of_ascending_node, 1.2, "node");
+            assert_close(recovered.argument_of_periapsis, 4.0, "periapsis");
+            assert_close(
+                recovered.true_anomaly(),
+                elements.true_anomaly().rem_euclid(2.0 * PI),
+                "true anomaly",
+            );
+        }
+    }
+}
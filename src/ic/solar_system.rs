@@ -0,0 +1,210 @@
+//! The Sun, the planets and the Moon at the J2000 epoch.
+//!
+//! Units are astronomical units, Julian years and solar masses, so `G = 4 pi^2`
+//! and the `mu` of the Sun is `4 pi^2`. The frame is the ecliptic and equinox
+//! of J2000 with the origin at the barycenter of the bodies. The planets are
+//! placed from the mean elements fitted to the DE405 ephemeris by Standish,
+//! which keep them within a few thousandths of an AU of it over 1800 to 2050.
+use std::f64::consts::PI;
+
+use super::kepler::{orbiting, Anomaly, OrbitalElements};
+use crate::bodies::BodySet;
+use crate::structures::Body;
+
+/// Julian date of the epoch, 2000 January 1.5 TDB
+pub const J2000: f64 = 2_451_545.0;
+/// Days in a Julian year, the unit of time
+pub const DAYS_PER_YEAR: f64 = 365.25;
+
+/// Gravitational constant in AU^3 / (solar mass year^2)
+const G: f64 = 4.0 * PI * PI;
+
+/// Mass in solar masses, from the ratio of `GM` to that of the Sun, and the
+/// semi-major axis in AU, eccentricity, inclination, mean longitude, longitude
+/// of perihelion and longitude of the ascending node in degrees of the orbit
+/// about the Sun. Earth's elements are those of the Earth-Moon barycenter.
+const PLANETS: [(&str, f64, [f64; 6]); 8] = [
+    (
+        "Mercury",
+        1.660_114_153e-7,
+        [
+            0.387_099_27,
+            0.205_635_93,
+            7.004_979_02,
+            252.250_323_50,
+            77.457_796_28,
+            48.330_765_93,
+        ],
+    ),
+    (
+        "Venus",
+        2.447_838_288e-6,
+        [
+            0.723_335_66,
+            0.006_776_72,
+            3.394_676_05,
+            181.979_099_50,
+            131.602_467_18,
+            76.679_842_55,
+        ],
+    ),
+    (
+        "Earth",
+        3.003_489_615e-6,
+        [
+            1.000_002_61,
+            0.016_711_23,
+            -0.000_015_31,
+            100.464_571_66,
+            102.937_681_93,
+            0.0,
+        ],
+    ),
+    (
+        "Mars",
+        3.227_156_038e-7,
+        [
+            1.523_710_34,
+            0.093_394_10,
+            1.849_691_42,
+            -4.553_432_05,
+            -23.943_629_59,
+            49.559_538_91,
+        ],
+    ),
+    (
+        "Jupiter",
+        9.547_919_384e-4,
+        [
+            5.202_887_00,
+            0.048_386_24,
+            1.304_396_95,
+            34.396_440_51,
+            14.728_479_83,
+            100.473_909_09,
+        ],
+    ),
+    (
+        "Saturn",
+        2.858_859_807e-4,
+        [
+            9.536_675_94,
+            0.053_861_79,
+            2.485_991_87,
+            49.954_244_23,
+            92.598_878_31,
+            113.662_424_48,
+        ],
+    ),
+    (
+        "Uranus",
+        4.366_244_043e-5,
+        [
+            19.189_164_64,
+            0.047_257_44,
+            0.772_637_83,
+            313.238_104_51,
+            170.954_276_30,
+            74.016_925_03,
+        ],
+    ),
+    (
+        "Neptune",
+        5.151_389_021e-5,
+        [
+            30.069_922_76,
+            0.008_590_48,
+            1.770_043_47,
+            -55.120_029_69,
+            44.964_762_27,
+            131.784_225_74,
+        ],
+    ),
+];
+
+/// The Moon's mass in solar masses and its mean orbit about the Earth, with the
+/// semi-major axis in AU and the inclination, node, argument of perigee and
+/// mean anomaly in degrees
+const MOON_MASS: f64 = 3.694_303_349e-8;
+const MOON_ORBIT: [f64; 6] = [0.002_569_555, 0.0549, 5.145, 125.08, 318.15, 135.27];
+
+fn body(mass: f64) -> Body {
+    Body {
+        mass: mass as f32,
+        mu: (G * mass) as f32,
+        ..Default::default()
+    }
+}
+
+/// The Sun, the planets from Mercury to Neptune and the Moon, named, in their
+/// barycentric frame at J2000
+pub fn solar_system() -> BodySet {
+    let sun = body(1.0);
+    let mut bodies = vec![("Sun", sun)];
+    for (name, mass, [a, e, i, longitude, perihelion, node]) in PLANETS {
+        let elements = OrbitalElements {
+            semi_major_axis: a,
+            eccentricity: e,
+            inclination: i.to_radians(),
+            longitude_of_ascending_node: node.to_radians(),
+            argument_of_periapsis: (perihelion - node).to_radians(),
+            anomaly: Anomaly::Mean((longitude - perihelion).to_radians()),
+        };
+        let planet = body(mass);
+        bodies.push((name, orbiting(&sun, &elements, planet.mu, planet.mass)));
+    }
+    // Split the Earth-Moon barycenter along the Moon's orbit
+    let barycenter = bodies[3].1;
+    let [a, e, i, node, perigee, anomaly] = MOON_ORBIT;
+    let elements = OrbitalElements {
+        semi_major_axis: a,
+        eccentricity: e,
+        inclination: i.to_radians(),
+        longitude_of_ascending_node: node.to_radians(),
+        argument_of_periapsis: perigee.to_radians(),
+        anomaly: Anomaly::Mean(anomaly.to_radians()),
+    };
+    let earth_mass = PLANETS[2].1;
+    let (position, velocity) = elements.state(G * (earth_mass + MOON_MASS));
+    let moon_share = MOON_MASS / (earth_mass + MOON_MASS);
+    let split = |share: f64| {
+        let mut body = barycenter;
+        for i in 0..3 {
+            body.position[i] = (barycenter.position[i] as f64 + share * position[i]) as f32;
+            body.velocity[i] = (barycenter.velocity[i] as f64 + share * velocity[i]) as f32;
+        }
+        body
+    };
+    bodies[3].1 = Body {
+        mass: earth_mass as f32,
+        mu: (G * earth_mass) as f32,
+        ..split(-moon_share)
+    };
+    bodies.push((
+        "Moon",
+        Body {
+            mass: MOON_MASS as f32,
+            mu: (G * MOON_MASS) as f32,
+            ..split(1.0 - moon_share)
+        },
+    ));
+    // From heliocentric to barycentric
+    let total: f64 = bodies.iter().map(|(_, body)| body.mass as f64).sum();
+    let mut position = [0.0; 3];
+    let mut velocity = [0.0; 3];
+    for (_, body) in &bodies {
+        for i in 0..3 {
+            position[i] += body.mass as f64 * body.position[i] as f64 / total;
+            velocity[i] += body.mass as f64 * body.velocity[i] as f64 / total;
+        }
+    }
+    let mut set = BodySet::new();
+    for (name, mut body) in bodies {
+        for i in 0..3 {
+            body.position[i] = (body.position[i] as f64 - position[i]) as f32;
+            body.velocity[i] = (body.velocity[i] as f64 - velocity[i]) as f32;
+        }
+        set.push_named(name, body);
+    }
+    set
+}
@@ -0,0 +1,249 @@
+//! Equilibrium star clusters sampled from spherical distribution functions.
+//!
+//! The models are in N-body units with `G = 1`, so the `mu` of every body is
+//! its mass. Clusters are shifted into their center of mass frame.
+use super::Rng;
+use crate::structures::Body;
+
+/// Radii beyond this many scale radii are resampled, leaving out the few bodies
+/// on the far tail which would only widen the time step range
+const MAX_RADIUS: f64 = 30.0;
+
+/// A Plummer sphere of `count` bodies sharing `total_mass`, with half of the
+/// mass within about 1.3 `scale_radius`. Velocities are drawn from the
+/// isotropic distribution function, so the cluster starts out in virial
+/// equilibrium.
+pub fn plummer(count: usize, total_mass: f32, scale_radius: f32, seed: u64) -> Vec<Body> {
+    let mut rng = Rng::new(seed);
+    let (total_mass, scale_radius) = (total_mass as f64, scale_radius as f64);
+    let mass = total_mass / count as f64;
+    let mut bodies: Vec<Body> = (0..count)
+        .map(|_| {
+            // Invert the cumulative mass M(r) / M = r^3 / (r^2 + 1)^(3/2)
+            let radius = loop {
+                let fraction = rng.uniform();
+                let radius = (fraction.powf(-2.0 / 3.0) - 1.0).powf(-0.5);
+                if radius.is_finite() && radius < MAX_RADIUS {
+                    break radius;
+                }
+            };
+            // Speed as a fraction of the escape speed, by rejection from
+            // g(q) = q^2 (1 - q^2)^(7/2), which peaks below 0.1
+            let fraction = loop {
+                let q = rng.uniform();
+                if 0.1 * rng.uniform() < q * q * (1.0 - q * q).powf(3.5) {
+                    break q;
+                }
+            };
+            let escape =
+                (2.0 * total_mass / scale_radius).sqrt() * (1.0 + radius * radius).powf(-0.25);
+            body(
+                rng.unit_vector().map(|x| x * radius * scale_radius),
+                rng.unit_vector().map(|x| x * fraction * escape),
+                mass,
+            )
+        })
+        .collect();
+    center(&mut bodies);
+    bodies
+}
+
+/// A Hernquist sphere of `count` bodies sharing `total_mass`, the cuspy profile
+/// of galaxy bulges and elliptical galaxies, with half of the mass within about
+/// 2.4 `scale_radius`. Velocities are drawn from the isotropic distribution
+/// function of Hernquist (1990).
+pub fn hernquist(count: usize, total_mass: f32, scale_radius: f32, seed: u64) -> Vec<Body> {
+    let mut rng = Rng::new(seed);
+    let (total_mass, scale_radius) = (total_mass as f64, scale_radius as f64);
+    let mass = total_mass / count as f64;
+    // The tail beyond the largest radius holds a few percent of the mass, which
+    // the bodies within make up for
+    let enclosed = (MAX_RADIUS / (MAX_RADIUS + 1.0)).powi(2);
+    let speed_scale = (total_mass / enclosed / scale_radius).sqrt();
+    let mut bodies: Vec<Body> = (0..count)
+        .map(|_| {
+            // Invert the cumulative mass M(r) / M = r^2 / (r + 1)^2
+            let radius = loop {
+                let root = rng.uniform().sqrt();
+                let radius = root / (1.0 - root);
+                if radius > 0.0 && radius < MAX_RADIUS {
+                    break radius;
+                }
+            };
+            // Relative potential and the distribution function of the binding
+            // energy, both in units of the total mass over the scale radius
+            let potential = 1.0 / (1.0 + radius);
+            let distribution = |energy: f64| {
+                let q = energy.sqrt();
+                let rest = 1.0 - energy;
+                (3.0 * q.asin()
+                    + (energy * rest).sqrt()
+                        * (1.0 - 2.0 * energy)
+                        * (8.0 * energy * energy - 8.0 * energy - 3.0))
+                    / rest.powf(2.5)
+            };
+            let escape = (2.0 * potential).sqrt();
+            let speed = sample_speed(&mut rng, escape, |v| {
+                v * v * distribution(potential - 0.5 * v * v).max(0.0)
+            });
+            body(
+                rng.unit_vector().map(|x| x * radius * scale_radius),
+                rng.unit_vector().map(|x| x * speed * speed_scale),
+                mass,
+            )
+        })
+        .collect();
+    center(&mut bodies);
+    bodies
+}
+
+/// A King model of `count` bodies sharing `total_mass`, the tidally truncated
+/// profile of globular clusters. `w0` is the central potential in units of the
+/// velocity dispersion squared, from about 1 for a loose cluster to 12 for a
+/// concentrated one, and sets the tidal radius in units of `core_radius`.
+/// Velocities are drawn from the lowered isothermal distribution function.
+pub fn king(count: usize, total_mass: f32, core_radius: f32, w0: f32, seed: u64) -> Vec<Body> {
+    assert!(
+        w0 > 0.0,
+        "The central potential of a King model must be positive"
+    );
+    let mut rng = Rng::new(seed);
+    let profile = king_profile(w0 as f64);
+    let (radii, potentials, masses) = (&profile[0], &profile[1], &profile[2]);
+    let king_mass = masses[masses.len() - 1];
+    // From the units of the profile, with the core radius, the central velocity
+    // dispersion parameter and G one
+    let length_scale = core_radius as f64;
+    let speed_scale = (total_mass as f64 / king_mass / length_scale).sqrt();
+    let mass = total_mass as f64 / count as f64;
+    let mut bodies: Vec<Body> = (0..count)
+        .map(|_| {
+            // Invert the tabulated cumulative mass
+            let target = rng.uniform() * king_mass;
+            let index = masses
+                .partition_point(|&mass| mass < target)
+                .clamp(1, masses.len() - 1);
+            let t = (target - masses[index - 1]) / (masses[index] - masses[index - 1]);
+            let radius = radii[index - 1] + t * (radii[index] - radii[index - 1]);
+            let potential = potentials[index - 1] + t * (potentials[index] - potentials[index - 1]);
+            let potential = potential.max(0.0);
+            let speed = sample_speed(&mut rng, (2.0 * potential).sqrt(), |v| {
+                v * v * ((potential - 0.5 * v * v).exp() - 1.0).max(0.0)
+            });
+            body(
+                rng.unit_vector().map(|x| x * radius * length_scale),
+                rng.unit_vector().map(|x| x * speed * speed_scale),
+                mass,
+            )
+        })
+        .collect();
+    center(&mut bodies);
+    bodies
+}
+
+/// Radius, potential and enclosed mass of the King model with central potential
+/// `w0` out to the tidal radius, from Poisson's equation `W'' + 2 W' / r =
+/// -9 rho(W) / rho(W0)` in units of the core radius with `4 pi G rho0 = 9`
+fn king_profile(w0: f64) -> [Vec<f64>; 3] {
+    let central = king_density(w0);
+    let derivative =
+        |r: f64, [w, dw]: [f64; 2]| [dw, -9.0 * king_density(w) / central - 2.0 * dw / r];
+    // Start off the singular center with the series W = W0 - 3 r^2 / 2
+    let mut r = 1e-4;
+    let mut state = [w0 - 1.5 * r * r, -3.0 * r];
+    let mut profile = [vec![0.0], vec![w0], vec![0.0]];
+    while state[0] > 0.0 {
+        let h = 1e-3 * (1.0 + r);
+        let k1 = derivative(r, state);
+        let k2 = derivative(r + h / 2.0, [0, 1].map(|i| state[i] + h / 2.0 * k1[i]));
+        let k3 = derivative(r + h / 2.0, [0, 1].map(|i| state[i] + h / 2.0 * k2[i]));
+        let k4 = derivative(r + h, [0, 1].map(|i| state[i] + h * k3[i]));
+        let next = [0, 1].map(|i| state[i] + h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]));
+        let step = if next[0] > 0.0 {
+            h
+        } else {
+            h * state[0] / (state[0] - next[0])
+        };
+        r += step;
+        state = if next[0] > 0.0 { next } else { [0.0, next[1]] };
+        // The enclosed mass follows from Gauss's law, M = -r^2 W'
+        profile[0].push(r);
+        profile[1].push(state[0]);
+        profile[2].push(-r * r * state[1]);
+    }
+    profile
+}
+
+/// Density of the lowered isothermal distribution at potential `w`, up to a
+/// constant factor, integrating over the speeds with Simpson's rule
+fn king_density(w: f64) -> f64 {
+    if w <= 0.0 {
+        return 0.0;
+    }
+    const INTERVALS: usize = 64;
+    let top = (2.0 * w).sqrt();
+    let h = top / INTERVALS as f64;
+    let integrand = |v: f64| v * v * ((w - 0.5 * v * v).exp() - 1.0);
+    let sum: f64 = (0..=INTERVALS)
+        .map(|i| {
+            let weight = match i {
+                0 => 1.0,
+                i if i == INTERVALS => 1.0,
+                i if i % 2 == 1 => 4.0,
+                _ => 2.0,
+            };
+            weight * integrand(i as f64 * h)
+        })
+        .sum();
+    sum * h / 3.0
+}
+
+/// A speed below `top` drawn from the density `pdf` by rejection, bounded by the
+/// largest value on a grid with some headroom
+fn sample_speed(rng: &mut Rng, top: f64, pdf: impl Fn(f64) -> f64) -> f64 {
+    const GRID: usize = 256;
+    let bound = 1.2
+        * (1..=GRID)
+            .map(|i| pdf(top * i as f64 / GRID as f64))
+            .fold(0.0, f64::max);
+    if bound <= 0.0 {
+        return 0.0;
+    }
+    loop {
+        let speed = top * rng.uniform();
+        if bound * rng.uniform() < pdf(speed) {
+            return speed;
+        }
+    }
+}
+
+pub(super) fn body(position: [f64; 3], velocity: [f64; 3], mass: f64) -> Body {
+    Body {
+        position: position.map(|x| x as f32),
+        mass: mass as f32,
+        velocity: velocity.map(|x| x as f32),
+        mu: mass as f32,
+    }
+}
+
+/// Move `bodies` into their center of mass frame
+pub(super) fn center(bodies: &mut [Body]) {
+    let total: f64 = bodies.iter().map(|body| body.mass as f64).sum();
+    if total <= 0.0 {
+        return;
+    }
+    let mut position = [0.0; 3];
+    let mut velocity = [0.0; 3];
+    for body in bodies.iter() {
+        for i in 0..3 {
+            position[i] += body.mass as f64 * body.position[i] as f64 / total;
+            velocity[i] += body.mass as f64 * body.velocity[i] as f64 / total;
+        }
+    }
+    for body in bodies {
+        for i in 0..3 {
+            body.position[i] = (body.position[i] as f64 - position[i]) as f32;
+            body.velocity[i] = (body.velocity[i] as f64 - velocity[i]) as f32;
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Earth satellites from two-line element sets.
+//!
+//! Units are kilometers, seconds and kilograms. The frame is that of the element
+//! sets, equatorial with z along the Earth's axis, so the zonal harmonics of the
+//! Earth apply as they are. The constants are those of WGS-72, which the element
+//! sets are fitted with.
+use std::{f64::consts::PI, fs, io, path::Path};
+
+use super::kepler::{Anomaly, OrbitalElements};
+use crate::bodies::BodySet;
+use crate::format::invalid_data;
+use crate::structures::{Body, BodyProperties};
+
+pub const EARTH_MU: f64 = 398_600.8;
+pub const EARTH_MASS: f64 = 5.9722e24;
+/// Equatorial radius the zonal harmonics are normalized to
+pub const EARTH_RADIUS: f64 = 6_378.135;
+pub const EARTH_J2: f64 = 1.082_616e-3;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+/// Shortest line holding every field up to the checksum
+const LINE_LENGTH: usize = 69;
+
+/// One element set. Angles are in radians and the mean motion in radians per
+/// second, the remaining fields are as in the set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tle {
+    /// From the line before the set, if there is one
+    pub name: Option<String>,
+    pub catalog_number: u32,
+    /// Julian date in UTC
+    pub epoch: f64,
+    pub inclination: f64,
+    pub right_ascension: f64,
+    pub eccentricity: f64,
+    pub argument_of_perigee: f64,
+    pub mean_anomaly: f64,
+    pub mean_motion: f64,
+    /// Drag term in inverse Earth radii, not applied
+    pub bstar: f64,
+}
+
+impl Tle {
+    /// The element sets of a catalog, each optionally preceded by a name line
+    pub fn parse(text: &str) -> io::Result<Vec<Tle>> {
+        let lines: Vec<(usize, &str)> = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+        let mut sets = Vec::new();
+        let mut name = None;
+        let mut i = 0;
+        while i < lines.len() {
+            let (number, line) = lines[i];
+            if line.starts_with("1 ") {
+                let second = match lines.get(i + 1) {
+                    Some((_, second)) if second.starts_with("2 ") => second,
+                    _ => {
+                        return Err(invalid_data(format!(
+                            "Line {}: expected the second line of the set",
+                            number + 1
+                        )))
+                    }
+                };
+                let tle = Tle::from_lines(name.take(), line, second)
+                    .map_err(|error| invalid_data(format!("Line {}: {}", number, error)))?;
+                sets.push(tle);
+                i += 2;
+            } else if line.starts_with("2 ") {
+                return Err(invalid_data(format!(
+                    "Line {}: second line of a set without the first",
+                    number
+                )));
+            } else {
+                // Catalogs in the three line format prefix the names with 0
+                let line = line.strip_prefix("0 ").unwrap_or(line);
+                name = Some(line.trim().to_string());
+                i += 1;
+            }
+        }
+        Ok(sets)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<Tle>> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn from_lines(name: Option<String>, first: &str, second: &str) -> Result<Tle, String> {
+        for (line, text) in [(1, first), (2, second)] {
+            if text.len() < LINE_LENGTH || !text.is_ascii() {
+                return Err(format!("line {} of the set is too short", line));
+            }
+            let expected = checksum(&text[..LINE_LENGTH - 1]);
+            if text[LINE_LENGTH - 1..LINE_LENGTH].parse() != Ok(expected) {
+                return Err(format!(
+                    "checksum of line {} of the set doesn't match",
+                    line
+                ));
+            }
+        }
+        let catalog_number = field(first, 3, 7, "catalog number")?;
+        if field::<u32>(second, 3, 7, "catalog number")? != catalog_number {
+            return Err("the lines are of different satellites".to_string());
+        }
+        let year: i32 = field(first, 19, 20, "epoch year")?;
+        let day: f64 = field(first, 21, 32, "epoch day")?;
+        // Two digit years from 57 on are of the last century
+        let year = if year < 57 { 2000 + year } else { 1900 + year };
+        let degrees =
+            |start, end, name| field::<f64>(second, start, end, name).map(f64::to_radians);
+        let revolutions: f64 = field(second, 53, 63, "mean motion")?;
+        Ok(Tle {
+            name,
+            catalog_number,
+            epoch: new_year(year) + day - 1.0,
+            inclination: degrees(9, 16, "inclination")?,
+            right_ascension: degrees(18, 25, "right ascension")?,
+            eccentricity: field(second, 27, 33, "eccentricity")
+                .map(|digits: u32| digits as f64 * 1e-7)?,
+            argument_of_perigee: degrees(35, 42, "argument of perigee")?,
+            mean_anomaly: degrees(44, 51, "mean anomaly")?,
+            mean_motion: revolutions * 2.0 * PI / SECONDS_PER_DAY,
+            bstar: exponent_field(first, 54, 61, "drag term")?,
+        })
+    }
+
+    /// Mean elements at Julian date `epoch`, from those of the set with the
+    /// secular drift due to J2. Used as osculating elements they place the
+    /// satellite within a few kilometers of where SGP4 would.
+    pub fn elements_at(&self, epoch: f64) -> OrbitalElements {
+        let (e, cos_i) = (self.eccentricity, self.inclination.cos());
+        let beta = (1.0 - e * e).sqrt();
+        // Recover the semi-major axis and mean motion from the mean motion of
+        // the set, which includes the J2 term, as SGP4 does
+        let k2 = 0.5 * EARTH_J2 * EARTH_RADIUS * EARTH_RADIUS;
+        let delta = |a: f64| 1.5 * k2 * (3.0 * cos_i * cos_i - 1.0) / (a * a * beta.powi(3));
+        let a1 = (EARTH_MU / (self.mean_motion * self.mean_motion)).cbrt();
+        let d1 = delta(a1);
+        let a0 = a1 * (1.0 - d1 / 3.0 - d1 * d1 - 134.0 / 81.0 * d1.powi(3));
+        let d0 = delta(a0);
+        let n = self.mean_motion / (1.0 + d0);
+        let a = a0 / (1.0 - d0);
+        let factor = EARTH_J2 * (EARTH_RADIUS / (a * beta * beta)).powi(2);
+        let elapsed = (epoch - self.epoch) * SECONDS_PER_DAY;
+        let node_rate = -1.5 * n * factor * cos_i;
+        let perigee_rate = 0.75 * n * factor * (5.0 * cos_i * cos_i - 1.0);
+        let anomaly_rate = n * (1.0 + 0.75 * factor * beta * (3.0 * cos_i * cos_i - 1.0));
+        OrbitalElements {
+            semi_major_axis: a,
+            eccentricity: e,
+            inclination: self.inclination,
+            longitude_of_ascending_node: (self.right_ascension + node_rate * elapsed)
+                .rem_euclid(2.0 * PI),
+            argument_of_periapsis: (self.argument_of_perigee + perigee_rate * elapsed)
+                .rem_euclid(2.0 * PI),
+            anomaly: Anomaly::Mean(
+                (self.mean_anomaly + anomaly_rate * elapsed).rem_euclid(2.0 * PI),
+            ),
+        }
+    }
+
+    /// The satellite at Julian date `epoch`, relative to the Earth at rest at the
+    /// origin, as a massless body
+    pub fn body_at(&self, epoch: f64) -> Body {
+        let (position, velocity) = self.elements_at(epoch).state(EARTH_MU);
+        Body {
+            position: position.map(|x| x as f32),
+            velocity: velocity.map(|x| x as f32),
+            ..Default::default()
+        }
+    }
+}
+
+/// The Earth at rest at the origin, with its properties for J2
+pub fn earth() -> (Body, BodyProperties) {
+    let body = Body {
+        mass: EARTH_MASS as f32,
+        mu: EARTH_MU as f32,
+        ..Default::default()
+    };
+    let properties = BodyProperties {
+        zonal: [EARTH_J2 as f32, 0.0, 0.0],
+        radius: EARTH_RADIUS as f32,
+        ..Default::default()
+    };
+    (body, properties)
+}
+
+/// The Earth followed by the satellites of `sets` at Julian date `epoch`. The
+/// satellites are named by the names of their sets, or by their catalog numbers
+/// for sets without one or with a name taken by an earlier set.
+pub fn satellites(sets: &[Tle], epoch: f64) -> BodySet {
+    let mut bodies = BodySet::new();
+    bodies.push_named("Earth", earth().0);
+    for tle in sets {
+        let body = tle.body_at(epoch);
+        let number = tle.catalog_number.to_string();
+        let name = tle
+            .name
+            .as_deref()
+            .filter(|name| !name.is_empty() && bodies.id_of(name).is_none())
+            .unwrap_or(&number);
+        if bodies.id_of(name).is_none() {
+            bodies.push_named(name, body);
+        } else {
+            bodies.push(body);
+        }
+    }
+    bodies
+}
+
+/// Sum of the digits modulo ten, with each minus sign counting as one
+fn checksum(line: &str) -> u32 {
+    line.chars()
+        .map(|c| match c {
+            '-' => 1,
+            c => c.to_digit(10).unwrap_or(0),
+        })
+        .sum::<u32>()
+        % 10
+}
+
+/// The field in the one-based inclusive columns `start` to `end`
+fn field<T: std::str::FromStr>(
+    line: &str,
+    start: usize,
+    end: usize,
+    name: &str,
+) -> Result<T, String> {
+    let text = line[start - 1..end].trim();
+    text.parse()
+        .map_err(|_| format!("{} is not a number: {:?}", name, text))
+}
+
+/// A field with an implied leading decimal point and a power of ten, such as
+/// ` 12345-3` for `0.12345e-3`
+fn exponent_field(line: &str, start: usize, end: usize, name: &str) -> Result<f64, String> {
+    let text = line[start - 1..end].trim();
+    let invalid = || format!("{} is not a number: {:?}", name, text);
+    if text.len() < 3 {
+        return Err(invalid());
+    }
+    let (mantissa, exponent) = text.split_at(text.len() - 2);
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, mantissa.trim_start_matches('+')),
+    };
+    let mantissa: f64 = format!("0.{}", digits).parse().map_err(|_| invalid())?;
+    let exponent: i32 = exponent.parse().map_err(|_| invalid())?;
+    Ok(sign * mantissa * 10f64.powi(exponent))
+}
+
+/// Julian date of 0h UTC on January 1 of `year` in the Gregorian calendar
+fn new_year(year: i32) -> f64 {
+    let y = (year - 1) as f64;
+    1_721_425.5 + 365.0 * y + (y / 4.0).floor() - (y / 100.0).floor() + (y / 400.0).floor()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISS: &str = "\
+ISS (ZARYA)
+1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927
+2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537
+";
+
+    fn error(text: &str) -> String {
+        let error = Tle::parse(text).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        error.to_string()
+    }
+
+    #[test]
+    fn iss_element_set() {
+        let sets = Tle::parse(ISS).unwrap();
+        assert_eq!(sets.len(), 1);
+        let iss = &sets[0];
+        assert_eq!(iss.name.as_deref(), Some("ISS (ZARYA)"));
+        assert_eq!(iss.catalog_number, 25544);
+        // Day 264.51782528 of 2008, which began at JD 2454466.5
+        assert!((iss.epoch - 2_454_730.017_825_28).abs() < 1e-8);
+        assert!((iss.inclination - 51.6416f64.to_radians()).abs() < 1e-12);
+        assert!((iss.eccentricity - 6.703e-4).abs() < 1e-12);
+        assert!((iss.mean_motion - 15.721_253_91 * 2.0 * PI / SECONDS_PER_DAY).abs() < 1e-15);
+        assert!((iss.bstar + 1.1606e-5).abs() < 1e-15);
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let text = ISS.replace("0  2927", "0  2928");
+        assert_eq!(
+            error(&text),
+            "Line 2: checksum of line 1 of the set doesn't match"
+        );
+    }
+
+    #[test]
+    fn short_line() {
+        let text = ISS.replace("15.72125391563537", "15.72125391");
+        assert_eq!(error(&text), "Line 2: line 2 of the set is too short");
+    }
+}
@@ -0,0 +1,147 @@
+//! Shader modules rendered from the templates, and the compute kernels compiled
+//! from their entry points
+use std::collections::{BTreeMap, HashMap};
+
+use wgpu::{ComputePipelineDescriptor, ShaderModuleDescriptor, ShaderSource};
+
+use crate::error::{describe, scoped, ParabodyError};
+use crate::shader::{self, ShaderDiagnostic};
+use crate::structures::SHARED_LAYOUTS;
+
+/// The module rendered from the shader template the pipeline was created with,
+/// which passes are taken from unless they name another
+pub const DYNAMICS_MODULE: &str = "dynamics";
+/// The module copying single fields out of the bodies for `Pipeline::read_field`
+pub const GATHER_MODULE: &str = "gather.wgsl";
+/// The module searching the nearest neighbors for `Pipeline::search_neighbors`
+pub const NEIGHBORS_MODULE: &str = "neighbors.wgsl";
+
+/// The shader modules of a pipeline and the kernels compiled from them. Every
+/// kernel is compiled once, on first use, and shared by all passes dispatching
+/// it, such as the kick of the forward and the reversed step.
+#[derive(Default)]
+pub struct KernelRegistry {
+    modules: BTreeMap<String, wgpu::ShaderModule>,
+    kernels: Vec<wgpu::ComputePipeline>,
+    /// Position in `kernels` of each module and entry point
+    indices: HashMap<(String, String), usize>,
+}
+
+impl KernelRegistry {
+    /// Render `templates`, pairs of a module name and its WGSL template, with
+    /// `context` and create their modules. Each is checked with naga first, which
+    /// can point the errors back into the template, and its structs shared with
+    /// the host are compared with `SHARED_LAYOUTS`.
+    pub fn render(
+        device: &wgpu::Device,
+        templates: &[(&str, &str)],
+        context: &tera::Context,
+        custom_force: Option<&str>,
+    ) -> Result<Self, ParabodyError> {
+        let mut tera = tera::Tera::default();
+        for (name, template) in templates {
+            tera.add_raw_template(name, template)
+                .map_err(|error| ParabodyError::Template(describe(&error)))?;
+        }
+        let mut registry = Self::default();
+        for (name, template) in templates {
+            let source = tera
+                .render(name, context)
+                .map_err(|error| ParabodyError::Template(describe(&error)))?;
+            let module = shader::validate(name, template, &source).map_err(|diagnostic| {
+                ParabodyError::Shader(blame_custom_force(diagnostic, &source, custom_force))
+            })?;
+            shader::check_layouts(name, &module, &SHARED_LAYOUTS).map_err(ParabodyError::Layout)?;
+            registry.add_module(device, name, &source)?;
+        }
+        Ok(registry)
+    }
+
+    /// Create the module `name` from WGSL `source`, replacing the kernels of any
+    /// module of the same name
+    pub fn add_module(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        source: &str,
+    ) -> Result<(), ParabodyError> {
+        let module = scoped(
+            device,
+            || format!("Could not compile {}", name),
+            || {
+                device.create_shader_module(ShaderModuleDescriptor {
+                    label: Some(name),
+                    source: ShaderSource::Wgsl(source.into()),
+                })
+            },
+        )?;
+        self.indices.retain(|(module, _), _| module != name);
+        self.modules.insert(name.to_string(), module);
+        Ok(())
+    }
+
+    /// Names of the modules, in order
+    pub fn modules(&self) -> Vec<&str> {
+        self.modules.keys().map(String::as_str).collect()
+    }
+
+    /// Index of the kernel of `entry_point` in `module`, compiled with `layout`
+    /// unless it already was
+    pub fn kernel(
+        &mut self,
+        device: &wgpu::Device,
+        module: &str,
+        entry_point: &str,
+        layout: &wgpu::PipelineLayout,
+    ) -> Result<usize, ParabodyError> {
+        let key = (module.to_string(), entry_point.to_string());
+        if let Some(&index) = self.indices.get(&key) {
+            return Ok(index);
+        }
+        let shader = self
+            .modules
+            .get(module)
+            .ok_or_else(|| ParabodyError::UnknownModule(module.to_string()))?;
+        let kernel = scoped(
+            device,
+            || format!("Could not create kernel {}", entry_point),
+            || {
+                device.create_compute_pipeline(&ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    module: shader,
+                    entry_point,
+                    layout: Some(layout),
+                })
+            },
+        )?;
+        self.kernels.push(kernel);
+        self.indices.insert(key, self.kernels.len() - 1);
+        Ok(self.kernels.len() - 1)
+    }
+
+    /// The kernel at `index`, as returned by `kernel`
+    pub fn get(&self, index: usize) -> &wgpu::ComputePipeline {
+        &self.kernels[index]
+    }
+}
+
+/// Point `diagnostic` into the custom force snippet if its line was rendered from it
+fn blame_custom_force(
+    mut diagnostic: Box<ShaderDiagnostic>,
+    rendered: &str,
+    custom_force: Option<&str>,
+) -> Box<ShaderDiagnostic> {
+    let snippet = custom_force.unwrap_or_default();
+    if snippet.is_empty() {
+        return diagnostic;
+    }
+    if let Some(offset) = rendered.find(snippet) {
+        let first = rendered[..offset].matches('\n').count() + 1;
+        let lines = first..first + snippet.lines().count();
+        if lines.contains(&diagnostic.line) {
+            diagnostic.template = "custom force".to_string();
+            diagnostic.template_line = Some(diagnostic.line - first + 1);
+        }
+    }
+    diagnostic
+}
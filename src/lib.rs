@@ -0,0 +1,59 @@
+pub mod accuracy;
+pub mod analysis;
+pub mod archive;
+pub mod bench;
+pub mod binaries;
+pub mod blender;
+pub mod bodies;
+pub mod cancel;
+pub mod chaos;
+pub mod checkpoint;
+pub mod cluster;
+pub mod ensemble;
+pub mod ephemeris;
+pub mod error;
+pub mod epoch;
+pub mod escape;
+pub mod events;
+pub mod format;
+pub mod groups;
+#[cfg(feature = "viewer")]
+pub mod gui;
+#[cfg(feature = "headless")]
+pub mod headless;
+pub mod ic;
+pub mod kernels;
+pub mod limits;
+#[cfg(feature = "nalgebra")]
+pub mod linalg;
+pub mod maneuver;
+pub mod manifest;
+pub mod mirror;
+pub mod montecarlo;
+pub mod neighbors;
+pub mod output;
+pub mod pipeline;
+pub mod pm;
+pub mod profile;
+pub mod progress;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod reversibility;
+pub mod scenario;
+pub mod section;
+pub mod shader;
+#[cfg(feature = "glam")]
+pub mod state;
+pub mod stop;
+pub mod structures;
+pub mod summary;
+pub mod sweep;
+pub mod throttle;
+pub mod thrust;
+pub mod trajectory;
+#[cfg(feature = "usd")]
+pub mod usd;
+mod vector;
+#[cfg(feature = "viewer")]
+pub mod viewer;
+pub mod watch;
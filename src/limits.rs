@@ -0,0 +1,272 @@
+//! Fitting the static configuration to what an adapter can actually do, so that
+//! downlevel hardware runs a reduced simulation instead of failing device creation.
+use std::mem::size_of;
+
+use wgpu::{DownlevelCapabilities, DownlevelFlags, Limits};
+
+use crate::error::ParabodyError;
+use crate::structures::{Body, ForceSolver, StaticConfig, Tangent, MAX_PM_GRID_SIZE};
+
+/// Storage buffers bound by the kernels, excluding the properties buffer
+const CORE_STORAGE_BUFFERS: u32 = 3;
+
+/// Storage buffers bound by the particle-mesh solver
+const GRID_STORAGE_BUFFERS: u32 = 3;
+
+/// Storage buffers bound by the neighbor grid
+const NEIGHBOR_STORAGE_BUFFERS: u32 = 3;
+
+/// Storage buffers bound for the gas state of hydrodynamics
+const HYDRO_STORAGE_BUFFERS: u32 = 1;
+
+/// Storage buffers bound for the bodies prescribed by an ephemeris
+const EPHEMERIS_STORAGE_BUFFERS: u32 = 2;
+
+/// Storage buffers bound for the continuous thrust
+const THRUST_STORAGE_BUFFERS: u32 = 2;
+
+/// Storage buffers bound for the sums of the center of mass
+const COM_STORAGE_BUFFERS: u32 = 1;
+
+/// Storage buffers bound for the tangent vectors
+const VARIATIONAL_STORAGE_BUFFERS: u32 = 1;
+
+/// Storage buffers bound for the systems of an ensemble
+const ENSEMBLE_STORAGE_BUFFERS: u32 = 1;
+
+/// Storage buffers the kernels bind with the features of `static_config`
+fn storage_buffers(static_config: &StaticConfig) -> u32 {
+    CORE_STORAGE_BUFFERS
+        + static_config.uses_properties() as u32
+        + match static_config.force_solver {
+            ForceSolver::Direct => 0,
+            ForceSolver::PM { .. } => GRID_STORAGE_BUFFERS,
+        }
+        + static_config
+            .neighbor_grid
+            .map_or(0, |_| NEIGHBOR_STORAGE_BUFFERS)
+        + static_config
+            .hydrodynamics
+            .map_or(0, |_| HYDRO_STORAGE_BUFFERS)
+        + static_config.ephemeris as u32 * EPHEMERIS_STORAGE_BUFFERS
+        + static_config.thrust as u32 * THRUST_STORAGE_BUFFERS
+        + static_config.com_correction as u32 * COM_STORAGE_BUFFERS
+        + static_config.variational as u32 * VARIATIONAL_STORAGE_BUFFERS
+        + static_config.ensemble as u32 * ENSEMBLE_STORAGE_BUFFERS
+}
+
+/// `requested`, or the largest workgroup size the adapter allows if smaller.
+/// The neighbor grid's scan keeps a `u32` per thread in workgroup memory.
+pub fn workgroup_size(requested: u32, limits: &Limits) -> u32 {
+    requested
+        .min(limits.max_compute_invocations_per_workgroup)
+        .min(limits.max_compute_workgroup_size_x)
+        .min(limits.max_compute_workgroup_storage_size / size_of::<u32>() as u32)
+}
+
+/// Largest storage buffer which can be bound whole
+pub fn storage_buffer_size(limits: &Limits) -> u32 {
+    limits
+        .max_storage_buffer_binding_size
+        .min(limits.max_buffer_size.min(u32::MAX as u64) as u32)
+}
+
+/// Most bodies an adapter can simulate, every per-body buffer has to fit in one
+/// binding and the bodies in one dispatch
+pub fn max_bodies(limits: &Limits, workgroup_size: u32) -> u32 {
+    (storage_buffer_size(limits) / size_of::<Body>() as u32).min(
+        limits
+            .max_compute_workgroups_per_dimension
+            .saturating_mul(workgroup_size),
+    )
+}
+
+/// Whether `static_config` still fits within `limits` with a workgroup size of
+/// `size`, without reducing anything
+pub fn supports_workgroup_size(static_config: &StaticConfig, limits: &Limits, size: u32) -> bool {
+    let dispatchable = |threads: u32| threads / size <= limits.max_compute_workgroups_per_dimension;
+    size > 0
+        && workgroup_size(size, limits) == size
+        && max_bodies(limits, size) >= static_config.max_bodies
+        && match static_config.force_solver {
+            ForceSolver::Direct => true,
+            ForceSolver::PM { grid_size } => dispatchable(grid_size.pow(3)),
+        }
+        && static_config
+            .neighbor_grid
+            .is_none_or(|grid| dispatchable(grid.table_size))
+}
+
+/// Adjust `static_config` to fit within `limits`, logging every change.
+///
+/// Fails if the adapter can't run compute shaders at all, can't hold the
+/// bodies of `StaticConfig::max_bodies`, or can't bind the thrust, the
+/// ephemeris or the per-body properties of the forces, which would change the
+/// scenario instead of only reducing it.
+pub fn fit_static_config(
+    mut static_config: StaticConfig,
+    limits: &Limits,
+    downlevel: &DownlevelCapabilities,
+) -> Result<StaticConfig, ParabodyError> {
+    if !downlevel.flags.contains(DownlevelFlags::COMPUTE_SHADERS) {
+        return Err(ParabodyError::Unsupported(
+            "compute shaders are not supported".to_string(),
+        ));
+    }
+    if limits.max_storage_buffers_per_shader_stage < CORE_STORAGE_BUFFERS {
+        return Err(ParabodyError::Unsupported(format!(
+            "{} storage buffers per shader stage, at least {} are required",
+            limits.max_storage_buffers_per_shader_stage, CORE_STORAGE_BUFFERS
+        )));
+    }
+    if !downlevel.is_webgpu_compliant() {
+        log::warn!("Adapter is downlevel, the configuration may be reduced to fit it");
+    }
+
+    assert!(
+        static_config.workgroup_size > 0,
+        "The workgroup size must be positive"
+    );
+    let workgroup_size = workgroup_size(static_config.workgroup_size, limits);
+    if workgroup_size < static_config.workgroup_size {
+        log::warn!(
+            "Reducing the workgroup size from {} to {}",
+            static_config.workgroup_size,
+            workgroup_size
+        );
+        static_config.workgroup_size = workgroup_size;
+    }
+
+    let buffer_size = storage_buffer_size(limits);
+    let max_bodies = max_bodies(limits, workgroup_size);
+    if static_config.max_bodies > max_bodies {
+        // Reducing it would leave the bodies it was sized for without room
+        return Err(ParabodyError::Unsupported(format!(
+            "max_bodies of {} exceeds the buffer limits, which fit {} bodies",
+            static_config.max_bodies, max_bodies
+        )));
+    }
+
+    if let ForceSolver::PM { grid_size } = static_config.force_solver {
+        assert!(
+            grid_size.is_power_of_two() && (2..=MAX_PM_GRID_SIZE).contains(&grid_size),
+            "The particle-mesh grid size must be a power of two between 2 and {}",
+            MAX_PM_GRID_SIZE
+        );
+        let cells = grid_size.pow(3);
+        if (cells as u64 * size_of::<[f32; 2]>() as u64) > buffer_size as u64
+            || cells / workgroup_size > limits.max_compute_workgroups_per_dimension
+        {
+            log::warn!(
+                "The adapter can't hold a {0}x{0}x{0} particle-mesh grid, \
+                 falling back to direct summation",
+                grid_size
+            );
+            static_config.force_solver = ForceSolver::Direct;
+        }
+    }
+
+    if let Some(grid) = static_config.neighbor_grid {
+        if grid.table_size / workgroup_size > limits.max_compute_workgroups_per_dimension {
+            log::warn!("The adapter can't dispatch the neighbor grid, falling back to all pairs");
+            static_config.neighbor_grid = None;
+        }
+    }
+
+    if static_config.hydrodynamics.is_some() && static_config.neighbor_grid.is_none() {
+        log::warn!("Hydrodynamics requires the neighbor grid, disabling it");
+        static_config.hydrodynamics = None;
+    }
+
+    let tangents = static_config.max_bodies as u64 * size_of::<Tangent>() as u64;
+    if static_config.variational && tangents > buffer_size as u64 {
+        log::warn!("The adapter can't hold the tangent vectors, leaving out the chaos indicators");
+        static_config.variational = false;
+    }
+
+    // Give up the bindings from the least to the most essential until they fit
+    let available = limits.max_storage_buffers_per_shader_stage;
+    let fits = |static_config: &StaticConfig| storage_buffers(static_config) <= available;
+    if static_config.ensemble && !fits(&static_config) {
+        log::warn!("The adapter can't bind the systems of an ensemble, run them one by one");
+        static_config.ensemble = false;
+    }
+    if static_config.variational && !fits(&static_config) {
+        log::warn!("The adapter can't bind the tangent vectors, leaving out the chaos indicators");
+        static_config.variational = false;
+    }
+    if static_config.com_correction && !fits(&static_config) {
+        log::warn!("The adapter can't bind the center of mass sums, leaving its drift");
+        static_config.com_correction = false;
+    }
+    // Without these the run would no longer be the scenario's
+    for (enabled, name) in [
+        (static_config.thrust, "the thrust"),
+        (static_config.ephemeris, "the ephemeris"),
+    ] {
+        if enabled && !fits(&static_config) {
+            return Err(ParabodyError::Unsupported(format!(
+                "{} storage buffers per shader stage, {} are required to bind {}",
+                available,
+                storage_buffers(&static_config),
+                name
+            )));
+        }
+    }
+    if static_config.hydrodynamics.is_some() && !fits(&static_config) {
+        log::warn!("The adapter can't bind the gas state, disabling hydrodynamics");
+        static_config.hydrodynamics = None;
+    }
+    if static_config.neighbor_grid.is_some() && !fits(&static_config) {
+        log::warn!("The adapter can't bind the neighbor grid, falling back to all pairs");
+        static_config.neighbor_grid = None;
+    }
+    if matches!(static_config.force_solver, ForceSolver::PM { .. }) && !fits(&static_config) {
+        log::warn!(
+            "The adapter can't bind the particle-mesh grid, falling back to direct summation"
+        );
+        static_config.force_solver = ForceSolver::Direct;
+    }
+    // Hydrodynamics was given up above, so what's left are forces of the scenario
+    if static_config.uses_properties() && !fits(&static_config) {
+        return Err(ParabodyError::Unsupported(format!(
+            "{} storage buffers per shader stage, {} are required to bind the per-body \
+             properties of the force law, zonal harmonics or radiation pressure",
+            available,
+            storage_buffers(&static_config)
+        )));
+    }
+
+    Ok(static_config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::ForceLaw;
+
+    #[test]
+    fn forces_needing_properties_are_not_dropped() {
+        let limits = Limits {
+            max_storage_buffers_per_shader_stage: CORE_STORAGE_BUFFERS,
+            ..Limits::default()
+        };
+        let static_config = StaticConfig {
+            force_law: ForceLaw::Coulomb {
+                coulomb_constant: 1.0,
+                gravity: true,
+            },
+            ..StaticConfig::default()
+        };
+        let fitted = fit_static_config(static_config, &limits, &DownlevelCapabilities::default());
+        assert!(matches!(fitted, Err(ParabodyError::Unsupported(_))));
+
+        // Gravity alone fits in the core buffers
+        let fitted = fit_static_config(
+            StaticConfig::default(),
+            &limits,
+            &DownlevelCapabilities::default(),
+        );
+        assert!(fitted.is_ok());
+    }
+}
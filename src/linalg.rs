@@ -0,0 +1,70 @@
+//! Body states as nalgebra vectors and matrices, for linear algebra on the
+//! results such as covariances and fits
+use nalgebra::{Matrix3, Matrix3xX, RowDVector, Vector3, Vector6};
+
+use crate::structures::Body;
+
+impl Body {
+    pub fn position_vector(&self) -> Vector3<f64> {
+        Vector3::from(self.position).cast()
+    }
+
+    pub fn velocity_vector(&self) -> Vector3<f64> {
+        Vector3::from(self.velocity).cast()
+    }
+
+    /// The body moved to the position and velocity of the state vector `state`,
+    /// as returned by `Vector6::from`
+    pub fn with_state(self, state: &Vector6<f64>) -> Self {
+        let state = state.cast::<f32>();
+        Body {
+            position: [state[0], state[1], state[2]],
+            velocity: [state[3], state[4], state[5]],
+            ..self
+        }
+    }
+}
+
+/// The position followed by the velocity
+impl From<Body> for Vector6<f64> {
+    fn from(body: Body) -> Self {
+        let (position, velocity) = (body.position_vector(), body.velocity_vector());
+        Vector6::new(
+            position.x, position.y, position.z, velocity.x, velocity.y, velocity.z,
+        )
+    }
+}
+
+impl From<Body> for (Vector3<f64>, Vector3<f64>) {
+    fn from(body: Body) -> Self {
+        (body.position_vector(), body.velocity_vector())
+    }
+}
+
+/// The positions of `bodies` as the columns of a matrix
+pub fn positions(bodies: &[Body]) -> Matrix3xX<f64> {
+    Matrix3xX::from_iterator(
+        bodies.len(),
+        bodies.iter().flat_map(|body| body.position.map(f64::from)),
+    )
+}
+
+/// The velocities of `bodies` as the columns of a matrix
+pub fn velocities(bodies: &[Body]) -> Matrix3xX<f64> {
+    Matrix3xX::from_iterator(
+        bodies.len(),
+        bodies.iter().flat_map(|body| body.velocity.map(f64::from)),
+    )
+}
+
+/// Covariance of the columns of `points` about their mean, such as the spread of
+/// the positions returned by `positions`
+pub fn covariance(points: &Matrix3xX<f64>) -> Matrix3<f64> {
+    let count = points.ncols();
+    if count < 2 {
+        return Matrix3::zeros();
+    }
+    let mean = points.column_mean();
+    let centered = points - mean * RowDVector::from_element(count, 1.0);
+    &centered * centered.transpose() / (count - 1) as f64
+}
@@ -1,43 +1,3001 @@
-use env_logger;
-use pollster;
-
-mod pipeline;
-mod structures;
-use crate::{
-    pipeline::Pipeline,
-    structures::{Body, StaticConfig},
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    iter,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
 };
 
-async fn async_entry() {
-    env_logger::init();
-    println!("Starting parabody.");
+use clap::{Args, Parser, Subcommand};
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "headless")]
+use parabody::headless::FrameWriter;
+#[cfg(feature = "render")]
+use parabody::render::{ColorMap, ColorQuantity};
+#[cfg(feature = "usd")]
+use parabody::usd::UsdExport;
+#[cfg(feature = "viewer")]
+use parabody::viewer::{Viewer, ViewerWindow};
+use parabody::{
+    accuracy::{self, KeplerTracker},
+    archive::{ArchiveHeader, ArchiveReader, ArchiveWriter},
+    bench::{BenchReport, BenchResult},
+    binaries,
+    blender::BlenderExport,
+    cancel::CancellationToken,
+    checkpoint::Checkpoint,
+    cluster,
+    ensemble::Ensemble,
+    ephemeris::Ephemeris,
+    escape::{Ejection, EscapeCriteria},
+    events::{self, Event, EventMonitor},
+    format::{self, Endianness},
+    groups, ic, limits,
+    manifest::RunManifest,
+    montecarlo::{self, MonteCarlo, SampleResult},
+    output::{self, DiagnosticFrame, SnapshotWriter},
+    pipeline::{AdapterSelection, PassGraph, Pipeline},
+    progress::Progress,
+    reversibility,
+    scenario::{self, Scenario},
+    section::{SectionCsvWriter, SectionPoint},
+    stop::{StopCondition, StopMonitor, StopReason},
+    structures::{
+        Body, ExternalPotential, ForceSolver, Integrator, StaticConfig, DEFAULT_WORKGROUP_SIZE,
+    },
+    summary::RunSummary,
+    sweep::{self, RunOutcome, RunResult, Sweep},
+    throttle::DutyCycleGuard,
+    watch::ShaderWatch,
+};
+use serde_json::{json, Value};
+use wgpu::{Backends, DownlevelFlags, Features, Instance, PowerPreference};
+
+/// Steps timed to estimate the runtime of a dry run
+const DRY_RUN_STEPS: usize = 10;
+/// Trips of a reversibility check unless given
+const DEFAULT_TRIPS: u64 = 4;
+/// Bodies and steps of the first trip of a reversibility check without a scenario
+const REVERSIBILITY_BODIES: usize = 256;
+const REVERSIBILITY_STEPS: usize = 1000;
+/// Length and resolution of the two-body orbits of the Kepler check unless given
+const DEFAULT_KEPLER_PERIODS: u64 = 100;
+const DEFAULT_STEPS_PER_PERIOD: u64 = 1000;
+const DEFAULT_ECCENTRICITY: f64 = 0.6;
+/// Snapshots per period the Kepler check follows the orbits with
+const KEPLER_SNAPSHOTS_PER_PERIOD: u64 = 4;
+/// Body counts and steps of a benchmark unless given
+const DEFAULT_BENCH_BODIES: [u64; 3] = [1024, 4096, 16384];
+const DEFAULT_BENCH_STEPS: u64 = 20;
+/// Periodic box and grid the particle-mesh solver is timed with
+const BENCH_BOX_SIZE: f64 = 4.0;
+const BENCH_PM_GRID_SIZE: u32 = 64;
+/// Most runs of a sweep or Monte Carlo samples in one ensemble unless given
+const DEFAULT_BATCH_SIZE: u64 = 4096;
+/// Number of progress updates over a run
+const PROGRESS_UPDATES: usize = 100;
+/// Frames recorded for export when no interval is given
+const DEFAULT_FRAMES: usize = 250;
+/// Size of headless frames when none is given
+#[cfg(feature = "headless")]
+const DEFAULT_FRAME_SIZE: (u32, u32) = (1280, 720);
+/// Largest submission in low-power mode, so the GPU gets frequent breaks
+const LOW_POWER_STEPS: usize = 16;
+/// Busy fraction allowed in low-power mode
+const LOW_POWER_DUTY_CYCLE: f64 = 0.5;
+/// Steps between redraws of the viewer window, until changed in its control panel
+#[cfg(feature = "viewer")]
+const VIEWER_STEPS_PER_FRAME: usize = 10;
+/// The duty cycle is measured over this trailing window
+const DUTY_CYCLE_WINDOW: Duration = Duration::from_secs(30);
+/// Where an interrupted run saves its state when no path is given
+const DEFAULT_CHECKPOINT: &str = "parabody.pbck";
+/// Exit status of a run stopped by an interrupt, as if killed by SIGINT
+const INTERRUPTED_STATUS: i32 = 130;
+
+/// Set when the snapshots go to stdout, moving the messages of the run to stderr
+static STDOUT_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for the messages of a run, which go to stderr instead while
+/// stdout carries the snapshots
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if STDOUT_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Bodies in the generated cluster when no scenario is given
+const DEFAULT_BODIES: usize = 1024;
+const DEMO_DT: f64 = 0.001;
+const DEMO_DURATION: f64 = 100.0;
+/// Keeps close encounters in the generated cluster from needing tiny steps
+const DEMO_SOFTENING: f64 = 0.05;
+
+/// Disk bodies of the first galaxy of the merger preset, the second galaxy and
+/// the bulges add to them
+const MERGER_DISK_BODIES: usize = 2048;
+const MERGER_BULGE_BODIES: usize = 512;
+const MERGER_DT: f64 = 0.01;
+const MERGER_DURATION: f64 = 100.0;
+
+/// Resolves the Moon's orbit with about 150 steps, in years
+const SOLAR_SYSTEM_DT: f64 = 0.0005;
+const SOLAR_SYSTEM_DURATION: f64 = 100.0;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Ten seconds for about 500 steps per orbit in low Earth orbit, and a day
+const TLE_DT: f64 = 10.0;
+const TLE_DURATION: f64 = 86_400.0;
+
+/// The Earth with J2 and the satellites of a catalog of two-line element sets,
+/// from the latest epoch among them
+fn tle_document(path: &Path) -> Value {
+    let sets = ic::tle::Tle::read(path).unwrap_or_else(|error| {
+        eprintln!("Could not read {}: {}", path.display(), error);
+        process::exit(1);
+    });
+    let epoch = sets.iter().map(|tle| tle.epoch).fold(f64::MIN, f64::max);
+    status!("Propagating {} satellites from JD {:.5}", sets.len(), epoch);
+    let (_, properties) = ic::tle::earth();
+    let bodies: Vec<Value> = ic::tle::satellites(&sets, epoch)
+        .iter()
+        .enumerate()
+        .map(|(i, (_, _, body))| {
+            let mut value = json!({
+                "position": body.position,
+                "velocity": body.velocity,
+                "mass": body.mass,
+                "mu": body.mu,
+                "group": usize::from(i > 0),
+                "label": i,
+            });
+            if i == 0 {
+                value["zonal"] = json!(properties.zonal);
+                value["radius"] = json!(properties.radius);
+            }
+            value
+        })
+        .collect();
+    json!({
+        "config": {
+            "dt": TLE_DT,
+            "steps": (TLE_DURATION / TLE_DT).ceil() as u64,
+            "zonal_harmonics": true,
+            "epoch": format!("JD {} UTC", epoch),
+            "time_unit": 1.0,
+        },
+        "bodies": bodies,
+    })
+}
+
+/// Built-in scenarios, run with `--preset`
+#[derive(Clone, Copy)]
+enum Preset {
+    /// Two equal disk galaxies with bulges on a parabolic encounter, the second
+    /// tilted, which merge after a few passages
+    Merger,
+    /// The Sun, planets and Moon from J2000, see `ic::solar_system`. Time is in
+    /// years, so `--duration 1000` runs for a millennium.
+    SolarSystem,
+}
+
+impl Preset {
+    fn document(self) -> Value {
+        match self {
+            Preset::Merger => json!({
+                "config": {
+                    "dt": MERGER_DT,
+                    "steps": (MERGER_DURATION / MERGER_DT).ceil() as u64,
+                    "softening": DEMO_SOFTENING,
+                },
+                "generator": {
+                    "kind": "Merger",
+                    "count": MERGER_DISK_BODIES,
+                    "mass": 1.0,
+                    "scale_length": 1.0,
+                    "scale_height": 0.1,
+                    "dispersion": 0.1,
+                    "bulge": { "count": MERGER_BULGE_BODIES, "mass": 0.25, "scale_radius": 0.2 },
+                    "mass_ratio": 1.0,
+                    "pericenter": 2.0,
+                    "separation": 20.0,
+                    "inclination": 0.5,
+                },
+            }),
+            Preset::SolarSystem => {
+                let bodies: Vec<Value> = ic::solar_system::solar_system()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (_, _, body))| {
+                        json!({
+                            "position": body.position,
+                            "velocity": body.velocity,
+                            "mass": body.mass,
+                            "mu": body.mu,
+                            "color": i,
+                            "label": i,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "config": {
+                        "dt": SOLAR_SYSTEM_DT,
+                        "steps": (SOLAR_SYSTEM_DURATION / SOLAR_SYSTEM_DT).ceil() as u64,
+                        "epoch": format!("JD {} TDB", ic::solar_system::J2000),
+                        "time_unit": ic::solar_system::DAYS_PER_YEAR * SECONDS_PER_DAY,
+                    },
+                    "bodies": bodies,
+                })
+            }
+        }
+    }
+}
+
+/// A cold uniform sphere of unit radius and unit total `mu`
+fn demo_document(bodies: usize) -> Value {
+    json!({
+        "config": {
+            "dt": DEMO_DT,
+            "steps": (DEMO_DURATION / DEMO_DT).ceil() as u64,
+            "softening": DEMO_SOFTENING,
+        },
+        "generator": { "kind": "UniformSphere", "count": bodies, "radius": 1.0, "mu": 1.0 },
+    })
+}
+
+/// The simulated time, with the calendar epoch if there is one
+fn timestamp(pipeline: &Pipeline) -> String {
+    match pipeline.epoch() {
+        Some(epoch) => format!("t = {:.4e} ({})", pipeline.time(), epoch),
+        None => format!("t = {:.4e}", pipeline.time()),
+    }
+}
+
+fn print_dry_run(scenario: &Scenario, pipeline: &mut Pipeline) {
+    let info = pipeline.adapter_info();
+    println!("Adapter:        {} ({:?})", info.name, info.backend);
+    let solver = match pipeline.static_config().force_solver {
+        ForceSolver::Direct => "direct summation".to_string(),
+        ForceSolver::PM { grid_size } => format!("particle-mesh, {}^3 grid", grid_size),
+    };
+    println!(
+        "Solver:         {}, {} passes per step",
+        solver,
+        pipeline.pass_graph().passes().len()
+    );
+    println!(
+        "Bodies:         {} (capacity {})",
+        scenario.bodies.len(),
+        pipeline.static_config().max_bodies
+    );
+    println!("Integrator:     {}", scenario.config.integrator.name());
+    if let Some(epoch) = scenario.epoch() {
+        println!("Epoch:          {}", epoch);
+    }
+    println!(
+        "Workgroup size: {}",
+        pipeline.static_config().workgroup_size
+    );
+    println!("dt:             {}", scenario.config.dt);
+    println!("Steps:          {}", scenario.config.steps);
+    println!(
+        "Simulated time: {}",
+        scenario.config.dt * scenario.config.steps as f32
+    );
+    println!(
+        "GPU memory:     {:.1} KiB",
+        pipeline.gpu_memory() as f64 / 1024.0
+    );
+    // Warm up first so that pipeline creation isn't part of the estimate
+    pipeline.benchmark(1);
+    let step_time = pipeline.benchmark(DRY_RUN_STEPS).as_secs_f64() / DRY_RUN_STEPS as f64;
+    println!(
+        "Estimated time: {:.1} s ({:.3} ms/step)",
+        step_time * scenario.config.steps as f64,
+        step_time * 1e3
+    );
+}
+
+fn print_info() {
+    let adapters = Pipeline::enumerate_adapters();
+    if adapters.is_empty() {
+        println!("No adapters found");
+    }
+    let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+    for adapter in adapters {
+        let limits = &adapter.limits;
+        println!("Adapter {}:        {}", adapter.index, adapter.info.name);
+        println!("Backend:          {:?}", adapter.info.backend);
+        println!("Device type:      {:?}", adapter.info.device_type);
+        let compute = adapter
+            .downlevel
+            .flags
+            .contains(DownlevelFlags::COMPUTE_SHADERS);
+        println!("Compute shaders:  {}", yes_no(compute));
+        println!(
+            "WebGPU compliant: {}",
+            yes_no(adapter.downlevel.is_webgpu_compliant())
+        );
+        println!(
+            "Max buffer:       {:.1} MiB",
+            limits.max_buffer_size as f64 / (1024.0 * 1024.0)
+        );
+        println!(
+            "Max binding:      {:.1} MiB",
+            limits.max_storage_buffer_binding_size as f64 / (1024.0 * 1024.0)
+        );
+        println!(
+            "Storage buffers:  {} per stage",
+            limits.max_storage_buffers_per_shader_stage
+        );
+        println!(
+            "Max workgroups:   {} per dimension",
+            limits.max_compute_workgroups_per_dimension
+        );
+        println!(
+            "Workgroup size:   {} invocations",
+            limits.max_compute_invocations_per_workgroup
+        );
+        println!(
+            "Timestamps:       {}",
+            yes_no(adapter.features.contains(Features::TIMESTAMP_QUERY))
+        );
+        println!(
+            "Push constants:   {}",
+            yes_no(adapter.features.contains(Features::PUSH_CONSTANTS))
+        );
+        println!(
+            "f64 in shaders:   {}",
+            yes_no(adapter.features.contains(Features::SHADER_FLOAT64))
+        );
+        if compute {
+            let workgroup_size = limits::workgroup_size(DEFAULT_WORKGROUP_SIZE, limits);
+            println!(
+                "Max bodies:       {}",
+                limits::max_bodies(limits, workgroup_size)
+            );
+        }
+        println!();
+    }
+}
+
+fn print_round_trip(pipeline: &mut Pipeline, steps: usize) {
+    let error = reversibility::round_trip(pipeline, steps);
+    println!("Round trip:     {} steps each way", error.steps);
+    println!(
+        "Position error: {:.3e} max, {:.3e} rms",
+        error.max_position, error.rms_position
+    );
+    println!(
+        "Velocity error: {:.3e} max, {:.3e} rms",
+        error.max_velocity, error.rms_velocity
+    );
+    if let Some(energy) = error.relative_energy {
+        println!("Energy error:   {:.3e} relative", energy);
+    }
+}
+
+/// Submit the run in chunks behind a progress bar with the estimated time remaining.
+/// With a guard the GPU backs off whenever it has been busy for too much of the
+/// recent past, in low-power mode the chunks are also kept small, and with a frame
+/// interval they end at every multiple of it. `on_chunk` is called with the
+/// completed steps before the first step and after every chunk, and stops the
+/// run by breaking. Once `interrupted` is cancelled the run stops after the
+/// submission in flight. Returns the completed steps and the number of
+/// backoff pauses.
+fn run_with_progress(
+    pipeline: &mut Pipeline,
+    steps: usize,
+    low_power: bool,
+    mut guard: Option<DutyCycleGuard>,
+    frame_interval: Option<usize>,
+    interrupted: &CancellationToken,
+    mut on_chunk: impl FnMut(&mut Pipeline, usize) -> ControlFlow<()>,
+) -> (usize, u64) {
+    let mut chunk = (steps / PROGRESS_UPDATES).max(1);
+    if low_power {
+        chunk = chunk.min(LOW_POWER_STEPS);
+    }
+    let bar = ProgressBar::new(steps as u64).with_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} steps, {msg}")
+            .expect("Invalid progress bar template"),
+    );
+    let moved_bar = bar.clone();
+    pipeline.set_progress_callback(steps, move |progress: &Progress| {
+        moved_bar.set_message(format!(
+            "t = {:.4e}, {:.1} steps/s, ETA {}",
+            progress.time,
+            progress.steps_per_second.unwrap_or(0.0),
+            progress
+                .eta
+                .map_or("?".to_string(), |eta| format!("{:.0} s", eta.as_secs_f64()))
+        ));
+        moved_bar.set_position(progress.completed_steps as u64);
+    });
+    let mut completed = 0;
+    let mut backoffs = 0;
+    let mut flow = on_chunk(pipeline, 0);
+    while completed < steps && !interrupted.is_cancelled() && flow.is_continue() {
+        let mut passes = chunk.min(steps - completed);
+        if let Some(interval) = frame_interval {
+            passes = passes.min(interval - completed % interval);
+        }
+        let submitted = Instant::now();
+        let passes = pipeline.run(passes, interrupted).unwrap_or_else(|error| {
+            bar.abandon();
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+        if let Some(guard) = &mut guard {
+            guard.record(submitted.elapsed());
+            let backoff = guard.backoff();
+            if !backoff.is_zero() {
+                log::debug!("Backing off for {:?}", backoff);
+                thread::sleep(backoff);
+                backoffs += 1;
+            }
+        }
+        completed += passes;
+        flow = on_chunk(pipeline, completed);
+    }
+    pipeline.clear_progress_callback();
+    // An interrupted run leaves the bar where it stopped
+    if completed < steps {
+        bar.abandon();
+    } else {
+        bar.finish();
+    }
+    (completed, backoffs)
+}
+
+/// Set up `pipeline` to run `scenario`, returning the bodies it starts with
+fn load_scenario(
+    pipeline: &mut Pipeline,
+    scenario: &Scenario,
+    ephemeris: Option<&Ephemeris>,
+) -> Vec<Body> {
+    pipeline.set_dt(scenario.config.dt);
+    pipeline.set_softening(scenario.config.softening);
+    if let Err(error) = pipeline.write_bodies(&scenario.bodies()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+    pipeline.write_properties(&scenario.properties());
+    pipeline.write_gas_state(&scenario.gas_states());
+    if let Some(ephemeris) = ephemeris {
+        if let Err(error) = pipeline.write_ephemeris(ephemeris) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+    // Only bound when the scenario has thrust
+    if pipeline.static_config().thrust {
+        if let Err(error) = pipeline.write_thrust(&scenario.thrust) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+    pipeline.set_maneuvers(scenario.maneuvers());
+    let initial = match scenario.config.com_frame {
+        true => {
+            pipeline.move_to_com_frame();
+            pipeline.read_bodies()
+        }
+        false => scenario.bodies(),
+    };
+    // Unless the adapter couldn't bind the sums
+    if pipeline.static_config().com_correction {
+        pipeline.set_com_correction(scenario.config.com_correction);
+    }
+    if let Some(epoch) = scenario.epoch() {
+        pipeline.set_epoch(epoch, scenario.config.time_unit);
+    }
+    pipeline.set_species(&scenario.species);
+    if let Some(post_newtonian) = &scenario.post_newtonian {
+        pipeline.set_post_newtonian(post_newtonian);
+    }
+    pipeline.set_box_size(scenario.config.box_size);
+    initial
+}
+
+/// Replace the state of `pipeline`, set up for `scenario`, with that of the
+/// checkpoint at `path`, returning the steps it was taken after
+fn resume(pipeline: &mut Pipeline, scenario: &Scenario, path: &Path) -> Result<usize, String> {
+    let checkpoint = Checkpoint::load(path).map_err(|error| error.to_string())?;
+    // The workgroup size only changes the speed, autotuning may have picked another
+    let saved = StaticConfig {
+        workgroup_size: pipeline.static_config().workgroup_size,
+        ..checkpoint.static_config
+    };
+    if serde_json::to_value(saved).ok() != serde_json::to_value(pipeline.static_config()).ok() {
+        return Err("it was saved with a different configuration".to_string());
+    }
+    pipeline
+        .load(&checkpoint)
+        .map_err(|error| error.to_string())?;
+    // Skipping the maneuvers applied before the interruption
+    pipeline.set_maneuvers(scenario.maneuvers());
+    Ok((checkpoint.time / scenario.config.dt as f64).abs().round() as usize)
+}
+
+/// GPU n-body simulation
+#[derive(Parser)]
+#[clap(version, about)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a scenario file, a preset or a satellite catalog, or a generated cluster
+    Run(Box<RunArgs>),
+    /// List the adapters and how large a simulation each can run
+    Info,
+    /// Run a scenario over a grid or a random sample of values of its parameters
+    Sweep(SweepArgs),
+    /// Propagate clones of a scenario with Gaussian errors on the initial states of its bodies
+    MonteCarlo(MonteCarloArgs),
+    /// Check the accuracy of the integration on this adapter
+    #[clap(subcommand)]
+    Validate(ValidateCommand),
+    /// Measure the steps and interactions per second across body counts and solvers
+    Bench(BenchArgs),
+    /// Render or export again the frames of an archive recorded with `run --archive`
+    Play(PlayArgs),
+    /// Convert an archive recorded with `run --archive` into an analysis format,
+    /// optionally keeping only some of its frames
+    Convert(ConvertArgs),
+}
+
+#[derive(Args)]
+struct PlayArgs {
+    /// Archive recorded with `run --archive`
+    archive: PathBuf,
+    /// Write the state of every body at every frame, as CSV, with the `hdf5`
+    /// feature as HDF5 or with the `arrow` feature as Parquet or an Arrow IPC
+    /// stream (`.arrows`), chosen by the extension
+    #[clap(long)]
+    output: Option<PathBuf>,
+    /// Export the trajectories with an import script for Blender
+    #[clap(long)]
+    blender: Option<PathBuf>,
+    /// Render numbered PNG frames into a directory
+    #[clap(long)]
+    frames: Option<PathBuf>,
+    /// Export the trajectories as a USD layer
+    #[clap(long)]
+    usd: Option<PathBuf>,
+    /// Size of rendered frames, `<width>x<height>`
+    #[clap(long, value_parser = parse_frame_size)]
+    frame_size: Option<(u32, u32)>,
+    /// Color the bodies by palette, group, mass, speed, potential or energy
+    #[clap(long)]
+    color_by: Option<String>,
+    /// Adapter to render on, by index or by part of its name
+    #[clap(long, value_parser = parse_adapter)]
+    gpu: Option<AdapterSelection>,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// Archive recorded with `run --archive`
+    archive: PathBuf,
+    /// File to write the frames to, as CSV, JSON lines, with the `hdf5` feature
+    /// as HDF5 or with the `arrow` feature as Parquet or an Arrow IPC stream
+    /// (`.arrows`), chosen by the extension
+    output: PathBuf,
+    /// Keep every this many frames of those in the time range
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    stride: u64,
+    /// Skip the frames before this simulated time
+    #[clap(long)]
+    from: Option<f64>,
+    /// Skip the frames after this simulated time
+    #[clap(long)]
+    to: Option<f64>,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    /// Numbers of bodies, separated by commas
+    #[clap(long, value_delimiter = ',', default_values_t = DEFAULT_BENCH_BODIES, value_parser = clap::value_parser!(u64).range(2..))]
+    bodies: Vec<u64>,
+    /// Solvers, `direct` and `pm`, separated by commas
+    #[clap(long, value_delimiter = ',', default_values_t = BenchSolver::ALL, value_parser = parse_bench_solver)]
+    solvers: Vec<BenchSolver>,
+    /// Steps timed of each
+    #[clap(long, default_value_t = DEFAULT_BENCH_STEPS, value_parser = clap::value_parser!(u64).range(1..))]
+    steps: u64,
+    /// Write the report as JSON to this file
+    #[clap(long, default_value = "bench.json")]
+    output: PathBuf,
+    /// Adapter to run on, by index or by part of its name
+    #[clap(long, value_parser = parse_adapter)]
+    gpu: Option<AdapterSelection>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BenchSolver {
+    Direct,
+    ParticleMesh,
+}
+
+impl BenchSolver {
+    const ALL: [Self; 2] = [Self::Direct, Self::ParticleMesh];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Direct => "direct",
+            Self::ParticleMesh => "pm",
+        }
+    }
+
+    /// The scenario of `bodies` bodies the solver is timed on
+    fn document(self, bodies: usize) -> Value {
+        let mut document = demo_document(bodies);
+        if self == Self::ParticleMesh {
+            // The cluster of the demo fits in a box twice its size
+            scenario::set(&mut document, "config.box_size", BENCH_BOX_SIZE.into());
+            scenario::set(
+                &mut document,
+                "generator.center",
+                Value::from(vec![BENCH_BOX_SIZE / 2.0; 3]),
+            );
+            scenario::set(
+                &mut document,
+                "force_solver",
+                json!({ "kind": "PM", "grid_size": BENCH_PM_GRID_SIZE }),
+            );
+        }
+        document
+    }
+}
+
+impl std::fmt::Display for BenchSolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn parse_bench_solver(arg: &str) -> Result<BenchSolver, String> {
+    BenchSolver::ALL
+        .into_iter()
+        .find(|solver| solver.name() == arg)
+        .ok_or_else(|| "must be `direct` or `pm`".to_string())
+}
+
+#[derive(Subcommand)]
+enum ValidateCommand {
+    /// Integrate forwards and back again over trips of doubling length, printing how far the
+    /// state ends from the start
+    Reversibility(Box<ReversibilityArgs>),
+    /// Integrate a circular and an eccentric two-body orbit with every integrator, printing the
+    /// errors of the period, energy and periapsis against the analytic orbit
+    Kepler(KeplerArgs),
+    /// Restart from a frame of an archive recorded with `run --archive`, checking that the frames
+    /// after it come out bit for bit the same. Archives of runs with thrust, an ephemeris,
+    /// maneuvers or the center of mass correction are refused, their state isn't archived
+    Restart(RestartArgs),
+}
+
+#[derive(Args)]
+struct RestartArgs {
+    /// Archive recorded with `run --archive`
+    archive: PathBuf,
+    /// Index of the frame to restart from
+    #[clap(long, default_value_t = 0)]
+    frame: usize,
+    /// Adapter to run on, by index or by part of its name
+    #[clap(long, value_parser = parse_adapter)]
+    gpu: Option<AdapterSelection>,
+}
+
+#[derive(Args)]
+struct KeplerArgs {
+    /// Periods to integrate
+    #[clap(long, default_value_t = DEFAULT_KEPLER_PERIODS, value_parser = clap::value_parser!(u64).range(1..))]
+    periods: u64,
+    /// Steps per period, the resolution of the orbits
+    #[clap(long, default_value_t = DEFAULT_STEPS_PER_PERIOD, value_parser = clap::value_parser!(u64).range(4..))]
+    steps_per_period: u64,
+    /// Eccentricity of the eccentric orbit
+    #[clap(long, default_value_t = DEFAULT_ECCENTRICITY, value_parser = parse_eccentricity)]
+    eccentricity: f64,
+    /// Adapter to run on, by index or by part of its name
+    #[clap(long, value_parser = parse_adapter)]
+    gpu: Option<AdapterSelection>,
+}
+
+#[derive(Args)]
+struct ReversibilityArgs {
+    /// Scenario file in TOML or JSON, whose steps are the length of the first trip
+    scenario: Option<PathBuf>,
+    /// Bodies in the generated cluster
+    #[clap(long, conflicts_with = "scenario", value_parser = clap::value_parser!(u64).range(1..))]
+    bodies: Option<u64>,
+    /// Trips, each twice as long as the one before
+    #[clap(long, default_value_t = DEFAULT_TRIPS, value_parser = clap::value_parser!(u64).range(1..))]
+    trips: u64,
+    /// Fail if the largest position error of any trip is above this
+    #[clap(long)]
+    tolerance: Option<f64>,
+    #[clap(flatten)]
+    overrides: ScenarioOverrides,
+    /// Adapter to run on, by index or by part of its name
+    #[clap(long, value_parser = parse_adapter)]
+    gpu: Option<AdapterSelection>,
+}
+
+#[derive(Args)]
+struct MonteCarloArgs {
+    /// Monte Carlo file in TOML or JSON
+    monte_carlo: PathBuf,
+    /// Write the initial and final states of the bodies of every sample to this CSV
+    #[clap(long, default_value = "montecarlo.csv")]
+    output: PathBuf,
+    /// Most samples integrated together as the systems of an ensemble
+    #[clap(long, default_value_t = DEFAULT_BATCH_SIZE, value_parser = clap::value_parser!(u64).range(1..))]
+    batch_size: u64,
+    /// Adapter to run on, by index or by part of its name
+    #[clap(long, value_parser = parse_adapter)]
+    gpu: Option<AdapterSelection>,
+}
+
+#[derive(Args)]
+struct SweepArgs {
+    /// Sweep file in TOML or JSON
+    sweep: PathBuf,
+    /// Write the values and diagnostics of every run to this CSV
+    #[clap(long, default_value = "sweep.csv")]
+    output: PathBuf,
+    /// Most runs integrated together as the systems of an ensemble
+    #[clap(long, default_value_t = DEFAULT_BATCH_SIZE, value_parser = clap::value_parser!(u64).range(1..))]
+    batch_size: u64,
+    /// Adapter to run on, by index or by part of its name
+    #[clap(long, value_parser = parse_adapter)]
+    gpu: Option<AdapterSelection>,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Scenario file in TOML or JSON
+    scenario: Option<PathBuf>,
+    /// Bodies in the generated cluster
+    #[clap(long, conflicts_with = "scenario", value_parser = clap::value_parser!(u64).range(1..))]
+    bodies: Option<u64>,
+    /// Run a built-in scenario instead, `merger` or `solar-system`. Its parameters can be changed
+    /// with `--set`, e.g. `--set generator.mass_ratio=0.3`.
+    #[clap(long, conflicts_with_all = &["scenario", "bodies"], value_parser = parse_preset)]
+    preset: Option<Preset>,
+    /// Run the satellites of a file of two-line element sets about the Earth
+    /// with J2, in kilometers and seconds from the latest epoch of the sets
+    #[clap(long, conflicts_with_all = &["scenario", "bodies", "preset"])]
+    tle: Option<PathBuf>,
+    #[clap(flatten)]
+    overrides: ScenarioOverrides,
+    /// Print the setup and an estimate of the runtime without running
+    #[clap(long)]
+    dry_run: bool,
+    /// Run forwards and back again, printing how far the state ends from the start
+    #[clap(long)]
+    round_trip: bool,
+    /// Time the passes on the GPU and print where a step spends its time
+    #[clap(long)]
+    profile: bool,
+    /// Time the candidate workgroup sizes before the run and keep the fastest
+    #[clap(long, conflicts_with = "workgroup-size")]
+    autotune: bool,
+    /// Integrate backwards in time, as a negative `config.dt` does
+    #[clap(long)]
+    backward: bool,
+    /// Submit small chunks and keep the GPU idle half of the time
+    #[clap(long)]
+    low_power: bool,
+    /// Show the bodies in a window as they move
+    #[clap(long)]
+    viewer: bool,
+    /// Most steps in one GPU submission, lower it if the driver resets the GPU
+    /// during long runs
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    steps_per_submit: Option<u64>,
+    /// Recreate the device and continue if the driver loses it, reading the state
+    /// back after every submission to resume from
+    #[clap(long, conflicts_with = "viewer")]
+    resilient: bool,
+    /// Largest fraction of the time the GPU may be busy
+    #[clap(long, value_parser = parse_duty_cycle)]
+    max_duty_cycle: Option<f64>,
+    /// Adapter to prefer, `low` or `high`
+    #[clap(long, value_parser = parse_power_preference)]
+    power_preference: Option<PowerPreference>,
+    /// Adapter to run on, by index or by part of its name
+    #[clap(long, conflicts_with = "power-preference", value_parser = parse_adapter)]
+    gpu: Option<AdapterSelection>,
+    /// WGSL file defining `fn custom_acceleration(idx: u32) -> vec3<f32>`, whose
+    /// acceleration is added to that of every body
+    #[clap(long)]
+    custom_force: Option<PathBuf>,
+    /// Recompile the kernels whenever `shaders/dynamics.wgsl` of the source tree
+    /// or the custom force changes on disk, and continue the run with them
+    #[clap(long)]
+    watch: bool,
+    /// Write a JSON summary of the run
+    #[clap(long)]
+    summary: Option<PathBuf>,
+    /// Write the final state
+    #[clap(long)]
+    snapshot: Option<PathBuf>,
+    /// Export the trajectories with an import script for Blender
+    #[clap(long)]
+    blender: Option<PathBuf>,
+    /// Render numbered PNG frames into a directory
+    #[clap(long)]
+    frames: Option<PathBuf>,
+    /// Export the trajectories as a USD layer
+    #[clap(long)]
+    usd: Option<PathBuf>,
+    /// Write the state of every body at the frame interval, as CSV, with the
+    /// `hdf5` feature as HDF5 or with the `arrow` feature as Parquet or an
+    /// Arrow IPC stream (`.arrows`), chosen by the extension. With --stdout the
+    /// format, `jsonl` or `csv`.
+    #[clap(long)]
+    output: Option<PathBuf>,
+    /// Stream the output to stdout, for piping into tools such as jq. The
+    /// messages of the run go to stderr.
+    #[clap(long, requires = "output", conflicts_with = "elements")]
+    stdout: bool,
+    /// Record the state of every body at the frame interval into a compressed
+    /// archive, which `parabody play` renders or exports again
+    #[clap(long)]
+    archive: Option<PathBuf>,
+    /// Sum the forces between neighbors in a fixed order, so that a run
+    /// restarted from a frame of the archive repeats it bit for bit. Not with
+    /// thrust, an ephemeris, the center of mass correction or maneuvers, whose
+    /// state the archive doesn't hold
+    #[clap(long)]
+    deterministic: bool,
+    /// Write the osculating orbital elements about the body at this index to
+    /// the CSV output in place of the states
+    #[clap(long, requires = "output")]
+    elements: Option<usize>,
+    /// Write the Jacobi constant and Tisserand parameter of every body to this
+    /// CSV alongside the output, about the primaries in the restricted
+    /// three-body mode and otherwise as given by --tisserand
+    #[clap(long, requires = "output")]
+    diagnostics: Option<PathBuf>,
+    /// Indices of the central body and the perturber the Tisserand parameters
+    /// are about, `<central>,<perturber>`
+    #[clap(long, requires = "diagnostics", value_parser = parse_body_pair)]
+    tisserand: Option<(usize, usize)>,
+    /// Write the largest Lyapunov exponent and the MEGNO of every body to this
+    /// CSV alongside the output, from tangent vectors integrated with the bodies
+    #[clap(long, requires = "output")]
+    chaos: Option<PathBuf>,
+    /// Write the center of mass, bound mass, velocity dispersion and Lagrangian
+    /// radii of every group of bodies to this CSV alongside the output
+    #[clap(long, requires = "output")]
+    groups: Option<PathBuf>,
+    /// Write the semi-major axis and eccentricity of every binary, a pair of
+    /// mutually nearest bodies bound to each other, to this CSV alongside the
+    /// output
+    #[clap(long, requires = "output")]
+    binaries: Option<PathBuf>,
+    /// Write the density center, core radius and Lagrangian radii about the
+    /// density center of all the bodies to this CSV alongside the output, for
+    /// following core collapse
+    #[clap(long, requires = "output")]
+    cluster: Option<PathBuf>,
+    /// Write the events of the scenario to this CSV, with the state of the body
+    /// at each
+    #[clap(long, conflicts_with = "viewer")]
+    events: Option<PathBuf>,
+    /// Write the points of the scenario's Poincaré surface of section to this
+    /// CSV, the state of each body piercing it
+    #[clap(long, conflicts_with = "viewer")]
+    section: Option<PathBuf>,
+    /// Report bodies farther than this from the barycenter as escaped
+    #[clap(long, conflicts_with = "viewer")]
+    escape_distance: Option<f64>,
+    /// Report bodies on unbound orbits about the other bodies as escaped
+    #[clap(long, conflicts_with = "viewer")]
+    escape_unbound: bool,
+    /// Remove escaped bodies from the simulation, the bodies after them move down
+    #[clap(long, conflicts_with_all = &["viewer", "blender", "usd", "elements", "tisserand", "groups"])]
+    cull_escapers: bool,
+    /// Stop once the total energy drifts from its initial value by more than
+    /// this fraction of it
+    #[clap(long, conflicts_with = "viewer", value_parser = parse_positive)]
+    stop_energy_error: Option<f64>,
+    /// Stop once two bodies come within this distance plus the sum of their radii
+    #[clap(long, conflicts_with = "viewer", value_parser = parse_positive)]
+    stop_collision: Option<f64>,
+    /// Stop once a body escapes by --escape-distance or --escape-unbound
+    #[clap(long, conflicts_with_all = &["viewer", "cull-escapers"])]
+    stop_on_escape: bool,
+    /// Stop once the run has taken this many seconds
+    #[clap(long, conflicts_with = "viewer", value_parser = parse_positive)]
+    max_wall_time: Option<f64>,
+    /// Stop once the simulated time reaches this, before the last step if that
+    /// comes first
+    #[clap(long, conflicts_with = "viewer")]
+    stop_time: Option<f64>,
+    /// Where to save the state when Ctrl-C or SIGTERM stops the run
+    #[clap(long, default_value = DEFAULT_CHECKPOINT)]
+    checkpoint: PathBuf,
+    /// Continue an interrupted run of the same scenario from the checkpoint it
+    /// saved, for the steps it had left. The step size and the other settings
+    /// which can change during a run come from the checkpoint.
+    #[clap(long, conflicts_with_all = &["dry-run", "round-trip"])]
+    resume: Option<PathBuf>,
+    /// Steps between exported frames and output snapshots
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    frame_interval: Option<u64>,
+    /// Size of rendered frames, `<width>x<height>`
+    #[clap(long, value_parser = parse_frame_size)]
+    frame_size: Option<(u32, u32)>,
+    /// Color the bodies by palette, group, mass, speed, potential or energy
+    #[clap(long)]
+    color_by: Option<String>,
+}
+
+/// Values replacing those of the scenario before it is validated, so that they
+/// get the same checks. Sections are given as inline TOML, e.g.
+/// `--force-solver '{ kind = "PM", grid_size = 64 }'`.
+#[derive(Args)]
+#[clap(next_help_heading = "SCENARIO OVERRIDES")]
+struct ScenarioOverrides {
+    /// Time step
+    #[clap(long, allow_hyphen_values = true)]
+    dt: Option<f64>,
+    /// Number of steps
+    #[clap(long, conflicts_with = "duration")]
+    steps: Option<u64>,
+    /// Simulated time, rounded up to whole steps
+    #[clap(long)]
+    duration: Option<f64>,
+    /// Time stepping scheme
+    #[clap(long, value_parser = parse_integrator)]
+    integrator: Option<Integrator>,
+    /// Capacity of the GPU buffers
+    #[clap(long)]
+    max_bodies: Option<u32>,
+    /// Apply the zonal harmonics of the bodies
+    #[clap(long)]
+    zonal_harmonics: bool,
+    /// Periodic box edge length, zero for open boundaries
+    #[clap(long)]
+    box_size: Option<f64>,
+    /// Plummer softening length of the direct-sum gravity
+    #[clap(long)]
+    softening: Option<f64>,
+    /// Threads per workgroup, reduced to what the adapter allows
+    #[clap(long)]
+    workgroup_size: Option<u64>,
+    /// Background potential, a table with a `kind`
+    #[clap(long, value_parser = parse_inline_toml)]
+    external_potential: Option<Value>,
+    /// Radiation pressure, a table with the `source` and `pressure`
+    #[clap(long, value_parser = parse_inline_toml)]
+    radiation_pressure: Option<Value>,
+    /// Post-Newtonian corrections, a table with the `speed_of_light`, the `bodies`
+    /// they apply to and optionally `radiation_reaction`
+    #[clap(long, value_parser = parse_inline_toml)]
+    post_newtonian: Option<Value>,
+    /// Pairwise force law, a table with a `kind`
+    #[clap(long, value_parser = parse_inline_toml)]
+    force_law: Option<Value>,
+    /// Gravity solver, a table with a `kind`
+    #[clap(long, value_parser = parse_inline_toml)]
+    force_solver: Option<Value>,
+    /// Neighbor grid, a table with the `table_size` and `cell_size`
+    #[clap(long, value_parser = parse_inline_toml)]
+    neighbor_grid: Option<Value>,
+    /// Gas dynamics, a table with the `smoothing_length` and optional coefficients
+    #[clap(long, value_parser = parse_inline_toml)]
+    hydrodynamics: Option<Value>,
+    /// Lennard-Jones species, an array of tables
+    #[clap(long, value_parser = parse_inline_toml)]
+    species: Option<Value>,
+    /// Any other value, `<path>=<value>` with a dotted path and an inline TOML
+    /// value, e.g. `--set generator.pericenter=1.5`
+    #[clap(long = "set", value_name = "PATH=VALUE", value_parser = parse_assignment)]
+    assignments: Vec<(String, Value)>,
+}
+
+impl ScenarioOverrides {
+    fn apply(&self, document: &mut Value) {
+        let values = [
+            ("config.dt", self.dt.map(Value::from)),
+            ("config.steps", self.steps.map(Value::from)),
+            (
+                "config.integrator",
+                self.integrator.map(|i| i.name().into()),
+            ),
+            ("config.max_bodies", self.max_bodies.map(Value::from)),
+            (
+                "config.zonal_harmonics",
+                self.zonal_harmonics.then_some(true.into()),
+            ),
+            ("config.box_size", self.box_size.map(Value::from)),
+            ("config.softening", self.softening.map(Value::from)),
+            (
+                "config.workgroup_size",
+                self.workgroup_size.map(Value::from),
+            ),
+            ("external_potential", self.external_potential.clone()),
+            ("radiation_pressure", self.radiation_pressure.clone()),
+            ("post_newtonian", self.post_newtonian.clone()),
+            ("force_law", self.force_law.clone()),
+            ("force_solver", self.force_solver.clone()),
+            ("neighbor_grid", self.neighbor_grid.clone()),
+            ("hydrodynamics", self.hydrodynamics.clone()),
+            ("species", self.species.clone()),
+        ];
+        for (path, value) in values {
+            if let Some(value) = value {
+                scenario::set(document, path, value);
+            }
+        }
+        for (path, value) in &self.assignments {
+            scenario::set(document, path, value.clone());
+        }
+        // With whichever dt ends up in the document, a bad one is reported by the validation
+        let dt = document.pointer("/config/dt").and_then(Value::as_f64);
+        if let (Some(duration), Some(dt)) = (self.duration, dt.filter(|&dt| dt != 0.0)) {
+            let steps = (duration / dt.abs()).ceil() as u64;
+            scenario::set(document, "config.steps", steps.into());
+        }
+    }
+}
+
+fn parse_duty_cycle(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(limit) if limit > 0.0 && limit <= 1.0 => Ok(limit),
+        _ => Err("must be a fraction in (0, 1]".to_string()),
+    }
+}
+
+fn parse_positive(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(value) if value > 0.0 => Ok(value),
+        _ => Err("must be a positive number".to_string()),
+    }
+}
+
+fn parse_power_preference(arg: &str) -> Result<PowerPreference, String> {
+    match arg {
+        "low" => Ok(PowerPreference::LowPower),
+        "high" => Ok(PowerPreference::HighPerformance),
+        _ => Err("must be `low` or `high`".to_string()),
+    }
+}
+
+fn parse_adapter(arg: &str) -> Result<AdapterSelection, String> {
+    let selection = AdapterSelection::parse(arg);
+    let adapters = Pipeline::enumerate_adapters();
+    if adapters
+        .iter()
+        .any(|adapter| selection.matches(adapter.index, &adapter.info))
+    {
+        Ok(selection)
+    } else {
+        let names: Vec<_> = adapters
+            .iter()
+            .map(|adapter| {
+                format!(
+                    "{}: {} ({:?})",
+                    adapter.index, adapter.info.name, adapter.info.backend
+                )
+            })
+            .collect();
+        Err(format!(
+            "no such adapter, available are {}",
+            names.join(", ")
+        ))
+    }
+}
+
+fn parse_frame_size(arg: &str) -> Result<(u32, u32), String> {
+    let size = arg.split_once('x').and_then(|(width, height)| {
+        Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+    });
+    match size {
+        Some((width, height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err("must be given as <width>x<height>".to_string()),
+    }
+}
+
+fn parse_body_pair(arg: &str) -> Result<(usize, usize), String> {
+    let pair = arg.split_once(',').and_then(|(first, second)| {
+        Some((first.trim().parse().ok()?, second.trim().parse().ok()?))
+    });
+    match pair {
+        Some((first, second)) if first != second => Ok((first, second)),
+        _ => Err("must be two different body indices, <first>,<second>".to_string()),
+    }
+}
+
+fn parse_integrator(arg: &str) -> Result<Integrator, String> {
+    Integrator::from_name(arg).ok_or_else(|| {
+        let names: Vec<_> = Integrator::ALL.iter().map(|i| i.name()).collect();
+        format!("must be one of {}", names.join(", "))
+    })
+}
+
+fn parse_preset(arg: &str) -> Result<Preset, String> {
+    match arg {
+        "merger" => Ok(Preset::Merger),
+        "solar-system" => Ok(Preset::SolarSystem),
+        _ => Err("must be merger or solar-system".to_string()),
+    }
+}
+
+/// `<path>=<value>` for `--set`
+fn parse_assignment(arg: &str) -> Result<(String, Value), String> {
+    match arg.split_once('=') {
+        Some((path, value)) if !path.trim().is_empty() => {
+            Ok((path.trim().to_string(), parse_inline_toml(value.trim())?))
+        }
+        _ => Err("must be <path>=<value>".to_string()),
+    }
+}
+
+/// An inline TOML value such as `{ kind = "Kepler", mu = 1.0 }`
+fn parse_inline_toml(arg: &str) -> Result<Value, String> {
+    let document: toml::Value =
+        toml::from_str(&format!("value = {}", arg)).map_err(|error| error.to_string())?;
+    serde_json::to_value(&document["value"]).map_err(|error| error.to_string())
+}
+
+/// A file `run` records the state to at every frame
+trait Recorder {
+    /// Record the latest state of `pipeline`, after `step` steps
+    fn record(&mut self, pipeline: &mut Pipeline, step: usize) -> io::Result<()>;
+
+    /// Number of frames recorded so far
+    fn frames(&self) -> usize;
+
+    /// Flush everything recorded, the recorder is not used afterwards
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+impl Recorder for Box<dyn SnapshotWriter> {
+    fn record(&mut self, pipeline: &mut Pipeline, step: usize) -> io::Result<()> {
+        self.write_snapshot(step, pipeline.time(), &pipeline.read_bodies())
+    }
+
+    fn frames(&self) -> usize {
+        self.snapshots()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+impl Recorder for ArchiveWriter {
+    fn record(&mut self, pipeline: &mut Pipeline, step: usize) -> io::Result<()> {
+        self.write_frame(step, &pipeline.capture())
+    }
+
+    fn frames(&self) -> usize {
+        self.snapshots()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        ArchiveWriter::finish(self)
+    }
+}
+
+impl Recorder for output::ChaosCsvWriter {
+    fn record(&mut self, pipeline: &mut Pipeline, step: usize) -> io::Result<()> {
+        let time = pipeline.time();
+        self.write_snapshot(step, time, &pipeline.read_chaos_indicators())
+    }
+
+    fn frames(&self) -> usize {
+        self.snapshots()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        output::ChaosCsvWriter::finish(self)
+    }
+}
+
+/// Statistics of the groups, given the group of every body in the scenario
+struct GroupsRecorder {
+    writer: output::GroupsCsvWriter,
+    groups: Vec<u32>,
+}
+
+impl Recorder for GroupsRecorder {
+    fn record(&mut self, pipeline: &mut Pipeline, step: usize) -> io::Result<()> {
+        let time = pipeline.time();
+        let bodies = pipeline.read_bodies();
+        let stats = groups::group_stats(pipeline, &bodies, &self.groups);
+        self.writer.write_snapshot(step, time, &stats)
+    }
+
+    fn frames(&self) -> usize {
+        self.writer.snapshots()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.finish()
+    }
+}
+
+impl Recorder for output::BinariesCsvWriter {
+    fn record(&mut self, pipeline: &mut Pipeline, step: usize) -> io::Result<()> {
+        let time = pipeline.time();
+        self.write_snapshot(step, time, &binaries::find_binaries(pipeline))
+    }
+
+    fn frames(&self) -> usize {
+        self.snapshots()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        output::BinariesCsvWriter::finish(self)
+    }
+}
+
+impl Recorder for output::ClusterCsvWriter {
+    fn record(&mut self, pipeline: &mut Pipeline, step: usize) -> io::Result<()> {
+        let time = pipeline.time();
+        self.write_snapshot(step, time, &cluster::cluster_stats(pipeline))
+    }
 
-    const NUM_BODIES: usize = 2_usize.pow(2);
-    let t = 100;
-    let dt = 0.001 as f32;
-    let steps = (t as f32 / dt).ceil() as usize;
+    fn frames(&self) -> usize {
+        self.snapshots()
+    }
 
-    let mut pipeline = Pipeline::create(
+    fn finish(&mut self) -> io::Result<()> {
+        output::ClusterCsvWriter::finish(self)
+    }
+}
+
+/// A recorder with the file it records to
+struct Recording {
+    recorder: Box<dyn Recorder>,
+    /// What is recorded, such as "snapshots of binaries"
+    contents: &'static str,
+    /// `None` for stdout
+    path: Option<PathBuf>,
+}
+
+impl Recording {
+    fn new(recorder: impl Recorder + 'static, contents: &'static str, path: &Path) -> Self {
+        Self {
+            recorder: Box::new(recorder),
+            contents,
+            path: Some(path.to_path_buf()),
+        }
+    }
+
+    /// The file in messages
+    fn target(&self) -> String {
+        self.path
+            .as_ref()
+            .map_or("stdout".to_string(), |path| path.display().to_string())
+    }
+}
+
+/// The writer created for `path`, exiting if it couldn't be
+fn created<T>(path: &Path, writer: io::Result<T>) -> T {
+    writer.unwrap_or_else(|error| {
+        eprintln!("Could not create {}: {}", path.display(), error);
+        process::exit(1);
+    })
+}
+
+async fn run(args: RunArgs) {
+    let RunArgs {
+        scenario: scenario_path,
+        bodies,
+        preset,
+        tle: tle_path,
+        overrides,
+        dry_run,
+        round_trip,
+        profile,
+        autotune,
+        backward,
+        low_power,
+        viewer: show_viewer,
+        steps_per_submit,
+        resilient,
+        max_duty_cycle: mut duty_cycle,
+        power_preference,
+        gpu,
+        custom_force: custom_force_path,
+        watch,
+        summary: summary_path,
+        snapshot: snapshot_path,
+        blender: blender_path,
+        frames: frames_path,
+        usd: usd_path,
+        output: output_path,
+        stdout,
+        archive: archive_path,
+        deterministic,
+        elements: elements_central,
+        diagnostics: diagnostics_path,
+        tisserand,
+        chaos: chaos_path,
+        groups: groups_path,
+        binaries: binaries_path,
+        cluster: cluster_path,
+        events: events_path,
+        section: section_path,
+        escape_distance,
+        escape_unbound,
+        cull_escapers,
+        stop_energy_error,
+        stop_collision,
+        stop_on_escape,
+        max_wall_time,
+        stop_time,
+        checkpoint: checkpoint_path,
+        resume: resume_path,
+        frame_interval,
+        frame_size,
+        color_by,
+    } = args;
+    STDOUT_OUTPUT.store(stdout, Ordering::Relaxed);
+    status!("Starting parabody.");
+    let frame_interval = frame_interval.map(|interval| interval as usize);
+    if show_viewer && !cfg!(feature = "viewer") {
+        eprintln!("--viewer requires building with the `viewer` feature");
+        process::exit(1);
+    }
+    if (frames_path.is_some() || frame_size.is_some()) && !cfg!(feature = "headless") {
+        eprintln!("--frames and --frame-size require building with the `headless` feature");
+        process::exit(1);
+    }
+    if usd_path.is_some() && !cfg!(feature = "usd") {
+        eprintln!("--usd requires building with the `usd` feature");
+        process::exit(1);
+    }
+    if color_by.is_some() && !cfg!(feature = "render") {
+        eprintln!("--color-by requires building with the `viewer` or `headless` feature");
+        process::exit(1);
+    }
+    #[cfg(feature = "render")]
+    let color_quantity = match color_by.as_deref() {
+        None => ColorQuantity::Palette,
+        Some(name) => ColorQuantity::from_name(name).unwrap_or_else(|| {
+            let names: Vec<_> = ColorQuantity::ALL.iter().map(|q| q.name()).collect();
+            eprintln!("--color-by must be one of {}", names.join(", "));
+            process::exit(1);
+        }),
+    };
+    let recording = blender_path.is_some()
+        || frames_path.is_some()
+        || usd_path.is_some()
+        || output_path.is_some()
+        || archive_path.is_some();
+    if show_viewer && recording {
+        eprintln!(
+            "--blender, --frames, --usd, --output and --archive cannot be combined with --viewer"
+        );
+        process::exit(1);
+    }
+    let mut document = match (&scenario_path, preset, &tle_path) {
+        (Some(path), _, _) => Scenario::read_document(path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        }),
+        (None, Some(preset), _) => preset.document(),
+        (None, None, Some(path)) => tle_document(path),
+        (None, None, None) => {
+            demo_document(bodies.map_or(DEFAULT_BODIES, |bodies| bodies as usize))
+        }
+    };
+    overrides.apply(&mut document);
+    let mut scenario = match Scenario::from_document(document) {
+        Ok((scenario, warnings)) => {
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
+            }
+            scenario
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    if backward {
+        scenario.config.dt = -scenario.config.dt.abs();
+    }
+    let section = section_path.as_ref().map(|_| {
+        scenario.section.clone().unwrap_or_else(|| {
+            eprintln!("--section requires a [section] in the scenario");
+            process::exit(1);
+        })
+    });
+
+    let ephemeris = scenario.ephemeris().unwrap_or_else(|error| {
+        eprintln!("Could not load the ephemeris: {}", error);
+        process::exit(1);
+    });
+
+    // The window is opened first so that the pipeline runs on an adapter which can present to it
+    let instance = Instance::new(Backends::all());
+    #[cfg(feature = "viewer")]
+    let window = (show_viewer && !dry_run).then(|| ViewerWindow::open(&instance, "parabody"));
+    #[cfg(feature = "viewer")]
+    let surface = window.as_ref().map(ViewerWindow::surface);
+    #[cfg(not(feature = "viewer"))]
+    let surface = None;
+    let mut builder = Pipeline::builder(
         include_str!("../shaders/dynamics.wgsl"),
-        "main",
         StaticConfig {
-            max_bodies: NUM_BODIES as u32,
+            variational: chaos_path.is_some(),
+            deterministic,
+            ..scenario.static_config()
         },
     )
-    .await;
-    pipeline.set_dt(dt);
-
-    let mut input: [Body; NUM_BODIES] = [Default::default(); NUM_BODIES];
-    input[0].mu = 1.0;
-    input[0].position = [10.0, 10.0, 10.0];
-    input[1].mu = 2.0;
-    pipeline.write_bodies(&input);
-    pipeline.submit_and_block(steps);
+    .pass_graph(PassGraph::split())
+    .adapter(gpu.unwrap_or_else(|| {
+        AdapterSelection::Preference(power_preference.unwrap_or(if low_power {
+            PowerPreference::LowPower
+        } else {
+            PowerPreference::HighPerformance
+        }))
+    }));
+    if let Some(path) = &custom_force_path {
+        let src = fs::read_to_string(path).unwrap_or_else(|error| {
+            eprintln!("Could not read {}: {}", path.display(), error);
+            process::exit(1);
+        });
+        builder = builder.custom_force(src);
+    }
+    // Only checked between the submissions of the progress bar
+    // The section's predicate comes after those of the scenario's events
+    let mut event_predicates = scenario.event_predicates();
+    event_predicates.extend(
+        section
+            .iter()
+            .map(|section| section.definition().predicate()),
+    );
+    if !event_predicates.is_empty() && !show_viewer {
+        builder = builder.events(event_predicates);
+    }
+    let mut pipeline = match surface {
+        Some(surface) => builder.surface(&instance, surface).build().await,
+        None => builder.build().await,
+    }
+    .unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    // The energy and momentum drift from the bodies the run starts with
+    let initial = load_scenario(&mut pipeline, &scenario, ephemeris.as_ref());
+    // Unless the adapter couldn't bind the tangent vectors
+    if pipeline.static_config().variational {
+        pipeline.reset_chaos_indicators();
+    }
+    let resumed_steps = resume_path.as_ref().map_or(0, |path| {
+        let steps = resume(&mut pipeline, &scenario, path).unwrap_or_else(|error| {
+            eprintln!("Could not resume from {}: {}", path.display(), error);
+            process::exit(1);
+        });
+        status!(
+            "Resuming after {} steps at {} from {}",
+            steps,
+            timestamp(&pipeline),
+            path.display()
+        );
+        steps
+    });
+    if let Some(steps) = steps_per_submit {
+        pipeline.set_steps_per_submit(steps as usize);
+    }
+    pipeline.set_resilient(resilient);
+    if watch {
+        let mut shader_watch = ShaderWatch::new().shader(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/shaders/dynamics.wgsl"
+        ));
+        if let Some(path) = &custom_force_path {
+            shader_watch = shader_watch.custom_force(path);
+        }
+        pipeline.set_shader_watch(Some(shader_watch));
+    }
+    if autotune {
+        let timings = pipeline.autotune().unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+        for (size, step_time) in timings {
+            status!(
+                "Workgroups of {:>4}: {:.4} ms/step",
+                size,
+                1e3 * step_time.as_secs_f64()
+            );
+        }
+        status!(
+            "Using workgroups of {} threads",
+            pipeline.static_config().workgroup_size
+        );
+    }
+    if profile {
+        if pipeline.supports_profiling() {
+            pipeline.set_profiling(true);
+        } else {
+            eprintln!(
+                "warning: {} does not support timestamp queries, not profiling",
+                pipeline.adapter_info().name
+            );
+        }
+    }
+    if dry_run {
+        print_dry_run(&scenario, &mut pipeline);
+        return;
+    }
+    if round_trip {
+        print_round_trip(&mut pipeline, scenario.config.steps);
+        return;
+    }
+
+    let start = Instant::now();
+    if low_power {
+        duty_cycle = Some(duty_cycle.map_or(LOW_POWER_DUTY_CYCLE, |limit: f64| {
+            limit.min(LOW_POWER_DUTY_CYCLE)
+        }));
+    }
+    let guard = duty_cycle.map(|limit| DutyCycleGuard::new(limit, DUTY_CYCLE_WINDOW));
+    // Stop between submissions rather than losing the run, a second interrupt exits at once
+    let interrupted = CancellationToken::new();
+    if !show_viewer {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || {
+            if interrupted.cancel() {
+                process::exit(INTERRUPTED_STATUS);
+            }
+        })
+        .expect("Could not install the interrupt handler");
+    }
+    let escape = EscapeCriteria {
+        max_distance: escape_distance,
+        unbound: escape_unbound,
+    };
+    if (cull_escapers || stop_on_escape) && !escape.is_enabled() {
+        eprintln!(
+            "--cull-escapers and --stop-on-escape need --escape-distance or --escape-unbound"
+        );
+        process::exit(1);
+    }
+    let conditions = [
+        stop_energy_error.map(StopCondition::EnergyError),
+        stop_collision.map(|distance| StopCondition::Collision { distance }),
+        stop_on_escape.then_some(StopCondition::Escape(escape)),
+        max_wall_time.map(|seconds| StopCondition::WallClock(Duration::from_secs_f64(seconds))),
+        stop_time.map(StopCondition::SimulatedTime),
+    ];
+    let monitor = StopMonitor::new(conditions.into_iter().flatten().collect(), &mut pipeline);
+    // Runs end on the simulated time limit
+    let steps = monitor.steps_until(
+        &pipeline,
+        scenario.config.steps.saturating_sub(resumed_steps),
+    );
+    // Fitted to the initial state and kept for the run, so that frames are comparable
+    #[cfg(feature = "render")]
+    let color_map = ColorMap::fit(color_quantity, &mut pipeline);
+    #[cfg(feature = "render")]
+    if color_quantity != ColorQuantity::Palette {
+        status!("Coloring by {}", color_map.legend());
+    }
+    // Embedded in every file the run writes
+    let mut manifest = RunManifest::new(&scenario, &pipeline);
+    manifest.arguments = env::args().collect();
+    let mut export = blender_path.as_ref().map(|path| {
+        created(
+            path,
+            BlenderExport::create(path, &scenario.visual_attributes(), &manifest),
+        )
+    });
+    #[cfg(feature = "headless")]
+    let mut frame_writer = frames_path.as_ref().map(|path| {
+        let writer = FrameWriter::create(
+            &pipeline,
+            path,
+            frame_size.unwrap_or(DEFAULT_FRAME_SIZE),
+            &scenario.bodies(),
+            &scenario.visual_attributes(),
+            color_map,
+        );
+        created(path, writer)
+    });
+    #[cfg(feature = "usd")]
+    let mut usd_export = usd_path.as_ref().map(|path| {
+        created(
+            path,
+            UsdExport::create(path, &scenario.visual_attributes(), &manifest),
+        )
+    });
+    if elements_central.is_some_and(|central| central >= scenario.bodies.len()) {
+        eprintln!("--elements must be the index of one of the bodies");
+        process::exit(1);
+    }
+    // In the order they're recorded and finished
+    let mut recordings = Vec::new();
+    if let Some(path) = &output_path {
+        let writer = match elements_central {
+            Some(central) => output::create_elements(path, central, &manifest),
+            None if stdout => output::create_stdout(&path.to_string_lossy(), &manifest),
+            None => output::create(path, &manifest, pipeline.dynamic_config()),
+        };
+        let mut recording = Recording::new(created(path, writer), "snapshots", path);
+        if stdout {
+            recording.path = None;
+        }
+        recordings.push(recording);
+    }
+    if let Some(path) = &archive_path {
+        let header = ArchiveHeader {
+            manifest: manifest.clone(),
+            maneuvers: !scenario.maneuvers.is_empty(),
+            attributes: scenario.visual_attributes(),
+        };
+        let writer = created(path, ArchiveWriter::create(path, &header));
+        recordings.push(Recording::new(writer, "archived snapshots", path));
+    }
+    if tisserand.is_some_and(|(central, perturber)| central.max(perturber) >= scenario.bodies.len())
+    {
+        eprintln!("--tisserand must be the indices of two of the bodies");
+        process::exit(1);
+    }
+    if let Some(path) = &diagnostics_path {
+        let frame = match (scenario.external_potential, tisserand) {
+            (
+                Some(ExternalPotential::Cr3bp {
+                    center,
+                    mass_parameter,
+                }),
+                _,
+            ) => DiagnosticFrame::Synodic {
+                center: center.map(f64::from),
+                mass_parameter: mass_parameter as f64,
+            },
+            (_, Some((central, perturber))) => DiagnosticFrame::Orbits { central, perturber },
+            (_, None) => {
+                eprintln!("--diagnostics needs --tisserand outside the restricted three-body mode");
+                process::exit(1);
+            }
+        };
+        let writer = created(path, output::create_diagnostics(path, frame, &manifest));
+        recordings.push(Recording::new(writer, "snapshots of diagnostics", path));
+    }
+    // Unless the adapter couldn't bind the tangent vectors
+    if let (Some(path), true) = (&chaos_path, pipeline.static_config().variational) {
+        let writer = created(path, output::ChaosCsvWriter::create(path, &manifest));
+        recordings.push(Recording::new(
+            writer,
+            "snapshots of chaos indicators",
+            path,
+        ));
+    }
+    if let Some(path) = &groups_path {
+        let recorder = GroupsRecorder {
+            writer: created(path, output::GroupsCsvWriter::create(path, &manifest)),
+            groups: scenario
+                .visual_attributes()
+                .iter()
+                .map(|attributes| attributes.group)
+                .collect(),
+        };
+        recordings.push(Recording::new(
+            recorder,
+            "snapshots of group statistics",
+            path,
+        ));
+    }
+    if let Some(path) = &binaries_path {
+        let writer = created(path, output::BinariesCsvWriter::create(path, &manifest));
+        recordings.push(Recording::new(writer, "snapshots of binaries", path));
+    }
+    if let Some(path) = &cluster_path {
+        let writer = created(path, output::ClusterCsvWriter::create(path, &manifest));
+        recordings.push(Recording::new(
+            writer,
+            "snapshots of cluster structure",
+            path,
+        ));
+    }
+    let mut section_writer = section_path
+        .as_ref()
+        .map(|path| created(path, SectionCsvWriter::create(path, &manifest)));
+    let frame_interval =
+        recording.then(|| frame_interval.unwrap_or((steps / DEFAULT_FRAMES).max(1)));
+    // Keep simulating when writing a frame fails, the frames so far are still usable
+    let mut record_frame = |pipeline: &mut Pipeline, step: usize| {
+        recordings.retain_mut(
+            |recording| match recording.recorder.record(pipeline, step) {
+                Ok(()) => true,
+                Err(error) => {
+                    eprintln!(
+                        "Could not write {} to {}, stopping them: {}",
+                        recording.contents,
+                        recording.target(),
+                        error
+                    );
+                    false
+                }
+            },
+        );
+        if let Some(writer) = &mut export {
+            if let Err(error) = writer.write_frame(&pipeline.read_bodies()) {
+                eprintln!("Could not write frame, stopping the export: {}", error);
+                export = None;
+            }
+        }
+        #[cfg(feature = "usd")]
+        if let Some(writer) = &mut usd_export {
+            if let Err(error) = writer.write_frame(&pipeline.read_bodies()) {
+                eprintln!("Could not write frame, stopping the USD export: {}", error);
+                usd_export = None;
+            }
+        }
+        #[cfg(feature = "headless")]
+        if let Some(writer) = &mut frame_writer {
+            if let Err(error) = writer.write_frame(pipeline) {
+                eprintln!("Could not write frame, stopping the rendering: {}", error);
+                frame_writer = None;
+            }
+        }
+    };
+    // Index in the scenario of every body left, culling moves the bodies down
+    let mut original: Vec<usize> = (0..scenario.bodies.len()).collect();
+    let mut reported = vec![false; scenario.bodies.len()];
+    let mut ejections = 0;
+    let mut stop_reason = None;
+    // Returns whether bodies were culled
+    let mut check_escapes = |pipeline: &mut Pipeline, original: &mut Vec<usize>| {
+        if !escape.is_enabled() {
+            return false;
+        }
+        let bodies = pipeline.read_bodies();
+        let escapers: Vec<Ejection> = escape
+            .escapers(&bodies)
+            .into_iter()
+            .filter(|ejection| !reported[original[ejection.body]])
+            .collect();
+        for ejection in &escapers {
+            status!(
+                "Body {} escaped at {}, {:.3e} from the barycenter with specific energy {}",
+                original[ejection.body],
+                timestamp(pipeline),
+                ejection.distance,
+                ejection
+                    .energy
+                    .map_or("?".to_string(), |energy| format!("{:.3e}", energy))
+            );
+            reported[original[ejection.body]] = true;
+        }
+        ejections += escapers.len() as u64;
+        // The last bodies stay to keep the simulation going
+        if cull_escapers && !escapers.is_empty() && escapers.len() < bodies.len() {
+            let indices: Vec<usize> = escapers.iter().map(|ejection| ejection.body).collect();
+            pipeline.remove_bodies(&indices);
+            *original = original
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !indices.contains(i))
+                .map(|(_, &index)| index)
+                .collect();
+            return true;
+        }
+        false
+    };
+    let mut event_monitor =
+        (!pipeline.event_predicates().is_empty()).then(|| EventMonitor::new(&mut pipeline));
+    // With the bodies by their index in the scenario
+    let mut logged_events: Vec<Event> = Vec::new();
+    let on_chunk = |pipeline: &mut Pipeline, completed: usize| {
+        if frame_interval.is_some_and(|interval| completed.is_multiple_of(interval)) {
+            // Numbered from the start of the run a resumed run continues
+            record_frame(pipeline, resumed_steps + completed);
+        }
+        if let Some(monitor) = &mut event_monitor {
+            for event in monitor.check(pipeline) {
+                let body = original[event.body];
+                let definition = match scenario.events.get(event.predicate) {
+                    Some(definition) => definition,
+                    None => {
+                        let point = SectionPoint::new(&Event { body, ..event });
+                        let recorded = section.as_ref().is_some_and(|s| s.records(body));
+                        if let (Some(writer), true) = (&mut section_writer, recorded) {
+                            if let Err(error) = writer.write_point(&point) {
+                                eprintln!("Could not write section, stopping it: {}", error);
+                                section_writer = None;
+                            }
+                        }
+                        continue;
+                    }
+                };
+                status!(
+                    "Event \"{}\" of body {} at t = {:.6e}",
+                    definition.name,
+                    body,
+                    event.time
+                );
+                if definition.terminal && stop_reason.is_none() {
+                    stop_reason = Some(StopReason::Event {
+                        event: event.predicate,
+                        body,
+                    });
+                }
+                logged_events.push(Event { body, ..event });
+            }
+        }
+        if check_escapes(pipeline, &mut original) {
+            if let Some(monitor) = &mut event_monitor {
+                monitor.restart(pipeline);
+            }
+        }
+        if stop_reason.is_some() {
+            return ControlFlow::Break(());
+        }
+        match monitor.check(pipeline) {
+            Some(reason) => {
+                stop_reason = Some(reason);
+                ControlFlow::Break(())
+            }
+            None => ControlFlow::Continue(()),
+        }
+    };
+    #[cfg(feature = "viewer")]
+    let (steps, backoffs) = match window {
+        Some(window) => {
+            let viewer = Viewer::new(
+                window,
+                &pipeline,
+                &scenario.bodies(),
+                &scenario.visual_attributes(),
+                color_map,
+            );
+            // Closing the window early ends the run
+            (viewer.run(&mut pipeline, steps, VIEWER_STEPS_PER_FRAME), 0)
+        }
+        None => run_with_progress(
+            &mut pipeline,
+            steps,
+            low_power,
+            guard,
+            frame_interval,
+            &interrupted,
+            on_chunk,
+        ),
+    };
+    #[cfg(not(feature = "viewer"))]
+    let (steps, backoffs) = run_with_progress(
+        &mut pipeline,
+        steps,
+        low_power,
+        guard,
+        frame_interval,
+        &interrupted,
+        on_chunk,
+    );
+    let interrupted = interrupted.is_cancelled();
+    if let Some(reason) = &stop_reason {
+        status!(
+            "Stopped after {} steps at {}: {}",
+            steps,
+            timestamp(&pipeline),
+            reason
+        );
+    }
+    let checkpoint = interrupted.then(|| match pipeline.checkpoint(&checkpoint_path) {
+        Ok(()) => {
+            status!(
+                "Interrupted after {} steps at {}, saved the state to {}, continue with --resume",
+                steps,
+                timestamp(&pipeline),
+                checkpoint_path.display()
+            );
+            Some(checkpoint_path)
+        }
+        Err(error) => {
+            eprintln!(
+                "Could not write checkpoint to {}: {}",
+                checkpoint_path.display(),
+                error
+            );
+            None
+        }
+    });
+    if let Some(stats) = pipeline.stats() {
+        status!("{}", stats);
+    }
     let output = pipeline.read_bodies();
-    println!("{:?}", output.first());
-    println!("{:?}", output.last());
+    let mut summary = RunSummary::new(
+        pipeline.adapter_info().name.clone(),
+        start.elapsed(),
+        steps,
+        scenario.config.dt,
+        &initial,
+        &output,
+    );
+    summary.outputs.extend(checkpoint.flatten());
+    summary.stop_reason = stop_reason.map(|reason| reason.to_string());
+    summary.final_time = pipeline.time();
+    summary.start_epoch = scenario.epoch().map(|epoch| epoch.to_string());
+    summary.final_epoch = pipeline.epoch().map(|epoch| epoch.to_string());
+    if backoffs > 0 {
+        summary
+            .events
+            .insert("duty cycle backoff".to_string(), backoffs);
+    }
+    if ejections > 0 {
+        summary.events.insert("ejection".to_string(), ejections);
+    }
+    for event in &logged_events {
+        let name = scenario.events[event.predicate].name.clone();
+        *summary.events.entry(name).or_default() += 1;
+    }
+    if let Some(path) = events_path {
+        match events::write_csv(&path, &scenario.events, &logged_events, &manifest) {
+            Ok(()) => summary.outputs.push(path),
+            Err(error) => eprintln!("Could not write events to {}: {}", path.display(), error),
+        }
+    }
+    summary.manifest = Some(manifest);
+    if let Some(path) = snapshot_path {
+        // The final state with the scenario's visual attributes, for external viewers
+        let attributes = scenario.visual_attributes();
+        let attributes: Vec<_> = original.iter().map(|&i| attributes[i]).collect();
+        let written = File::create(&path).and_then(|file| {
+            format::write_attributed_bodies(
+                BufWriter::new(file),
+                &output,
+                &attributes,
+                Endianness::native(),
+            )?
+            .flush()
+        });
+        match written {
+            Ok(_) => summary.outputs.push(path),
+            Err(error) => eprintln!("Could not write snapshot to {}: {}", path.display(), error),
+        }
+    }
+    if let (Some(export), Some(interval)) = (export, frame_interval) {
+        let frames = export.frames();
+        match export.finish(interval, scenario.config.dt) {
+            Ok(script) => {
+                status!(
+                    "Exported {} frames, import with `blender --python {}`",
+                    frames,
+                    script.display()
+                );
+                summary.outputs.push(script);
+            }
+            Err(error) => eprintln!("Could not write Blender import script: {}", error),
+        }
+    }
+    #[cfg(feature = "usd")]
+    if let (Some(export), Some(interval)) = (usd_export, frame_interval) {
+        let frames = export.frames();
+        match export.finish(interval, scenario.config.dt) {
+            Ok(layer) => {
+                status!("Exported {} frames to {}", frames, layer.display());
+                summary.outputs.push(layer);
+            }
+            Err(error) => eprintln!("Could not write USD layer: {}", error),
+        }
+    }
+    for mut recording in recordings {
+        match recording.recorder.finish() {
+            Ok(()) => {
+                status!(
+                    "Wrote {} {} to {}",
+                    recording.recorder.frames(),
+                    recording.contents,
+                    recording.target()
+                );
+                summary.outputs.extend(recording.path);
+            }
+            Err(error) => eprintln!(
+                "Could not write {} to {}: {}",
+                recording.contents,
+                recording.target(),
+                error
+            ),
+        }
+    }
+    if let (Some(mut writer), Some(path)) = (section_writer, section_path) {
+        match writer.finish() {
+            Ok(()) => {
+                status!(
+                    "Wrote {} points of the section to {}",
+                    writer.points(),
+                    path.display()
+                );
+                summary.outputs.push(path);
+            }
+            Err(error) => eprintln!("Could not write {}: {}", path.display(), error),
+        }
+    }
+    #[cfg(feature = "headless")]
+    if let (Some(writer), Some(path)) = (frame_writer, frames_path) {
+        status!("Rendered {} frames to {}", writer.frames(), path.display());
+        summary.outputs.push(path);
+    }
+    if let Some(path) = summary_path {
+        summary.outputs.push(path.clone());
+        if let Err(error) = summary.write_json(&path) {
+            eprintln!("Could not write summary to {}: {}", path.display(), error);
+        }
+    }
+    status!("{}", summary);
+    if interrupted {
+        process::exit(INTERRUPTED_STATUS);
+    }
+}
+
+fn sweep(args: SweepArgs) {
+    let SweepArgs {
+        sweep: sweep_path,
+        output,
+        batch_size,
+        gpu,
+    } = args;
+    let sweep = Sweep::load(&sweep_path).unwrap_or_else(|error| {
+        eprintln!("Could not load {}: {}", sweep_path.display(), error);
+        process::exit(1);
+    });
+    let base = Scenario::read_document(&sweep.scenario).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let runs = sweep.runs();
+    println!(
+        "Sweeping {} over {} runs",
+        sweep.scenario.display(),
+        runs.len()
+    );
+    // Invalid values only fail their own run
+    let scenarios: Vec<Result<Scenario, String>> = runs
+        .iter()
+        .map(|values| {
+            let mut document = base.clone();
+            sweep.apply(&mut document, values);
+            Scenario::from_document(document)
+                .map(|(scenario, _)| scenario)
+                .map_err(|error| error.to_string().replace("\n  ", " "))
+        })
+        .collect();
+    let adapter = gpu.unwrap_or(AdapterSelection::Preference(
+        PowerPreference::HighPerformance,
+    ));
+    let results: Vec<RunResult> = runs
+        .into_iter()
+        .zip(run_scenarios(&scenarios, batch_size, &adapter))
+        .map(|(values, (batch, finished))| RunResult {
+            values,
+            batch,
+            outcome: finished
+                .map(|finished| RunOutcome::new(&finished.initial, &finished.last, finished.time)),
+        })
+        .collect();
+    match sweep::write_results(&output, &sweep, &results) {
+        Ok(()) => println!("Wrote the results to {}", output.display()),
+        Err(error) => {
+            eprintln!("Could not write {}: {}", output.display(), error);
+            process::exit(1);
+        }
+    }
+}
+
+fn monte_carlo(args: MonteCarloArgs) {
+    let MonteCarloArgs {
+        monte_carlo: monte_carlo_path,
+        output,
+        batch_size,
+        gpu,
+    } = args;
+    let monte_carlo = MonteCarlo::load(&monte_carlo_path).unwrap_or_else(|error| {
+        eprintln!("Could not load {}: {}", monte_carlo_path.display(), error);
+        process::exit(1);
+    });
+    let nominal = match Scenario::load(&monte_carlo.scenario) {
+        Ok((scenario, warnings)) => {
+            for warning in &warnings {
+                eprintln!("warning: {}", warning);
+            }
+            scenario
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    if let Err(error) = monte_carlo.check_bodies(nominal.bodies.len()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+    println!(
+        "Propagating {} samples of {}",
+        monte_carlo.samples,
+        monte_carlo.scenario.display()
+    );
+    let scenarios: Vec<Result<Scenario, String>> = monte_carlo
+        .scenarios(&nominal)
+        .into_iter()
+        .map(Ok)
+        .collect();
+    let adapter = gpu.unwrap_or(AdapterSelection::Preference(
+        PowerPreference::HighPerformance,
+    ));
+    let results: Vec<SampleResult> = scenarios
+        .iter()
+        .zip(run_scenarios(&scenarios, batch_size, &adapter))
+        .map(|(scenario, (_, finished))| match finished {
+            Ok(finished) => SampleResult {
+                initial: finished.initial,
+                last: Ok(finished.last),
+            },
+            Err(error) => SampleResult {
+                initial: scenario.as_ref().map(Scenario::bodies).unwrap_or_default(),
+                last: Err(error),
+            },
+        })
+        .collect();
+    match montecarlo::write_results(&output, &results) {
+        Ok(()) => println!("Wrote the samples to {}", output.display()),
+        Err(error) => {
+            eprintln!("Could not write {}: {}", output.display(), error);
+            process::exit(1);
+        }
+    }
+}
+
+fn validate_reversibility(args: ReversibilityArgs) {
+    let ReversibilityArgs {
+        scenario: scenario_path,
+        bodies,
+        trips,
+        tolerance,
+        overrides,
+        gpu,
+    } = args;
+    let mut document = match &scenario_path {
+        Some(path) => Scenario::read_document(path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        }),
+        None => {
+            let mut document =
+                demo_document(bodies.map_or(REVERSIBILITY_BODIES, |bodies| bodies as usize));
+            scenario::set(&mut document, "config.steps", REVERSIBILITY_STEPS.into());
+            document
+        }
+    };
+    overrides.apply(&mut document);
+    let scenario = match Scenario::from_document(document) {
+        Ok((scenario, warnings)) => {
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
+            }
+            scenario
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    let ephemeris = scenario.ephemeris().unwrap_or_else(|error| {
+        eprintln!("Could not load the ephemeris: {}", error);
+        process::exit(1);
+    });
+    let pipeline = pollster::block_on(
+        Pipeline::builder(
+            include_str!("../shaders/dynamics.wgsl"),
+            scenario.static_config(),
+        )
+        .pass_graph(PassGraph::split())
+        .adapter(gpu.unwrap_or(AdapterSelection::Preference(
+            PowerPreference::HighPerformance,
+        )))
+        .build(),
+    );
+    let mut pipeline = pipeline.unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    load_scenario(&mut pipeline, &scenario, ephemeris.as_ref());
+    let integrator = scenario.config.integrator;
+    println!(
+        "Round trips of {} bodies with {} on {}",
+        scenario.bodies.len(),
+        integrator.name(),
+        pipeline.adapter_info().name
+    );
+    if !integrator.is_time_symmetric() {
+        println!(
+            "{} is not time-symmetric, expect the errors to grow with the steps",
+            integrator.name()
+        );
+    }
+    let steps: Vec<usize> = (0..trips)
+        .map(|trip| scenario.config.steps << trip)
+        .collect();
+    let errors = reversibility::round_trips(&mut pipeline, &steps);
+    println!(
+        "{:>10}  {:>12}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "Steps", "Max position", "RMS position", "Max velocity", "RMS velocity", "Energy"
+    );
+    for error in &errors {
+        println!(
+            "{:>10}  {:>12.3e}  {:>12.3e}  {:>12.3e}  {:>12.3e}  {:>12}",
+            error.steps,
+            error.max_position,
+            error.rms_position,
+            error.max_velocity,
+            error.rms_velocity,
+            error
+                .relative_energy
+                .map_or("-".to_string(), |energy| format!("{:.3e}", energy))
+        );
+    }
+    if let Some(tolerance) = tolerance {
+        // A NaN fails too
+        if let Some(error) = errors
+            .iter()
+            .find(|error| !error.max_position.is_finite() || error.max_position > tolerance)
+        {
+            eprintln!(
+                "The position error {:.3e} after {} steps is above the tolerance {:.3e}",
+                error.max_position, error.steps, tolerance
+            );
+            process::exit(1);
+        }
+    }
+}
+
+fn validate_kepler(args: KeplerArgs) {
+    let KeplerArgs {
+        periods,
+        steps_per_period,
+        eccentricity,
+        gpu,
+    } = args;
+    let adapter = gpu.unwrap_or(AdapterSelection::Preference(
+        PowerPreference::HighPerformance,
+    ));
+    let period = accuracy::two_body_period();
+    let dt = period / steps_per_period as f64;
+    let steps = (periods * steps_per_period) as usize;
+    let interval = (steps_per_period / KEPLER_SNAPSHOTS_PER_PERIOD) as usize;
+    println!(
+        "Two-body orbits over {} periods of {} steps",
+        periods, steps_per_period
+    );
+    println!(
+        "{:<10}  {:>12}  {:>12}  {:>12}  {:>12}  {:>14}",
+        "Integrator", "Eccentricity", "Period", "Energy", "Position", "Periapsis/orbit"
+    );
+    for integrator in Integrator::ALL {
+        for eccentricity in [0.0, eccentricity] {
+            let bodies = accuracy::two_body(eccentricity);
+            let document = json!({
+                "config": {
+                    "dt": dt,
+                    "steps": steps,
+                    "integrator": integrator.name(),
+                },
+                "bodies": bodies
+                    .iter()
+                    .map(|body| json!({
+                        "position": body.position,
+                        "velocity": body.velocity,
+                        "mu": body.mu,
+                        "mass": body.mass,
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+            let scenario = match Scenario::from_document(document) {
+                Ok((scenario, _)) => scenario,
+                Err(error) => panic!("Invalid two-body scenario: {}", error),
+            };
+            let pipeline = pollster::block_on(
+                Pipeline::builder(
+                    include_str!("../shaders/dynamics.wgsl"),
+                    scenario.static_config(),
+                )
+                .pass_graph(PassGraph::split())
+                .adapter(adapter.clone())
+                .build(),
+            );
+            let mut pipeline = pipeline.unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                process::exit(1);
+            });
+            let initial = load_scenario(&mut pipeline, &scenario, None);
+            let mut tracker = KeplerTracker::new(&initial);
+            pipeline.run_with_snapshots(steps, interval, |_, time, bodies| {
+                tracker.record(time, bodies)
+            });
+            match tracker.accuracy() {
+                Some(accuracy) => println!(
+                    "{:<10}  {:>12}  {:>12.3e}  {:>12.3e}  {:>12.3e}  {:>14}",
+                    integrator.name(),
+                    eccentricity,
+                    accuracy.period_error,
+                    accuracy.energy_drift,
+                    accuracy.position_error,
+                    accuracy
+                        .perihelion_drift
+                        .map_or("-".to_string(), |drift| format!("{:.3e}", drift))
+                ),
+                None => println!(
+                    "{:<10}  {:>12}  the orbit stopped being an ellipse",
+                    integrator.name(),
+                    eccentricity
+                ),
+            }
+        }
+    }
+}
+
+fn parse_eccentricity(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(eccentricity) if (0.0..1.0).contains(&eccentricity) => Ok(eccentricity),
+        _ => Err("must be at least 0 and less than 1".to_string()),
+    }
+}
+
+fn bench(args: BenchArgs) {
+    let BenchArgs {
+        bodies,
+        solvers,
+        steps,
+        output,
+        gpu,
+    } = args;
+    let adapter = gpu.unwrap_or(AdapterSelection::Preference(
+        PowerPreference::HighPerformance,
+    ));
+    let steps = steps as usize;
+    let mut report: Option<BenchReport> = None;
+    println!(
+        "{:<8}  {:>8}  {:>9}  {:>12}  {:>14}",
+        "Solver", "Bodies", "Workgroup", "Steps/s", "Interactions/s"
+    );
+    for &solver in &solvers {
+        for &num_bodies in &bodies {
+            let num_bodies = num_bodies as usize;
+            let scenario = match Scenario::from_document(solver.document(num_bodies)) {
+                Ok((scenario, _)) => scenario,
+                Err(error) => panic!("Invalid benchmark scenario: {}", error),
+            };
+            let pipeline = pollster::block_on(
+                Pipeline::builder(
+                    include_str!("../shaders/dynamics.wgsl"),
+                    scenario.static_config(),
+                )
+                .pass_graph(PassGraph::split())
+                .adapter(adapter.clone())
+                .build(),
+            );
+            let mut pipeline = pipeline.unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                process::exit(1);
+            });
+            let report = report.get_or_insert_with(|| BenchReport::new(pipeline.adapter_info()));
+            if (pipeline.static_config().max_bodies as usize) < num_bodies {
+                eprintln!(
+                    "warning: {} can't hold {} bodies, skipping them",
+                    report.adapter, num_bodies
+                );
+                continue;
+            }
+            load_scenario(&mut pipeline, &scenario, None);
+            // Warm up first so that pipeline creation isn't timed
+            pipeline.benchmark(1);
+            let elapsed = pipeline.benchmark(steps);
+            let result = BenchResult::new(
+                solver.name().to_string(),
+                num_bodies,
+                pipeline.static_config().workgroup_size,
+                steps,
+                elapsed,
+            );
+            println!(
+                "{:<8}  {:>8}  {:>9}  {:>12.1}  {:>14.3e}",
+                result.solver,
+                result.bodies,
+                result.workgroup_size,
+                result.steps_per_second,
+                result.interactions_per_second
+            );
+            report.results.push(result);
+        }
+    }
+    if let Some(report) = report {
+        match report.write_json(&output) {
+            Ok(()) => println!("Wrote the report to {}", output.display()),
+            Err(error) => {
+                eprintln!("Could not write {}: {}", output.display(), error);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "headless"), allow(unused_variables))]
+fn play(args: PlayArgs) {
+    let PlayArgs {
+        archive: archive_path,
+        output: output_path,
+        blender: blender_path,
+        frames: frames_path,
+        usd: usd_path,
+        frame_size,
+        color_by,
+        gpu,
+    } = args;
+    let rendering = frames_path.is_some() || frame_size.is_some() || color_by.is_some();
+    if rendering && !cfg!(feature = "headless") {
+        eprintln!(
+            "--frames, --frame-size and --color-by require building with the `headless` feature"
+        );
+        process::exit(1);
+    }
+    if usd_path.is_some() && !cfg!(feature = "usd") {
+        eprintln!("--usd requires building with the `usd` feature");
+        process::exit(1);
+    }
+    #[cfg(feature = "headless")]
+    let color_quantity = match color_by.as_deref() {
+        None => ColorQuantity::Palette,
+        Some(name) => ColorQuantity::from_name(name).unwrap_or_else(|| {
+            let names: Vec<_> = ColorQuantity::ALL.iter().map(|q| q.name()).collect();
+            eprintln!("--color-by must be one of {}", names.join(", "));
+            process::exit(1);
+        }),
+    };
+    let mut reader = ArchiveReader::open(&archive_path).unwrap_or_else(|error| {
+        eprintln!("Could not open {}: {}", archive_path.display(), error);
+        process::exit(1);
+    });
+    let header = reader.header().clone();
+    let first = match reader.read_frame() {
+        Ok(Some(frame)) => frame,
+        Ok(None) => {
+            eprintln!("{} has no frames", archive_path.display());
+            process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("Could not read {}: {}", archive_path.display(), error);
+            process::exit(1);
+        }
+    };
+    println!(
+        "Playing {} bodies from step {} at t = {} of {}",
+        first.state.bodies.len(),
+        first.step,
+        first.state.time,
+        archive_path.display()
+    );
+    // The exports play back at the step size of the first frame
+    let dynamic_config = first.state.dynamic_config;
+    let mut output_writer = output_path.as_ref().map(|path| {
+        output::create(path, &header.manifest, &dynamic_config).unwrap_or_else(|error| {
+            eprintln!("Could not create {}: {}", path.display(), error);
+            process::exit(1);
+        })
+    });
+    let mut export = blender_path.as_ref().map(|path| {
+        BlenderExport::create(path, &header.attributes, &header.manifest).unwrap_or_else(|error| {
+            eprintln!("Could not create {}: {}", path.display(), error);
+            process::exit(1);
+        })
+    });
+    #[cfg(feature = "usd")]
+    let mut usd_export = usd_path.as_ref().map(|path| {
+        UsdExport::create(path, &header.attributes, &header.manifest).unwrap_or_else(|error| {
+            eprintln!("Could not create {}: {}", path.display(), error);
+            process::exit(1);
+        })
+    });
+    // Rendering draws the bodies from a pipeline loaded with each frame in turn
+    #[cfg(feature = "headless")]
+    let mut renderer = frames_path.as_ref().map(|path| {
+        let pipeline = pollster::block_on(
+            Pipeline::builder(
+                include_str!("../shaders/dynamics.wgsl"),
+                header.manifest.static_config,
+            )
+            .pass_graph(PassGraph::split())
+            .adapter(gpu.unwrap_or(AdapterSelection::Preference(
+                PowerPreference::HighPerformance,
+            )))
+            .build(),
+        );
+        let mut pipeline = pipeline.unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+        if let Err(error) = pipeline.load(&first.state) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        let color_map = ColorMap::fit(color_quantity, &mut pipeline);
+        let writer = FrameWriter::create(
+            &pipeline,
+            path,
+            frame_size.unwrap_or(DEFAULT_FRAME_SIZE),
+            &first.state.bodies,
+            &header.attributes,
+            color_map,
+        )
+        .unwrap_or_else(|error| {
+            eprintln!("Could not create {}: {}", path.display(), error);
+            process::exit(1);
+        });
+        (pipeline, writer)
+    });
+    let mut frames = 0;
+    let mut steps = (first.step, first.step);
+    // The steps between the first two frames, at which the exports play back
+    let mut interval = None;
+    for frame in iter::once(Ok(first)).chain(reader) {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(error) => {
+                eprintln!(
+                    "Could not read {}, stopping after {} frames: {}",
+                    archive_path.display(),
+                    frames,
+                    error
+                );
+                break;
+            }
+        };
+        if frames == 1 {
+            interval = Some(frame.step.saturating_sub(steps.0).max(1));
+        }
+        steps.1 = frame.step;
+        frames += 1;
+        if let Some(writer) = &mut output_writer {
+            let state = &frame.state;
+            if let Err(error) = writer.write_snapshot(frame.step, state.time, &state.bodies) {
+                eprintln!("Could not write snapshot, stopping the output: {}", error);
+                output_writer = None;
+            }
+        }
+        if let Some(writer) = &mut export {
+            if let Err(error) = writer.write_frame(&frame.state.bodies) {
+                eprintln!("Could not write frame, stopping the export: {}", error);
+                export = None;
+            }
+        }
+        #[cfg(feature = "usd")]
+        if let Some(writer) = &mut usd_export {
+            if let Err(error) = writer.write_frame(&frame.state.bodies) {
+                eprintln!("Could not write frame, stopping the USD export: {}", error);
+                usd_export = None;
+            }
+        }
+        #[cfg(feature = "headless")]
+        if let Some((pipeline, writer)) = &mut renderer {
+            if let Err(error) = pipeline.load(&frame.state) {
+                eprintln!("Could not load frame, stopping the rendering: {}", error);
+                renderer = None;
+            } else if let Err(error) = writer.write_frame(pipeline) {
+                eprintln!("Could not write frame, stopping the rendering: {}", error);
+                renderer = None;
+            }
+        }
+    }
+    println!(
+        "Played {} frames of steps {} to {}",
+        frames, steps.0, steps.1
+    );
+    let interval = interval.unwrap_or(1);
+    if let (Some(mut writer), Some(path)) = (output_writer, output_path) {
+        match writer.finish() {
+            Ok(()) => println!(
+                "Wrote {} snapshots to {}",
+                writer.snapshots(),
+                path.display()
+            ),
+            Err(error) => eprintln!("Could not write {}: {}", path.display(), error),
+        }
+    }
+    if let Some(export) = export {
+        let frames = export.frames();
+        match export.finish(interval, dynamic_config.dt) {
+            Ok(script) => println!(
+                "Exported {} frames, import with `blender --python {}`",
+                frames,
+                script.display()
+            ),
+            Err(error) => eprintln!("Could not write Blender import script: {}", error),
+        }
+    }
+    #[cfg(feature = "usd")]
+    if let Some(export) = usd_export {
+        let frames = export.frames();
+        match export.finish(interval, dynamic_config.dt) {
+            Ok(layer) => println!("Exported {} frames to {}", frames, layer.display()),
+            Err(error) => eprintln!("Could not write USD layer: {}", error),
+        }
+    }
+    #[cfg(feature = "headless")]
+    if let (Some((_, writer)), Some(path)) = (renderer, frames_path) {
+        println!("Rendered {} frames to {}", writer.frames(), path.display());
+    }
+}
+
+fn validate_restart(args: RestartArgs) {
+    let RestartArgs {
+        archive: archive_path,
+        frame: first,
+        gpu,
+    } = args;
+    let mut reader = ArchiveReader::open(&archive_path).unwrap_or_else(|error| {
+        eprintln!("Could not open {}: {}", archive_path.display(), error);
+        process::exit(1);
+    });
+    let static_config = reader.header().manifest.static_config;
+    // State the archive doesn't hold
+    if static_config.com_correction
+        || static_config.thrust
+        || static_config.ephemeris
+        || reader.header().maneuvers
+    {
+        eprintln!(
+            "{} was recorded with state kept on the host, the center of mass correction, thrust, \
+             an ephemeris or maneuvers, and can't be restarted exactly",
+            archive_path.display()
+        );
+        process::exit(1);
+    }
+    let mut frames = reader.by_ref().skip(first);
+    let start = match frames.next() {
+        Some(Ok(frame)) => frame,
+        Some(Err(error)) => {
+            eprintln!("Could not read {}: {}", archive_path.display(), error);
+            process::exit(1);
+        }
+        None => {
+            eprintln!("{} has no frame {}", archive_path.display(), first);
+            process::exit(1);
+        }
+    };
+    let pipeline = pollster::block_on(
+        Pipeline::builder(include_str!("../shaders/dynamics.wgsl"), static_config)
+            .pass_graph(PassGraph::split())
+            .adapter(gpu.unwrap_or(AdapterSelection::Preference(
+                PowerPreference::HighPerformance,
+            )))
+            .build(),
+    );
+    let mut pipeline = pipeline.unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    if let Err(error) = pipeline.load(&start.state) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+    println!(
+        "Restarting {} bodies from step {} on {}",
+        start.state.bodies.len(),
+        start.step,
+        pipeline.adapter_info().name
+    );
+    if !static_config.deterministic && static_config.neighbor_grid.is_some() {
+        println!("The archive was not recorded with --deterministic, expect the sums over neighbors to differ");
+    }
+    let mut step = start.step;
+    let mut checked = 0;
+    for frame in frames {
+        let frame = frame.unwrap_or_else(|error| {
+            eprintln!("Could not read {}: {}", archive_path.display(), error);
+            process::exit(1);
+        });
+        pipeline.submit_and_block(frame.step - step);
+        step = frame.step;
+        if pipeline.time() != frame.state.time {
+            eprintln!(
+                "The restart is at t = {} at step {}, the archive at t = {}",
+                pipeline.time(),
+                frame.step,
+                frame.state.time
+            );
+            process::exit(1);
+        }
+        let bodies = pipeline.read_bodies();
+        let matches = bodies.len() == frame.state.bodies.len()
+            && bytemuck::cast_slice::<Body, u32>(&bodies)
+                == bytemuck::cast_slice::<Body, u32>(&frame.state.bodies);
+        if !matches {
+            let body = bodies
+                .iter()
+                .zip(&frame.state.bodies)
+                .position(|(body, archived)| {
+                    bytemuck::bytes_of(body) != bytemuck::bytes_of(archived)
+                })
+                .unwrap_or(bodies.len().min(frame.state.bodies.len()));
+            eprintln!(
+                "The restart differs from the archive at step {}, first at body {}",
+                frame.step, body
+            );
+            process::exit(1);
+        }
+        checked += 1;
+    }
+    println!(
+        "{} frames up to step {} match the archive bit for bit",
+        checked, step
+    );
+}
+
+fn convert(args: ConvertArgs) {
+    let ConvertArgs {
+        archive: archive_path,
+        output: output_path,
+        stride,
+        from,
+        to,
+    } = args;
+    if from.zip(to).is_some_and(|(from, to)| from > to) {
+        eprintln!("--from must not be after --to");
+        process::exit(1);
+    }
+    let reader = ArchiveReader::open(&archive_path).unwrap_or_else(|error| {
+        eprintln!("Could not open {}: {}", archive_path.display(), error);
+        process::exit(1);
+    });
+    let manifest = reader.header().manifest.clone();
+    let in_range =
+        |time: f64| from.is_none_or(|from| time >= from) && to.is_none_or(|to| time <= to);
+    let frames = reader
+        .filter(|frame| {
+            frame
+                .as_ref()
+                .map_or(true, |frame| in_range(frame.state.time))
+        })
+        .step_by(stride as usize);
+    let mut writer: Option<Box<dyn output::SnapshotWriter>> = None;
+    let mut steps = (0, 0);
+    for frame in frames {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(error) => {
+                eprintln!(
+                    "Could not read {}, stopping after {} frames: {}",
+                    archive_path.display(),
+                    writer.as_ref().map_or(0, |writer| writer.snapshots()),
+                    error
+                );
+                break;
+            }
+        };
+        // Formats with room for metadata record the config of the first frame
+        let writer = writer.get_or_insert_with(|| {
+            steps.0 = frame.step;
+            output::create(&output_path, &manifest, &frame.state.dynamic_config).unwrap_or_else(
+                |error| {
+                    eprintln!("Could not create {}: {}", output_path.display(), error);
+                    process::exit(1);
+                },
+            )
+        });
+        let state = &frame.state;
+        if let Err(error) = writer.write_snapshot(frame.step, state.time, &state.bodies) {
+            eprintln!("Could not write {}: {}", output_path.display(), error);
+            process::exit(1);
+        }
+        steps.1 = frame.step;
+    }
+    let mut writer = writer.unwrap_or_else(|| {
+        eprintln!("{} has no frames in the time range", archive_path.display());
+        process::exit(1);
+    });
+    match writer.finish() {
+        Ok(()) => println!(
+            "Wrote {} frames of steps {} to {} to {}",
+            writer.snapshots(),
+            steps.0,
+            steps.1,
+            output_path.display()
+        ),
+        Err(error) => {
+            eprintln!("Could not write {}: {}", output_path.display(), error);
+            process::exit(1);
+        }
+    }
+}
+
+/// A run which got to its last step, with the bodies at its start and end
+#[derive(Clone)]
+struct FinishedRun {
+    initial: Vec<Body>,
+    last: Vec<Body>,
+    time: f64,
+}
+
+/// Run the valid `scenarios`, those which can share a pipeline together in
+/// batches of at most `batch_size`, returning the batch each ran in and how
+/// it ended
+fn run_scenarios(
+    scenarios: &[Result<Scenario, String>],
+    batch_size: u64,
+    adapter: &AdapterSelection,
+) -> Vec<(Option<usize>, Result<FinishedRun, String>)> {
+    // Batches of the runs which can share a pipeline, in the order of their first run
+    let mut batches: Vec<(Option<String>, Vec<usize>)> = Vec::new();
+    for (run, scenario) in scenarios.iter().enumerate() {
+        if let Ok(scenario) = scenario {
+            let key = sweep::batch_key(scenario);
+            match batches.iter_mut().find(|(other, runs)| {
+                key.is_some() && *other == key && (runs.len() as u64) < batch_size
+            }) {
+                Some((_, runs)) => runs.push(run),
+                None => batches.push((key, vec![run])),
+            }
+        }
+    }
+    let start = Instant::now();
+    let bar = ProgressBar::new(scenarios.len() as u64).with_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} runs, {msg}")
+            .expect("Invalid progress bar template"),
+    );
+    let mut results: Vec<(Option<usize>, Result<FinishedRun, String>)> = scenarios
+        .iter()
+        .map(|scenario| {
+            (
+                None,
+                Err(scenario.as_ref().err().cloned().unwrap_or_default()),
+            )
+        })
+        .collect();
+    bar.inc(
+        scenarios
+            .iter()
+            .filter(|scenario| scenario.is_err())
+            .count() as u64,
+    );
+    for (batch, (_, members)) in batches.iter().enumerate() {
+        bar.set_message(format!("batch {} of {}", batch + 1, batches.len()));
+        let batch_scenarios: Vec<&Scenario> = members
+            .iter()
+            .filter_map(|&run| scenarios[run].as_ref().ok())
+            .collect();
+        for (&run, finished) in members.iter().zip(run_batch(&batch_scenarios, adapter)) {
+            results[run] = (Some(batch), finished);
+        }
+        bar.inc(members.len() as u64);
+    }
+    bar.finish_and_clear();
+    let failed = results
+        .iter()
+        .filter(|(_, finished)| finished.is_err())
+        .count();
+    println!(
+        "Ran {} runs in {} batches in {:.3} s, {} failed",
+        results.len(),
+        batches.len(),
+        start.elapsed().as_secs_f64(),
+        failed
+    );
+    results
+}
+
+/// Run `scenarios`, which share a batch key if there are several, together as
+/// an ensemble. Falls back to smaller batches down to single runs where the
+/// adapter can't hold them.
+fn run_batch(
+    scenarios: &[&Scenario],
+    adapter: &AdapterSelection,
+) -> Vec<Result<FinishedRun, String>> {
+    let first = scenarios[0];
+    let num_bodies: usize = scenarios.iter().map(|scenario| scenario.bodies.len()).sum();
+    let ensemble = scenarios.len() > 1;
+    let static_config = match ensemble {
+        true => StaticConfig {
+            max_bodies: num_bodies as u32,
+            ensemble,
+            ..first.static_config()
+        },
+        false => first.static_config(),
+    };
+    let pipeline = pollster::block_on(
+        Pipeline::builder(include_str!("../shaders/dynamics.wgsl"), static_config)
+            .pass_graph(PassGraph::split())
+            .adapter(adapter.clone())
+            .build(),
+    );
+    let mut pipeline = match pipeline {
+        Ok(pipeline) => pipeline,
+        Err(error) => return vec![Err(error.to_string()); scenarios.len()],
+    };
+    let fits = pipeline.static_config().max_bodies as usize >= num_bodies;
+    if ensemble && !(fits && pipeline.static_config().ensemble) {
+        drop(pipeline);
+        let (left, right) = scenarios.split_at(scenarios.len() / 2);
+        let mut outcomes = run_batch(left, adapter);
+        outcomes.extend(run_batch(right, adapter));
+        return outcomes;
+    }
+    if !fits {
+        return vec![Err("Too many bodies for the adapter".to_string())];
+    }
+    let initial = match ensemble {
+        true => {
+            let mut ensemble = Ensemble::new();
+            for scenario in scenarios {
+                ensemble.push(&scenario.bodies(), scenario.config.softening);
+            }
+            pipeline.set_dt(first.config.dt);
+            if let Err(error) = pipeline.write_ensemble(&ensemble) {
+                return vec![Err(error.to_string())];
+            }
+            let properties: Vec<_> = scenarios.iter().flat_map(|s| s.properties()).collect();
+            pipeline.write_properties(&properties);
+            let gas: Vec<_> = scenarios.iter().flat_map(|s| s.gas_states()).collect();
+            pipeline.write_gas_state(&gas);
+            if let Some(epoch) = first.epoch() {
+                pipeline.set_epoch(epoch, first.config.time_unit);
+            }
+            pipeline.set_species(&first.species);
+            pipeline.set_box_size(first.config.box_size);
+            scenarios.iter().map(|scenario| scenario.bodies()).collect()
+        }
+        false => match first.ephemeris() {
+            Ok(ephemeris) => vec![load_scenario(&mut pipeline, first, ephemeris.as_ref())],
+            Err(error) => return vec![Err(format!("Could not load the ephemeris: {}", error))],
+        },
+    };
+    if let Err(error) = pipeline.run(first.config.steps, &CancellationToken::new()) {
+        return vec![Err(error.to_string()); scenarios.len()];
+    }
+    let last = match ensemble {
+        true => pipeline.read_systems(),
+        false => vec![pipeline.read_bodies()],
+    };
+    initial
+        .into_iter()
+        .zip(last)
+        .map(|(initial, last)| {
+            Ok(FinishedRun {
+                initial,
+                last,
+                time: pipeline.time(),
+            })
+        })
+        .collect()
 }
 
 fn main() {
-    pollster::block_on(async_entry());
+    let cli = Cli::parse();
+    env_logger::init();
+    match cli.command {
+        Command::Run(args) => pollster::block_on(run(*args)),
+        Command::Info => print_info(),
+        Command::Sweep(args) => sweep(args),
+        Command::MonteCarlo(args) => monte_carlo(args),
+        Command::Validate(ValidateCommand::Reversibility(args)) => validate_reversibility(*args),
+        Command::Validate(ValidateCommand::Kepler(args)) => validate_kepler(args),
+        Command::Validate(ValidateCommand::Restart(args)) => validate_restart(args),
+        Command::Bench(args) => bench(args),
+        Command::Play(args) => play(args),
+        Command::Convert(args) => convert(args),
+    }
 }
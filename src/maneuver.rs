@@ -0,0 +1,151 @@
+//! Impulsive maneuvers, instantaneous changes of a body's velocity at scheduled
+//! times.
+//!
+//! The pipeline ends a submission at the first step boundary at or past each
+//! maneuver's time and applies the impulse before the next one, so a maneuver
+//! lands less than a step after its time. Integrating backwards takes the
+//! impulses off again at the boundaries they were applied at.
+use serde::{Deserialize, Serialize};
+
+use crate::pipeline::TimeDirection;
+
+/// Fraction of a step by which a boundary may fall short of a maneuver's time
+/// and still count as reaching it, for the rounding of the accumulated time
+const TOLERANCE: f64 = 1e-6;
+
+/// A change of velocity `delta_v` of the body at index `body` at `time`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Maneuver {
+    pub time: f64,
+    pub body: usize,
+    pub delta_v: [f64; 3],
+}
+
+/// Maneuvers in the order of their times, and how far the run got through them
+#[derive(Debug, Clone, Default)]
+pub struct ManeuverSchedule {
+    /// Sorted by time, those at the same time in the order given
+    maneuvers: Vec<Maneuver>,
+    /// Simulated times the first maneuvers were applied at, in order
+    applied: Vec<f64>,
+}
+
+impl ManeuverSchedule {
+    pub fn new(mut maneuvers: Vec<Maneuver>) -> Self {
+        maneuvers.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            maneuvers,
+            applied: Vec::new(),
+        }
+    }
+
+    pub fn maneuvers(&self) -> &[Maneuver] {
+        &self.maneuvers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maneuvers.is_empty()
+    }
+
+    /// Number of maneuvers applied so far
+    pub fn applied(&self) -> usize {
+        self.applied.len()
+    }
+
+    /// Count the maneuvers before `time` as applied, for a run continued from
+    /// a state which already has them
+    pub fn skip_to(&mut self, time: f64) {
+        self.applied = self
+            .maneuvers
+            .iter()
+            .take_while(|maneuver| maneuver.time < time)
+            .map(|maneuver| maneuver.time)
+            .collect();
+    }
+
+    /// Move the maneuvers to the new indices of their bodies, dropping those of
+    /// bodies without one
+    pub(crate) fn renumber(&mut self, index: impl Fn(usize) -> Option<usize>) {
+        let mut applied = self.applied.iter().copied();
+        let mut kept_applied = Vec::new();
+        self.maneuvers.retain_mut(|maneuver| {
+            let time = applied.next();
+            match index(maneuver.body) {
+                Some(body) => {
+                    maneuver.body = body;
+                    kept_applied.extend(time);
+                    true
+                }
+                None => false,
+            }
+        });
+        self.applied = kept_applied;
+    }
+
+    /// The impulses due at the boundary at `time` between steps of about `step`,
+    /// as the body and the change of its velocity. Backwards these undo the
+    /// maneuvers applied at the boundary.
+    pub(crate) fn due(
+        &mut self,
+        time: f64,
+        direction: TimeDirection,
+        step: f64,
+    ) -> Vec<(usize, [f64; 3])> {
+        let tolerance = TOLERANCE * step;
+        let mut impulses = Vec::new();
+        match direction {
+            TimeDirection::Forward => {
+                while let Some(maneuver) = self.maneuvers.get(self.applied.len()) {
+                    if maneuver.time > time + tolerance {
+                        break;
+                    }
+                    impulses.push((maneuver.body, maneuver.delta_v));
+                    self.applied.push(time);
+                }
+            }
+            TimeDirection::Backward => {
+                while self
+                    .applied
+                    .last()
+                    .is_some_and(|&applied| applied >= time - tolerance)
+                {
+                    self.applied.pop();
+                    let maneuver = &self.maneuvers[self.applied.len()];
+                    impulses.push((maneuver.body, maneuver.delta_v.map(|v| -v)));
+                }
+            }
+        }
+        impulses
+    }
+
+    /// Steps from `time` to the next boundary where impulses are due, at most
+    /// `max_steps`, with step `i` of signed size `dt(i)`
+    pub(crate) fn steps_until(
+        &self,
+        time: f64,
+        direction: TimeDirection,
+        max_steps: usize,
+        dt: impl Fn(usize) -> f32,
+    ) -> usize {
+        let tolerance = TOLERANCE * dt(0).abs() as f64;
+        // The distance left to cover in the direction of integration
+        let distance = match direction {
+            TimeDirection::Forward => match self.maneuvers.get(self.applied.len()) {
+                Some(maneuver) => maneuver.time - time,
+                None => return max_steps,
+            },
+            TimeDirection::Backward => match self.applied.last() {
+                Some(applied) => time - applied,
+                None => return max_steps,
+            },
+        };
+        let mut covered = 0.0;
+        for step in 0..max_steps {
+            if covered >= distance - tolerance {
+                return step.max(1);
+            }
+            covered += dt(step).abs() as f64;
+        }
+        max_steps
+    }
+}
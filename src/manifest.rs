@@ -0,0 +1,79 @@
+//! Provenance of a run, embedded in the header of every file it writes so that
+//! the results can be traced back to their inputs and reproduced: the complete
+//! scenario, the build and the adapter it ran on.
+//!
+//! Text formats carry the manifest as JSON on one line, in CSV as a comment
+//! starting with `#` before the header, which pandas and polars skip with
+//! `comment="#"` and `comment_prefix="#"`. Binary formats keep it in their
+//! metadata.
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::pipeline::Pipeline;
+use crate::scenario::Scenario;
+use crate::structures::{Integrator, StaticConfig};
+
+/// Everything needed to reproduce a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    /// Version of the crate which ran it
+    pub version: String,
+    /// Commit it was built from, ending in `-dirty` with uncommitted changes.
+    /// `None` when built outside a git checkout.
+    pub git_hash: Option<String>,
+    /// Name of the adapter, which for software renderers includes the driver
+    /// version. wgpu reports no driver version of its own.
+    pub adapter: String,
+    pub backend: String,
+    pub device_type: String,
+    /// PCI ids of the adapter, zero where the backend has none
+    pub vendor_id: usize,
+    pub device_id: usize,
+    pub integrator: Integrator,
+    /// Seeds of the random initial conditions
+    pub seeds: Vec<u64>,
+    /// Config the kernels were built for
+    pub static_config: StaticConfig,
+    /// The scenario with its defaults filled in and any overrides applied
+    pub scenario: Value,
+    /// Command line of the run, whose options can change it further. Empty
+    /// when not run from the command line.
+    pub arguments: Vec<String>,
+}
+
+impl RunManifest {
+    /// The manifest of `scenario` running on `pipeline`
+    pub fn new(scenario: &Scenario, pipeline: &Pipeline) -> Self {
+        let info = pipeline.adapter_info();
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: option_env!("PARABODY_GIT_HASH").map(str::to_string),
+            adapter: info.name.clone(),
+            backend: format!("{:?}", info.backend),
+            device_type: format!("{:?}", info.device_type),
+            vendor_id: info.vendor,
+            device_id: info.device,
+            integrator: scenario.config.integrator,
+            seeds: scenario
+                .generator
+                .iter()
+                .filter_map(|generator| generator.seed())
+                .collect(),
+            static_config: *pipeline.static_config(),
+            scenario: scenario.document.clone(),
+            arguments: Vec::new(),
+        }
+    }
+
+    /// As JSON on one line
+    pub fn to_json(&self) -> io::Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Write the manifest as a CSV comment line, `# ` followed by the JSON
+    pub fn write_comment(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "# {}", self.to_json()?)
+    }
+}
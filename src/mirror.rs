@@ -0,0 +1,52 @@
+use std::ops::Range;
+
+use crate::structures::Body;
+
+/// Host copy of the body state last read back from or written to the GPU
+#[derive(Debug, Default)]
+pub struct HostMirror {
+    bodies: Vec<Body>,
+    stale: bool,
+}
+
+impl HostMirror {
+    pub fn new() -> Self {
+        Self {
+            bodies: Vec::new(),
+            stale: true,
+        }
+    }
+
+    /// The mirrored state, or `None` if the GPU has advanced since it was taken
+    pub fn get(&self) -> Option<&[Body]> {
+        if self.stale {
+            None
+        } else {
+            Some(&self.bodies)
+        }
+    }
+
+    pub fn update(&mut self, bodies: &[Body]) {
+        self.bodies.clear();
+        self.bodies.extend_from_slice(bodies);
+        self.stale = false;
+    }
+
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// The smallest range of indices which must be uploaded for the GPU to hold `bodies`,
+    /// or `None` if it already does
+    pub fn diff(&self, bodies: &[Body]) -> Option<Range<usize>> {
+        if self.stale || self.bodies.len() != bodies.len() {
+            return Some(0..bodies.len());
+        }
+        // Compare bitwise so that NaNs don't count as changes forever
+        let changed = |(a, b): (&Body, &Body)| bytemuck::bytes_of(a) != bytemuck::bytes_of(b);
+        let pairs = || self.bodies.iter().zip(bodies.iter());
+        let start = pairs().position(changed)?;
+        let end = bodies.len() - pairs().rev().position(changed).unwrap();
+        Some(start..end)
+    }
+}
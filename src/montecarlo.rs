@@ -0,0 +1,321 @@
+//! Monte Carlo clones of a scenario, its bodies with Gaussian errors drawn on
+//! their initial states, for propagating uncertainties such as those of an
+//! orbit determination.
+//!
+//! Each perturbation draws deviations of a few elements of a body's state,
+//! correlated by their covariance or independent with the standard deviations
+//! given. The elements of different perturbations are drawn independently.
+//!
+//! ```toml
+//! scenario = "asteroid.toml"
+//! samples = 1000
+//!
+//! [[perturbations]]
+//! body = 1
+//! elements = ["x", "vy"]
+//! covariance = [[1e-6, 2e-8], [2e-8, 4e-9]]
+//! ```
+//!
+//! The clones differ only in their bodies, so they run together as the
+//! systems of an ensemble wherever the scenario could, see `sweep::batch_key`.
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::ensemble::Ensemble;
+use crate::format::invalid_data;
+use crate::ic::Rng;
+use crate::scenario::{self, Scenario};
+use crate::structures::Body;
+
+/// Relative size of the pivots of a covariance below which its directions are
+/// taken as certain, for the rounding of singular matrices
+const SINGULAR_TOLERANCE: f64 = 1e-12;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonteCarlo {
+    /// Scenario file of the nominal state, relative to the Monte Carlo file
+    pub scenario: PathBuf,
+    pub samples: usize,
+    #[serde(default)]
+    pub seed: u64,
+    pub perturbations: Vec<Perturbation>,
+}
+
+/// An element of a body's state, a component of its position or velocity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateElement {
+    X,
+    Y,
+    Z,
+    Vx,
+    Vy,
+    Vz,
+}
+
+impl StateElement {
+    pub const ALL: [Self; 6] = [Self::X, Self::Y, Self::Z, Self::Vx, Self::Vy, Self::Vz];
+
+    /// Index in the state as position followed by velocity
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Errors on the state of the body at index `body`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Perturbation {
+    pub body: usize,
+    /// The elements drawn, all six by default
+    #[serde(default = "all_elements")]
+    pub elements: Vec<StateElement>,
+    #[serde(flatten)]
+    pub spread: Spread,
+}
+
+fn all_elements() -> Vec<StateElement> {
+    StateElement::ALL.to_vec()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Spread {
+    /// Covariance of the elements, in their order
+    Covariance { covariance: Vec<Vec<f64>> },
+    /// Standard deviations of uncorrelated elements
+    Sigma { sigma: Vec<f64> },
+}
+
+impl Spread {
+    fn covariance(&self) -> Vec<Vec<f64>> {
+        match self {
+            Spread::Covariance { covariance } => covariance.clone(),
+            Spread::Sigma { sigma } => (0..sigma.len())
+                .map(|i| {
+                    (0..sigma.len())
+                        .map(|j| if i == j { sigma[i] * sigma[i] } else { 0.0 })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MonteCarlo {
+    /// Load a Monte Carlo file in TOML or JSON, chosen by the extension
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let document = Scenario::read_document(path).map_err(|error| match error {
+            scenario::ScenarioError::Io(error) => error,
+            error => invalid_data(error.to_string()),
+        })?;
+        let mut monte_carlo: MonteCarlo =
+            serde_json::from_value(document).map_err(|error| invalid_data(error.to_string()))?;
+        if let Some(directory) = path.parent() {
+            monte_carlo.scenario = directory.join(&monte_carlo.scenario);
+        }
+        monte_carlo.check()?;
+        Ok(monte_carlo)
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.samples == 0 {
+            return Err(invalid_data("samples must be positive".to_string()));
+        }
+        for (index, perturbation) in self.perturbations.iter().enumerate() {
+            let elements = &perturbation.elements;
+            let covariance = perturbation.spread.covariance();
+            let problem = if elements.is_empty() {
+                Some("has no elements")
+            } else if (1..elements.len()).any(|i| elements[..i].contains(&elements[i])) {
+                Some("has an element twice")
+            } else if covariance.len() != elements.len()
+                || covariance.iter().any(|row| row.len() != elements.len())
+            {
+                Some("needs a value for every element, or a row of the covariance")
+            } else if covariance.iter().flatten().any(|value| !value.is_finite()) {
+                Some("must have finite values")
+            } else if (0..covariance.len())
+                .any(|i| (0..i).any(|j| covariance[i][j] != covariance[j][i]))
+            {
+                Some("needs a symmetric covariance")
+            } else if cholesky(&covariance).is_none() {
+                Some("needs a positive semi-definite covariance")
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                return Err(invalid_data(format!(
+                    "perturbations.{} of body {} {}",
+                    index, perturbation.body, problem
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the perturbed bodies are among `num_bodies`
+    pub fn check_bodies(&self, num_bodies: usize) -> io::Result<()> {
+        match self
+            .perturbations
+            .iter()
+            .find(|perturbation| perturbation.body >= num_bodies)
+        {
+            Some(perturbation) => Err(invalid_data(format!(
+                "The scenario has no body {}, only {}",
+                perturbation.body, num_bodies
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// The deviations of every sample from the nominal state, one per body as
+    /// its position followed by its velocity. Bodies without a perturbation
+    /// are left at zero.
+    pub fn deviations(&self, num_bodies: usize) -> Vec<Vec<[f64; 6]>> {
+        let factors: Vec<Vec<Vec<f64>>> = self
+            .perturbations
+            .iter()
+            .map(|perturbation| {
+                cholesky(&perturbation.spread.covariance())
+                    .expect("Checked to be positive semi-definite on loading")
+            })
+            .collect();
+        let mut rng = Rng::new(self.seed);
+        (0..self.samples)
+            .map(|_| {
+                let mut deviations = vec![[0.0; 6]; num_bodies];
+                for (perturbation, factor) in self.perturbations.iter().zip(&factors) {
+                    let normal: Vec<f64> = factor.iter().map(|_| rng.normal()).collect();
+                    for (i, element) in perturbation.elements.iter().enumerate() {
+                        deviations[perturbation.body][element.index()] +=
+                            (0..=i).map(|j| factor[i][j] * normal[j]).sum::<f64>();
+                    }
+                }
+                deviations
+            })
+            .collect()
+    }
+
+    /// Copies of the `nominal` bodies with the deviations of every sample
+    pub fn clones(&self, nominal: &[Body]) -> Vec<Vec<Body>> {
+        self.deviations(nominal.len())
+            .into_iter()
+            .map(|deviations| {
+                nominal
+                    .iter()
+                    .zip(deviations)
+                    .map(|(body, deviation)| {
+                        let mut body = *body;
+                        perturb(&mut body.position, &mut body.velocity, deviation);
+                        body
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The clones of the `nominal` bodies as the systems of an ensemble
+    pub fn ensemble(&self, nominal: &[Body], softening: f32) -> Ensemble {
+        let mut ensemble = Ensemble::new();
+        for bodies in self.clones(nominal) {
+            ensemble.push(&bodies, softening);
+        }
+        ensemble
+    }
+
+    /// Copies of the `nominal` scenario with the deviations of every sample
+    pub fn scenarios(&self, nominal: &Scenario) -> Vec<Scenario> {
+        self.deviations(nominal.bodies.len())
+            .into_iter()
+            .map(|deviations| {
+                let mut scenario = nominal.clone();
+                for (body, deviation) in scenario.bodies.iter_mut().zip(deviations) {
+                    perturb(&mut body.position, &mut body.velocity, deviation);
+                }
+                scenario
+            })
+            .collect()
+    }
+}
+
+fn perturb(position: &mut [f32; 3], velocity: &mut [f32; 3], deviation: [f64; 6]) {
+    for axis in 0..3 {
+        position[axis] = (position[axis] as f64 + deviation[axis]) as f32;
+        velocity[axis] = (velocity[axis] as f64 + deviation[3 + axis]) as f32;
+    }
+}
+
+/// The lower triangular `L` with `L Lᵀ` equal to the symmetric `matrix`, `None`
+/// unless it's positive semi-definite
+fn cholesky(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let size = matrix.len();
+    let scale = (0..size).map(|i| matrix[i][i].abs()).fold(0.0, f64::max);
+    let tolerance = SINGULAR_TOLERANCE * scale;
+    let mut factor = vec![vec![0.0; size]; size];
+    for j in 0..size {
+        let pivot = matrix[j][j] - (0..j).map(|k| factor[j][k] * factor[j][k]).sum::<f64>();
+        if pivot < -tolerance {
+            return None;
+        }
+        if pivot <= tolerance {
+            // A certain direction, the rest of the column has to vanish too
+            for i in j + 1..size {
+                let rest = matrix[i][j] - (0..j).map(|k| factor[i][k] * factor[j][k]).sum::<f64>();
+                if rest.abs() > tolerance {
+                    return None;
+                }
+            }
+            continue;
+        }
+        factor[j][j] = pivot.sqrt();
+        for i in j + 1..size {
+            let rest = matrix[i][j] - (0..j).map(|k| factor[i][k] * factor[j][k]).sum::<f64>();
+            factor[i][j] = rest / factor[j][j];
+        }
+    }
+    Some(factor)
+}
+
+/// A sample of a Monte Carlo run, how it started and how it ended
+#[derive(Debug, Clone)]
+pub struct SampleResult {
+    pub initial: Vec<Body>,
+    /// The bodies at the end of the run, or why it failed
+    pub last: Result<Vec<Body>, String>,
+}
+
+/// Write one row per body of every sample with its initial and final state,
+/// and a single row with the error for the samples which failed
+pub fn write_results(path: &Path, results: &[SampleResult]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "sample,body,x0,y0,z0,vx0,vy0,vz0,x,y,z,vx,vy,vz,error"
+    )?;
+    for (sample, result) in results.iter().enumerate() {
+        match &result.last {
+            Ok(last) => {
+                for (body, (initial, last)) in result.initial.iter().zip(last).enumerate() {
+                    let state: Vec<String> = [initial, last]
+                        .into_iter()
+                        .flat_map(|body| body.position.into_iter().chain(body.velocity))
+                        .map(|value| value.to_string())
+                        .collect();
+                    writeln!(writer, "{},{},{},", sample, body, state.join(","))?;
+                }
+            }
+            Err(error) => writeln!(
+                writer,
+                "{},,,,,,,,,,,,,,\"{}\"",
+                sample,
+                error.replace('"', "\"\"")
+            )?,
+        }
+    }
+    writer.flush()
+}
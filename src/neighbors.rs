@@ -0,0 +1,213 @@
+//! The neighbor grid, a counting sort of the bodies by cell rebuilt before the
+//! forces of every step. The kernels are rendered into the shader when enabled.
+//!
+//! The nearest neighbor search of the analysis hashes the bodies the same way
+//! into a table of its own, built when searching, see `NeighborSearch`.
+use std::mem::size_of;
+
+use crate::pipeline::{Domain, Pass};
+use crate::structures::{Body, Neighbor, NeighborQuery};
+
+/// The passes building the grid from the input bodies, sorting the bodies of
+/// every entry by index if `deterministic`
+pub fn passes(table_size: u32, workgroup_size: u32, deterministic: bool) -> Vec<Pass> {
+    let mut passes = vec![
+        Pass {
+            domain: Domain::Threads(table_size),
+            ..Pass::bodies("neighbor_clear")
+        },
+        Pass::bodies("neighbor_count"),
+        // A single workgroup
+        Pass {
+            domain: Domain::Threads(workgroup_size),
+            ..Pass::bodies("neighbor_scan")
+        },
+        Pass::bodies("neighbor_scatter"),
+    ];
+    if deterministic {
+        passes.push(Pass {
+            domain: Domain::Threads(table_size),
+            ..Pass::bodies("neighbor_sort")
+        });
+    }
+    passes
+}
+
+/// Size of the counts, offsets and sorted indices in bytes
+pub fn memory(table_size: u32, max_bodies: u32) -> u64 {
+    ((2 * table_size as usize + 1 + max_bodies as usize) * size_of::<u32>()) as u64
+}
+
+/// Shells of cells a neighbor search visits around a body's own at most
+pub const SEARCH_RINGS: u32 = 8;
+
+/// The kernels of a neighbor search in the order they're dispatched, building
+/// the hash table like the passes of the grid and then searching it
+pub const SEARCH_ENTRY_POINTS: [&str; 5] = [
+    "search_clear",
+    "search_count",
+    "search_scan",
+    "search_scatter",
+    "search_neighbors",
+];
+
+/// The nearest neighbors of every body and the density about it, see
+/// `Pipeline::search_neighbors`
+#[derive(Debug, Clone, Default)]
+pub struct NeighborSearch {
+    /// Neighbors searched per body
+    pub k: usize,
+    /// `k` per body, nearest first, padded with indices of `u32::MAX`
+    neighbors: Vec<Neighbor>,
+    /// Mass density about every body, from a cubic spline kernel reaching to
+    /// its farthest neighbor
+    pub densities: Vec<f32>,
+}
+
+impl NeighborSearch {
+    pub fn new(k: usize, neighbors: Vec<Neighbor>, densities: Vec<f32>) -> Self {
+        assert_eq!(neighbors.len(), k * densities.len());
+        Self {
+            k,
+            neighbors,
+            densities,
+        }
+    }
+
+    pub fn num_bodies(&self) -> usize {
+        self.densities.len()
+    }
+
+    /// The neighbors found of `body`, nearest first. Fewer than `k` only when
+    /// there are fewer other bodies.
+    pub fn neighbors(&self, body: usize) -> &[Neighbor] {
+        let all = &self.neighbors[body * self.k..(body + 1) * self.k];
+        let found = all
+            .iter()
+            .position(|neighbor| neighbor.index == u32::MAX)
+            .unwrap_or(self.k);
+        &all[..found]
+    }
+
+    /// Search again by brute force the `bodies` whose `query` may have missed
+    /// neighbors in cells further than the kernel visits, the few far out of
+    /// the bulk of the bodies
+    pub fn search_outliers(&mut self, query: &NeighborQuery, bodies: &[Body]) {
+        let rings = 2 * query.rings + 1;
+        if query.cells_per_axis > 0 && rings >= query.cells_per_axis {
+            return;
+        }
+        let reach = query.rings as f32 * query.cell_size;
+        for (body, own) in bodies.iter().enumerate() {
+            let found = self.neighbors(body);
+            if found.len() == self.k && found[self.k - 1].distance <= reach {
+                continue;
+            }
+            let mut found: Vec<Neighbor> = bodies
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != body)
+                .map(|(index, other)| Neighbor {
+                    index: index as u32,
+                    distance: distance(own.position, other.position, query.box_size),
+                })
+                .collect();
+            found.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+            found.truncate(self.k);
+            let h = found.last().map_or(0.0, |neighbor| neighbor.distance);
+            self.densities[body] = match h > 0.0 {
+                true => {
+                    own.mass * density_kernel(0.0, h)
+                        + found
+                            .iter()
+                            .map(|neighbor| {
+                                bodies[neighbor.index as usize].mass
+                                    * density_kernel(neighbor.distance, h)
+                            })
+                            .sum::<f32>()
+                }
+                false => 0.0,
+            };
+            found.resize(
+                self.k,
+                Neighbor {
+                    index: u32::MAX,
+                    distance: 0.0,
+                },
+            );
+            self.neighbors[body * self.k..(body + 1) * self.k].copy_from_slice(&found);
+        }
+    }
+}
+
+/// Distance between two bodies, to the nearest image in a periodic box of
+/// `box_size`
+fn distance(a: [f32; 3], b: [f32; 3], box_size: f32) -> f32 {
+    (0..3)
+        .map(|axis| {
+            let mut separation = b[axis] - a[axis];
+            if box_size > 0.0 {
+                separation -= box_size * (separation / box_size).round();
+            }
+            separation * separation
+        })
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Cubic spline kernel with support `h`, as in `neighbors.wgsl`
+fn density_kernel(distance: f32, h: f32) -> f32 {
+    let q = 2.0 * distance / h;
+    let norm = 8.0 / (std::f32::consts::PI * h * h * h);
+    if q < 1.0 {
+        norm * (1.0 - 1.5 * q * q + 0.75 * q * q * q)
+    } else if q < 2.0 {
+        norm * 0.25 * (2.0 - q).powi(3)
+    } else {
+        0.0
+    }
+}
+
+/// The query of the `k` nearest neighbors of bodies at `positions`. The cells
+/// hold about `k` bodies where they're as dense as in the middle half of their
+/// extent along every axis.
+pub fn query(positions: &[[f32; 3]], k: usize, box_size: f32) -> NeighborQuery {
+    let num_bodies = positions.len();
+    let mut volume = 1.0;
+    let mut dimensions = 0;
+    for axis in 0..3 {
+        let mut coordinates: Vec<f32> = positions.iter().map(|position| position[axis]).collect();
+        coordinates.sort_by(f32::total_cmp);
+        let quartile = |fraction: f64| coordinates[(fraction * (num_bodies - 1) as f64) as usize];
+        let spread = (quartile(0.75) - quartile(0.25)) as f64;
+        if spread > 0.0 {
+            volume *= spread;
+            dimensions += 1;
+        }
+    }
+    let mut cell_size = match dimensions {
+        0 => 1.0,
+        _ => {
+            let density = num_bodies as f64 / 2f64.powi(dimensions) / volume;
+            (k as f64 / density).powf(1.0 / dimensions as f64) as f32
+        }
+    };
+    let (cells_per_axis, rings) = match box_size > 0.0 {
+        true => {
+            // A whole number of cells in the box, and no cell visited twice
+            let cells = ((box_size / cell_size) as u32).max(1);
+            cell_size = box_size / cells as f32;
+            (cells, SEARCH_RINGS.min((cells - 1) / 2))
+        }
+        false => (0, SEARCH_RINGS),
+    };
+    NeighborQuery {
+        num_bodies: num_bodies as u32,
+        k: k as u32,
+        cell_size,
+        box_size,
+        cells_per_axis,
+        rings,
+        table_size: (num_bodies as u32).next_power_of_two(),
+    }
+}
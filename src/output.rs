@@ -0,0 +1,180 @@
+//! Snapshot output for analysis in other tools, written at a fixed interval of
+//! steps while the simulation runs. The format is chosen by the file extension.
+use std::{
+    io::{self, BufWriter},
+    path::Path,
+};
+
+use crate::manifest::RunManifest;
+use crate::pipeline::Pipeline;
+use crate::structures::{Body, DynamicConfig};
+
+#[cfg(feature = "arrow")]
+mod arrow;
+mod binaries;
+mod chaos;
+mod cluster;
+mod csv;
+mod diagnostics;
+mod elements;
+mod groups;
+#[cfg(feature = "hdf5")]
+mod hdf5;
+mod jsonl;
+
+#[cfg(feature = "arrow")]
+pub use self::arrow::{ArrowStreamWriter, ParquetWriter};
+pub use self::binaries::BinariesCsvWriter;
+pub use self::chaos::ChaosCsvWriter;
+pub use self::cluster::ClusterCsvWriter;
+pub use self::csv::CsvWriter;
+pub use self::diagnostics::{DiagnosticFrame, DiagnosticsCsvWriter};
+pub use self::elements::ElementsCsvWriter;
+pub use self::groups::GroupsCsvWriter;
+#[cfg(feature = "hdf5")]
+pub use self::hdf5::Hdf5Writer;
+pub use self::jsonl::JsonLinesWriter;
+
+/// Extensions of the formats `create` supports
+const EXTENSIONS: &[&str] = &[
+    ".csv",
+    ".jsonl",
+    #[cfg(feature = "hdf5")]
+    ".h5",
+    #[cfg(feature = "hdf5")]
+    ".hdf5",
+    #[cfg(feature = "arrow")]
+    ".parquet",
+    #[cfg(feature = "arrow")]
+    ".arrows",
+];
+
+/// Receives the state of every body at each snapshot
+pub trait SnapshotWriter {
+    /// Append the state after `step` steps, at the simulated `time`
+    fn write_snapshot(&mut self, step: usize, time: f64, bodies: &[Body]) -> io::Result<()>;
+
+    /// Number of snapshots written so far
+    fn snapshots(&self) -> usize;
+
+    /// Flush everything written, the writer is not used afterwards
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Create a writer for the format of `path`'s extension, which records the
+/// `manifest` of the run. Binary formats record the configs too.
+#[cfg_attr(not(any(feature = "hdf5", feature = "arrow")), allow(unused_variables))]
+pub fn create(
+    path: &Path,
+    manifest: &RunManifest,
+    dynamic_config: &DynamicConfig,
+) -> io::Result<Box<dyn SnapshotWriter>> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => Ok(Box::new(CsvWriter::create(path, manifest)?)),
+        Some("jsonl") => Ok(Box::new(JsonLinesWriter::create(path, manifest)?)),
+        #[cfg(feature = "hdf5")]
+        Some("h5" | "hdf5") => Ok(Box::new(Hdf5Writer::create(
+            path,
+            manifest,
+            dynamic_config,
+        )?)),
+        #[cfg(feature = "arrow")]
+        Some("parquet") => Ok(Box::new(ParquetWriter::create(
+            path,
+            manifest,
+            dynamic_config,
+        )?)),
+        #[cfg(feature = "arrow")]
+        Some("arrows") => Ok(Box::new(ArrowStreamWriter::create(
+            path,
+            manifest,
+            dynamic_config,
+        )?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Unknown output format of {}, expected {}",
+                path.display(),
+                EXTENSIONS.join(", ")
+            ),
+        )),
+    }
+}
+
+/// Create a writer to stdout in the text `format`, `jsonl` or `csv`, for piping
+/// the snapshots into other tools
+pub fn create_stdout(format: &str, manifest: &RunManifest) -> io::Result<Box<dyn SnapshotWriter>> {
+    let mut stdout = BufWriter::new(io::stdout());
+    match format {
+        "jsonl" => Ok(Box::new(JsonLinesWriter::new(stdout, manifest)?)),
+        "csv" => {
+            manifest.write_comment(&mut stdout)?;
+            Ok(Box::new(CsvWriter::new(stdout)?))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Unknown output format {} for stdout, expected jsonl or csv",
+                format
+            ),
+        )),
+    }
+}
+
+/// Create a writer of the osculating elements of the orbits about the body at
+/// index `central`, which only CSV supports
+pub fn create_elements(
+    path: &Path,
+    central: usize,
+    manifest: &RunManifest,
+) -> io::Result<Box<dyn SnapshotWriter>> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => Ok(Box::new(ElementsCsvWriter::create(path, central, manifest)?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Orbital elements are written as CSV, not to {}",
+                path.display()
+            ),
+        )),
+    }
+}
+
+/// Create a writer of the Jacobi constants and Tisserand parameters relative to
+/// `frame`, which only CSV supports
+pub fn create_diagnostics(
+    path: &Path,
+    frame: DiagnosticFrame,
+    manifest: &RunManifest,
+) -> io::Result<Box<dyn SnapshotWriter>> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => Ok(Box::new(DiagnosticsCsvWriter::create(path, frame, manifest)?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Diagnostics are written as CSV, not to {}", path.display()),
+        )),
+    }
+}
+
+/// Run `steps` steps on `pipeline`, writing a snapshot before the first and after
+/// every `interval` steps. Snapshots are written while the following steps run.
+/// The writer is finished afterwards.
+pub fn record(
+    pipeline: &mut Pipeline,
+    steps: usize,
+    interval: usize,
+    writer: &mut dyn SnapshotWriter,
+) -> io::Result<()> {
+    assert!(interval > 0, "The snapshot interval must be positive");
+    let dt = pipeline.dynamic_config().dt as f64;
+    writer.write_snapshot(0, 0.0, &pipeline.read_bodies())?;
+    let mut result = Ok(());
+    pipeline.run_with_snapshots(steps, interval, |completed, _, bodies| {
+        // Keep the first error, the remaining steps still run
+        if result.is_ok() {
+            result = writer.write_snapshot(completed, completed as f64 * dt, bodies);
+        }
+    });
+    result?;
+    writer.finish()
+}
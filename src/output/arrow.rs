@@ -0,0 +1,216 @@
+//! Snapshots as Parquet files or Arrow IPC streams, for dataframe libraries
+//! such as pandas, polars or DuckDB.
+//!
+//! Both hold one table with a row per body and snapshot, in the columns of the
+//! CSV output followed by `mu`. The configs and the manifest of the run are
+//! kept in the metadata of the schema, under the names of the HDF5 attributes. Every snapshot is a record
+//! batch, which Parquet gathers into row groups.
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use arrow_array::{ArrayRef, Float32Array, Float64Array, RecordBatch, UInt64Array};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+
+use super::SnapshotWriter;
+use crate::manifest::RunManifest;
+use crate::structures::{Body, DynamicConfig};
+
+/// The table of the snapshots, with the configs and the manifest as metadata
+fn schema(manifest: &RunManifest, dynamic_config: &DynamicConfig) -> io::Result<SchemaRef> {
+    let mut fields = vec![
+        Field::new("step", DataType::UInt64, false),
+        Field::new("time", DataType::Float64, false),
+        Field::new("id", DataType::UInt64, false),
+    ];
+    fields.extend(
+        ["x", "y", "z", "vx", "vy", "vz", "mass", "mu"]
+            .map(|name| Field::new(name, DataType::Float32, false)),
+    );
+    let metadata = HashMap::from([
+        (
+            "num_bodies".to_string(),
+            dynamic_config.num_bodies.to_string(),
+        ),
+        ("dt".to_string(), dynamic_config.dt.to_string()),
+        ("box_size".to_string(), dynamic_config.box_size.to_string()),
+        (
+            "softening".to_string(),
+            dynamic_config.softening.to_string(),
+        ),
+        (
+            "static_config".to_string(),
+            serde_json::to_string(&manifest.static_config)?,
+        ),
+        ("manifest".to_string(), manifest.to_json()?),
+    ]);
+    Ok(Arc::new(Schema::new_with_metadata(fields, metadata)))
+}
+
+/// The rows of one snapshot
+fn record_batch(
+    schema: &SchemaRef,
+    step: usize,
+    time: f64,
+    bodies: &[Body],
+) -> io::Result<RecordBatch> {
+    let column = |value: fn(&Body) -> f32| -> ArrayRef {
+        Arc::new(Float32Array::from_iter_values(bodies.iter().map(value)))
+    };
+    let columns = vec![
+        Arc::new(UInt64Array::from(vec![step as u64; bodies.len()])) as ArrayRef,
+        Arc::new(Float64Array::from(vec![time; bodies.len()])),
+        Arc::new(UInt64Array::from_iter_values(0..bodies.len() as u64)),
+        column(|body| body.position[0]),
+        column(|body| body.position[1]),
+        column(|body| body.position[2]),
+        column(|body| body.velocity[0]),
+        column(|body| body.velocity[1]),
+        column(|body| body.velocity[2]),
+        column(|body| body.mass),
+        column(|body| body.mu),
+    ];
+    RecordBatch::try_new(schema.clone(), columns).map_err(io::Error::other)
+}
+
+/// Snapshots as a zstd-compressed Parquet file
+pub struct ParquetWriter<W: Write + Send = BufWriter<File>> {
+    writer: ArrowWriter<W>,
+    schema: SchemaRef,
+    snapshots: usize,
+}
+
+impl ParquetWriter {
+    pub fn create(
+        path: &Path,
+        manifest: &RunManifest,
+        dynamic_config: &DynamicConfig,
+    ) -> io::Result<Self> {
+        Self::new(
+            BufWriter::new(File::create(path)?),
+            manifest,
+            dynamic_config,
+        )
+    }
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    /// Start a file at the beginning of `writer`, with the configs and the
+    /// manifest in its schema
+    pub fn new(
+        writer: W,
+        manifest: &RunManifest,
+        dynamic_config: &DynamicConfig,
+    ) -> io::Result<Self> {
+        let schema = schema(manifest, dynamic_config)?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let writer = ArrowWriter::try_new(writer, schema.clone(), Some(properties))
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            writer,
+            schema,
+            snapshots: 0,
+        })
+    }
+
+    /// The underlying writer, after `finish`
+    pub fn into_inner(self) -> io::Result<W> {
+        self.writer.into_inner().map_err(io::Error::other)
+    }
+}
+
+impl<W: Write + Send> SnapshotWriter for ParquetWriter<W> {
+    fn write_snapshot(&mut self, step: usize, time: f64, bodies: &[Body]) -> io::Result<()> {
+        let batch = record_batch(&self.schema, step, time, bodies)?;
+        self.writer.write(&batch).map_err(io::Error::other)?;
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    /// Write the footer, without which the file can't be read
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.finish().map_err(io::Error::other)?;
+        self.writer.inner_mut().flush()
+    }
+}
+
+/// Snapshots as an Arrow IPC stream, readable while it's written
+pub struct ArrowStreamWriter<W: Write = BufWriter<File>> {
+    writer: StreamWriter<W>,
+    schema: SchemaRef,
+    snapshots: usize,
+}
+
+impl ArrowStreamWriter {
+    pub fn create(
+        path: &Path,
+        manifest: &RunManifest,
+        dynamic_config: &DynamicConfig,
+    ) -> io::Result<Self> {
+        Self::new(
+            BufWriter::new(File::create(path)?),
+            manifest,
+            dynamic_config,
+        )
+    }
+}
+
+impl<W: Write> ArrowStreamWriter<W> {
+    /// Start a stream on `writer` with the schema, holding the configs and the
+    /// manifest
+    pub fn new(
+        writer: W,
+        manifest: &RunManifest,
+        dynamic_config: &DynamicConfig,
+    ) -> io::Result<Self> {
+        let schema = schema(manifest, dynamic_config)?;
+        let writer = StreamWriter::try_new(writer, &schema).map_err(io::Error::other)?;
+        Ok(Self {
+            writer,
+            schema,
+            snapshots: 0,
+        })
+    }
+
+    /// The underlying writer, after `finish`
+    pub fn into_inner(self) -> io::Result<W> {
+        self.writer.into_inner().map_err(io::Error::other)
+    }
+}
+
+impl<W: Write> SnapshotWriter for ArrowStreamWriter<W> {
+    fn write_snapshot(&mut self, step: usize, time: f64, bodies: &[Body]) -> io::Result<()> {
+        let batch = record_batch(&self.schema, step, time, bodies)?;
+        self.writer.write(&batch).map_err(io::Error::other)?;
+        // Readers following the stream see every snapshot as it's written
+        self.writer.get_mut().flush()?;
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    /// Mark the end of the stream
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.finish().map_err(io::Error::other)?;
+        self.writer.get_mut().flush()
+    }
+}
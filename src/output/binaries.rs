@@ -0,0 +1,73 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::binaries::Binary;
+use crate::manifest::RunManifest;
+
+const HEADER: &str =
+    "step,time,primary,secondary,separation,semi_major_axis,eccentricity,binding_energy";
+
+/// One row per binary and snapshot with its orbit. Not a `SnapshotWriter`, as
+/// finding the binaries needs the pipeline.
+pub struct BinariesCsvWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    snapshots: usize,
+}
+
+impl BinariesCsvWriter {
+    pub fn create(path: &Path, manifest: &RunManifest) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        manifest.write_comment(&mut writer)?;
+        Self::new(writer)
+    }
+}
+
+impl<W: Write> BinariesCsvWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(Self {
+            writer,
+            snapshots: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Append the binaries after `step` steps, at the simulated `time`
+    pub fn write_snapshot(
+        &mut self,
+        step: usize,
+        time: f64,
+        binaries: &[Binary],
+    ) -> io::Result<()> {
+        for binary in binaries {
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{}",
+                step,
+                time,
+                binary.bodies[0],
+                binary.bodies[1],
+                binary.separation,
+                binary.semi_major_axis,
+                binary.eccentricity,
+                binary.binding_energy
+            )?;
+        }
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    pub fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::chaos::ChaosIndicators;
+use crate::manifest::RunManifest;
+
+const HEADER: &str = "step,time,id,lyapunov,megno,mean_megno";
+
+/// One row per body and snapshot with its chaos indicators. Not a
+/// `SnapshotWriter`, as the indicators come from the pipeline and not the bodies.
+pub struct ChaosCsvWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    snapshots: usize,
+}
+
+impl ChaosCsvWriter {
+    pub fn create(path: &Path, manifest: &RunManifest) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        manifest.write_comment(&mut writer)?;
+        Self::new(writer)
+    }
+}
+
+impl<W: Write> ChaosCsvWriter<W> {
+    /// Write the header to `writer`
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(Self {
+            writer,
+            snapshots: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Append the indicators after `step` steps, at the simulated `time`
+    pub fn write_snapshot(
+        &mut self,
+        step: usize,
+        time: f64,
+        indicators: &[ChaosIndicators],
+    ) -> io::Result<()> {
+        for (id, indicators) in indicators.iter().enumerate() {
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{}",
+                step, time, id, indicators.lyapunov, indicators.megno, indicators.mean_megno
+            )?;
+        }
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    pub fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
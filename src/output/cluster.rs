@@ -0,0 +1,75 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::cluster::ClusterStats;
+use crate::groups::LAGRANGIAN_FRACTIONS;
+use crate::manifest::RunManifest;
+
+/// One row per snapshot with the structure of the bodies. Not a
+/// `SnapshotWriter`, as the densities need the pipeline.
+pub struct ClusterCsvWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    snapshots: usize,
+}
+
+impl ClusterCsvWriter {
+    pub fn create(path: &Path, manifest: &RunManifest) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        manifest.write_comment(&mut writer)?;
+        Self::new(writer)
+    }
+}
+
+impl<W: Write> ClusterCsvWriter<W> {
+    /// Write the header to `writer`, with the Lagrangian radii named as by
+    /// `GroupsCsvWriter`
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        let radii: Vec<String> = LAGRANGIAN_FRACTIONS
+            .iter()
+            .map(|fraction| format!("r{}", (fraction * 100.0).round()))
+            .collect();
+        writeln!(
+            writer,
+            "step,time,x,y,z,core_radius,core_density,{}",
+            radii.join(",")
+        )?;
+        Ok(Self {
+            writer,
+            snapshots: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Append the structure after `step` steps, at the simulated `time`
+    pub fn write_snapshot(
+        &mut self,
+        step: usize,
+        time: f64,
+        stats: &ClusterStats,
+    ) -> io::Result<()> {
+        let values: Vec<String> = stats
+            .density_center
+            .iter()
+            .chain([&stats.core_radius, &stats.core_density])
+            .chain(&stats.lagrangian_radii)
+            .map(|value| value.to_string())
+            .collect();
+        writeln!(self.writer, "{},{},{}", step, time, values.join(","))?;
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    pub fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use super::SnapshotWriter;
+use crate::manifest::RunManifest;
+use crate::structures::Body;
+
+const HEADER: &str = "step,time,id,x,y,z,vx,vy,vz,mass";
+
+/// One row per body and snapshot, under a header naming the columns. The id is
+/// the index of the body.
+pub struct CsvWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    snapshots: usize,
+}
+
+impl CsvWriter {
+    /// With the manifest of the run as a comment ahead of the header
+    pub fn create(path: &Path, manifest: &RunManifest) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        manifest.write_comment(&mut writer)?;
+        Self::new(writer)
+    }
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Write the header to `writer`
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(Self {
+            writer,
+            snapshots: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> SnapshotWriter for CsvWriter<W> {
+    fn write_snapshot(&mut self, step: usize, time: f64, bodies: &[Body]) -> io::Result<()> {
+        for (id, body) in bodies.iter().enumerate() {
+            let [x, y, z] = body.position;
+            let [vx, vy, vz] = body.velocity;
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{},{},{}",
+                step, time, id, x, y, z, vx, vy, vz, body.mass
+            )?;
+        }
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
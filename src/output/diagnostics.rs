@@ -0,0 +1,120 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use super::SnapshotWriter;
+use crate::analysis::{jacobi_constants, synodic_tisserand_parameters, tisserand_parameters};
+use crate::manifest::RunManifest;
+use crate::structures::Body;
+
+const HEADER: &str = "step,time,id,jacobi,tisserand";
+
+/// What the diagnostics are relative to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticFrame {
+    /// The rotating frame of the restricted three-body problem about `center`,
+    /// with the Jacobi constant and the Tisserand parameter about the primary
+    Synodic {
+        center: [f64; 3],
+        mass_parameter: f64,
+    },
+    /// The orbits about the body at `central` relative to that of the body at
+    /// `perturber`, which have no Jacobi constant
+    Orbits { central: usize, perturber: usize },
+}
+
+/// One row per body and snapshot with its Jacobi constant and Tisserand
+/// parameter, left empty where they aren't defined. Bodies the orbits are about
+/// have no rows.
+pub struct DiagnosticsCsvWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    frame: DiagnosticFrame,
+    snapshots: usize,
+}
+
+impl DiagnosticsCsvWriter {
+    pub fn create(path: &Path, frame: DiagnosticFrame, manifest: &RunManifest) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        manifest.write_comment(&mut writer)?;
+        Self::new(writer, frame)
+    }
+}
+
+impl<W: Write> DiagnosticsCsvWriter<W> {
+    /// Write the header to `writer`
+    pub fn new(mut writer: W, frame: DiagnosticFrame) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(Self {
+            writer,
+            frame,
+            snapshots: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> SnapshotWriter for DiagnosticsCsvWriter<W> {
+    fn write_snapshot(&mut self, step: usize, time: f64, bodies: &[Body]) -> io::Result<()> {
+        let (jacobi, tisserand, skipped) = match self.frame {
+            DiagnosticFrame::Synodic {
+                center,
+                mass_parameter,
+            } => (
+                jacobi_constants(bodies, center, mass_parameter)
+                    .into_iter()
+                    .map(Some)
+                    .collect(),
+                synodic_tisserand_parameters(bodies, center, mass_parameter),
+                [None; 2],
+            ),
+            DiagnosticFrame::Orbits { central, perturber } => {
+                if central.max(perturber) >= bodies.len() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "No bodies {} and {} among {} bodies",
+                            central,
+                            perturber,
+                            bodies.len()
+                        ),
+                    ));
+                }
+                (
+                    vec![None; bodies.len()],
+                    tisserand_parameters(bodies, central, perturber),
+                    [Some(central), Some(perturber)],
+                )
+            }
+        };
+        let field = |value: Option<f64>| value.map_or(String::new(), |value| value.to_string());
+        for (id, (jacobi, tisserand)) in jacobi.into_iter().zip(tisserand).enumerate() {
+            if skipped.contains(&Some(id)) {
+                continue;
+            }
+            writeln!(
+                self.writer,
+                "{},{},{},{},{}",
+                step,
+                time,
+                id,
+                field(jacobi),
+                field(tisserand)
+            )?;
+        }
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use super::SnapshotWriter;
+use crate::analysis::osculating_elements;
+use crate::ic::kepler::mean_from_true;
+use crate::manifest::RunManifest;
+use crate::structures::Body;
+
+const HEADER: &str = "step,time,id,a,e,i,node,periapsis,true_anomaly,mean_anomaly";
+
+/// One row per body orbiting the central body and snapshot, with the osculating
+/// elements in place of the state. Angles are in radians. Bodies on radial or
+/// parabolic paths leave the elements empty.
+pub struct ElementsCsvWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    /// Index of the body the orbits are about
+    central: usize,
+    snapshots: usize,
+}
+
+impl ElementsCsvWriter {
+    pub fn create(path: &Path, central: usize, manifest: &RunManifest) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        manifest.write_comment(&mut writer)?;
+        Self::new(writer, central)
+    }
+}
+
+impl<W: Write> ElementsCsvWriter<W> {
+    /// Write the header to `writer`
+    pub fn new(mut writer: W, central: usize) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(Self {
+            writer,
+            central,
+            snapshots: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> SnapshotWriter for ElementsCsvWriter<W> {
+    fn write_snapshot(&mut self, step: usize, time: f64, bodies: &[Body]) -> io::Result<()> {
+        if self.central >= bodies.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "No central body {} among {} bodies",
+                    self.central,
+                    bodies.len()
+                ),
+            ));
+        }
+        let elements = osculating_elements(bodies, self.central);
+        for (id, elements) in elements.iter().enumerate() {
+            if id == self.central {
+                continue;
+            }
+            match elements {
+                Some(elements) => {
+                    let true_anomaly = elements.true_anomaly();
+                    writeln!(
+                        self.writer,
+                        "{},{},{},{},{},{},{},{},{},{}",
+                        step,
+                        time,
+                        id,
+                        elements.semi_major_axis,
+                        elements.eccentricity,
+                        elements.inclination,
+                        elements.longitude_of_ascending_node,
+                        elements.argument_of_periapsis,
+                        true_anomaly,
+                        mean_from_true(true_anomaly, elements.eccentricity)
+                    )?;
+                }
+                None => writeln!(self.writer, "{},{},{},,,,,,,", step, time, id)?,
+            }
+        }
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
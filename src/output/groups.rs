@@ -0,0 +1,92 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::groups::{GroupStats, LAGRANGIAN_FRACTIONS};
+use crate::manifest::RunManifest;
+
+/// One row per group and snapshot with its statistics. Not a `SnapshotWriter`,
+/// as the statistics need the pipeline and the groups of the bodies.
+pub struct GroupsCsvWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    snapshots: usize,
+}
+
+impl GroupsCsvWriter {
+    pub fn create(path: &Path, manifest: &RunManifest) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        manifest.write_comment(&mut writer)?;
+        Self::new(writer)
+    }
+}
+
+impl<W: Write> GroupsCsvWriter<W> {
+    /// Write the header to `writer`, with a column per Lagrangian radius named
+    /// by the percentage of the mass it encloses
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        let radii: Vec<String> = LAGRANGIAN_FRACTIONS
+            .iter()
+            .map(|fraction| format!("r{}", (fraction * 100.0).round()))
+            .collect();
+        writeln!(
+            writer,
+            "step,time,group,bodies,mass,x,y,z,vx,vy,vz,bound_mass,velocity_dispersion,{}",
+            radii.join(",")
+        )?;
+        Ok(Self {
+            writer,
+            snapshots: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Append the statistics after `step` steps, at the simulated `time`
+    pub fn write_snapshot(
+        &mut self,
+        step: usize,
+        time: f64,
+        stats: &[GroupStats],
+    ) -> io::Result<()> {
+        for group in stats {
+            let vectors: Vec<String> = group
+                .center_of_mass
+                .iter()
+                .chain(&group.velocity)
+                .map(|value| value.to_string())
+                .collect();
+            let radii: Vec<String> = group
+                .lagrangian_radii
+                .iter()
+                .map(|radius| radius.to_string())
+                .collect();
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{},{}",
+                step,
+                time,
+                group.group,
+                group.bodies,
+                group.mass,
+                vectors.join(","),
+                group.bound_mass,
+                group.velocity_dispersion,
+                radii.join(",")
+            )?;
+        }
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    pub fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,474 @@
+//! The subset of HDF5 needed for snapshots, written directly so that no C library
+//! is required: a version 0 superblock, version 1 object headers, groups indexed
+//! by symbol tables and contiguous little-endian datasets.
+//!
+//! The root group holds the configs and the manifest of the run as attributes and a `snapshot_NNNNNN` group
+//! per snapshot, with its `step` and `time` as attributes and the `position`,
+//! `velocity`, `mass` and `mu` datasets indexed by body.
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use super::SnapshotWriter;
+use crate::manifest::RunManifest;
+use crate::structures::{Body, DynamicConfig};
+
+const SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+const UNDEFINED: u64 = u64::MAX;
+/// End of the local heap free list, the heaps are written without free space
+const NO_FREE_BLOCK: u64 = 1;
+const SUPERBLOCK_SIZE: u64 = 96;
+/// Symbol table nodes hold up to twice this many entries
+const GROUP_LEAF_K: usize = 4;
+/// Group B-tree nodes hold up to twice this many children
+const GROUP_INTERNAL_K: usize = 16;
+const ENTRY_SIZE: usize = 40;
+const SYMBOL_NODE_SIZE: usize = 8 + 2 * GROUP_LEAF_K * ENTRY_SIZE;
+const BTREE_NODE_SIZE: usize = 24 + 2 * GROUP_INTERNAL_K * 8 + (2 * GROUP_INTERNAL_K + 1) * 8;
+
+// Object header message types
+const DATASPACE: u16 = 0x0001;
+const DATATYPE: u16 = 0x0003;
+const FILL_VALUE: u16 = 0x0005;
+const LAYOUT: u16 = 0x0008;
+const ATTRIBUTE: u16 = 0x000C;
+const SYMBOL_TABLE: u16 = 0x0011;
+
+#[derive(Debug, Clone, Copy)]
+enum Datatype {
+    F32,
+    F64,
+    U64,
+    /// Null-terminated ASCII of the given size, including the terminator
+    String(usize),
+}
+
+impl Datatype {
+    fn size(self) -> usize {
+        match self {
+            Datatype::F32 => 4,
+            Datatype::F64 | Datatype::U64 => 8,
+            Datatype::String(size) => size,
+        }
+    }
+
+    fn encode(self) -> Vec<u8> {
+        // Class in the low and version 1 in the high nibble, then the class bit fields
+        let mut bytes = match self {
+            // Little-endian with an implied leading mantissa bit, the sign at the top
+            Datatype::F32 => vec![0x11, 0x20, 31, 0],
+            Datatype::F64 => vec![0x11, 0x20, 63, 0],
+            Datatype::U64 => vec![0x10, 0, 0, 0],
+            Datatype::String(_) => vec![0x13, 0, 0, 0],
+        };
+        bytes.extend((self.size() as u32).to_le_bytes());
+        match self {
+            Datatype::F32 => {
+                bytes.extend([0, 0, 32, 0, 23, 8, 0, 23]);
+                bytes.extend(127_u32.to_le_bytes());
+            }
+            Datatype::F64 => {
+                bytes.extend([0, 0, 64, 0, 52, 11, 0, 52]);
+                bytes.extend(1023_u32.to_le_bytes());
+            }
+            Datatype::U64 => bytes.extend([0, 0, 64, 0]),
+            Datatype::String(_) => {}
+        }
+        bytes
+    }
+}
+
+/// A version 1 dataspace, scalar without dimensions
+fn dataspace(dimensions: &[u64]) -> Vec<u8> {
+    let mut bytes = vec![1, dimensions.len() as u8, 0, 0, 0, 0, 0, 0];
+    for dimension in dimensions {
+        bytes.extend(dimension.to_le_bytes());
+    }
+    bytes
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+    bytes.resize(bytes.len().next_multiple_of(8), 0);
+}
+
+#[derive(Debug, Clone)]
+struct Attribute {
+    name: &'static str,
+    datatype: Datatype,
+    data: Vec<u8>,
+}
+
+impl Attribute {
+    fn f64(name: &'static str, value: f64) -> Self {
+        Self {
+            name,
+            datatype: Datatype::F64,
+            data: value.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn u64(name: &'static str, value: u64) -> Self {
+        Self {
+            name,
+            datatype: Datatype::U64,
+            data: value.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn string(name: &'static str, value: &str) -> Self {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        Self {
+            name,
+            datatype: Datatype::String(data.len()),
+            data,
+        }
+    }
+
+    /// A version 1 attribute message of a scalar
+    fn message(&self) -> (u16, Vec<u8>) {
+        let datatype = self.datatype.encode();
+        let dataspace = dataspace(&[]);
+        let mut bytes = vec![1, 0];
+        bytes.extend((self.name.len() as u16 + 1).to_le_bytes());
+        bytes.extend((datatype.len() as u16).to_le_bytes());
+        bytes.extend((dataspace.len() as u16).to_le_bytes());
+        bytes.extend(self.name.as_bytes());
+        bytes.push(0);
+        pad(&mut bytes);
+        bytes.extend(datatype);
+        pad(&mut bytes);
+        bytes.extend(dataspace);
+        pad(&mut bytes);
+        bytes.extend(&self.data);
+        (ATTRIBUTE, bytes)
+    }
+}
+
+/// A version 1 object header holding `messages`
+fn object_header(messages: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (kind, data) in messages {
+        let size = data.len().next_multiple_of(8);
+        body.extend(kind.to_le_bytes());
+        body.extend((size as u16).to_le_bytes());
+        body.extend([0; 4]);
+        body.extend(data);
+        body.resize(body.len() + size - data.len(), 0);
+    }
+    let mut bytes = vec![1, 0];
+    bytes.extend((messages.len() as u16).to_le_bytes());
+    bytes.extend(1_u32.to_le_bytes());
+    bytes.extend((body.len() as u32).to_le_bytes());
+    bytes.extend([0; 4]);
+    bytes.extend(body);
+    bytes
+}
+
+/// Where a group's index lives, cached in the symbol table entries pointing at it
+#[derive(Debug, Clone, Copy)]
+struct GroupAddresses {
+    header: u64,
+    btree: u64,
+    heap: u64,
+}
+
+/// A member of a group being written
+#[derive(Debug, Clone)]
+struct Link {
+    name: String,
+    header: u64,
+    /// Set for groups
+    group: Option<GroupAddresses>,
+}
+
+fn symbol_table_entry(
+    bytes: &mut Vec<u8>,
+    name_offset: u64,
+    header: u64,
+    group: Option<GroupAddresses>,
+) {
+    bytes.extend(name_offset.to_le_bytes());
+    bytes.extend(header.to_le_bytes());
+    match group {
+        Some(group) => {
+            bytes.extend(1_u32.to_le_bytes());
+            bytes.extend([0; 4]);
+            bytes.extend(group.btree.to_le_bytes());
+            bytes.extend(group.heap.to_le_bytes());
+        }
+        None => bytes.extend([0; 24]),
+    }
+}
+
+/// Writes snapshots as HDF5 groups, the file is only complete once finished
+pub struct Hdf5Writer<W: Write + Seek = BufWriter<File>> {
+    writer: W,
+    /// Address of the next write, always the end of the file
+    end: u64,
+    root_attributes: Vec<Attribute>,
+    snapshots: Vec<Link>,
+    finished: bool,
+}
+
+impl Hdf5Writer {
+    pub fn create(
+        path: &Path,
+        manifest: &RunManifest,
+        dynamic_config: &DynamicConfig,
+    ) -> io::Result<Self> {
+        Self::new(
+            BufWriter::new(File::create(path)?),
+            manifest,
+            dynamic_config,
+        )
+    }
+}
+
+impl<W: Write + Seek> Hdf5Writer<W> {
+    /// Start a file at the beginning of `writer`, with the configs and the
+    /// manifest as attributes of the root group
+    pub fn new(
+        mut writer: W,
+        manifest: &RunManifest,
+        dynamic_config: &DynamicConfig,
+    ) -> io::Result<Self> {
+        // The superblock points at the root group, which is written last
+        writer.write_all(&[0; SUPERBLOCK_SIZE as usize])?;
+        let static_config = serde_json::to_string(&manifest.static_config)?;
+        Ok(Self {
+            writer,
+            end: SUPERBLOCK_SIZE,
+            root_attributes: vec![
+                Attribute::u64("num_bodies", dynamic_config.num_bodies as u64),
+                Attribute::f64("dt", dynamic_config.dt as f64),
+                Attribute::f64("box_size", dynamic_config.box_size as f64),
+                Attribute::f64("softening", dynamic_config.softening as f64),
+                Attribute::string("static_config", &static_config),
+                Attribute::string("manifest", &manifest.to_json()?),
+            ],
+            snapshots: Vec::new(),
+            finished: false,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Append `bytes`, returning their address
+    fn append(&mut self, bytes: &[u8]) -> io::Result<u64> {
+        let address = self.end;
+        self.writer.write_all(bytes)?;
+        self.end += bytes.len() as u64;
+        Ok(address)
+    }
+
+    fn write_dataset(
+        &mut self,
+        datatype: Datatype,
+        dimensions: &[u64],
+        data: &[u8],
+    ) -> io::Result<u64> {
+        let address = if data.is_empty() {
+            UNDEFINED
+        } else {
+            self.append(data)?
+        };
+        // Version 2 fill value, allocated late, written if set, not set
+        let fill_value = vec![2, 2, 2, 0];
+        let mut layout = vec![3, 1];
+        layout.extend(address.to_le_bytes());
+        layout.extend((data.len() as u64).to_le_bytes());
+        let header = object_header(&[
+            (DATASPACE, dataspace(dimensions)),
+            (DATATYPE, datatype.encode()),
+            (FILL_VALUE, fill_value),
+            (LAYOUT, layout),
+        ]);
+        self.append(&header)
+    }
+
+    /// Write the index of a group holding `links` and its object header
+    fn write_group(
+        &mut self,
+        mut links: Vec<Link>,
+        attributes: &[Attribute],
+    ) -> io::Result<GroupAddresses> {
+        links.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // Local heap of the names, offset zero is the empty string
+        let mut names = vec![0; 8];
+        let mut name_offsets = Vec::with_capacity(links.len());
+        for link in &links {
+            name_offsets.push(names.len() as u64);
+            names.extend(link.name.as_bytes());
+            names.push(0);
+            pad(&mut names);
+        }
+        let heap = self.end;
+        let mut bytes = b"HEAP".to_vec();
+        bytes.extend([0; 4]);
+        bytes.extend((names.len() as u64).to_le_bytes());
+        bytes.extend(NO_FREE_BLOCK.to_le_bytes());
+        bytes.extend((heap + 32).to_le_bytes());
+        bytes.extend(names);
+        self.append(&bytes)?;
+
+        // Symbol table nodes of sorted entries, with the largest name in each
+        let mut children = Vec::new();
+        for (chunk, offsets) in links
+            .chunks(2 * GROUP_LEAF_K)
+            .zip(name_offsets.chunks(2 * GROUP_LEAF_K))
+        {
+            let mut bytes = b"SNOD".to_vec();
+            bytes.extend([1, 0]);
+            bytes.extend((chunk.len() as u16).to_le_bytes());
+            for (link, &offset) in chunk.iter().zip(offsets) {
+                symbol_table_entry(&mut bytes, offset, link.header, link.group);
+            }
+            bytes.resize(SYMBOL_NODE_SIZE, 0);
+            children.push((self.append(&bytes)?, *offsets.last().unwrap()));
+        }
+
+        // B-tree levels from the leaves up, until one node covers everything.
+        // Child `i` holds the names after key `i` up to and including key `i + 1`.
+        let mut level = 0_u8;
+        let btree = loop {
+            // An empty group still has a root node
+            let nodes: Vec<&[(u64, u64)]> = if children.is_empty() {
+                vec![&[]]
+            } else {
+                children.chunks(2 * GROUP_INTERNAL_K).collect()
+            };
+            let first = self.end;
+            let address = |i: usize| first + (i * BTREE_NODE_SIZE) as u64;
+            let mut parents = Vec::new();
+            let mut left_key = 0_u64;
+            for (i, node) in nodes.iter().enumerate() {
+                let mut bytes = b"TREE".to_vec();
+                bytes.extend([0, level]);
+                bytes.extend((node.len() as u16).to_le_bytes());
+                let left = if i > 0 { address(i - 1) } else { UNDEFINED };
+                let right = if i + 1 < nodes.len() {
+                    address(i + 1)
+                } else {
+                    UNDEFINED
+                };
+                bytes.extend(left.to_le_bytes());
+                bytes.extend(right.to_le_bytes());
+                bytes.extend(left_key.to_le_bytes());
+                for &(child, key) in node.iter() {
+                    bytes.extend(child.to_le_bytes());
+                    bytes.extend(key.to_le_bytes());
+                }
+                bytes.resize(BTREE_NODE_SIZE, 0);
+                let key = node.last().map_or(0, |&(_, key)| key);
+                parents.push((self.append(&bytes)?, key));
+                left_key = key;
+            }
+            if parents.len() == 1 {
+                break parents[0].0;
+            }
+            children = parents;
+            level += 1;
+        };
+
+        let mut symbol_table = btree.to_le_bytes().to_vec();
+        symbol_table.extend(heap.to_le_bytes());
+        let mut messages = vec![(SYMBOL_TABLE, symbol_table)];
+        messages.extend(attributes.iter().map(Attribute::message));
+        let header = self.append(&object_header(&messages))?;
+        Ok(GroupAddresses {
+            header,
+            btree,
+            heap,
+        })
+    }
+
+    /// Write the root group and point the superblock at it
+    fn write_root(&mut self) -> io::Result<()> {
+        let attributes = std::mem::take(&mut self.root_attributes);
+        let root = self.write_group(self.snapshots.clone(), &attributes)?;
+
+        let mut superblock = SIGNATURE.to_vec();
+        // Versions of the superblock, free space, root entry and shared headers
+        superblock.extend([0, 0, 0, 0, 0]);
+        // Sizes of offsets and lengths
+        superblock.extend([8, 8, 0]);
+        superblock.extend((GROUP_LEAF_K as u16).to_le_bytes());
+        superblock.extend((GROUP_INTERNAL_K as u16).to_le_bytes());
+        superblock.extend(0_u32.to_le_bytes());
+        // Base, free space, end of file and driver information addresses
+        superblock.extend(0_u64.to_le_bytes());
+        superblock.extend(UNDEFINED.to_le_bytes());
+        superblock.extend(self.end.to_le_bytes());
+        superblock.extend(UNDEFINED.to_le_bytes());
+        symbol_table_entry(&mut superblock, 0, root.header, Some(root));
+        debug_assert_eq!(superblock.len() as u64, SUPERBLOCK_SIZE);
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&superblock)?;
+        self.writer.seek(SeekFrom::Start(self.end))?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> SnapshotWriter for Hdf5Writer<W> {
+    fn write_snapshot(&mut self, step: usize, time: f64, bodies: &[Body]) -> io::Result<()> {
+        assert!(!self.finished, "Snapshot written after finishing");
+        let n = bodies.len() as u64;
+        let vectors = |vector: fn(&Body) -> [f32; 3]| -> Vec<u8> {
+            bodies
+                .iter()
+                .flat_map(vector)
+                .flat_map(f32::to_le_bytes)
+                .collect()
+        };
+        let scalars = |scalar: fn(&Body) -> f32| -> Vec<u8> {
+            bodies
+                .iter()
+                .map(scalar)
+                .flat_map(f32::to_le_bytes)
+                .collect()
+        };
+        let datasets = [
+            ("position", vec![n, 3], vectors(|body| body.position)),
+            ("velocity", vec![n, 3], vectors(|body| body.velocity)),
+            ("mass", vec![n], scalars(|body| body.mass)),
+            ("mu", vec![n], scalars(|body| body.mu)),
+        ];
+        let mut links = Vec::new();
+        for (name, dimensions, data) in datasets {
+            links.push(Link {
+                name: name.to_string(),
+                header: self.write_dataset(Datatype::F32, &dimensions, &data)?,
+                group: None,
+            });
+        }
+        let attributes = [
+            Attribute::u64("step", step as u64),
+            Attribute::f64("time", time),
+        ];
+        let group = self.write_group(links, &attributes)?;
+        self.snapshots.push(Link {
+            name: format!("snapshot_{:06}", self.snapshots.len()),
+            header: group.header,
+            group: Some(group),
+        });
+        Ok(())
+    }
+
+    fn snapshots(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.finished = true;
+            self.write_root()?;
+        }
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::Serialize;
+use serde_json::json;
+
+use super::SnapshotWriter;
+use crate::manifest::RunManifest;
+use crate::structures::Body;
+
+/// Borrowed so the fields keep their single precision in the JSON
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    step: usize,
+    time: f64,
+    bodies: Vec<BodyState<'a>>,
+}
+
+#[derive(Serialize)]
+struct BodyState<'a> {
+    position: &'a [f32; 3],
+    velocity: &'a [f32; 3],
+    mass: f32,
+    mu: f32,
+}
+
+/// One JSON object per line and snapshot, with the step, the time and the
+/// bodies by index, after a line with the manifest of the run as `manifest`. Every snapshot is flushed as it's written, so that tools
+/// reading from a pipe see it straight away.
+pub struct JsonLinesWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    snapshots: usize,
+}
+
+impl JsonLinesWriter {
+    pub fn create(path: &Path, manifest: &RunManifest) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), manifest)
+    }
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    /// Write the line with the manifest to `writer`
+    pub fn new(mut writer: W, manifest: &RunManifest) -> io::Result<Self> {
+        serde_json::to_writer(&mut writer, &json!({ "manifest": manifest }))?;
+        writeln!(writer)?;
+        Ok(Self {
+            writer,
+            snapshots: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> SnapshotWriter for JsonLinesWriter<W> {
+    fn write_snapshot(&mut self, step: usize, time: f64, bodies: &[Body]) -> io::Result<()> {
+        let snapshot = Snapshot {
+            step,
+            time,
+            bodies: bodies
+                .iter()
+                .map(|body| BodyState {
+                    position: &body.position,
+                    velocity: &body.velocity,
+                    mass: body.mass,
+                    mu: body.mu,
+                })
+                .collect(),
+        };
+        serde_json::to_writer(&mut self.writer, &snapshot)?;
+        writeln!(self.writer)?;
+        self.writer.flush()?;
+        self.snapshots += 1;
+        Ok(())
+    }
+
+    fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -1,29 +1,455 @@
 use core::sync::atomic::Ordering;
 use std::{
-    mem::size_of,
-    sync::{atomic::AtomicBool, Arc},
+    collections::{hash_map::DefaultHasher, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    io,
+    mem::{self, discriminant, size_of, size_of_val},
+    num::NonZeroU64,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{atomic::AtomicBool, mpsc, Arc},
+    time::{Duration, Instant},
 };
 
 use wgpu::{
     self, Backends, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingType, BufferBindingType, BufferDescriptor, BufferSlice,
-    BufferUsages, CommandEncoderDescriptor, ComputePassDescriptor, ComputePipelineDescriptor,
-    DeviceDescriptor, Features, Instance, Limits, Maintain, MapMode, PipelineLayoutDescriptor,
-    PowerPreference, RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferBinding, BufferBindingType,
+    BufferDescriptor, BufferSlice, BufferUsages, CommandEncoderDescriptor, ComputePassDescriptor,
+    DeviceDescriptor, Features, Instance, Maintain, MapMode, PipelineLayoutDescriptor,
+    PowerPreference, PushConstantRange, RequestAdapterOptions, ShaderStages, Surface,
 };
 
-use crate::structures::{Body, DynamicConfig, StaticConfig};
+use crate::cancel::CancellationToken;
+use crate::chaos::{self, ChaosIndicators};
+use crate::checkpoint::Checkpoint;
+use crate::ensemble::{self, Ensemble};
+use crate::ephemeris::Ephemeris;
+use crate::epoch::Epoch;
+use crate::error::{scoped, ParabodyError};
+use crate::events::{EventLog, EventPredicate, StepCrossing};
+use crate::kernels::{KernelRegistry, DYNAMICS_MODULE, GATHER_MODULE, NEIGHBORS_MODULE};
+use crate::limits::{fit_static_config, supports_workgroup_size};
+use crate::maneuver::ManeuverSchedule;
+use crate::mirror::HostMirror;
+use crate::neighbors::{self, NeighborSearch};
+use crate::pm;
+use crate::profile::{PipelineStats, Profiler, MAX_TIMESTAMPS};
+use crate::progress::{EtaEstimator, Progress, ProgressCallback, ETA_WINDOW};
+use crate::structures::{
+    Body, BodyField, BodyProperties, DynamicConfig, EphemerisState, EventRecord, ExternalPotential,
+    ForceLaw, ForceSolver, GasState, Neighbor, NeighborGrid, NeighborQuery, PostNewtonian, Species,
+    StaticConfig, StepParams, System, Tangent, ThrustState, MAX_EPHEMERIS_BODIES,
+    MAX_EVENT_RECORDS, MAX_NEIGHBORS, MAX_POST_NEWTONIAN_BODIES, MAX_SPECIES,
+};
+use crate::thrust::{Thrust, ThrustProfile};
+use crate::watch::ShaderWatch;
+
+/// Fixed-point scale of the particle-mesh deposit for a total mass of one,
+/// leaving headroom below `i32::MAX` for rounding
+const PM_MASS_SCALE: f32 = (1 << 30) as f32;
+/// Steps encoded into one command buffer unless changed with `set_steps_per_submit`
+pub const DEFAULT_STEPS_PER_SUBMIT: usize = 256;
+/// Snapshots `run_with_snapshots` keeps in flight, each in a staging buffer of its own
+pub const READBACK_BUFFERS: usize = 2;
+/// Kernels of other workgroup sizes or shaders a pipeline keeps compiled, so that
+/// `autotune` and hot reloads switching back to them don't compile them again
+const KERNEL_CACHE_SIZE: usize = 8;
+/// Workgroup sizes tried by `autotune`, those the adapter can't run are skipped
+pub const AUTOTUNE_WORKGROUP_SIZES: [u32; 6] = [32, 64, 128, 256, 512, 1024];
+/// Steps timed per candidate by `autotune`, after as many warm-up steps
+pub const AUTOTUNE_STEPS: usize = 16;
+/// Bodies in the synthetic workload of `autotune` when none have been written
+pub const AUTOTUNE_BODIES: u32 = 4096;
+/// Written past the last body of each body buffer by `set_slack_check`. They
+/// differ between the buffers so that copies show, and a step changes them.
+const SENTINELS: [Body; 2] = [
+    Body {
+        position: [-1e30; 3],
+        mass: -1.0,
+        velocity: [1e30; 3],
+        mu: 0.0,
+    },
+    Body {
+        position: [-1e30; 3],
+        mass: -2.0,
+        velocity: [1e30; 3],
+        mu: 0.0,
+    },
+];
+
 pub struct Pipeline {
+    /// Shared with the pipeline replacing this one after a lost device
+    adapter: Arc<wgpu::Adapter>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config_bindgroup_layout: wgpu::BindGroupLayout,
     body_bindgroup_layout: wgpu::BindGroupLayout,
-    pipeline: wgpu::ComputePipeline,
+    grid_bindgroup_layout: Option<wgpu::BindGroupLayout>,
+    gather_bindgroup_layout: wgpu::BindGroupLayout,
+    /// Of `search_neighbors`, created on first use as they bind more storage
+    /// buffers than some adapters have
+    search_layouts: Option<(wgpu::BindGroupLayout, wgpu::PipelineLayout)>,
+    pipeline_layouts: PipelineLayouts,
+    /// The shader templates and pass graph the kernels are compiled from
+    shader_src: String,
+    /// See `PipelineBuilder::module`
+    modules: Vec<(String, String)>,
+    /// WGSL rendered into the template, see `PipelineBuilder::custom_force`
+    custom_force: Option<String>,
+    /// Rendered into the template, see `PipelineBuilder::events`
+    event_predicates: Vec<EventPredicate>,
+    base_pass_graph: PassGraph,
+    kernels: Kernels,
+    /// Identifies the shaders and static configuration `kernels` were compiled from
+    kernel_key: u64,
+    /// Kernels switched away from, most recent first, see `replace_kernels`
+    kernel_cache: VecDeque<(u64, Kernels)>,
+    adapter_info: wgpu::AdapterInfo,
     config_buffer: wgpu::Buffer,
     body_buffers: [wgpu::Buffer; 2],
+    acceleration_buffer: wgpu::Buffer,
+    properties_buffer: wgpu::Buffer,
+    gas_buffer: wgpu::Buffer,
+    /// Single fields of the bodies for `read_field`, and the buffer they're read back through
+    gather_buffer: wgpu::Buffer,
+    gather_staging_buffer: wgpu::Buffer,
+    /// Staging buffers of `run_with_snapshots`, created on first use
+    readback_buffers: Vec<wgpu::Buffer>,
+    /// Counts, offsets and sorted indices of the neighbor grid
+    neighbor_buffers: Option<[wgpu::Buffer; 3]>,
+    /// Density and the two complex grids of the particle-mesh solver
+    grid_buffers: Option<[wgpu::Buffer; 3]>,
+    /// State and samples of the ephemeris
+    ephemeris_buffers: Option<[wgpu::Buffer; 2]>,
+    /// The ephemeris last written, whose state is kept in step with the time
+    ephemeris: Option<(Ephemeris, EphemerisState)>,
+    /// Every body's thrust and the samples of the tables
+    thrust_buffers: Option<[wgpu::Buffer; 2]>,
+    /// The thrust last written, with the states whose table clocks are kept in
+    /// step with the time
+    thrust: Option<(Vec<Thrust>, Vec<ThrustState>)>,
+    /// Partial and total sums of the center of mass
+    com_buffer: Option<wgpu::Buffer>,
+    /// Steps between shifts into the frame of the center of mass, and the steps
+    /// since the last
+    com_correction: Option<(usize, usize)>,
+    /// Tangent vectors of the bodies, see `read_chaos_indicators`
+    tangent_buffer: Option<wgpu::Buffer>,
+    /// Systems of the ensemble
+    system_buffer: Option<wgpu::Buffer>,
+    /// The systems last written, empty if the bodies form one system
+    systems: Vec<System>,
+    /// Values of the predicates before the next step and those of
+    /// `evaluate_events`, and the event log
+    event_buffers: Option<[wgpu::Buffer; 2]>,
+    /// Simulated time at the start of every step logged and at the end of the last
+    event_times: Vec<f64>,
+    /// Whether the values before the next step are those of the latest state
+    events_primed: bool,
+    /// Whether steps go unlogged, see `evaluate_accelerations`
+    events_paused: bool,
+    /// Impulses applied between submissions as the simulated time passes them
+    maneuvers: ManeuverSchedule,
     active_source: SourceBuffer,
+    time_direction: TimeDirection,
+    /// Simulated time advanced by the steps so far
+    time: f64,
+    /// Epoch of the simulated time zero and the seconds in a unit of time
+    epoch: Option<(Epoch, f64)>,
+    steps_per_submit: usize,
+    progress: Option<(EtaEstimator, ProgressCallback)>,
+    profiler: Option<Profiler>,
+    /// Whether submissions check the slack past the last body, see `set_slack_check`
+    slack_check: bool,
+    /// Whether `run` recovers from a lost device, see `set_resilient`
+    resilient: bool,
+    /// Shader files reloaded between submissions, see `set_shader_watch`
+    shader_watch: Option<ShaderWatch>,
     static_config: StaticConfig,
     dynamic_config: DynamicConfig,
+    mirror: Option<HostMirror>,
+}
+
+/// Layouts of the kernels. Where push constants are supported, the kernels over
+/// the bodies take the step parameters as push constants. The others don't, as
+/// the GL backend can only push to kernels which read them.
+struct PipelineLayouts {
+    threads: wgpu::PipelineLayout,
+    bodies: Option<wgpu::PipelineLayout>,
+    /// Of the kernels of `gather.wgsl`
+    gather: wgpu::PipelineLayout,
+}
+
+impl PipelineLayouts {
+    fn get(&self, domain: Domain) -> &wgpu::PipelineLayout {
+        match domain {
+            Domain::Bodies => self.bodies.as_ref().unwrap_or(&self.threads),
+            Domain::Threads(_) => &self.threads,
+        }
+    }
+}
+
+/// What the kernels are rendered from besides the static configuration
+struct ShaderSources<'a> {
+    shader_src: &'a str,
+    /// See `PipelineBuilder::module`
+    modules: &'a [(String, String)],
+    /// See `PipelineBuilder::custom_force`
+    custom_force: Option<&'a str>,
+    /// See `PipelineBuilder::events`
+    events: &'a [EventPredicate],
+}
+
+impl ShaderSources<'_> {
+    /// Hash of the sources and the static configuration they're rendered with
+    fn key(&self, static_config: &StaticConfig) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.shader_src.hash(&mut hasher);
+        self.modules.hash(&mut hasher);
+        self.custom_force.hash(&mut hasher);
+        self.events.hash(&mut hasher);
+        // Some fields are floats, which don't implement `Hash`
+        serde_json::to_string(static_config)
+            .expect("Could not serialize the static configuration")
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// The kernels of a static configuration and the passes dispatching them
+struct Kernels {
+    registry: KernelRegistry,
+    pass_graph: PassGraph,
+    /// Indices into `registry` of the kernel of every pass
+    passes: Vec<usize>,
+    reversed_pass_graph: PassGraph,
+    reversed_passes: Vec<usize>,
+    potential_pass: usize,
+    /// Sum the center of mass and shift the bodies into its frame
+    com_passes: Option<[usize; 2]>,
+    /// Advance the tangent vectors
+    variational_pass: Option<usize>,
+    /// Log the events of a step and count it, evaluate the predicates of the
+    /// latest state as the values before the next step and for `evaluate_events`
+    event_passes: Option<[usize; 4]>,
+    /// Copy out a field of the bodies, indexed like `BodyField::ALL`
+    gather: Vec<usize>,
+}
+
+impl Kernels {
+    /// Render the shader and the extra modules with the static configuration
+    /// and compile the passes of `pass_graph` extended for it, forwards and reversed
+    fn compile(
+        device: &wgpu::Device,
+        layouts: &PipelineLayouts,
+        sources: &ShaderSources,
+        pass_graph: &PassGraph,
+        static_config: &StaticConfig,
+    ) -> Result<Self, ParabodyError> {
+        let ShaderSources {
+            shader_src,
+            modules,
+            custom_force,
+            events,
+        } = *sources;
+        let workgroup_size = static_config.workgroup_size;
+        let grid_size = match static_config.force_solver {
+            ForceSolver::Direct => None,
+            ForceSolver::PM { grid_size } => Some(grid_size),
+        };
+
+        // Render the shaders with their static configuration
+        let mut templates = vec![
+            (DYNAMICS_MODULE, shader_src),
+            (GATHER_MODULE, include_str!("../shaders/gather.wgsl")),
+            (NEIGHBORS_MODULE, include_str!("../shaders/neighbors.wgsl")),
+        ];
+        templates.extend(
+            modules
+                .iter()
+                .map(|(name, template)| (name.as_str(), template.as_str())),
+        );
+        let mut context = tera::Context::new();
+        context.insert("static_config", static_config);
+        context.insert("workgroup_size", &workgroup_size);
+        context.insert("push_constants", &layouts.bodies.is_some());
+        context.insert("custom_force", &custom_force.unwrap_or_default());
+        context.insert("events", events);
+        context.insert("max_neighbors", &MAX_NEIGHBORS);
+        context.insert(
+            "fft_passes",
+            &grid_size.map_or(Vec::new(), pm::all_fft_passes),
+        );
+        let mut registry = KernelRegistry::render(device, &templates, &context, custom_force)?;
+
+        let potential_pass = registry.kernel(
+            device,
+            DYNAMICS_MODULE,
+            "potential",
+            layouts.get(Domain::Bodies),
+        )?;
+        let com_passes = static_config
+            .com_correction
+            .then(|| {
+                Ok::<_, ParabodyError>([
+                    registry.kernel(
+                        device,
+                        DYNAMICS_MODULE,
+                        "com_reduce",
+                        layouts.get(Domain::Threads(workgroup_size)),
+                    )?,
+                    registry.kernel(
+                        device,
+                        DYNAMICS_MODULE,
+                        "com_shift",
+                        layouts.get(Domain::Bodies),
+                    )?,
+                ])
+            })
+            .transpose()?;
+        let variational_pass = static_config
+            .variational
+            .then(|| {
+                registry.kernel(
+                    device,
+                    DYNAMICS_MODULE,
+                    "variational",
+                    layouts.get(Domain::Bodies),
+                )
+            })
+            .transpose()?;
+        let event_passes = (!events.is_empty())
+            .then(|| {
+                Ok::<_, ParabodyError>([
+                    registry.kernel(
+                        device,
+                        DYNAMICS_MODULE,
+                        "events",
+                        layouts.get(Domain::Bodies),
+                    )?,
+                    registry.kernel(
+                        device,
+                        DYNAMICS_MODULE,
+                        "events_tick",
+                        layouts.get(Domain::Threads(1)),
+                    )?,
+                    registry.kernel(
+                        device,
+                        DYNAMICS_MODULE,
+                        "events_prime",
+                        layouts.get(Domain::Bodies),
+                    )?,
+                    registry.kernel(
+                        device,
+                        DYNAMICS_MODULE,
+                        "events_probe",
+                        layouts.get(Domain::Bodies),
+                    )?,
+                ])
+            })
+            .transpose()?;
+        let gather = BodyField::ALL
+            .iter()
+            .map(|field| {
+                registry.kernel(device, GATHER_MODULE, field.entry_point(), &layouts.gather)
+            })
+            .collect::<Result<_, _>>()?;
+        // Filled in by `select`
+        let mut kernels = Self {
+            registry,
+            pass_graph: pass_graph.clone(),
+            passes: Vec::new(),
+            reversed_pass_graph: pass_graph.clone(),
+            reversed_passes: Vec::new(),
+            potential_pass,
+            com_passes,
+            variational_pass,
+            event_passes,
+            gather,
+        };
+        kernels.select(device, layouts, pass_graph, static_config)?;
+        Ok(kernels)
+    }
+
+    /// Dispatch the passes of `pass_graph` extended for the static configuration,
+    /// compiling the kernels none of the passes so far dispatched
+    fn select(
+        &mut self,
+        device: &wgpu::Device,
+        layouts: &PipelineLayouts,
+        pass_graph: &PassGraph,
+        static_config: &StaticConfig,
+    ) -> Result<(), ParabodyError> {
+        let pass_graph = pass_graph
+            .clone()
+            .with_solver(static_config.force_solver)
+            .with_neighbor_grid(static_config.neighbor_grid, static_config)
+            .with_hydrodynamics(static_config.hydrodynamics.is_some())
+            .with_ephemeris(static_config.ephemeris)
+            .with_thrust(static_config.thrust);
+        let reversed_pass_graph = pass_graph.reversed();
+        let passes = self.sequence(device, layouts, &pass_graph)?;
+        let reversed_passes = self.sequence(device, layouts, &reversed_pass_graph)?;
+        self.pass_graph = pass_graph;
+        self.passes = passes;
+        self.reversed_pass_graph = reversed_pass_graph;
+        self.reversed_passes = reversed_passes;
+        Ok(())
+    }
+
+    /// The kernels of the passes of `pass_graph`, in order
+    fn sequence(
+        &mut self,
+        device: &wgpu::Device,
+        layouts: &PipelineLayouts,
+        pass_graph: &PassGraph,
+    ) -> Result<Vec<usize>, ParabodyError> {
+        pass_graph
+            .passes()
+            .iter()
+            .map(|pass| {
+                self.registry.kernel(
+                    device,
+                    &pass.module,
+                    &pass.entry_point,
+                    layouts.get(pass.domain),
+                )
+            })
+            .collect()
+    }
+}
+
+/// A snapshot of `run_with_snapshots` copied into a readback buffer
+struct PendingSnapshot {
+    buffer: usize,
+    completed: usize,
+    time: f64,
+    /// Set once the buffer is mapped
+    mapped: Arc<AtomicBool>,
+}
+
+/// The bind groups of one submission
+struct BindGroups {
+    config: wgpu::BindGroup,
+    /// Bodies with buffer A as the source
+    active_a: wgpu::BindGroup,
+    active_b: wgpu::BindGroup,
+    grids: Option<[wgpu::BindGroup; 2]>,
+}
+
+impl BindGroups {
+    /// The groups of a dispatch reading the bodies from `source`, after the grids
+    /// were swapped `grid_swaps` times
+    fn select(&self, source: SourceBuffer, grid_swaps: usize) -> [Option<&wgpu::BindGroup>; 3] {
+        let bodies = match source {
+            SourceBuffer::A => &self.active_a,
+            SourceBuffer::B => &self.active_b,
+        };
+        let grid = self.grids.as_ref().map(|grids| &grids[grid_swaps % 2]);
+        [Some(&self.config), Some(bodies), grid]
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -41,52 +467,503 @@ impl SourceBuffer {
     }
 }
 
+/// Which way the steps advance time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeDirection {
+    Forward,
+    Backward,
+}
+
+impl TimeDirection {
+    pub fn sign(self) -> f32 {
+        match self {
+            TimeDirection::Forward => 1.0,
+            TimeDirection::Backward => -1.0,
+        }
+    }
+}
+
+/// The number of threads a pass is dispatched over
+#[derive(Debug, Clone, Copy)]
+pub enum Domain {
+    /// One thread per body
+    Bodies,
+    /// A fixed number of threads, such as one per grid cell
+    Threads(u32),
+}
+
+/// A single kernel dispatch
+#[derive(Debug, Clone)]
+pub struct Pass {
+    /// Name of the shader module the entry point is in, see `PipelineBuilder::module`
+    pub module: String,
+    pub entry_point: String,
+    pub domain: Domain,
+    /// Whether the pass writes the particle-mesh grid, which swaps the grid buffers
+    pub swaps_grid: bool,
+    /// Whether the passes after this one read its output as their input, on
+    /// top of the swap at the end of every step
+    pub swaps_bodies: bool,
+}
+
+impl Pass {
+    pub fn bodies(entry_point: &str) -> Self {
+        Self {
+            module: DYNAMICS_MODULE.to_string(),
+            entry_point: entry_point.to_string(),
+            domain: Domain::Bodies,
+            swaps_grid: false,
+            swaps_bodies: false,
+        }
+    }
+
+    /// Take the entry point from the module registered as `module`
+    pub fn in_module(mut self, module: &str) -> Self {
+        self.module = module.to_string();
+        self
+    }
+}
+
+/// The ordered set of shader entry points dispatched for every step
+#[derive(Debug, Clone)]
+pub struct PassGraph {
+    passes: Vec<Pass>,
+}
+
+impl PassGraph {
+    pub fn new(entry_points: Vec<&'static str>) -> Self {
+        Self::from_passes(entry_points.into_iter().map(Pass::bodies).collect())
+    }
+
+    pub fn from_passes(passes: Vec<Pass>) -> Self {
+        assert!(!passes.is_empty());
+        Self { passes }
+    }
+
+    /// A single kernel which accumulates forces and integrates in one pass
+    pub fn fused() -> Self {
+        Self::new(vec!["main"])
+    }
+
+    /// Separate force, kick and drift kernels, trading extra dispatches per step
+    /// for lower register pressure in each kernel
+    pub fn split() -> Self {
+        Self::new(vec!["accelerate", "kick", "drift"])
+    }
+
+    /// Replace the direct-sum force pass with the passes of `solver`
+    pub fn with_solver(self, solver: ForceSolver) -> Self {
+        let grid_size = match solver {
+            ForceSolver::Direct => return self,
+            ForceSolver::PM { grid_size } => grid_size,
+        };
+        assert!(
+            self.passes.iter().all(|pass| pass.entry_point != "main"),
+            "The particle-mesh solver requires the split pass graph"
+        );
+        let passes = self
+            .passes
+            .into_iter()
+            .flat_map(|pass| match pass.entry_point.as_str() {
+                "accelerate" => pm::passes(grid_size),
+                _ => vec![pass],
+            })
+            .collect();
+        Self::from_passes(passes)
+    }
+
+    /// Build the neighbor grid before the first force pass
+    pub fn with_neighbor_grid(
+        mut self,
+        grid: Option<NeighborGrid>,
+        static_config: &StaticConfig,
+    ) -> Self {
+        let grid = match grid {
+            Some(grid) => grid,
+            None => return self,
+        };
+        let position = self
+            .passes
+            .iter()
+            .position(|pass| matches!(pass.entry_point.as_str(), "main" | "accelerate"));
+        if let Some(position) = position {
+            self.passes.splice(
+                position..position,
+                neighbors::passes(
+                    grid.table_size,
+                    static_config.workgroup_size,
+                    static_config.deterministic,
+                ),
+            );
+        }
+        self
+    }
+
+    /// Compute the gas densities before the first force pass
+    pub fn with_hydrodynamics(mut self, enabled: bool) -> Self {
+        let position = self
+            .passes
+            .iter()
+            .position(|pass| matches!(pass.entry_point.as_str(), "main" | "accelerate"));
+        if let (true, Some(position)) = (enabled, position) {
+            self.passes.insert(position, Pass::bodies("sph_density"));
+        }
+        self
+    }
+
+    /// Place the prescribed bodies at the end of every step
+    pub fn with_ephemeris(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.passes
+                .extend([Pass::bodies("ephemeris"), Pass::bodies("ephemeris_tick")]);
+        }
+        self
+    }
+
+    /// Advance the clocks of the thrust tables at the end of every step
+    pub fn with_thrust(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.passes.push(Pass::bodies("thrust_tick"));
+        }
+        self
+    }
+
+    /// The inverse of a kick-drift step for integrating backwards in time. Run
+    /// with the negated step size, drifting first and kicking last undoes the
+    /// forward step exactly up to rounding, also for the symplectic split graph.
+    pub fn reversed(&self) -> Self {
+        let mut passes = vec![Pass {
+            swaps_bodies: true,
+            ..Pass::bodies("drift_first")
+        }];
+        // Turn the thrust tables back before the forces, which read them at the
+        // start of the forward step
+        passes.extend(
+            self.passes
+                .iter()
+                .filter(|pass| pass.entry_point == "thrust_tick")
+                .cloned(),
+        );
+        for pass in &self.passes {
+            match pass.entry_point.as_str() {
+                "drift" | "thrust_tick" => (),
+                "main" => passes.extend([Pass::bodies("accelerate"), Pass::bodies("kick")]),
+                _ => passes.push(pass.clone()),
+            }
+        }
+        Self::from_passes(passes)
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    pub fn entry_points(&self) -> Vec<&str> {
+        self.passes
+            .iter()
+            .map(|pass| pass.entry_point.as_str())
+            .collect()
+    }
+}
+
+/// How the adapter a pipeline runs on is picked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdapterSelection {
+    /// Whichever adapter wgpu prefers for the power preference
+    Preference(PowerPreference),
+    /// Position in `Pipeline::enumerate_adapters`
+    Index(usize),
+    /// The first adapter whose name contains the string, ignoring case
+    Name(String),
+}
+
+impl AdapterSelection {
+    /// An index if `arg` is a number, otherwise a name
+    pub fn parse(arg: &str) -> Self {
+        match arg.parse() {
+            Ok(index) => AdapterSelection::Index(index),
+            Err(_) => AdapterSelection::Name(arg.to_string()),
+        }
+    }
+
+    /// Whether the adapter at `index` is selected, always for a preference
+    pub fn matches(&self, index: usize, info: &wgpu::AdapterInfo) -> bool {
+        match self {
+            AdapterSelection::Preference(_) => true,
+            AdapterSelection::Index(selected) => index == *selected,
+            AdapterSelection::Name(name) => info.name.to_lowercase().contains(&name.to_lowercase()),
+        }
+    }
+}
+
+impl fmt::Display for AdapterSelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AdapterSelection::Preference(preference) => write!(f, "{:?}", preference),
+            AdapterSelection::Index(index) => write!(f, "adapter {}", index),
+            AdapterSelection::Name(name) => write!(f, "an adapter named like \"{}\"", name),
+        }
+    }
+}
+
+/// An adapter as listed by `Pipeline::enumerate_adapters`
+#[derive(Debug, Clone)]
+pub struct AdapterDescription {
+    pub index: usize,
+    /// Name, backend and device type
+    pub info: wgpu::AdapterInfo,
+    pub limits: wgpu::Limits,
+    pub features: Features,
+    pub downlevel: wgpu::DownlevelCapabilities,
+}
+
+/// Configures a pipeline before creating it, see `Pipeline::builder`
+pub struct PipelineBuilder<'a> {
+    shader_src: &'static str,
+    modules: Vec<(String, String)>,
+    custom_force: Option<String>,
+    events: Vec<EventPredicate>,
+    static_config: StaticConfig,
+    pass_graph: PassGraph,
+    adapter: AdapterSelection,
+    surface: Option<(&'a Instance, &'a Surface)>,
+}
+
+impl<'a> PipelineBuilder<'a> {
+    pub fn pass_graph(mut self, pass_graph: PassGraph) -> Self {
+        self.pass_graph = pass_graph;
+        self
+    }
+
+    /// Add the acceleration returned by `fn custom_acceleration(idx: u32) -> vec3<f32>`,
+    /// defined in the WGSL of `src`, to that of every body, for force laws the
+    /// crate doesn't have. The snippet is rendered into the shader after the
+    /// built-in forces and can read what they read, such as `input`, `config`,
+    /// `dt()` and `num_bodies()`. `build` fails with the line of the snippet if
+    /// it doesn't compile.
+    pub fn custom_force(mut self, src: impl Into<String>) -> Self {
+        self.custom_force = Some(src.into());
+        self
+    }
+
+    /// Log the events of `predicates` after every step, for
+    /// `events::EventMonitor` to find their times. The expressions are rendered
+    /// into the shader like `custom_force`, indexed by their order.
+    pub fn events(mut self, predicates: Vec<EventPredicate>) -> Self {
+        self.events = predicates;
+        self
+    }
+
+    /// Render `template` with the same context as the shader and register it as
+    /// the module `name`, for passes taking their entry points from it with
+    /// `Pass::in_module`. Its kernels are compiled when a pass graph first
+    /// dispatches them.
+    pub fn module(mut self, name: &str, template: impl Into<String>) -> Self {
+        assert!(
+            ![DYNAMICS_MODULE, GATHER_MODULE, NEIGHBORS_MODULE].contains(&name),
+            "The module name {} is taken by the pipeline",
+            name
+        );
+        self.modules.retain(|(module, _)| module != name);
+        self.modules.push((name.to_string(), template.into()));
+        self
+    }
+
+    /// Let wgpu pick the adapter, prefer `PowerPreference::LowPower` to stay on an
+    /// integrated GPU where there is one
+    pub fn power_preference(self, power_preference: PowerPreference) -> Self {
+        self.adapter(AdapterSelection::Preference(power_preference))
+    }
+
+    pub fn adapter(mut self, adapter: AdapterSelection) -> Self {
+        self.adapter = adapter;
+        self
+    }
+
+    /// Run on an adapter of `instance` which can present to `surface`, so that
+    /// the body buffers can be drawn without a readback
+    pub fn surface<'b>(self, instance: &'b Instance, surface: &'b Surface) -> PipelineBuilder<'b> {
+        PipelineBuilder {
+            shader_src: self.shader_src,
+            modules: self.modules,
+            custom_force: self.custom_force,
+            events: self.events,
+            static_config: self.static_config,
+            pass_graph: self.pass_graph,
+            adapter: self.adapter,
+            surface: Some((instance, surface)),
+        }
+    }
+
+    /// Panics if no adapter matches the selection
+    pub async fn build(self) -> Result<Pipeline, ParabodyError> {
+        let owned_instance;
+        let (instance, surface) = match self.surface {
+            Some((instance, surface)) => (instance, Some(surface)),
+            None => {
+                owned_instance = Instance::new(Backends::all());
+                (&owned_instance, None)
+            }
+        };
+        let adapter = match &self.adapter {
+            AdapterSelection::Preference(power_preference) => instance
+                .request_adapter(&RequestAdapterOptions {
+                    power_preference: *power_preference,
+                    force_fallback_adapter: false,
+                    compatible_surface: surface,
+                })
+                .await
+                .expect("Could not get adapter"),
+            selection => {
+                let adapter = instance
+                    .enumerate_adapters(Backends::all())
+                    .enumerate()
+                    .find(|(index, adapter)| selection.matches(*index, &adapter.get_info()))
+                    .map(|(_, adapter)| adapter)
+                    .unwrap_or_else(|| panic!("Could not find {}", selection));
+                if let Some(surface) = surface {
+                    assert!(
+                        adapter.is_surface_supported(surface),
+                        "{} can't present to the window",
+                        adapter.get_info().name
+                    );
+                }
+                adapter
+            }
+        };
+        Pipeline::create_on_adapter(
+            Arc::new(adapter),
+            self.shader_src.to_string(),
+            self.modules,
+            self.custom_force,
+            self.events,
+            self.pass_graph,
+            self.static_config,
+        )
+        .await
+    }
+}
+
 impl Pipeline {
+    /// Build the pipeline on an adapter matching `power_preference`, prefer
+    /// `PowerPreference::LowPower` to stay on an integrated GPU where there is one
     pub async fn create(
         shader_src: &'static str,
-        entry_point: &'static str,
+        pass_graph: PassGraph,
         static_config: StaticConfig,
-    ) -> Self {
-        // Render the shader with its static configuration
-        let mut tera = tera::Tera::default();
-        tera.add_raw_template("shader", shader_src).unwrap();
-        let mut context = tera::Context::new();
-        context.insert("static_config", &static_config);
-        let shader = ShaderModuleDescriptor {
-            label: None,
-            source: ShaderSource::Wgsl(
-                tera.render("shader", &context)
-                    .expect("Failed to render shader from template")
-                    .into(),
-            ),
-        };
+        power_preference: PowerPreference,
+    ) -> Result<Self, ParabodyError> {
+        Self::builder(shader_src, static_config)
+            .pass_graph(pass_graph)
+            .power_preference(power_preference)
+            .build()
+            .await
+    }
 
-        // Create default config
-        let dynamic_config = DynamicConfig::default();
+    /// Like `create`, but on an adapter of `instance` which can present to
+    /// `surface`, so that the body buffers can be drawn without a readback
+    pub async fn create_for_surface(
+        instance: &Instance,
+        surface: Option<&Surface>,
+        shader_src: &'static str,
+        pass_graph: PassGraph,
+        static_config: StaticConfig,
+        power_preference: PowerPreference,
+    ) -> Result<Self, ParabodyError> {
+        let builder = Self::builder(shader_src, static_config)
+            .pass_graph(pass_graph)
+            .power_preference(power_preference);
+        match surface {
+            Some(surface) => builder.surface(instance, surface).build().await,
+            None => builder.build().await,
+        }
+    }
 
-        // Construct the pipeline
-        let instance = Instance::new(Backends::all());
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: None,
+    /// Start configuring a pipeline, by default with the split pass graph on the
+    /// adapter wgpu prefers for high performance
+    pub fn builder(
+        shader_src: &'static str,
+        static_config: StaticConfig,
+    ) -> PipelineBuilder<'static> {
+        PipelineBuilder {
+            shader_src,
+            modules: Vec::new(),
+            custom_force: None,
+            events: Vec::new(),
+            static_config,
+            pass_graph: PassGraph::split(),
+            adapter: AdapterSelection::Preference(PowerPreference::HighPerformance),
+            surface: None,
+        }
+    }
+
+    /// The adapters of all backends, in the order `AdapterSelection::Index` refers to
+    pub fn enumerate_adapters() -> Vec<AdapterDescription> {
+        Instance::new(Backends::all())
+            .enumerate_adapters(Backends::all())
+            .enumerate()
+            .map(|(index, adapter)| AdapterDescription {
+                index,
+                info: adapter.get_info(),
+                limits: adapter.limits(),
+                features: adapter.features(),
+                downlevel: adapter.get_downlevel_capabilities(),
             })
-            .await
-            .expect("Could not get adapter");
+            .collect()
+    }
+
+    async fn create_on_adapter(
+        adapter: Arc<wgpu::Adapter>,
+        shader_src: String,
+        modules: Vec<(String, String)>,
+        custom_force: Option<String>,
+        event_predicates: Vec<EventPredicate>,
+        pass_graph: PassGraph,
+        static_config: StaticConfig,
+    ) -> Result<Self, ParabodyError> {
+        // Construct the pipeline
+        let adapter_limits = adapter.limits();
+        let static_config = fit_static_config(
+            static_config,
+            &adapter_limits,
+            &adapter.get_downlevel_capabilities(),
+        )?;
+        let grid_size = match static_config.force_solver {
+            ForceSolver::Direct => None,
+            ForceSolver::PM { grid_size } => Some(grid_size),
+        };
+
+        // Create default config
+        let mut dynamic_config = DynamicConfig {
+            force_params: static_config.force_law.params(),
+            ..Default::default()
+        };
+        if let Some(potential) = static_config.external_potential {
+            dynamic_config.external_params = potential.params();
+        }
+        if let Some(radiation) = static_config.radiation_pressure {
+            dynamic_config.luminous_body = radiation.source;
+            dynamic_config.radiation_pressure = radiation.pressure;
+        }
+        if let Some(hydrodynamics) = static_config.hydrodynamics {
+            dynamic_config.hydro_params = hydrodynamics.params();
+        }
 
+        let push_constants = adapter.features().contains(Features::PUSH_CONSTANTS)
+            && adapter_limits.max_push_constant_size >= size_of::<StepParams>() as u32;
         let (device, queue) = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: Some("Compute device"),
-                    features: Features::empty(),
-                    limits: Limits::downlevel_defaults(),
+                    // Requested where available so that profiling can be enabled later
+                    features: adapter.features()
+                        & (Features::TIMESTAMP_QUERY | Features::PUSH_CONSTANTS),
+                    limits: adapter_limits,
                 },
                 None,
             )
             .await
             .expect("Could not acquire WebGPU device");
-        let shader = device.create_shader_module(shader);
         let config_bindgroup_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: None,
             entries: &[BindGroupLayoutEntry {
@@ -100,197 +977,2494 @@ impl Pipeline {
                 count: None,
             }],
         });
+        let mut body_entries = vec![
+            storage_entry(0, true),
+            storage_entry(1, false),
+            storage_entry(2, false),
+        ];
+        // Only bind the properties when they're read, so that adapters with
+        // fewer storage buffers can still run the basic configuration
+        if static_config.uses_properties() {
+            body_entries.push(storage_entry(3, true));
+        }
+        if static_config.neighbor_grid.is_some() {
+            body_entries.extend((4..7).map(|binding| storage_entry(binding, false)));
+        }
+        if static_config.hydrodynamics.is_some() {
+            body_entries.push(storage_entry(7, false));
+        }
+        if static_config.ephemeris {
+            body_entries.extend([storage_entry(8, false), storage_entry(9, true)]);
+        }
+        if static_config.thrust {
+            body_entries.extend([storage_entry(10, false), storage_entry(11, true)]);
+        }
+        if static_config.com_correction {
+            body_entries.push(storage_entry(12, false));
+        }
+        if static_config.variational {
+            body_entries.push(storage_entry(13, false));
+        }
+        if static_config.ensemble {
+            body_entries.push(storage_entry(14, true));
+        }
+        if !event_predicates.is_empty() {
+            body_entries.extend([storage_entry(15, false), storage_entry(16, false)]);
+        }
         let body_bindgroup_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: None,
-            entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::COMPUTE,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::COMPUTE,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Storage { read_only: false },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-            ],
-        });
-        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("Compute pipeline layout"),
-            bind_group_layouts: &[&config_bindgroup_layout, &body_bindgroup_layout],
-            ..Default::default()
+            entries: &body_entries,
         });
-        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
-            label: Some("Compute pipeline"),
-            module: &shader,
-            entry_point: entry_point,
-            layout: Some(&pipeline_layout),
+        let grid_bindgroup_layout = grid_size.map(|_| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Grid bind group layout"),
+                entries: &[
+                    storage_entry(0, false),
+                    storage_entry(1, true),
+                    storage_entry(2, false),
+                ],
+            })
         });
-        let config_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Config"),
-            size: size_of::<DynamicConfig>() as u64,
-            usage: BufferUsages::UNIFORM | BufferUsages::MAP_WRITE,
-            mapped_at_creation: false,
+        let gather_bindgroup_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Gather bind group layout"),
+            entries: &[storage_entry(0, true), storage_entry(1, false)],
         });
+        let mut bind_group_layouts = vec![&config_bindgroup_layout, &body_bindgroup_layout];
+        bind_group_layouts.extend(grid_bindgroup_layout.as_ref());
+        let pipeline_layouts = PipelineLayouts {
+            threads: device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Compute pipeline layout"),
+                bind_group_layouts: &bind_group_layouts,
+                ..Default::default()
+            }),
+            bodies: push_constants.then(|| {
+                device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Body pipeline layout"),
+                    bind_group_layouts: &bind_group_layouts,
+                    push_constant_ranges: &[PushConstantRange {
+                        stages: ShaderStages::COMPUTE,
+                        range: 0..size_of::<StepParams>() as u32,
+                    }],
+                })
+            }),
+            gather: device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Gather pipeline layout"),
+                bind_group_layouts: &[&gather_bindgroup_layout],
+                ..Default::default()
+            }),
+        };
+        let sources = ShaderSources {
+            shader_src: &shader_src,
+            modules: &modules,
+            custom_force: custom_force.as_deref(),
+            events: &event_predicates,
+        };
+        let kernels = Kernels::compile(
+            &device,
+            &pipeline_layouts,
+            &sources,
+            &pass_graph,
+            &static_config,
+        )?;
+        let kernel_key = sources.key(&static_config);
+        let config_buffer = create_buffer(
+            &device,
+            &BufferDescriptor {
+                label: Some("Config"),
+                size: size_of::<DynamicConfig>() as u64,
+                usage: BufferUsages::UNIFORM | BufferUsages::MAP_WRITE,
+                mapped_at_creation: false,
+            },
+        )?;
         let body_buffers = [
-            device.create_buffer(&BufferDescriptor {
-                label: Some("Buffer A"),
-                size: (static_config.max_bodies as usize * size_of::<Body>()) as u64,
+            create_buffer(
+                &device,
+                &BufferDescriptor {
+                    label: Some("Buffer A"),
+                    size: (static_config.max_bodies as usize * size_of::<Body>()) as u64,
+                    usage: BufferUsages::STORAGE
+                        | BufferUsages::VERTEX
+                        | BufferUsages::COPY_SRC
+                        | BufferUsages::MAP_READ
+                        | BufferUsages::MAP_WRITE,
+                    mapped_at_creation: false,
+                },
+            )?,
+            create_buffer(
+                &device,
+                &BufferDescriptor {
+                    label: Some("Buffer B"),
+                    size: (static_config.max_bodies as usize * size_of::<Body>()) as u64,
+                    usage: BufferUsages::STORAGE
+                        | BufferUsages::VERTEX
+                        | BufferUsages::COPY_SRC
+                        | BufferUsages::MAP_READ
+                        | BufferUsages::MAP_WRITE,
+                    mapped_at_creation: false,
+                },
+            )?,
+        ];
+        let acceleration_buffer = create_buffer(
+            &device,
+            &BufferDescriptor {
+                label: Some("Accelerations"),
+                size: (static_config.max_bodies as usize * size_of::<[f32; 4]>()) as u64,
+                usage: BufferUsages::STORAGE | BufferUsages::VERTEX | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            },
+        )?;
+        let properties_buffer = create_buffer(
+            &device,
+            &BufferDescriptor {
+                label: Some("Properties"),
+                size: (static_config.max_bodies as usize * size_of::<BodyProperties>()) as u64,
                 usage: BufferUsages::STORAGE | BufferUsages::MAP_READ | BufferUsages::MAP_WRITE,
                 mapped_at_creation: false,
-            }),
-            device.create_buffer(&BufferDescriptor {
-                label: Some("Buffer B"),
-                size: (static_config.max_bodies as usize * size_of::<Body>()) as u64,
+            },
+        )?;
+        let gas_buffer = create_buffer(
+            &device,
+            &BufferDescriptor {
+                label: Some("Gas"),
+                size: (static_config.max_bodies as usize * size_of::<GasState>()) as u64,
                 usage: BufferUsages::STORAGE | BufferUsages::MAP_READ | BufferUsages::MAP_WRITE,
                 mapped_at_creation: false,
-            }),
-        ];
+            },
+        )?;
+        // Sized for the largest field, positions or velocities
+        let gather_size = (static_config.max_bodies as usize * size_of::<[f32; 3]>()) as u64;
+        let gather_buffer = create_buffer(
+            &device,
+            &BufferDescriptor {
+                label: Some("Gather"),
+                size: gather_size,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            },
+        )?;
+        let gather_staging_buffer = create_buffer(
+            &device,
+            &BufferDescriptor {
+                label: Some("Gather staging"),
+                size: gather_size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            },
+        )?;
+        let neighbor_buffers = static_config
+            .neighbor_grid
+            .map(|grid| {
+                let neighbor_buffer = |label, len: u32| {
+                    create_buffer(
+                        &device,
+                        &BufferDescriptor {
+                            label: Some(label),
+                            size: (len as usize * size_of::<u32>()) as u64,
+                            usage: BufferUsages::STORAGE,
+                            mapped_at_creation: false,
+                        },
+                    )
+                };
+                Ok::<_, ParabodyError>([
+                    neighbor_buffer("Cell counts", grid.table_size)?,
+                    neighbor_buffer("Cell start", grid.table_size + 1)?,
+                    neighbor_buffer("Cell bodies", static_config.max_bodies)?,
+                ])
+            })
+            .transpose()?;
+        let grid_buffers = grid_size
+            .map(|grid_size| {
+                let cells = grid_size.pow(3) as usize;
+                let grid_buffer = |label, element_size| {
+                    create_buffer(
+                        &device,
+                        &BufferDescriptor {
+                            label: Some(label),
+                            size: (cells * element_size) as u64,
+                            usage: BufferUsages::STORAGE,
+                            mapped_at_creation: false,
+                        },
+                    )
+                };
+                Ok::<_, ParabodyError>([
+                    grid_buffer("Density", size_of::<i32>())?,
+                    grid_buffer("Grid A", size_of::<[f32; 2]>())?,
+                    grid_buffer("Grid B", size_of::<[f32; 2]>())?,
+                ])
+            })
+            .transpose()?;
+        // Holds no bodies until `write_ephemeris` replaces the samples
+        let ephemeris_buffers = static_config
+            .ephemeris
+            .then(|| {
+                let ephemeris_buffer = |label, size: usize| {
+                    create_buffer(
+                        &device,
+                        &BufferDescriptor {
+                            label: Some(label),
+                            size: size as u64,
+                            usage: BufferUsages::STORAGE | BufferUsages::MAP_WRITE,
+                            mapped_at_creation: true,
+                        },
+                    )
+                };
+                let buffers = [
+                    ephemeris_buffer("Ephemeris state", size_of::<EphemerisState>())?,
+                    ephemeris_buffer("Ephemeris samples", 2 * size_of::<[f32; 4]>())?,
+                ];
+                for buffer in &buffers {
+                    buffer.slice(..).get_mapped_range_mut().fill(0);
+                    buffer.unmap();
+                }
+                Ok::<_, ParabodyError>(buffers)
+            })
+            .transpose()?;
+        // No body thrusts until `write_thrust`
+        let thrust_buffers = static_config
+            .thrust
+            .then(|| {
+                let thrust_buffer = |label, size: usize| {
+                    create_buffer(
+                        &device,
+                        &BufferDescriptor {
+                            label: Some(label),
+                            size: size as u64,
+                            usage: BufferUsages::STORAGE | BufferUsages::MAP_WRITE,
+                            mapped_at_creation: true,
+                        },
+                    )
+                };
+                let buffers = [
+                    thrust_buffer(
+                        "Thrust states",
+                        static_config.max_bodies as usize * size_of::<ThrustState>(),
+                    )?,
+                    thrust_buffer("Thrust samples", size_of::<[f32; 4]>())?,
+                ];
+                for buffer in &buffers {
+                    buffer.slice(..).get_mapped_range_mut().fill(0);
+                    buffer.unmap();
+                }
+                Ok::<_, ParabodyError>(buffers)
+            })
+            .transpose()?;
+        let com_buffer = static_config
+            .com_correction
+            .then(|| {
+                create_buffer(
+                    &device,
+                    &BufferDescriptor {
+                        label: Some("Center of mass sums"),
+                        size: size_of::<[[f32; 4]; 2]>() as u64,
+                        usage: BufferUsages::STORAGE,
+                        mapped_at_creation: false,
+                    },
+                )
+            })
+            .transpose()?;
+        let tangent_buffer = static_config
+            .variational
+            .then(|| {
+                create_buffer(
+                    &device,
+                    &BufferDescriptor {
+                        label: Some("Tangents"),
+                        size: (static_config.max_bodies as usize * size_of::<Tangent>()) as u64,
+                        usage: BufferUsages::STORAGE
+                            | BufferUsages::MAP_READ
+                            | BufferUsages::MAP_WRITE,
+                        mapped_at_creation: false,
+                    },
+                )
+            })
+            .transpose()?;
+        // At most a system per body
+        let system_buffer = static_config
+            .ensemble
+            .then(|| {
+                create_buffer(
+                    &device,
+                    &BufferDescriptor {
+                        label: Some("Systems"),
+                        size: (static_config.max_bodies as usize * size_of::<System>()) as u64,
+                        usage: BufferUsages::STORAGE | BufferUsages::MAP_WRITE,
+                        mapped_at_creation: false,
+                    },
+                )
+            })
+            .transpose()?;
+        // The log starts empty
+        let event_buffers = (!event_predicates.is_empty())
+            .then(|| {
+                let values = (2 * event_predicates.len() * static_config.max_bodies as usize)
+                    * size_of::<f32>();
+                let log = 2 * size_of::<u32>() + MAX_EVENT_RECORDS * size_of::<EventRecord>();
+                let event_buffer = |label, size: usize| {
+                    create_buffer(
+                        &device,
+                        &BufferDescriptor {
+                            label: Some(label),
+                            size: size as u64,
+                            usage: BufferUsages::STORAGE
+                                | BufferUsages::MAP_READ
+                                | BufferUsages::MAP_WRITE,
+                            mapped_at_creation: true,
+                        },
+                    )
+                };
+                let buffers = [
+                    event_buffer("Event values", values)?,
+                    event_buffer("Event log", log)?,
+                ];
+                for buffer in &buffers {
+                    buffer.slice(..).get_mapped_range_mut().fill(0);
+                    buffer.unmap();
+                }
+                Ok::<_, ParabodyError>(buffers)
+            })
+            .transpose()?;
 
         let mut pipeline = Self {
+            adapter_info: adapter.get_info(),
+            adapter,
             device,
             queue,
             config_bindgroup_layout,
             body_bindgroup_layout,
-            pipeline,
+            grid_bindgroup_layout,
+            gather_bindgroup_layout,
+            search_layouts: None,
+            pipeline_layouts,
+            shader_src,
+            modules,
+            custom_force,
+            event_predicates,
+            base_pass_graph: pass_graph,
+            kernels,
+            kernel_key,
+            kernel_cache: VecDeque::new(),
             config_buffer,
             body_buffers,
+            acceleration_buffer,
+            properties_buffer,
+            gas_buffer,
+            gather_buffer,
+            gather_staging_buffer,
+            readback_buffers: Vec::new(),
+            neighbor_buffers,
+            grid_buffers,
+            ephemeris_buffers,
+            ephemeris: None,
+            thrust_buffers,
+            thrust: None,
+            com_buffer,
+            com_correction: None,
+            tangent_buffer,
+            system_buffer,
+            systems: Vec::new(),
+            event_buffers,
+            event_times: vec![0.0],
+            events_primed: false,
+            events_paused: false,
+            maneuvers: ManeuverSchedule::default(),
             static_config,
             dynamic_config,
             active_source: SourceBuffer::A,
+            time_direction: TimeDirection::Forward,
+            time: 0.0,
+            epoch: None,
+            steps_per_submit: DEFAULT_STEPS_PER_SUBMIT,
+            progress: None,
+            profiler: None,
+            slack_check: cfg!(debug_assertions),
+            resilient: false,
+            shader_watch: None,
+            mirror: None,
         };
+        if let Some(grid) = static_config.neighbor_grid {
+            pipeline.set_cell_size(grid.cell_size);
+        }
         pipeline.synchronize_dynamic_config();
 
+        Ok(pipeline)
+    }
+
+    /// Recreate the pipeline from a file written by `checkpoint`, on an adapter
+    /// matching `power_preference`. The shader and pass graph aren't stored, so
+    /// they must be the ones the checkpointed pipeline was created with. Neither
+    /// are the settings made on the host, such as the maneuvers, which
+    /// `set_maneuvers` skips up to the restored time, and the interval of the
+    /// center of mass correction.
+    pub async fn restore(
+        path: &Path,
+        shader_src: &'static str,
+        pass_graph: PassGraph,
+        power_preference: PowerPreference,
+    ) -> Result<Self, ParabodyError> {
+        let checkpoint = Checkpoint::load(path)?;
+        let mut pipeline = Self::create(
+            shader_src,
+            pass_graph,
+            checkpoint.static_config,
+            power_preference,
+        )
+        .await?;
+        pipeline.load(&checkpoint)?;
+        Ok(pipeline)
+    }
+
+    /// Continue from the state in `checkpoint`, such as one of `capture`. The
+    /// maneuvers and events logged so far are left behind.
+    pub fn load(&mut self, checkpoint: &Checkpoint) -> Result<(), ParabodyError> {
+        self.dynamic_config = checkpoint.dynamic_config;
+        // Checkpoints don't hold the systems of an ensemble
+        self.dynamic_config.num_systems = 0;
+        self.time_direction = checkpoint.time_direction;
+        self.time = checkpoint.time;
+        self.write_bodies(&checkpoint.bodies)?;
+        self.write_properties(&checkpoint.properties);
+        self.write_gas_state(&checkpoint.gas);
+        self.clear_events();
+        Ok(())
+    }
+
+    /// Replace the device and everything on it with a fresh device on the same
+    /// adapter, loaded with `checkpoint`. Settings made on the host carry over.
+    fn recover(&mut self, checkpoint: &Checkpoint) {
+        let mut pipeline = pollster::block_on(Self::create_on_adapter(
+            self.adapter.clone(),
+            self.shader_src.clone(),
+            self.modules.clone(),
+            self.custom_force.clone(),
+            self.event_predicates.clone(),
+            self.base_pass_graph.clone(),
+            self.static_config,
+        ))
+        .expect("Could not recreate the pipeline after losing the device");
+        pipeline.steps_per_submit = self.steps_per_submit;
+        pipeline.progress = self.progress.take();
+        if self.profiler.is_some() {
+            pipeline.set_profiling(true);
+        }
+        pipeline.slack_check = self.slack_check;
+        pipeline.resilient = self.resilient;
+        pipeline.shader_watch = self.shader_watch.take();
+        pipeline.set_host_mirror(self.mirror.is_some());
+        pipeline.epoch = self.epoch;
+        pipeline.com_correction = self.com_correction;
         pipeline
+            .load(checkpoint)
+            .expect("The checkpoint came from the same configuration");
+        if let Some((ephemeris, _)) = &self.ephemeris {
+            pipeline
+                .write_ephemeris(ephemeris)
+                .expect("The ephemeris was written to the lost device");
+        }
+        if let Some((thrust, _)) = &self.thrust {
+            pipeline
+                .write_thrust(thrust)
+                .expect("The thrust was written to the lost device");
+        }
+        if !self.systems.is_empty() {
+            pipeline.write_systems(&self.systems);
+        }
+        if pipeline.tangent_buffer.is_some() {
+            log::warn!("Restarting the chaos indicators from the recovered state");
+            pipeline.reset_chaos_indicators();
+        }
+        *self = pipeline;
     }
 
-    pub fn set_dt(&mut self, dt: f32) {
-        self.dynamic_config.dt = dt;
+    /// Capture the latest state, everything `restore` needs to continue from it
+    pub fn capture(&mut self) -> Checkpoint {
+        Checkpoint {
+            static_config: self.static_config,
+            dynamic_config: self.dynamic_config,
+            time_direction: self.time_direction,
+            time: self.time,
+            bodies: self.read_bodies(),
+            properties: self.read_properties(),
+            gas: self.read_gas_state(),
+        }
     }
 
-    fn synchronize_dynamic_config(&mut self) {
-        // Map the config buffer and write the data from the host to the GPU
-        let slice = self.config_buffer.slice(..);
-        self.map_slice_blocking(MapMode::Write, slice);
-        {
-            let mut config = slice.get_mapped_range_mut();
-            let config_bytes: [u8; size_of::<DynamicConfig>()] =
-                bytemuck::cast(self.dynamic_config);
-            config.as_mut().copy_from_slice(&config_bytes);
+    /// Write the latest state to `path` for `restore`. Fails for the
+    /// configurations with state a checkpoint doesn't hold, see `uncaptured_state`.
+    pub fn checkpoint(&mut self, path: &Path) -> io::Result<()> {
+        if let Some(state) = self.uncaptured_state() {
+            return Err(io::Error::other(format!(
+                "A checkpoint can't hold {}",
+                state
+            )));
         }
-        self.config_buffer.unmap();
+        self.capture().save(path)
     }
 
-    pub fn write_bodies(&mut self, input: &[Body]) {
-        assert!(input.len() <= self.static_config.max_bodies as usize);
-        self.dynamic_config.num_bodies = input.len() as u32;
-        // Map the input buffer and write the data from the host to the GPU
-        let upper_bound = (self.dynamic_config.num_bodies * size_of::<Body>() as u32) as u64;
-        let slice = match self.active_source {
-            SourceBuffer::A => self.body_buffers[0].slice(..upper_bound),
-            SourceBuffer::B => self.body_buffers[1].slice(..upper_bound),
-        };
-        self.map_slice_blocking(MapMode::Write, slice);
-        {
-            let mut source = slice.get_mapped_range_mut();
-            source.as_mut().copy_from_slice(bytemuck::cast_slice(input));
+    /// The state on the device which `capture` leaves out, without which a
+    /// restored run would follow different dynamics
+    pub fn uncaptured_state(&self) -> Option<&'static str> {
+        if self.static_config.thrust {
+            Some("the clocks of the thrust profiles")
+        } else if self.static_config.ephemeris {
+            Some("the ephemeris of the prescribed bodies")
+        } else if self.static_config.variational {
+            Some("the tangent vectors of the chaos indicators")
+        } else if !self.systems.is_empty() {
+            Some("the systems of an ensemble")
+        } else {
+            None
         }
-        match self.active_source {
-            SourceBuffer::A => self.body_buffers[0].unmap(),
-            SourceBuffer::B => self.body_buffers[1].unmap(),
-        };
     }
 
-    pub fn read_bodies(&self) -> Vec<Body> {
-        // Wait for the output buffer to become mappable and read it out to the host
-        let upper_bound = (self.dynamic_config.num_bodies * size_of::<Body>() as u32) as u64;
-        let slice = match self.active_source {
-            SourceBuffer::A => self.body_buffers[1].slice(..upper_bound),
-            SourceBuffer::B => self.body_buffers[0].slice(..upper_bound),
+    pub fn static_config(&self) -> &StaticConfig {
+        &self.static_config
+    }
+
+    pub fn dynamic_config(&self) -> &DynamicConfig {
+        &self.dynamic_config
+    }
+
+    pub fn pass_graph(&self) -> &PassGraph {
+        &self.kernels.pass_graph
+    }
+
+    /// The shader modules and the kernels compiled from them so far
+    pub fn kernels(&self) -> &KernelRegistry {
+        &self.kernels.registry
+    }
+
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    pub fn adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// The buffer holding the latest body state, also usable as a vertex buffer
+    /// and as the source of copies
+    pub fn body_buffer(&self) -> &wgpu::Buffer {
+        match self.active_source {
+            SourceBuffer::A => &self.body_buffers[0],
+            SourceBuffer::B => &self.body_buffers[1],
+        }
+    }
+
+    /// Total size of the GPU buffers owned by the pipeline in bytes
+    pub fn gpu_memory(&self) -> u64 {
+        let max_bodies = self.static_config.max_bodies as usize;
+        let per_body = 2 * size_of::<Body>()
+            + 2 * size_of::<[f32; 3]>()
+            + size_of::<[f32; 4]>()
+            + size_of::<BodyProperties>()
+            + size_of::<GasState>()
+            + self.static_config.variational as usize * size_of::<Tangent>()
+            + self.static_config.ensemble as usize * size_of::<System>();
+        let grid = match self.static_config.force_solver {
+            ForceSolver::Direct => 0,
+            ForceSolver::PM { grid_size } => pm::grid_memory(grid_size),
+        };
+        let neighbors = self.static_config.neighbor_grid.map_or(0, |grid| {
+            neighbors::memory(grid.table_size, self.static_config.max_bodies)
+        });
+        (size_of::<DynamicConfig>() + max_bodies * per_body) as u64 + grid + neighbors
+    }
+
+    /// The accelerations of the latest state, as the next step forwards would
+    /// evaluate them. Takes that step and undoes it, restoring the bodies, gas,
+    /// time and maneuvers but not the chaos indicators.
+    pub fn evaluate_accelerations(&mut self) -> Vec<[f32; 3]> {
+        assert_eq!(
+            self.time_direction,
+            TimeDirection::Forward,
+            "Backward steps evaluate the forces after moving the bodies"
+        );
+        let bodies = self.read_bodies();
+        let gas = self.read_gas_state();
+        let time = self.time;
+        let maneuvers = self.maneuvers.clone();
+        self.events_paused = true;
+        self.submit_and_block(1);
+        self.events_paused = false;
+        let accelerations = self.read_accelerations();
+        self.upload_bodies(&bodies);
+        self.write_gas_state(&gas);
+        self.time = time;
+        self.maneuvers = maneuvers;
+        accelerations
+    }
+
+    /// Time `steps` steps on the current bodies, then restore them
+    pub fn benchmark(&mut self, steps: usize) -> Duration {
+        let bodies = self.read_bodies();
+        let time = self.time;
+        let start = Instant::now();
+        self.events_paused = true;
+        self.submit_and_block(steps);
+        self.events_paused = false;
+        let elapsed = start.elapsed();
+        self.upload_bodies(&bodies);
+        self.time = time;
+        elapsed
+    }
+
+    /// Recompile the kernels for workgroups of `size` threads, which the adapter
+    /// must be able to run with the current configuration
+    pub fn set_workgroup_size(&mut self, size: u32) -> Result<(), ParabodyError> {
+        assert!(
+            supports_workgroup_size(&self.static_config, &self.adapter.limits(), size),
+            "The adapter can't run workgroups of {} threads with this configuration",
+            size
+        );
+        if size == self.static_config.workgroup_size {
+            return Ok(());
+        }
+        let static_config = StaticConfig {
+            workgroup_size: size,
+            ..self.static_config
         };
+        self.replace_kernels(
+            self.shader_src.clone(),
+            self.custom_force.clone(),
+            static_config,
+        )
+    }
+
+    /// Switch to the kernels of the shaders and `static_config`, taken from the
+    /// cache if they were used before. The current kernels are cached in turn.
+    fn replace_kernels(
+        &mut self,
+        shader_src: String,
+        custom_force: Option<String>,
+        static_config: StaticConfig,
+    ) -> Result<(), ParabodyError> {
+        let sources = ShaderSources {
+            shader_src: &shader_src,
+            modules: &self.modules,
+            custom_force: custom_force.as_deref(),
+            events: &self.event_predicates,
+        };
+        let key = sources.key(&static_config);
+        if key == self.kernel_key {
+            return Ok(());
+        }
+        let cached = self
+            .kernel_cache
+            .iter()
+            .position(|(cached, _)| *cached == key)
+            .and_then(|index| self.kernel_cache.remove(index));
+        let kernels = match cached {
+            Some((_, mut kernels)) => {
+                // Passes selected since they were cached
+                kernels.select(
+                    &self.device,
+                    &self.pipeline_layouts,
+                    &self.base_pass_graph,
+                    &static_config,
+                )?;
+                kernels
+            }
+            None => Kernels::compile(
+                &self.device,
+                &self.pipeline_layouts,
+                &sources,
+                &self.base_pass_graph,
+                &static_config,
+            )?,
+        };
+        let previous = mem::replace(&mut self.kernels, kernels);
+        self.kernel_cache.push_front((self.kernel_key, previous));
+        self.kernel_cache.truncate(KERNEL_CACHE_SIZE);
+        self.kernel_key = key;
+        self.shader_src = shader_src;
+        self.custom_force = custom_force;
+        self.static_config = static_config;
+        Ok(())
+    }
+
+    /// Dispatch the passes of `pass_graph` from the next step on, extended for
+    /// the static configuration like the graph the pipeline was created with.
+    /// Only the kernels no earlier pass dispatched are compiled.
+    pub fn set_pass_graph(&mut self, pass_graph: PassGraph) -> Result<(), ParabodyError> {
+        self.kernels.select(
+            &self.device,
+            &self.pipeline_layouts,
+            &pass_graph,
+            &self.static_config,
+        )?;
+        self.base_pass_graph = pass_graph;
+        Ok(())
+    }
+
+    /// Time each of `AUTOTUNE_WORKGROUP_SIZES` the adapter can run on a synthetic
+    /// workload as large as the current one, and keep the fastest. Passes are timed
+    /// with timestamp queries where supported, otherwise the whole submission is
+    /// timed on the host. The state is restored afterwards.
+    ///
+    /// Returns the time per step of every candidate tried, in order.
+    pub fn autotune(&mut self) -> Result<Vec<(u32, Duration)>, ParabodyError> {
+        let bodies = self.read_bodies();
+        let gas = self.read_gas_state();
+        let time = self.time;
+        let profiler = self.profiler.take();
+
+        // Uniform sphere of unit total mass, filling the periodic box if there is one
+        let count = match self.dynamic_config.num_bodies {
+            0 => self.static_config.max_bodies.min(AUTOTUNE_BODIES),
+            count => count,
+        } as usize;
+        let box_size = self.dynamic_config.box_size;
+        let (radius, center) = if box_size > 0.0 {
+            (0.5 * box_size, [0.5 * box_size; 3])
+        } else {
+            (1.0, [0.0; 3])
+        };
+        let mu = 1.0 / count as f32;
+        let workload: Vec<Body> = crate::ic::uniform_sphere(count, radius, center)
+            .into_iter()
+            .map(|position| Body {
+                position,
+                mass: mu,
+                velocity: [0.0; 3],
+                mu,
+            })
+            .collect();
+
+        let limits = self.adapter.limits();
+        let profiled = self.supports_profiling();
+        let mut timings = Vec::new();
+        for size in AUTOTUNE_WORKGROUP_SIZES {
+            if !supports_workgroup_size(&self.static_config, &limits, size) {
+                continue;
+            }
+            self.set_workgroup_size(size)?;
+            self.write_bodies(&workload)?;
+            self.submit_and_block(AUTOTUNE_STEPS);
+            let elapsed = if profiled {
+                self.profiler = Some(Profiler::new(&self.device, &self.queue));
+                self.submit_and_block(AUTOTUNE_STEPS);
+                self.profiler.take().unwrap().stats().total()
+            } else {
+                let start = Instant::now();
+                self.submit_and_block(AUTOTUNE_STEPS);
+                start.elapsed()
+            };
+            let step_time = elapsed.div_f64(AUTOTUNE_STEPS as f64);
+            log::debug!("Workgroups of {} threads: {:?}/step", size, step_time);
+            timings.push((size, step_time));
+        }
+        let (fastest, _) = *timings
+            .iter()
+            .min_by_key(|(_, step_time)| *step_time)
+            .expect("The adapter can't run any of the candidate workgroup sizes");
+        self.set_workgroup_size(fastest)?;
+
+        self.upload_bodies(&bodies);
+        self.write_gas_state(&gas);
+        self.time = time;
+        self.profiler = profiler;
+        Ok(timings)
+    }
+
+    /// Set the step size, which the steps take off the time instead while
+    /// integrating backwards. A negative `dt` turns the integration backwards
+    /// with steps of its size, as `set_time_direction` does.
+    pub fn set_dt(&mut self, dt: f32) {
+        if dt < 0.0 {
+            self.time_direction = TimeDirection::Backward;
+        }
+        self.dynamic_config.dt = self.time_direction.sign() * dt.abs();
+    }
+
+    /// Set the Plummer softening length of the direct-sum gravity, which keeps
+    /// close encounters from blowing up at the cost of accuracy below it
+    pub fn set_softening(&mut self, softening: f32) {
+        assert!(softening >= 0.0);
+        self.dynamic_config.softening = softening;
+    }
+
+    /// Simulated time advanced by the steps so far, which backward steps take off
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Tie the simulated time zero to `epoch`, with `seconds_per_unit` seconds of
+    /// TDB in a unit of simulated time
+    pub fn set_epoch(&mut self, epoch: Epoch, seconds_per_unit: f64) {
+        assert!(seconds_per_unit > 0.0);
+        self.epoch = Some((epoch, seconds_per_unit));
+    }
+
+    /// The calendar epoch of the simulated time, if one was set
+    pub fn epoch(&self) -> Option<Epoch> {
+        self.epoch
+            .map(|(epoch, seconds_per_unit)| epoch.after(self.time * seconds_per_unit))
+    }
+
+    /// Shift the bodies into the frame of their center of mass every `interval`
+    /// steps on the GPU, removing the drift of accumulated momentum errors. Needs
+    /// `StaticConfig::com_correction`, `None` turns it off. The shifts make the
+    /// steps irreversible and are meant for isolated systems, not for runs with
+    /// an ephemeris or an external potential to drift against or in a periodic
+    /// box, which scenarios reject.
+    pub fn set_com_correction(&mut self, interval: Option<usize>) {
+        assert!(interval.is_none() || self.static_config.com_correction);
+        assert!(interval != Some(0));
+        self.com_correction = interval.map(|interval| (interval, 0));
+    }
+
+    /// Shift the bodies into the frame of their center of mass, weighted by
+    /// their `mu`. Does nothing if no body has one.
+    pub fn move_to_com_frame(&mut self) {
+        let mut bodies = self.read_bodies();
+        let mut total = 0.0;
+        let mut position = [0.0f64; 3];
+        let mut velocity = [0.0f64; 3];
+        for body in &bodies {
+            let mu = body.mu as f64;
+            total += mu;
+            for axis in 0..3 {
+                position[axis] += mu * body.position[axis] as f64;
+                velocity[axis] += mu * body.velocity[axis] as f64;
+            }
+        }
+        if total <= 0.0 {
+            return;
+        }
+        for body in &mut bodies {
+            for axis in 0..3 {
+                body.position[axis] = (body.position[axis] as f64 - position[axis] / total) as f32;
+                body.velocity[axis] = (body.velocity[axis] as f64 - velocity[axis] / total) as f32;
+            }
+        }
+        self.upload_bodies(&bodies);
+    }
+
+    pub fn time_direction(&self) -> TimeDirection {
+        self.time_direction
+    }
+
+    /// Integrate forwards or backwards in time from the latest state. Backward
+    /// steps negate the step size and dispatch the reversed pass graph, so that
+    /// they retrace forward steps up to rounding with a time-symmetric
+    /// integrator, see `Integrator::is_time_symmetric`.
+    pub fn set_time_direction(&mut self, direction: TimeDirection) {
+        if direction != self.time_direction {
+            self.dynamic_config.dt = -self.dynamic_config.dt;
+            self.time_direction = direction;
+        }
+    }
+
+    /// Make the domain a periodic cube of edge length `box_size`, or open if zero.
+    /// Positions are wrapped into the box on the next step.
+    pub fn set_box_size(&mut self, box_size: f32) {
+        assert!(box_size >= 0.0);
+        self.dynamic_config.box_size = box_size;
+    }
+
+    /// Set the edge length of the neighbor grid cells, which must cover the
+    /// range of the short-range forces
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        assert!(
+            self.static_config.neighbor_grid.is_some(),
+            "Pipeline was created without a neighbor grid"
+        );
+        if let ForceLaw::LennardJones { cutoff, .. } = self.static_config.force_law {
+            assert!(
+                cell_size >= cutoff,
+                "The cell size must be at least the Lennard-Jones cutoff"
+            );
+        }
+        if let Some(hydrodynamics) = self.static_config.hydrodynamics {
+            assert!(
+                cell_size >= 2.0 * hydrodynamics.smoothing_length,
+                "The cell size must be at least twice the smoothing length"
+            );
+        }
+        self.dynamic_config.cell_size = cell_size;
+    }
+
+    /// Set the Lennard-Jones parameters, indexed by `BodyProperties::species`
+    pub fn set_species(&mut self, species: &[Species]) {
+        assert!(species.len() <= MAX_SPECIES);
+        self.dynamic_config.species = [[0.0; 4]; MAX_SPECIES];
+        for (entry, species) in self.dynamic_config.species.iter_mut().zip(species) {
+            *entry = [species.sigma, species.epsilon, 0.0, 0.0];
+        }
+    }
+
+    /// Set the bodies with post-Newtonian corrections and the speed of light,
+    /// needs `StaticConfig::post_newtonian`
+    pub fn set_post_newtonian(&mut self, post_newtonian: &PostNewtonian) {
+        assert!(self.static_config.post_newtonian);
+        assert!(post_newtonian.bodies.len() <= MAX_POST_NEWTONIAN_BODIES);
+        assert!(post_newtonian.speed_of_light > 0.0);
+        self.dynamic_config.post_newtonian_bodies = [u32::MAX; MAX_POST_NEWTONIAN_BODIES];
+        for (entry, &body) in self
+            .dynamic_config
+            .post_newtonian_bodies
+            .iter_mut()
+            .zip(&post_newtonian.bodies)
+        {
+            *entry = body as u32;
+        }
+        self.dynamic_config.post_newtonian_params = [
+            post_newtonian.speed_of_light,
+            post_newtonian.radiation_reaction as u32 as f32,
+            0.0,
+            0.0,
+        ];
+    }
+
+    /// Update the parameters of the external potential, the kind is baked into
+    /// the shader so it must match the one the pipeline was created with
+    pub fn set_external_potential(&mut self, potential: ExternalPotential) {
+        let current = self
+            .static_config
+            .external_potential
+            .expect("Pipeline was created without an external potential");
+        assert_eq!(
+            discriminant(&current),
+            discriminant(&potential),
+            "Cannot change the kind of external potential after creation"
+        );
+        self.static_config.external_potential = Some(potential);
+        self.dynamic_config.external_params = potential.params();
+    }
+
+    /// Split long runs into command buffers of at most `steps` steps, each
+    /// submitted once the previous one finished. Smaller submissions keep a single
+    /// command buffer from running long enough to trip the driver's watchdog.
+    pub fn set_steps_per_submit(&mut self, steps: usize) {
+        assert!(steps > 0);
+        self.steps_per_submit = steps;
+    }
+
+    /// Call `callback` after every submission with the progress through the next
+    /// `total_steps` steps, until `clear_progress_callback`
+    pub fn set_progress_callback(
+        &mut self,
+        total_steps: usize,
+        callback: impl FnMut(&Progress) + 'static,
+    ) {
+        self.progress = Some((
+            EtaEstimator::new(total_steps, ETA_WINDOW),
+            Box::new(callback),
+        ));
+    }
+
+    pub fn clear_progress_callback(&mut self) {
+        self.progress = None;
+    }
+
+    /// Run `f` on the pipeline without reporting progress, such as to replay
+    /// steps whose progress was reported already
+    pub fn without_progress<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let progress = self.progress.take();
+        let result = f(self);
+        self.progress = progress;
+        result
+    }
+
+    /// Whether the adapter can write the timestamps `set_profiling` needs
+    pub fn supports_profiling(&self) -> bool {
+        self.device.features().contains(Features::TIMESTAMP_QUERY)
+    }
+
+    /// Time every pass on the GPU and collect the timings into `stats`. Submissions
+    /// wait for the timestamps to be read back, so runs get a little slower.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler = if enabled {
+            assert!(
+                self.supports_profiling(),
+                "The adapter does not support timestamp queries"
+            );
+            Some(Profiler::new(&self.device, &self.queue))
+        } else {
+            None
+        };
+    }
+
+    /// GPU timings of the passes since profiling was enabled
+    pub fn stats(&self) -> Option<&PipelineStats> {
+        self.profiler.as_ref().map(Profiler::stats)
+    }
+
+    /// Fill the slack of the body buffers, the bodies past the last one up to the
+    /// end of its workgroup, with sentinels before every submission and panic if a
+    /// kernel wrote to them. Those threads are dispatched but must return without
+    /// touching memory. On by default in debug builds.
+    pub fn set_slack_check(&mut self, enabled: bool) {
+        self.slack_check = enabled;
+    }
+
+    /// Bodies past the last one whose threads are dispatched
+    fn slack(&self) -> Range<usize> {
+        let num_bodies = self.dynamic_config.num_bodies;
+        let dispatched = num_bodies.div_ceil(self.static_config.workgroup_size)
+            * self.static_config.workgroup_size;
+        num_bodies as usize..dispatched.min(self.static_config.max_bodies) as usize
+    }
+
+    fn fill_slack(&self) {
+        // Mapped from the start like `write_bodies`, offset mappings are unreliable on GL
+        let slack = self.slack();
+        let lower_bound = slack.start * size_of::<Body>();
+        let upper_bound = slack.end * size_of::<Body>();
+        for (buffer, sentinel) in self.body_buffers.iter().zip(SENTINELS) {
+            let slice = buffer.slice(..upper_bound as u64);
+            self.map_slice_blocking(MapMode::Write, slice);
+            slice.get_mapped_range_mut()[lower_bound..upper_bound]
+                .copy_from_slice(bytemuck::cast_slice(&vec![sentinel; slack.len()]));
+            buffer.unmap();
+        }
+    }
+
+    fn verify_slack(&self) {
+        let slack = self.slack();
+        let lower_bound = slack.start * size_of::<Body>();
+        let upper_bound = slack.end * size_of::<Body>();
+        for ((name, buffer), sentinel) in ["A", "B"]
+            .into_iter()
+            .zip(&self.body_buffers)
+            .zip(SENTINELS)
+        {
+            let slice = buffer.slice(..upper_bound as u64);
+            self.map_slice_blocking(MapMode::Read, slice);
+            let overwritten = bytemuck::cast_slice::<u8, Body>(
+                &slice.get_mapped_range()[lower_bound..upper_bound],
+            )
+            .iter()
+            .position(|body| bytemuck::bytes_of(body) != bytemuck::bytes_of(&sentinel));
+            buffer.unmap();
+            if let Some(offset) = overwritten {
+                panic!(
+                    "A kernel wrote past the last body, to index {} of buffer {}",
+                    slack.start + offset,
+                    name
+                );
+            }
+        }
+    }
+
+    /// Recover from a lost device during `run`, such as after a driver reset,
+    /// for long unattended runs. The state is read back before every submission,
+    /// and when the device is lost the pipeline recreates it on the same adapter,
+    /// uploads that state and resubmits the steps since. Renderers sharing the
+    /// device don't survive this. Profiling starts over after a recovery.
+    pub fn set_resilient(&mut self, enabled: bool) {
+        self.resilient = enabled;
+    }
+
+    /// Check the files of `watch` for changes before every submission, and
+    /// recompile the kernels from the changed shaders to continue with. Shaders
+    /// which fail to compile are logged and the previous kernels keep running.
+    pub fn set_shader_watch(&mut self, watch: Option<ShaderWatch>) {
+        self.shader_watch = watch;
+    }
+
+    /// Recompile the kernels from a new shader template and custom force, see
+    /// `PipelineBuilder::custom_force`, keeping the current ones if that fails
+    pub fn set_shaders(
+        &mut self,
+        shader_src: String,
+        custom_force: Option<String>,
+    ) -> Result<(), ParabodyError> {
+        self.replace_kernels(shader_src, custom_force, self.static_config)
+    }
+
+    /// Recompile the kernels if `shader_watch` saw the shaders change
+    fn reload_shaders(&mut self) {
+        let (shader_src, custom_force) = match &mut self.shader_watch {
+            Some(watch) => watch.poll(),
+            None => return,
+        };
+        if shader_src.is_none() && custom_force.is_none() {
+            return;
+        }
+        let shader_src = shader_src.unwrap_or_else(|| self.shader_src.clone());
+        let custom_force = custom_force.or_else(|| self.custom_force.clone());
+        match self.set_shaders(shader_src, custom_force) {
+            Ok(()) => log::info!("Reloaded the shaders"),
+            Err(error) => log::error!("Keeping the previous kernels: {}", error),
+        }
+    }
+
+    /// Keep a host copy of the body state so that repeated reads between
+    /// submissions don't go back to the GPU, and writes only upload what changed
+    pub fn set_host_mirror(&mut self, enabled: bool) {
+        self.mirror = if enabled {
+            Some(HostMirror::new())
+        } else {
+            None
+        };
+    }
+
+    fn synchronize_dynamic_config(&self) {
+        // Map the config buffer and write the data from the host to the GPU
+        let slice = self.config_buffer.slice(..);
+        self.map_slice_blocking(MapMode::Write, slice);
+        {
+            let mut config = slice.get_mapped_range_mut();
+            config
+                .as_mut()
+                .copy_from_slice(bytemuck::bytes_of(&self.dynamic_config));
+        }
+        self.config_buffer.unmap();
+    }
+
+    /// Replace the bodies with `input`, at most `StaticConfig::max_bodies` of them
+    pub fn write_bodies(&mut self, input: &[Body]) -> Result<(), ParabodyError> {
+        if input.len() > self.static_config.max_bodies as usize {
+            return Err(ParabodyError::TooManyBodies {
+                bodies: input.len(),
+                max_bodies: self.static_config.max_bodies,
+            });
+        }
+        self.upload_bodies(input);
+        Ok(())
+    }
+
+    /// `write_bodies` for bodies known to fit, such as those read back
+    fn upload_bodies(&mut self, input: &[Body]) {
+        debug_assert!(input.len() <= self.static_config.max_bodies as usize);
+        // The predicates before the next step are those of the new bodies
+        self.events_primed = false;
+        self.dynamic_config.num_bodies = input.len() as u32;
+        if let ForceSolver::PM { .. } = self.static_config.force_solver {
+            let total: f32 = input.iter().map(|body| body.mu).sum();
+            self.dynamic_config.pm_mass_scale = if total > 0.0 {
+                PM_MASS_SCALE / total
+            } else {
+                1.0
+            };
+        }
+        // Only upload the bodies which differ from the mirrored state
+        let range = match &self.mirror {
+            Some(mirror) => match mirror.diff(input) {
+                Some(range) => range,
+                None => return,
+            },
+            None => 0..input.len(),
+        };
+        // Map the input buffer and write the data from the host to the GPU
+        let lower_bound = range.start * size_of::<Body>();
+        let upper_bound = range.end * size_of::<Body>();
+        let slice = match self.active_source {
+            SourceBuffer::A => self.body_buffers[0].slice(..upper_bound as u64),
+            SourceBuffer::B => self.body_buffers[1].slice(..upper_bound as u64),
+        };
+        self.map_slice_blocking(MapMode::Write, slice);
+        {
+            let mut source = slice.get_mapped_range_mut();
+            source.as_mut()[lower_bound..upper_bound]
+                .copy_from_slice(bytemuck::cast_slice(&input[range]));
+        }
+        match self.active_source {
+            SourceBuffer::A => self.body_buffers[0].unmap(),
+            SourceBuffer::B => self.body_buffers[1].unmap(),
+        };
+        if let Some(mirror) = &mut self.mirror {
+            mirror.update(input);
+        }
+    }
+
+    /// Write the per-body properties, indices correspond to those passed to `write_bodies`
+    pub fn write_properties(&mut self, properties: &[BodyProperties]) {
+        assert!(properties.len() <= self.static_config.max_bodies as usize);
+        let upper_bound = size_of_val(properties) as u64;
+        let slice = self.properties_buffer.slice(..upper_bound);
+        self.map_slice_blocking(MapMode::Write, slice);
+        {
+            let mut mapped = slice.get_mapped_range_mut();
+            mapped
+                .as_mut()
+                .copy_from_slice(bytemuck::cast_slice(properties));
+        }
+        self.properties_buffer.unmap();
+    }
+
+    /// Write the internal energies of the gas bodies, the densities and pressures
+    /// are recomputed before they're used
+    pub fn write_gas_state(&mut self, states: &[GasState]) {
+        assert!(states.len() <= self.static_config.max_bodies as usize);
+        let upper_bound = size_of_val(states) as u64;
+        let slice = self.gas_buffer.slice(..upper_bound);
+        self.map_slice_blocking(MapMode::Write, slice);
+        {
+            let mut mapped = slice.get_mapped_range_mut();
+            mapped
+                .as_mut()
+                .copy_from_slice(bytemuck::cast_slice(states));
+        }
+        self.gas_buffer.unmap();
+    }
+
+    /// Prescribe the bodies of the tracks of `ephemeris` instead of integrating
+    /// them. After every step they're placed at their interpolated state, held
+    /// at the first or last sample outside of the ephemeris. They're also placed
+    /// at the current time right away, so write the bodies first.
+    pub fn write_ephemeris(&mut self, ephemeris: &Ephemeris) -> Result<(), ParabodyError> {
+        let invalid = |error: String| Err(ParabodyError::InvalidInput(error));
+        if !self.static_config.ephemeris {
+            return invalid("Pipeline was created without an ephemeris".to_string());
+        }
+        if ephemeris.tracks.len() > MAX_EPHEMERIS_BODIES {
+            return invalid(format!(
+                "An ephemeris can prescribe at most {} bodies",
+                MAX_EPHEMERIS_BODIES
+            ));
+        }
+        let num_samples = ephemeris.num_samples();
+        if num_samples < 2 || ephemeris.interval <= 0.0 {
+            return invalid(
+                "An ephemeris needs at least two samples a positive interval apart".to_string(),
+            );
+        }
+        for (i, track) in ephemeris.tracks.iter().enumerate() {
+            if track.body >= self.dynamic_config.num_bodies as usize {
+                return invalid(format!("No body {} to prescribe", track.body));
+            }
+            if ephemeris.tracks[..i]
+                .iter()
+                .any(|other| other.body == track.body)
+            {
+                return invalid(format!("Body {} is prescribed twice", track.body));
+            }
+            if track.states.len() != num_samples {
+                return invalid(format!(
+                    "Body {} has {} samples, the first track has {}",
+                    track.body,
+                    track.states.len(),
+                    num_samples
+                ));
+            }
+        }
+        let mut state = EphemerisState {
+            num_bodies: ephemeris.tracks.len() as u32,
+            num_samples: num_samples as u32,
+            interval: ephemeris.interval as f32,
+            ..Default::default()
+        };
+        let mut samples = Vec::with_capacity(2 * num_samples * ephemeris.tracks.len());
+        for (i, track) in ephemeris.tracks.iter().enumerate() {
+            state.bodies[i] = track.body as u32;
+            for (position, velocity) in &track.states {
+                let [x, y, z] = position.map(|x| x as f32);
+                let [vx, vy, vz] = velocity.map(|v| v as f32);
+                samples.extend([[x, y, z, 0.0], [vx, vy, vz, 0.0]]);
+            }
+        }
+        let buffer = create_buffer(
+            &self.device,
+            &BufferDescriptor {
+                label: Some("Ephemeris samples"),
+                size: size_of_val(samples.as_slice()) as u64,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: true,
+            },
+        )?;
+        buffer
+            .slice(..)
+            .get_mapped_range_mut()
+            .copy_from_slice(bytemuck::cast_slice(&samples));
+        buffer.unmap();
+        if let Some(buffers) = &mut self.ephemeris_buffers {
+            buffers[1] = buffer;
+        }
+        self.ephemeris = Some((ephemeris.clone(), state));
+        self.synchronize_ephemeris();
+        let mut bodies = self.read_bodies();
+        for (i, track) in ephemeris.tracks.iter().enumerate() {
+            let (position, velocity) = ephemeris.state_at(i, self.time);
+            bodies[track.body].position = position.map(|x| x as f32);
+            bodies[track.body].velocity = velocity.map(|v| v as f32);
+        }
+        self.upload_bodies(&bodies);
+        Ok(())
+    }
+
+    /// Set the continuous thrust of the bodies, replacing that written before.
+    /// Bodies without an entry coast.
+    pub fn write_thrust(&mut self, thrust: &[Thrust]) -> Result<(), ParabodyError> {
+        let invalid = |error: String| Err(ParabodyError::InvalidInput(error));
+        if !self.static_config.thrust {
+            return invalid("Pipeline was created without thrust".to_string());
+        }
+        let mut samples: Vec<[f32; 4]> = Vec::new();
+        for (i, entry) in thrust.iter().enumerate() {
+            if entry.body >= self.dynamic_config.num_bodies as usize {
+                return invalid(format!("No body {} to thrust", entry.body));
+            }
+            if thrust[..i].iter().any(|other| other.body == entry.body) {
+                return invalid(format!("Body {} has two thrust profiles", entry.body));
+            }
+            if let ThrustProfile::Table {
+                interval,
+                accelerations,
+                ..
+            } = &entry.profile
+            {
+                if accelerations.len() < 2 || *interval <= 0.0 {
+                    return invalid(
+                        "A thrust table needs at least two samples a positive interval apart"
+                            .to_string(),
+                    );
+                }
+                samples.extend(
+                    accelerations
+                        .iter()
+                        .map(|&[x, y, z]| [x as f32, y as f32, z as f32, *interval as f32]),
+                );
+            }
+        }
+        if !samples.is_empty() {
+            let buffer = create_buffer(
+                &self.device,
+                &BufferDescriptor {
+                    label: Some("Thrust samples"),
+                    size: size_of_val(samples.as_slice()) as u64,
+                    usage: BufferUsages::STORAGE,
+                    mapped_at_creation: true,
+                },
+            )?;
+            buffer
+                .slice(..)
+                .get_mapped_range_mut()
+                .copy_from_slice(bytemuck::cast_slice(&samples));
+            buffer.unmap();
+            if let Some(buffers) = &mut self.thrust_buffers {
+                buffers[1] = buffer;
+            }
+        }
+        let states = vec![ThrustState::default(); self.static_config.max_bodies as usize];
+        self.thrust = Some((thrust.to_vec(), states));
+        self.write_thrust_states();
+        Ok(())
+    }
+
+    /// Set the clocks of the thrust tables from the simulated time, which they
+    /// were advanced by in single precision on the GPU
+    fn synchronize_thrust(&mut self) {
+        let tables = self.thrust.as_ref().is_some_and(|(thrust, _)| {
+            thrust
+                .iter()
+                .any(|entry| matches!(entry.profile, ThrustProfile::Table { .. }))
+        });
+        if tables {
+            self.write_thrust_states();
+        }
+    }
+
+    /// Write the states of the thrust at the simulated time
+    fn write_thrust_states(&mut self) {
+        let time = self.time;
+        let states = match &mut self.thrust {
+            Some((thrust, states)) => {
+                let mut first = 0;
+                for entry in thrust.iter() {
+                    states[entry.body] = entry.profile.state(time, first);
+                    if let ThrustProfile::Table { accelerations, .. } = &entry.profile {
+                        first += accelerations.len();
+                    }
+                }
+                bytemuck::cast_slice::<ThrustState, u8>(states).to_vec()
+            }
+            _ => return,
+        };
+        if let Some([buffer, _]) = &self.thrust_buffers {
+            let slice = buffer.slice(..);
+            self.map_slice_blocking(MapMode::Write, slice);
+            slice.get_mapped_range_mut().copy_from_slice(&states);
+            buffer.unmap();
+        }
+    }
+
+    /// Schedule impulsive maneuvers, replacing those scheduled before. Maneuvers
+    /// before the current time count as applied already.
+    pub fn set_maneuvers(&mut self, mut maneuvers: ManeuverSchedule) {
+        for maneuver in maneuvers.maneuvers() {
+            assert!(
+                maneuver.body < self.dynamic_config.num_bodies as usize,
+                "No body {} to maneuver",
+                maneuver.body
+            );
+        }
+        maneuvers.skip_to(self.time);
+        self.maneuvers = maneuvers;
+    }
+
+    pub fn maneuvers(&self) -> &ManeuverSchedule {
+        &self.maneuvers
+    }
+
+    /// Apply the impulses due at the current time, which integrating backwards
+    /// takes off again
+    fn apply_maneuvers(&mut self, dt: f32) {
+        let impulses = self
+            .maneuvers
+            .due(self.time, self.time_direction, dt.abs() as f64);
+        if impulses.is_empty() {
+            return;
+        }
+        let mut bodies = self.read_bodies();
+        for (body, delta_v) in impulses {
+            log::debug!(
+                "Changing the velocity of body {} by {:?} at time {}",
+                body,
+                delta_v,
+                self.time
+            );
+            for (v, dv) in bodies[body].velocity.iter_mut().zip(delta_v) {
+                *v += dv as f32;
+            }
+        }
+        self.upload_bodies(&bodies);
+        // The following steps change the bodies behind the mirror's back
+        if let Some(mirror) = &mut self.mirror {
+            mirror.invalidate();
+        }
+    }
+
+    /// Remove the bodies at `indices` from the simulation, the bodies after them
+    /// move down. Their properties, gas state, tangent vectors, thrust, ephemeris
+    /// tracks, post-Newtonian corrections and maneuvers go with them, those of the other
+    /// bodies follow them to their new indices.
+    pub fn remove_bodies(&mut self, indices: &[usize]) {
+        let num_bodies = self.dynamic_config.num_bodies as usize;
+        let mut kept = vec![true; num_bodies];
+        for &index in indices {
+            assert!(index < num_bodies, "No body {} to remove", index);
+            kept[index] = false;
+        }
+        assert!(kept.contains(&true), "Can't remove every body");
+        let mut next = 0;
+        let renumbered: Vec<Option<usize>> = kept
+            .iter()
+            .map(|&keep| {
+                next += keep as usize;
+                keep.then_some(next - 1)
+            })
+            .collect();
+        let index = |body: usize| renumbered[body];
+        fn retain<T>(items: Vec<T>, kept: &[bool]) -> Vec<T> {
+            items
+                .into_iter()
+                .zip(kept)
+                .filter_map(|(item, &keep)| keep.then_some(item))
+                .collect()
+        }
+        let bodies = retain(self.read_bodies(), &kept);
+        let properties = retain(self.read_properties(), &kept);
+        let gas = retain(self.read_gas_state(), &kept);
+        self.upload_bodies(&bodies);
+        self.write_properties(&properties);
+        self.write_gas_state(&gas);
+        if self.tangent_buffer.is_some() {
+            let tangents = retain(self.read_tangents(), &kept);
+            self.write_tangents(&tangents);
+        }
+        if !self.systems.is_empty() {
+            let systems = ensemble::compact(&self.systems, &kept);
+            self.write_systems(&systems);
+        }
+        if let Some((thrust, _)) = self.thrust.take() {
+            let thrust: Vec<Thrust> = thrust
+                .into_iter()
+                .filter_map(|entry| {
+                    Some(Thrust {
+                        body: index(entry.body)?,
+                        ..entry
+                    })
+                })
+                .collect();
+            self.write_thrust(&thrust)
+                .expect("The profiles left are of bodies left");
+        }
+        if let Some((mut ephemeris, _)) = self.ephemeris.take() {
+            ephemeris
+                .tracks
+                .retain_mut(|track| match index(track.body) {
+                    Some(body) => {
+                        track.body = body;
+                        true
+                    }
+                    None => false,
+                });
+            if !ephemeris.tracks.is_empty() {
+                self.write_ephemeris(&ephemeris)
+                    .expect("The tracks left are of bodies left");
+            } else if let Some([buffer, _]) = &self.ephemeris_buffers {
+                // Nothing left to prescribe
+                let slice = buffer.slice(..);
+                self.map_slice_blocking(MapMode::Write, slice);
+                slice
+                    .get_mapped_range_mut()
+                    .copy_from_slice(bytemuck::bytes_of(&EphemerisState::default()));
+                buffer.unmap();
+            }
+        }
+        for body in &mut self.dynamic_config.post_newtonian_bodies {
+            *body = renumbered
+                .get(*body as usize)
+                .copied()
+                .flatten()
+                .map_or(u32::MAX, |body| body as u32);
+        }
+        self.maneuvers.renumber(index);
+    }
+
+    /// Set the ephemeris clock from the simulated time, which it was advanced by
+    /// in single precision on the GPU
+    fn synchronize_ephemeris(&mut self) {
+        let time = self.time;
+        let state = match &mut self.ephemeris {
+            Some((ephemeris, state)) => {
+                let position = (time - ephemeris.start) / ephemeris.interval;
+                let span = 0.0..=(ephemeris.num_samples() - 1) as f64;
+                let previous = state.sample_index as f64 + state.fraction as f64;
+                if !span.contains(&position) && span.contains(&previous) {
+                    log::warn!(
+                        "The simulated time {} is outside of the ephemeris from {} to {}, \
+                         holding the prescribed bodies at its ends",
+                        time,
+                        ephemeris.start,
+                        ephemeris.end()
+                    );
+                }
+                let sample = position.floor().clamp(i32::MIN as f64, i32::MAX as f64);
+                state.sample_index = sample as i32;
+                state.fraction = (position - sample) as f32;
+                *state
+            }
+            None => return,
+        };
+        if let Some([buffer, _]) = &self.ephemeris_buffers {
+            let slice = buffer.slice(..);
+            self.map_slice_blocking(MapMode::Write, slice);
+            slice
+                .get_mapped_range_mut()
+                .copy_from_slice(bytemuck::bytes_of(&state));
+            buffer.unmap();
+        }
+    }
+
+    /// Replace the bodies with those of every system of `ensemble`, whose
+    /// bodies only attract each other from now on. Needs `StaticConfig::ensemble`.
+    pub fn write_ensemble(&mut self, ensemble: &Ensemble) -> Result<(), ParabodyError> {
+        assert!(
+            !ensemble.is_empty(),
+            "An ensemble needs at least one system"
+        );
+        self.write_bodies(ensemble.bodies())?;
+        self.write_systems(ensemble.systems());
+        Ok(())
+    }
+
+    /// The latest state of the bodies of each system of the ensemble, which
+    /// shrink as bodies are removed and disappear with their last body
+    pub fn read_systems(&mut self) -> Vec<Vec<Body>> {
+        assert!(!self.systems.is_empty(), "No ensemble was written");
+        let bodies = self.read_bodies();
+        ensemble::split(&self.systems, &bodies)
+            .into_iter()
+            .map(<[Body]>::to_vec)
+            .collect()
+    }
+
+    fn write_systems(&mut self, systems: &[System]) {
+        let buffer = self
+            .system_buffer
+            .as_ref()
+            .expect("Pipeline was created without an ensemble");
+        let slice = buffer.slice(..size_of_val(systems) as u64);
+        self.map_slice_blocking(MapMode::Write, slice);
+        slice
+            .get_mapped_range_mut()
+            .copy_from_slice(bytemuck::cast_slice(systems));
+        buffer.unmap();
+        self.dynamic_config.num_systems = systems.len() as u32;
+        self.systems = systems.to_vec();
+    }
+
+    /// Start the tangent vectors of the bodies over in fixed directions, with the
+    /// chaos indicators from the current time on. Needs `StaticConfig::variational`.
+    pub fn reset_chaos_indicators(&mut self) {
+        let tangents = chaos::initial_tangents(self.dynamic_config.num_bodies as usize);
+        self.write_tangents(&tangents);
+    }
+
+    /// The chaos indicators of every body since the last
+    /// `reset_chaos_indicators`, from the tangent vectors advanced by the steps
+    /// forwards in time
+    pub fn read_chaos_indicators(&self) -> Vec<ChaosIndicators> {
+        self.read_tangents()
+            .iter()
+            .map(ChaosIndicators::from_tangent)
+            .collect()
+    }
+
+    fn write_tangents(&mut self, tangents: &[Tangent]) {
+        let buffer = self
+            .tangent_buffer
+            .as_ref()
+            .expect("Pipeline was created without tangent vectors");
+        let slice = buffer.slice(..size_of_val(tangents) as u64);
+        self.map_slice_blocking(MapMode::Write, slice);
+        slice
+            .get_mapped_range_mut()
+            .copy_from_slice(bytemuck::cast_slice(tangents));
+        buffer.unmap();
+    }
+
+    fn read_tangents(&self) -> Vec<Tangent> {
+        let buffer = self
+            .tangent_buffer
+            .as_ref()
+            .expect("Pipeline was created without tangent vectors");
+        let upper_bound = (self.dynamic_config.num_bodies * size_of::<Tangent>() as u32) as u64;
+        let slice = buffer.slice(..upper_bound);
         self.map_slice_blocking(MapMode::Read, slice);
         let output = bytemuck::cast_slice(slice.get_mapped_range().as_ref()).to_owned();
-        match self.active_source {
-            SourceBuffer::A => self.body_buffers[1].unmap(),
-            SourceBuffer::B => self.body_buffers[0].unmap(),
+        buffer.unmap();
+        output
+    }
+
+    /// The predicates the pipeline was built with, see `PipelineBuilder::events`
+    pub fn event_predicates(&self) -> &[EventPredicate] {
+        &self.event_predicates
+    }
+
+    /// The crossings logged since the log was last read or the state loaded,
+    /// and clear it. Empty unless the pipeline was built with events.
+    pub fn read_events(&mut self) -> EventLog {
+        let buffer = match &self.event_buffers {
+            Some([_, log]) => log,
+            None => return EventLog::default(),
+        };
+        let slice = buffer.slice(..);
+        self.map_slice_blocking(MapMode::Read, slice);
+        let (count, steps, records) = {
+            let data = slice.get_mapped_range();
+            let header: &[u32] = bytemuck::cast_slice(&data[..2 * size_of::<u32>()]);
+            let count = header[0] as usize;
+            let end =
+                2 * size_of::<u32>() + count.min(MAX_EVENT_RECORDS) * size_of::<EventRecord>();
+            let records: Vec<EventRecord> =
+                bytemuck::cast_slice(&data[2 * size_of::<u32>()..end]).to_owned();
+            (count, header[1] as usize, records)
         };
-        return output;
+        buffer.unmap();
+        let mut crossings: Vec<StepCrossing> = records
+            .iter()
+            .map(|record| {
+                let step = record.step as usize;
+                StepCrossing {
+                    predicate: record.predicate as usize,
+                    body: record.body as usize,
+                    step,
+                    times: [self.event_times[step], self.event_times[step + 1]],
+                    values: [record.before, record.after],
+                }
+            })
+            .collect();
+        crossings.sort_by_key(|crossing| (crossing.step, crossing.predicate, crossing.body));
+        self.clear_events();
+        EventLog {
+            steps,
+            crossings,
+            dropped: count.saturating_sub(MAX_EVENT_RECORDS),
+        }
     }
 
-    pub fn submit_and_block(&mut self, num_passes: usize) {
-        // Synchronize configurations
+    /// Empty the event log, logging the steps from the latest state on
+    fn clear_events(&mut self) {
+        self.event_times = vec![self.time];
+        if let Some([_, log]) = &self.event_buffers {
+            let slice = log.slice(..2 * size_of::<u32>() as u64);
+            self.map_slice_blocking(MapMode::Write, slice);
+            slice.get_mapped_range_mut().fill(0);
+            log.unmap();
+        }
+    }
+
+    /// The predicates of `bodies` in place of the latest state, which is kept,
+    /// those of a body after another in the order of the predicates.
+    ///
+    /// Panics if there are more `bodies` than `StaticConfig::max_bodies`.
+    pub fn evaluate_events(&mut self, bodies: &[Body]) -> Vec<f32> {
+        let probe = match self.kernels.event_passes {
+            Some([_, _, _, probe]) => probe,
+            None => panic!("Pipeline was created without events"),
+        };
+        let latest = self.read_bodies();
+        self.write_bodies(bodies)
+            .unwrap_or_else(|error| panic!("{}", error));
         self.synchronize_dynamic_config();
-        // Fire off the job
-        let config_bindgroup = self.device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Config bind group"),
-            layout: &self.config_bindgroup_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: self.config_buffer.as_entire_binding(),
-            }],
-        });
-        let active_a_bindgroup = self.device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Active-A bind group"),
-            layout: &self.body_bindgroup_layout,
+        if let Err(error) = self.submit_event_pass(probe, "events_probe") {
+            panic!("{}", error);
+        }
+        let buffer = &self
+            .event_buffers
+            .as_ref()
+            .expect("Created with the kernels")[0];
+        let predicates = self.event_predicates.len();
+        let offset = predicates * self.static_config.max_bodies as usize * size_of::<f32>();
+        let size = predicates * bodies.len() * size_of::<f32>();
+        let slice = buffer.slice(..(offset + size) as u64);
+        self.map_slice_blocking(MapMode::Read, slice);
+        let values = bytemuck::cast_slice(&slice.get_mapped_range()[offset..]).to_owned();
+        buffer.unmap();
+        self.upload_bodies(&latest);
+        values
+    }
+
+    /// Dispatch an event kernel over the latest state in a submission of its
+    /// own, later submissions are ordered after it
+    fn submit_event_pass(&self, kernel: usize, entry_point: &str) -> Result<(), ParabodyError> {
+        let bindgroups = self.create_bindgroups();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        self.dispatch(
+            &mut encoder,
+            self.kernels.registry.get(kernel),
+            entry_point,
+            Domain::Bodies,
+            bindgroups.select(self.active_source, 0),
+            StepParams {
+                num_bodies: self.dynamic_config.num_bodies as i32,
+                dt: self.dynamic_config.dt,
+            },
+        )?;
+        self.queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
+
+    /// Read the per-body properties last written by `write_properties`
+    pub fn read_properties(&self) -> Vec<BodyProperties> {
+        let upper_bound =
+            (self.dynamic_config.num_bodies * size_of::<BodyProperties>() as u32) as u64;
+        let slice = self.properties_buffer.slice(..upper_bound);
+        self.map_slice_blocking(MapMode::Read, slice);
+        let output = bytemuck::cast_slice(slice.get_mapped_range().as_ref()).to_owned();
+        self.properties_buffer.unmap();
+        output
+    }
+
+    /// Read the gas state, with the densities and pressures from the start of the last step
+    pub fn read_gas_state(&self) -> Vec<GasState> {
+        let upper_bound = (self.dynamic_config.num_bodies * size_of::<GasState>() as u32) as u64;
+        let slice = self.gas_buffer.slice(..upper_bound);
+        self.map_slice_blocking(MapMode::Read, slice);
+        let output = bytemuck::cast_slice(slice.get_mapped_range().as_ref()).to_owned();
+        self.gas_buffer.unmap();
+        output
+    }
+
+    pub fn read_bodies(&mut self) -> Vec<Body> {
+        if let Some(bodies) = self.mirror.as_ref().and_then(HostMirror::get) {
+            return bodies.to_vec();
+        }
+        // Wait for the most recently written buffer to become mappable and read it out to the host
+        let upper_bound = (self.dynamic_config.num_bodies * size_of::<Body>() as u32) as u64;
+        let slice = match self.active_source {
+            SourceBuffer::A => self.body_buffers[0].slice(..upper_bound),
+            SourceBuffer::B => self.body_buffers[1].slice(..upper_bound),
+        };
+        self.map_slice_blocking(MapMode::Read, slice);
+        let output: Vec<Body> = bytemuck::cast_slice(slice.get_mapped_range().as_ref()).to_owned();
+        match self.active_source {
+            SourceBuffer::A => self.body_buffers[0].unmap(),
+            SourceBuffer::B => self.body_buffers[1].unmap(),
+        };
+        if let Some(mirror) = &mut self.mirror {
+            mirror.update(&output);
+        }
+        output
+    }
+
+    /// Read one field of every body in the latest state, the components packed in
+    /// body order. The field is gathered into a compact buffer on the GPU, so only
+    /// it is copied back.
+    pub fn read_field(&self, field: BodyField) -> Vec<f32> {
+        if let Some(bodies) = self.mirror.as_ref().and_then(HostMirror::get) {
+            return bodies
+                .iter()
+                .flat_map(|body| field.of(body))
+                .copied()
+                .collect();
+        }
+        let num_bodies = self.dynamic_config.num_bodies;
+        if num_bodies == 0 {
+            return Vec::new();
+        }
+        let size = (num_bodies as usize * field.components() * size_of::<f32>()) as u64;
+        let bindgroup = self.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Gather bind group"),
+            layout: &self.gather_bindgroup_layout,
             entries: &[
                 BindGroupEntry {
                     binding: 0,
-                    resource: self.body_buffers[0].as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: self.body_buffers[1].as_entire_binding(),
+                    resource: self.body_buffer().as_entire_binding(),
                 },
-            ],
-        });
-        let active_b_bindgroup = self.device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Active-B bind group"),
-            layout: &self.body_bindgroup_layout,
-            entries: &[
                 BindGroupEntry {
                     binding: 1,
-                    resource: self.body_buffers[0].as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 0,
-                    resource: self.body_buffers[1].as_entire_binding(),
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: &self.gather_buffer,
+                        offset: 0,
+                        size: NonZeroU64::new(size),
+                    }),
                 },
             ],
         });
-
         let mut encoder = self
             .device
             .create_command_encoder(&CommandEncoderDescriptor { label: None });
-
-        for _ in 0..num_passes {
+        {
             let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
-            pass.set_pipeline(&self.pipeline);
-            pass.set_bind_group(0, &config_bindgroup, &[]);
-            match self.active_source {
-                SourceBuffer::A => pass.set_bind_group(1, &active_a_bindgroup, &[]),
-                SourceBuffer::B => pass.set_bind_group(1, &active_b_bindgroup, &[]),
-            };
-            pass.dispatch_workgroups(
-                (self.dynamic_config.num_bodies as f32 / 64.0).ceil() as u32,
-                1,
-                1,
+            pass.set_pipeline(
+                self.kernels
+                    .registry
+                    .get(self.kernels.gather[field as usize]),
             );
-            self.active_source = self.active_source.other();
+            pass.set_bind_group(0, &bindgroup, &[]);
+            pass.dispatch_workgroups(num_bodies.div_ceil(self.static_config.workgroup_size), 1, 1);
         }
+        encoder.copy_buffer_to_buffer(&self.gather_buffer, 0, &self.gather_staging_buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+        let slice = self.gather_staging_buffer.slice(..size);
+        self.map_slice_blocking(MapMode::Read, slice);
+        let output = bytemuck::cast_slice(slice.get_mapped_range().as_ref()).to_owned();
+        self.gather_staging_buffer.unmap();
+        output
+    }
 
-        println!("Submitting");
+    /// The `k` nearest neighbors of every body in the latest state and the
+    /// density about it, searched on the GPU in a spatial hash of the bodies.
+    /// Bodies far from the rest are searched again on the host. Panics unless `k` is from one to `MAX_NEIGHBORS`.
+    pub fn search_neighbors(&mut self, k: usize) -> NeighborSearch {
+        assert!(
+            (1..=MAX_NEIGHBORS).contains(&k),
+            "Can search 1 to {} neighbors, not {}",
+            MAX_NEIGHBORS,
+            k
+        );
+        let num_bodies = self.dynamic_config.num_bodies as usize;
+        if num_bodies == 0 {
+            return NeighborSearch::new(k, Vec::new(), Vec::new());
+        }
+        let bodies = self.read_bodies();
+        let positions: Vec<[f32; 3]> = bodies.iter().map(|body| body.position).collect();
+        let query = neighbors::query(&positions, k, self.dynamic_config.box_size);
+        if self.search_layouts.is_none() {
+            let bindgroup_layout =
+                self.device
+                    .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                        label: Some("Search bind group layout"),
+                        entries: &[
+                            storage_entry(0, true),
+                            storage_entry(1, true),
+                            storage_entry(2, false),
+                            storage_entry(3, false),
+                            storage_entry(4, false),
+                            storage_entry(5, false),
+                            storage_entry(6, false),
+                        ],
+                    });
+            let layout = self
+                .device
+                .create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Search pipeline layout"),
+                    bind_group_layouts: &[&bindgroup_layout],
+                    ..Default::default()
+                });
+            self.search_layouts = Some((bindgroup_layout, layout));
+        }
+        let (bindgroup_layout, layout) = self.search_layouts.as_ref().expect("Created above");
+        let mut kernels = [0; 5];
+        for (kernel, entry_point) in kernels.iter_mut().zip(neighbors::SEARCH_ENTRY_POINTS) {
+            *kernel = self
+                .kernels
+                .registry
+                .kernel(&self.device, NEIGHBORS_MODULE, entry_point, layout)
+                .unwrap_or_else(|error| panic!("{}", error));
+        }
+        let query_buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Neighbor query"),
+            size: size_of::<NeighborQuery>() as u64,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: true,
+        });
+        query_buffer
+            .slice(..)
+            .get_mapped_range_mut()
+            .copy_from_slice(bytemuck::bytes_of(&query));
+        query_buffer.unmap();
+        let storage = |label, size: usize, usage| {
+            self.device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size: size as u64,
+                usage: BufferUsages::STORAGE | usage,
+                mapped_at_creation: false,
+            })
+        };
+        let table_size = query.table_size as usize;
+        let neighbors_size = num_bodies * k * size_of::<Neighbor>();
+        let densities_size = num_bodies * size_of::<f32>();
+        let buffers = [
+            storage(
+                "Search counts",
+                table_size * size_of::<u32>(),
+                BufferUsages::empty(),
+            ),
+            storage(
+                "Search start",
+                (table_size + 1) * size_of::<u32>(),
+                BufferUsages::empty(),
+            ),
+            storage(
+                "Search bodies",
+                num_bodies * size_of::<u32>(),
+                BufferUsages::empty(),
+            ),
+            storage("Neighbors", neighbors_size, BufferUsages::COPY_SRC),
+            storage("Densities", densities_size, BufferUsages::COPY_SRC),
+        ];
+        let staging = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Neighbors staging"),
+            size: (neighbors_size + densities_size) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut entries = vec![
+            BindGroupEntry {
+                binding: 0,
+                resource: self.body_buffer().as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: query_buffer.as_entire_binding(),
+            },
+        ];
+        entries.extend(
+            buffers
+                .iter()
+                .zip(2..)
+                .map(|(buffer, binding)| BindGroupEntry {
+                    binding,
+                    resource: buffer.as_entire_binding(),
+                }),
+        );
+        let bindgroup = self.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Search bind group"),
+            layout: bindgroup_layout,
+            entries: &entries,
+        });
+        let workgroup_size = self.static_config.workgroup_size;
+        let workgroups = [
+            query.table_size.div_ceil(workgroup_size),
+            query.num_bodies.div_ceil(workgroup_size),
+            1,
+            query.num_bodies.div_ceil(workgroup_size),
+            query.num_bodies.div_ceil(workgroup_size),
+        ];
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
+            pass.set_bind_group(0, &bindgroup, &[]);
+            for (kernel, workgroups) in kernels.iter().zip(workgroups) {
+                pass.set_pipeline(self.kernels.registry.get(*kernel));
+                pass.dispatch_workgroups(workgroups, 1, 1);
+            }
+        }
+        encoder.copy_buffer_to_buffer(&buffers[3], 0, &staging, 0, neighbors_size as u64);
+        encoder.copy_buffer_to_buffer(
+            &buffers[4],
+            0,
+            &staging,
+            neighbors_size as u64,
+            densities_size as u64,
+        );
         self.queue.submit(Some(encoder.finish()));
+        let slice = staging.slice(..);
+        self.map_slice_blocking(MapMode::Read, slice);
+        let mut search = {
+            let mapped = slice.get_mapped_range();
+            NeighborSearch::new(
+                k,
+                bytemuck::cast_slice(&mapped[..neighbors_size]).to_owned(),
+                bytemuck::cast_slice(&mapped[neighbors_size..]).to_owned(),
+            )
+        };
+        staging.unmap();
+        search.search_outliers(&query, &bodies);
+        search
+    }
+
+    /// Read the positions of the latest state, see `read_field`
+    pub fn read_positions(&self) -> Vec<[f32; 3]> {
+        bytemuck::cast_slice(&self.read_field(BodyField::Position)).to_owned()
+    }
+
+    /// Read the velocities of the latest state, see `read_field`
+    pub fn read_velocities(&self) -> Vec<[f32; 3]> {
+        bytemuck::cast_slice(&self.read_field(BodyField::Velocity)).to_owned()
+    }
+
+    /// Read the accelerations computed from the state before the last step
+    pub fn read_accelerations(&self) -> Vec<[f32; 3]> {
+        let upper_bound = (self.dynamic_config.num_bodies * size_of::<[f32; 4]>() as u32) as u64;
+        let slice = self.acceleration_buffer.slice(..upper_bound);
+        self.map_slice_blocking(MapMode::Read, slice);
+        let output = bytemuck::cast_slice::<u8, [f32; 4]>(slice.get_mapped_range().as_ref())
+            .iter()
+            .map(|a| [a[0], a[1], a[2]])
+            .collect();
+        self.acceleration_buffer.unmap();
+        output
+    }
+
+    /// Run `num_passes` steps and wait for them, submitted in chunks of at most
+    /// the steps set with `set_steps_per_submit`. Panics if wgpu rejects a
+    /// dispatch, `run` returns the error instead.
+    pub fn submit_and_block(&mut self, num_passes: usize) {
+        let dt = self.dynamic_config.dt;
+        if let Err(error) = self.submit_steps(num_passes, |_| dt, None, None) {
+            panic!("{}", error);
+        }
+    }
+
+    /// Run `num_steps` steps like `submit_and_block`, stopping before the next
+    /// submission once `token` is cancelled. The steps submitted so far complete,
+    /// so the state stays consistent. Returns the number of steps completed.
+    pub fn run(
+        &mut self,
+        num_steps: usize,
+        token: &CancellationToken,
+    ) -> Result<usize, ParabodyError> {
+        let dt = self.dynamic_config.dt;
+        // Without bodies there is no state to recover
+        if !self.resilient || self.dynamic_config.num_bodies == 0 {
+            return self.submit_steps(num_steps, |_| dt, None, Some(token));
+        }
+        let mut completed = 0;
+        while completed < num_steps && !token.is_cancelled() {
+            let checkpoint = self.capture();
+            let maneuvers = self.maneuvers.clone();
+            let steps = self.steps_per_submit.min(num_steps - completed);
+            // wgpu reports a lost device as a panic or an error of whichever call
+            // came next
+            let submitted = panic::catch_unwind(AssertUnwindSafe(|| {
+                self.submit_steps(steps, |_| dt, None, Some(token))
+            }));
+            match submitted {
+                Ok(Ok(steps)) => completed += steps,
+                _ if self.device_is_lost() => {
+                    log::warn!(
+                        "The device was lost after {} of {} steps, recreating it",
+                        completed,
+                        num_steps
+                    );
+                    self.recover(&checkpoint);
+                    self.maneuvers = maneuvers;
+                }
+                Ok(Err(error)) => return Err(error),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+        Ok(completed)
+    }
+
+    /// Run `num_steps` steps backwards in time like `run`, such as to propagate
+    /// an orbit to an earlier epoch, then turn to the direction before
+    pub fn run_backward(&mut self, num_steps: usize) -> Result<usize, ParabodyError> {
+        let direction = self.time_direction;
+        self.set_time_direction(TimeDirection::Backward);
+        let completed = self.run(num_steps, &CancellationToken::new());
+        self.set_time_direction(direction);
+        completed
+    }
+
+    /// Run a step of each size in `dts` and wait for them, for ramped or adaptive
+    /// step sizes. The sizes are negated while integrating backwards. Where push
+    /// constants are supported the steps share submissions, otherwise every change
+    /// of the step size remaps the config buffer in a submission of its own.
+    pub fn submit_schedule(&mut self, dts: &[f32]) {
+        let sign = self.time_direction.sign();
+        if let Err(error) = self.submit_steps(dts.len(), |step| sign * dts[step], None, None) {
+            panic!("{}", error);
+        }
+    }
+
+    /// Whether the step size and body count are pushed with every dispatch
+    /// instead of read from the config buffer
+    pub fn uses_push_constants(&self) -> bool {
+        self.pipeline_layouts.bodies.is_some()
+    }
+
+    /// Run `num_steps` steps, calling `on_snapshot` with the number of steps
+    /// completed, the time and the bodies after every `interval` steps and after
+    /// the last. Snapshots are copied into a ring of `READBACK_BUFFERS` staging
+    /// buffers and mapped while the following steps are already running, so that
+    /// `on_snapshot`, such as writing to disk, overlaps the simulation. Profiling
+    /// and the slack check wait for the GPU and undo the overlap.
+    pub fn run_with_snapshots(
+        &mut self,
+        num_steps: usize,
+        interval: usize,
+        mut on_snapshot: impl FnMut(usize, f64, &[Body]),
+    ) {
+        assert!(interval > 0, "The snapshot interval must be positive");
+        if self.readback_buffers.is_empty() {
+            self.readback_buffers = (0..READBACK_BUFFERS)
+                .map(|_| {
+                    self.device.create_buffer(&BufferDescriptor {
+                        label: Some("Snapshot readback"),
+                        size: (self.static_config.max_bodies as usize * size_of::<Body>()) as u64,
+                        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    })
+                })
+                .collect();
+        }
+        let size = (self.dynamic_config.num_bodies as usize * size_of::<Body>()) as u64;
+        let dt = self.dynamic_config.dt;
+        if size == 0 || self.resilient {
+            // Nothing to copy, and empty buffers can't be mapped. Resilient runs read
+            // back in between instead, a lost device would take pending mappings along.
+            let mut completed = 0;
+            while completed < num_steps {
+                let steps = interval.min(num_steps - completed);
+                if let Err(error) = self.run(steps, &CancellationToken::new()) {
+                    panic!("{}", error);
+                }
+                completed += steps;
+                let bodies = if size == 0 {
+                    Vec::new()
+                } else {
+                    self.read_bodies()
+                };
+                on_snapshot(completed, self.time, &bodies);
+            }
+            return;
+        }
+        let mut pending = VecDeque::new();
+        let mut completed = 0;
+        let mut snapshots = 0;
+        while completed < num_steps {
+            let buffer = snapshots % READBACK_BUFFERS;
+            // Reusing the oldest buffer waits for its snapshot to be handled
+            if pending.len() == READBACK_BUFFERS {
+                self.finish_snapshot(pending.pop_front().unwrap(), &mut on_snapshot);
+            }
+            let steps = interval.min(num_steps - completed);
+            if let Err(error) = self.submit_steps(steps, |_| dt, Some(buffer), None) {
+                panic!("{}", error);
+            }
+            completed += steps;
+            snapshots += 1;
+            let mapped = Arc::new(AtomicBool::new(false));
+            let moved_mapped = mapped.clone();
+            self.readback_buffers[buffer]
+                .slice(..size)
+                .map_async(MapMode::Read, move |result| {
+                    result.expect("Failed to map a snapshot");
+                    moved_mapped.store(true, Ordering::SeqCst);
+                });
+            pending.push_back(PendingSnapshot {
+                buffer,
+                completed,
+                time: self.time,
+                mapped,
+            });
+            // Hand over the snapshots which are already mapped, without waiting
+            self.device.poll(Maintain::Poll);
+            while pending
+                .front()
+                .is_some_and(|snapshot| snapshot.mapped.load(Ordering::SeqCst))
+            {
+                self.finish_snapshot(pending.pop_front().unwrap(), &mut on_snapshot);
+            }
+        }
+        for snapshot in pending {
+            self.finish_snapshot(snapshot, &mut on_snapshot);
+        }
+    }
+
+    /// Wait for the mapping of `snapshot`, pass it to `on_snapshot` and unmap it
+    fn finish_snapshot(
+        &self,
+        snapshot: PendingSnapshot,
+        on_snapshot: &mut impl FnMut(usize, f64, &[Body]),
+    ) {
+        while !snapshot.mapped.load(Ordering::SeqCst) {
+            self.device.poll(Maintain::Wait);
+        }
+        let size = (self.dynamic_config.num_bodies as usize * size_of::<Body>()) as u64;
+        let buffer = &self.readback_buffers[snapshot.buffer];
+        on_snapshot(
+            snapshot.completed,
+            snapshot.time,
+            bytemuck::cast_slice(buffer.slice(..size).get_mapped_range().as_ref()),
+        );
+        buffer.unmap();
+    }
+
+    /// Run `num_steps` steps and wait for them, step `i` of signed size `dt(i)`.
+    /// With a `snapshot`, the last submission also copies the bodies into that
+    /// readback buffer and isn't waited for. Stops between submissions once
+    /// `token` is cancelled, returns the number of steps submitted. A submission
+    /// failing validation isn't submitted, those before it are kept.
+    fn submit_steps(
+        &mut self,
+        num_steps: usize,
+        dt: impl Fn(usize) -> f32,
+        snapshot: Option<usize>,
+        token: Option<&CancellationToken>,
+    ) -> Result<usize, ParabodyError> {
+        let base_dt = self.dynamic_config.dt;
+        // Synchronize configurations
+        self.synchronize_dynamic_config();
+        if let Some(mirror) = &mut self.mirror {
+            mirror.invalidate();
+        }
+        let slack_check = self.slack_check && !self.slack().is_empty();
+        if slack_check {
+            self.fill_slack();
+        }
+        // Fire off the job
+        let bindgroups = self.create_bindgroups();
+        let event_passes = self.kernels.event_passes.filter(|_| !self.events_paused);
+        if let Some([_, _, prime, _]) = event_passes.filter(|_| !self.events_primed) {
+            self.submit_event_pass(prime, "events_prime")?;
+            self.events_primed = true;
+        }
+        let logged = self.event_times.len();
+        let mut grid_swaps = 0;
+        let mut source = self.active_source;
+        let mut submitted = 0;
+        while submitted < num_steps && !token.is_some_and(CancellationToken::is_cancelled) {
+            self.reload_shaders();
+            self.synchronize_ephemeris();
+            self.synchronize_thrust();
+            self.apply_maneuvers(dt(submitted));
+            let (kernels, pass_graph) = match self.time_direction {
+                TimeDirection::Forward => (&self.kernels.passes, &self.kernels.pass_graph),
+                TimeDirection::Backward => (
+                    &self.kernels.reversed_passes,
+                    &self.kernels.reversed_pass_graph,
+                ),
+            };
+            let mut chunk = self.steps_per_submit.min(num_steps - submitted);
+            if !self.maneuvers.is_empty() {
+                // End the submission where the next maneuver is due
+                chunk = self
+                    .maneuvers
+                    .steps_until(self.time, self.time_direction, chunk, |step| {
+                        dt(submitted + step)
+                    });
+            }
+            if self.profiler.is_some() {
+                // One timestamp after every pass and one before the first
+                let steps = (MAX_TIMESTAMPS as usize - 1) / pass_graph.passes().len();
+                chunk = chunk.min(steps);
+            }
+            if !self.uses_push_constants() {
+                // The config buffer holds one step size per submission
+                let chunk_dt = dt(submitted);
+                chunk = (1..chunk)
+                    .find(|&step| dt(submitted + step) != chunk_dt)
+                    .unwrap_or(chunk);
+                if chunk_dt != self.dynamic_config.dt {
+                    self.dynamic_config.dt = chunk_dt;
+                    self.synchronize_dynamic_config();
+                }
+            }
+            let mut encoder = self
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor { label: None });
+            let mut timestamps = 0;
+            if let Some(profiler) = &self.profiler {
+                profiler.timestamp(&mut encoder, timestamps);
+                timestamps += 1;
+            }
+            let mut elapsed = 0.0;
+            let mut failed = None;
+            'steps: for step in submitted..submitted + chunk {
+                let params = StepParams {
+                    num_bodies: self.dynamic_config.num_bodies as i32,
+                    dt: dt(step),
+                };
+                elapsed += params.dt as f64;
+                // Each kernel gets its own compute pass so that writes are visible to the next
+                for (kernel, description) in kernels.iter().zip(pass_graph.passes()) {
+                    if let Err(error) = self.dispatch(
+                        &mut encoder,
+                        self.kernels.registry.get(*kernel),
+                        &description.entry_point,
+                        description.domain,
+                        bindgroups.select(source, grid_swaps),
+                        params,
+                    ) {
+                        failed = Some(error);
+                        break 'steps;
+                    }
+                    if let Some(profiler) = &self.profiler {
+                        profiler.timestamp(&mut encoder, timestamps);
+                        timestamps += 1;
+                    }
+                    if description.swaps_grid {
+                        grid_swaps += 1;
+                    }
+                    if description.swaps_bodies {
+                        source = source.other();
+                    }
+                }
+                source = source.other();
+                if let (Some(kernel), TimeDirection::Forward) =
+                    (self.kernels.variational_pass, self.time_direction)
+                {
+                    if let Err(error) = self.dispatch(
+                        &mut encoder,
+                        self.kernels.registry.get(kernel),
+                        "variational",
+                        Domain::Bodies,
+                        bindgroups.select(source, grid_swaps),
+                        params,
+                    ) {
+                        failed = Some(error);
+                        break 'steps;
+                    }
+                }
+                if let (Some((interval, since)), Some([reduce, shift])) =
+                    (&mut self.com_correction, self.kernels.com_passes)
+                {
+                    *since += 1;
+                    if *since >= *interval {
+                        *since = 0;
+                        let workgroup_size = self.static_config.workgroup_size;
+                        for (kernel, entry_point, domain) in [
+                            (reduce, "com_reduce", Domain::Threads(workgroup_size)),
+                            (shift, "com_shift", Domain::Bodies),
+                        ] {
+                            if let Err(error) = self.dispatch(
+                                &mut encoder,
+                                self.kernels.registry.get(kernel),
+                                entry_point,
+                                domain,
+                                bindgroups.select(source, grid_swaps),
+                                params,
+                            ) {
+                                failed = Some(error);
+                                break 'steps;
+                            }
+                        }
+                        source = source.other();
+                    }
+                }
+                // After the shift, so that the predicates see the bodies the step ends with
+                if let Some([log, tick, _, _]) = event_passes {
+                    for (kernel, entry_point, domain) in [
+                        (log, "events", Domain::Bodies),
+                        (tick, "events_tick", Domain::Threads(1)),
+                    ] {
+                        if let Err(error) = self.dispatch(
+                            &mut encoder,
+                            self.kernels.registry.get(kernel),
+                            entry_point,
+                            domain,
+                            bindgroups.select(source, grid_swaps),
+                            params,
+                        ) {
+                            failed = Some(error);
+                            break 'steps;
+                        }
+                    }
+                    self.event_times.push(self.time + elapsed);
+                }
+            }
+            if let Some(error) = failed {
+                self.dynamic_config.dt = base_dt;
+                self.event_times.truncate(logged + submitted);
+                return Err(error);
+            }
+            log::debug!(
+                "Submitting steps {} to {} of {}",
+                submitted + 1,
+                submitted + chunk,
+                num_steps
+            );
+            if let Some(profiler) = &self.profiler {
+                profiler.resolve(&mut encoder, timestamps);
+            }
+            submitted += chunk;
+            let snapshot = snapshot.filter(|_| submitted == num_steps);
+            if let Some(index) = snapshot {
+                let latest = match source {
+                    SourceBuffer::A => &self.body_buffers[0],
+                    SourceBuffer::B => &self.body_buffers[1],
+                };
+                let size = (self.dynamic_config.num_bodies as usize * size_of::<Body>()) as u64;
+                encoder.copy_buffer_to_buffer(latest, 0, &self.readback_buffers[index], 0, size);
+            }
+            self.queue.submit(Some(encoder.finish()));
+            if snapshot.is_none() {
+                self.wait_for_queue();
+            }
+            if let Some(profiler) = &self.profiler {
+                let size = timestamps as u64 * size_of::<u64>() as u64;
+                let slice = profiler.readback_buffer().slice(..size);
+                self.map_slice_blocking(MapMode::Read, slice);
+                let values: Vec<u64> =
+                    bytemuck::cast_slice(slice.get_mapped_range().as_ref()).to_owned();
+                profiler.readback_buffer().unmap();
+                let pass_graph = match self.time_direction {
+                    TimeDirection::Forward => &self.kernels.pass_graph,
+                    TimeDirection::Backward => &self.kernels.reversed_pass_graph,
+                };
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.record(&values, chunk, pass_graph);
+                }
+            }
+            self.time += elapsed;
+            self.active_source = source;
+            if let Some((eta, callback)) = &mut self.progress {
+                eta.record(eta.completed_steps() + chunk);
+                callback(&Progress {
+                    completed_steps: eta.completed_steps(),
+                    total_steps: eta.total_steps(),
+                    time: self.time,
+                    steps_per_second: eta.steps_per_second(),
+                    eta: eta.remaining(),
+                });
+            }
+        }
+        // The step size of the schedule only applies to its steps
+        self.dynamic_config.dt = base_dt;
+        if slack_check {
+            self.verify_slack();
+        }
+        if submitted < num_steps {
+            log::debug!("Cancelled after {} of {} steps", submitted, num_steps);
+        } else {
+            log::debug!("Done");
+        }
+        Ok(submitted)
+    }
 
+    /// Block until all submitted work has finished
+    fn wait_for_queue(&self) {
         let signal = Arc::new(AtomicBool::new(false));
         let moved_signal = signal.clone();
         self.queue.on_submitted_work_done(move || {
@@ -301,7 +3475,260 @@ impl Pipeline {
         while !signal.load(Ordering::SeqCst) {
             self.device.poll(Maintain::Poll);
         }
-        println!("Done");
+    }
+
+    /// Evaluate the gravitational potential of every body in the latest state
+    /// into the `w` of its acceleration, where `read_potentials` and renderers
+    /// reading `acceleration_buffer` find it. The next step overwrites it.
+    /// Only submits the work, later submissions are ordered after it.
+    pub fn compute_potentials(&mut self) {
+        self.synchronize_dynamic_config();
+        let bindgroups = self.create_bindgroups();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        if let Err(error) = self.dispatch(
+            &mut encoder,
+            self.kernels.registry.get(self.kernels.potential_pass),
+            "potential",
+            Domain::Bodies,
+            bindgroups.select(self.active_source, 0),
+            StepParams {
+                num_bodies: self.dynamic_config.num_bodies as i32,
+                dt: self.dynamic_config.dt,
+            },
+        ) {
+            panic!("{}", error);
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Potentials from the last `compute_potentials`
+    pub fn read_potentials(&self) -> Vec<f32> {
+        let upper_bound = (self.dynamic_config.num_bodies * size_of::<[f32; 4]>() as u32) as u64;
+        let slice = self.acceleration_buffer.slice(..upper_bound);
+        self.map_slice_blocking(MapMode::Read, slice);
+        let output = bytemuck::cast_slice::<u8, [f32; 4]>(slice.get_mapped_range().as_ref())
+            .iter()
+            .map(|a| a[3])
+            .collect();
+        self.acceleration_buffer.unmap();
+        output
+    }
+
+    /// The potentials of `bodies` in the field of each other alone, as the
+    /// kernel of `compute_potentials` evaluates them. Leaves the latest state as
+    /// it was, outside of ensembles.
+    ///
+    /// Panics if there are more `bodies` than `StaticConfig::max_bodies`.
+    pub fn evaluate_potentials(&mut self, bodies: &[Body]) -> Vec<f32> {
+        let latest = self.read_bodies();
+        self.write_bodies(bodies)
+            .unwrap_or_else(|error| panic!("{}", error));
+        self.compute_potentials();
+        let potentials = self.read_potentials();
+        self.upload_bodies(&latest);
+        potentials
+    }
+
+    /// Accelerations with the potentials in `w`, also usable as a vertex buffer
+    pub fn acceleration_buffer(&self) -> &wgpu::Buffer {
+        &self.acceleration_buffer
+    }
+
+    fn create_bindgroups(&self) -> BindGroups {
+        let config = self.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Config bind group"),
+            layout: &self.config_bindgroup_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: self.config_buffer.as_entire_binding(),
+            }],
+        });
+        let body_bindgroup = |label, source: &wgpu::Buffer, destination: &wgpu::Buffer| {
+            let mut entries = vec![
+                BindGroupEntry {
+                    binding: 0,
+                    resource: source.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: destination.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.acceleration_buffer.as_entire_binding(),
+                },
+            ];
+            if self.static_config.uses_properties() {
+                entries.push(BindGroupEntry {
+                    binding: 3,
+                    resource: self.properties_buffer.as_entire_binding(),
+                });
+            }
+            if let Some(buffers) = &self.neighbor_buffers {
+                entries.extend((4..).zip(buffers).map(|(binding, buffer)| BindGroupEntry {
+                    binding,
+                    resource: buffer.as_entire_binding(),
+                }));
+            }
+            if self.static_config.hydrodynamics.is_some() {
+                entries.push(BindGroupEntry {
+                    binding: 7,
+                    resource: self.gas_buffer.as_entire_binding(),
+                });
+            }
+            if let Some(buffers) = &self.thrust_buffers {
+                entries.extend((10..).zip(buffers).map(|(binding, buffer)| BindGroupEntry {
+                    binding,
+                    resource: buffer.as_entire_binding(),
+                }));
+            }
+            if let Some(buffer) = &self.com_buffer {
+                entries.push(BindGroupEntry {
+                    binding: 12,
+                    resource: buffer.as_entire_binding(),
+                });
+            }
+            if let Some(buffer) = &self.tangent_buffer {
+                entries.push(BindGroupEntry {
+                    binding: 13,
+                    resource: buffer.as_entire_binding(),
+                });
+            }
+            if let Some(buffer) = &self.system_buffer {
+                entries.push(BindGroupEntry {
+                    binding: 14,
+                    resource: buffer.as_entire_binding(),
+                });
+            }
+            if let Some(buffers) = &self.event_buffers {
+                entries.extend((15..).zip(buffers).map(|(binding, buffer)| BindGroupEntry {
+                    binding,
+                    resource: buffer.as_entire_binding(),
+                }));
+            }
+            if let Some(buffers) = &self.ephemeris_buffers {
+                entries.extend((8..).zip(buffers).map(|(binding, buffer)| BindGroupEntry {
+                    binding,
+                    resource: buffer.as_entire_binding(),
+                }));
+            }
+            self.device.create_bind_group(&BindGroupDescriptor {
+                label: Some(label),
+                layout: &self.body_bindgroup_layout,
+                entries: &entries,
+            })
+        };
+        let active_a = body_bindgroup(
+            "Active-A bind group",
+            &self.body_buffers[0],
+            &self.body_buffers[1],
+        );
+        let active_b = body_bindgroup(
+            "Active-B bind group",
+            &self.body_buffers[1],
+            &self.body_buffers[0],
+        );
+        // Both orders of the complex grids, indexed by the number of grid swaps so far
+        let grids = self
+            .grid_buffers
+            .as_ref()
+            .zip(self.grid_bindgroup_layout.as_ref())
+            .map(|([density, a, b], layout)| {
+                assert!(
+                    self.dynamic_config.box_size > 0.0,
+                    "The particle-mesh solver requires a periodic box"
+                );
+                [(a, b), (b, a)].map(|(source, destination)| {
+                    self.device.create_bind_group(&BindGroupDescriptor {
+                        label: Some("Grid bind group"),
+                        layout,
+                        entries: &[
+                            BindGroupEntry {
+                                binding: 0,
+                                resource: density.as_entire_binding(),
+                            },
+                            BindGroupEntry {
+                                binding: 1,
+                                resource: source.as_entire_binding(),
+                            },
+                            BindGroupEntry {
+                                binding: 2,
+                                resource: destination.as_entire_binding(),
+                            },
+                        ],
+                    })
+                })
+            });
+        BindGroups {
+            config,
+            active_a,
+            active_b,
+            grids,
+        }
+    }
+
+    /// Record one kernel as its own compute pass, reading `source`. The pass is
+    /// validated as it ends, errors name the kernel by `entry_point`.
+    fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        kernel: &wgpu::ComputePipeline,
+        entry_point: &str,
+        domain: Domain,
+        bindgroups: [Option<&wgpu::BindGroup>; 3],
+        params: StepParams,
+    ) -> Result<(), ParabodyError> {
+        let threads = match domain {
+            Domain::Bodies => self.dynamic_config.num_bodies,
+            Domain::Threads(threads) => threads,
+        };
+        scoped(
+            &self.device,
+            || format!("Could not dispatch kernel {}", entry_point),
+            || {
+                let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
+                pass.set_pipeline(kernel);
+                if self.uses_push_constants() && matches!(domain, Domain::Bodies) {
+                    pass.set_push_constants(0, bytemuck::bytes_of(&params));
+                }
+                for (index, bindgroup) in bindgroups.into_iter().enumerate() {
+                    if let Some(bindgroup) = bindgroup {
+                        pass.set_bind_group(index as u32, bindgroup, &[]);
+                    }
+                }
+                pass.dispatch_workgroups(
+                    (threads as f32 / self.static_config.workgroup_size as f32).ceil() as u32,
+                    1,
+                    1,
+                );
+            },
+        )
+    }
+
+    /// Whether the device stopped running work, such as after a driver reset.
+    /// Probed by mapping a buffer, whose callback gets an error on a lost device.
+    fn device_is_lost(&self) -> bool {
+        let probe = || {
+            let buffer = scoped(&self.device, String::new, || {
+                self.device.create_buffer(&BufferDescriptor {
+                    label: Some("Device probe"),
+                    size: size_of::<u32>() as u64,
+                    usage: BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                })
+            })
+            .ok()?;
+            let (sender, receiver) = mpsc::channel();
+            buffer.slice(..).map_async(MapMode::Read, move |result| {
+                sender.send(result.is_ok()).ok();
+            });
+            self.device.poll(Maintain::Wait);
+            receiver.try_recv().ok()
+        };
+        // Calls on a lost device may panic as well
+        !matches!(panic::catch_unwind(AssertUnwindSafe(probe)), Ok(Some(true)))
     }
 
     pub fn map_slice_blocking(&self, mode: MapMode, slice: BufferSlice) {
@@ -318,3 +3745,127 @@ impl Pipeline {
         }
     }
 }
+
+/// Create a buffer, failing with its label if wgpu rejects it
+fn create_buffer(
+    device: &wgpu::Device,
+    descriptor: &BufferDescriptor,
+) -> Result<wgpu::Buffer, ParabodyError> {
+    scoped(
+        device,
+        || format!("Could not create buffer {}", descriptor.label.unwrap_or("")),
+        || device.create_buffer(descriptor),
+    )
+}
+
+fn storage_entry(binding: u32, read_only: bool) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bodies one unit apart on a line
+    fn line_of_bodies(count: usize) -> Vec<Body> {
+        (0..count)
+            .map(|i| Body {
+                position: [i as f32, 0.0, 0.0],
+                mass: 1.0,
+                mu: 1e-3,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn kernels_leave_the_slack_untouched() {
+        for pass_graph in [PassGraph::fused(), PassGraph::split()] {
+            // Room for more bodies than are written, so that the threads of the
+            // last workgroup past the last body fall on memory of the buffers
+            let mut pipeline = pollster::block_on(Pipeline::create(
+                include_str!("../shaders/dynamics.wgsl"),
+                pass_graph,
+                StaticConfig {
+                    max_bodies: 256,
+                    workgroup_size: 64,
+                    ..Default::default()
+                },
+                PowerPreference::HighPerformance,
+            ))
+            .expect("Could not create the pipeline");
+            pipeline.set_dt(1e-3);
+            pipeline.write_bodies(&line_of_bodies(100)).unwrap();
+            assert_eq!(pipeline.slack(), 100..128);
+            pipeline.set_slack_check(true);
+            // Panics if a kernel wrote to a sentinel
+            pipeline.submit_and_block(16);
+            pipeline.verify_slack();
+        }
+    }
+
+    #[test]
+    fn restore_continues_from_the_checkpoint() {
+        const STEPS: usize = 8;
+        let static_config = StaticConfig {
+            max_bodies: 64,
+            deterministic: true,
+            ..Default::default()
+        };
+        let path = std::env::temp_dir().join(format!("parabody-{}.pbck", std::process::id()));
+        let mut pipeline = pollster::block_on(Pipeline::create(
+            include_str!("../shaders/dynamics.wgsl"),
+            PassGraph::split(),
+            static_config,
+            PowerPreference::HighPerformance,
+        ))
+        .expect("Could not create the pipeline");
+        pipeline.set_dt(1e-3);
+        pipeline.set_softening(0.1);
+        pipeline.write_bodies(&line_of_bodies(40)).unwrap();
+        pipeline.submit_and_block(STEPS);
+        pipeline.checkpoint(&path).unwrap();
+        pipeline.submit_and_block(STEPS);
+        let time = pipeline.time();
+        let bodies = pipeline.read_bodies();
+        // The GL backend can't open a second device on a thread still holding one
+        drop(pipeline);
+
+        let restored = pollster::block_on(Pipeline::restore(
+            &path,
+            include_str!("../shaders/dynamics.wgsl"),
+            PassGraph::split(),
+            PowerPreference::HighPerformance,
+        ));
+        std::fs::remove_file(&path).unwrap();
+        let mut restored = restored.expect("Could not restore the checkpoint");
+        restored.submit_and_block(STEPS);
+        assert_eq!(restored.time(), time);
+        assert_eq!(
+            bytemuck::cast_slice::<Body, u32>(&restored.read_bodies()),
+            bytemuck::cast_slice::<Body, u32>(&bodies),
+        );
+    }
+
+    #[test]
+    fn working_device_is_not_lost() {
+        // Otherwise resilient runs would recreate it after every error
+        let pipeline = pollster::block_on(Pipeline::create(
+            include_str!("../shaders/dynamics.wgsl"),
+            PassGraph::split(),
+            StaticConfig::default(),
+            PowerPreference::HighPerformance,
+        ))
+        .expect("Could not create the pipeline");
+        assert!(!pipeline.device_is_lost());
+    }
+}
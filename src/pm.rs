@@ -0,0 +1,77 @@
+//! Particle-mesh gravity. The kernels are rendered into the shader for the `PM`
+//! solver, this builds the sequence of passes which replaces the direct-sum `accelerate`.
+use std::mem::size_of;
+
+use serde::Serialize;
+
+use crate::pipeline::{Domain, Pass};
+
+/// One radix-2 pass of the FFT along an axis, rendered as its own entry point
+#[derive(Debug, Clone, Serialize)]
+pub struct FftPass {
+    pub entry_point: String,
+    /// Distance between consecutive elements along the axis
+    pub stride: u32,
+    /// Length of the transforms combined by this pass
+    pub span: u32,
+    /// -1 for the forward transform, 1 for the inverse
+    pub sign: i32,
+}
+
+/// The passes of a 3D transform over a `grid_size^3` grid, one axis at a time
+pub fn fft_passes(grid_size: u32, sign: i32) -> Vec<FftPass> {
+    let direction = if sign < 0 { "forward" } else { "inverse" };
+    let mut passes = Vec::new();
+    for axis in 0..3 {
+        let stride = grid_size.pow(axis);
+        let mut span = 1;
+        while span < grid_size {
+            passes.push(FftPass {
+                entry_point: format!("pm_fft_{}_{}_{}", direction, axis, span),
+                stride,
+                span,
+                sign,
+            });
+            span *= 2;
+        }
+    }
+    passes
+}
+
+/// Forward then inverse transform passes, as rendered into the shader
+pub fn all_fft_passes(grid_size: u32) -> Vec<FftPass> {
+    let mut passes = fft_passes(grid_size, -1);
+    passes.extend(fft_passes(grid_size, 1));
+    passes
+}
+
+/// The passes computing the accelerations for one step
+pub fn passes(grid_size: u32) -> Vec<Pass> {
+    let cells = grid_size.pow(3);
+    let grid = |entry_point: &str, threads| Pass {
+        domain: Domain::Threads(threads),
+        swaps_grid: true,
+        ..Pass::bodies(entry_point)
+    };
+    let fft = |pass: FftPass| grid(&pass.entry_point, cells / 2);
+
+    let mut passes = vec![
+        Pass {
+            swaps_grid: false,
+            ..grid("pm_clear", cells)
+        },
+        Pass::bodies("pm_deposit"),
+        grid("pm_load", cells),
+    ];
+    passes.extend(fft_passes(grid_size, -1).into_iter().map(fft));
+    passes.push(grid("pm_green", cells));
+    passes.extend(fft_passes(grid_size, 1).into_iter().map(fft));
+    passes.push(Pass::bodies("pm_accelerate"));
+    passes
+}
+
+/// Size of the density and the two complex grids in bytes
+pub fn grid_memory(grid_size: u32) -> u64 {
+    let cells = grid_size.pow(3) as u64;
+    cells * (size_of::<i32>() + 2 * size_of::<[f32; 2]>()) as u64
+}
@@ -0,0 +1,172 @@
+//! GPU timings of the passes of a step, from timestamps written between them
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use wgpu::{BufferDescriptor, BufferUsages, QuerySetDescriptor, QueryType, QUERY_SET_MAX_QUERIES};
+
+use crate::pipeline::PassGraph;
+
+/// Timestamps written per submission at most, one before the first pass and one after each
+pub const MAX_TIMESTAMPS: u32 = QUERY_SET_MAX_QUERIES;
+
+/// GPU time of one pass of the pass graph, summed over the profiled steps
+#[derive(Debug, Clone, Default)]
+pub struct PassStats {
+    pub entry_point: String,
+    pub time: Duration,
+}
+
+/// GPU time spent in the passes of the steps profiled so far. Passes are in the
+/// order of the pass graph dispatched, dispatching another one, such as the
+/// reversed graph, starts over.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineStats {
+    pub steps: u64,
+    pub passes: Vec<PassStats>,
+}
+
+impl PipelineStats {
+    pub fn total(&self) -> Duration {
+        self.passes.iter().map(|pass| pass.time).sum()
+    }
+
+    /// Time per kernel, summed over the passes dispatching it
+    pub fn kernels(&self) -> BTreeMap<&str, Duration> {
+        let mut kernels = BTreeMap::new();
+        for pass in &self.passes {
+            *kernels.entry(pass.entry_point.as_str()).or_default() += pass.time;
+        }
+        kernels
+    }
+
+    /// Average time of a step
+    pub fn step_time(&self) -> Duration {
+        if self.steps == 0 {
+            Duration::ZERO
+        } else {
+            self.total().div_f64(self.steps as f64)
+        }
+    }
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.total().as_secs_f64();
+        let share = |time: Duration| {
+            if total > 0.0 {
+                100.0 * time.as_secs_f64() / total
+            } else {
+                0.0
+            }
+        };
+        let per_step = |time: Duration| 1e3 * time.as_secs_f64() / self.steps.max(1) as f64;
+        writeln!(
+            f,
+            "{} steps profiled, {:.3} ms/step on the GPU",
+            self.steps,
+            1e3 * self.step_time().as_secs_f64()
+        )?;
+        writeln!(f, "Passes:")?;
+        for (index, pass) in self.passes.iter().enumerate() {
+            writeln!(
+                f,
+                "  {:>3} {:<24} {:>10.4} ms/step {:>5.1}%",
+                index,
+                pass.entry_point,
+                per_step(pass.time),
+                share(pass.time)
+            )?;
+        }
+        writeln!(f, "Kernels:")?;
+        for (entry_point, time) in self.kernels() {
+            writeln!(
+                f,
+                "      {:<24} {:>10.4} ms/step {:>5.1}%",
+                entry_point,
+                per_step(time),
+                share(time)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Owns the query set and the buffer the timestamps are read back through
+pub struct Profiler {
+    query_set: wgpu::QuerySet,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    stats: PipelineStats,
+}
+
+impl Profiler {
+    /// The device must have been created with `Features::TIMESTAMP_QUERY`
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = MAX_TIMESTAMPS as u64 * std::mem::size_of::<u64>() as u64;
+        Self {
+            query_set: device.create_query_set(&QuerySetDescriptor {
+                label: Some("Timestamps"),
+                ty: QueryType::Timestamp,
+                count: MAX_TIMESTAMPS,
+            }),
+            readback_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("Timestamp readback"),
+                size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            stats: PipelineStats::default(),
+        }
+    }
+
+    pub fn timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        encoder.write_timestamp(&self.query_set, index);
+    }
+
+    /// Resolve the first `count` timestamps into the readback buffer
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder, count: u32) {
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.readback_buffer, 0);
+    }
+
+    pub fn readback_buffer(&self) -> &wgpu::Buffer {
+        &self.readback_buffer
+    }
+
+    /// Add the intervals between the timestamps of `steps` steps of `pass_graph`
+    pub fn record(&mut self, timestamps: &[u64], steps: usize, pass_graph: &PassGraph) {
+        let passes = pass_graph.passes();
+        if self.stats.passes.len() != passes.len()
+            || self
+                .stats
+                .passes
+                .iter()
+                .zip(passes)
+                .any(|(stats, pass)| stats.entry_point != pass.entry_point)
+        {
+            self.reset();
+            self.stats.passes = passes
+                .iter()
+                .map(|pass| PassStats {
+                    entry_point: pass.entry_point.clone(),
+                    time: Duration::ZERO,
+                })
+                .collect();
+        }
+        for (index, interval) in timestamps.windows(2).enumerate() {
+            // Timestamps aren't guaranteed to be monotonic across passes on every backend
+            let ticks = interval[1].saturating_sub(interval[0]);
+            let nanos = (ticks as f64 * self.period as f64) as u64;
+            self.stats.passes[index % passes.len()].time += Duration::from_nanos(nanos);
+        }
+        self.stats.steps += steps as u64;
+    }
+
+    pub fn stats(&self) -> &PipelineStats {
+        &self.stats
+    }
+
+    pub fn reset(&mut self) {
+        self.stats = PipelineStats::default();
+    }
+}
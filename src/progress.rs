@@ -0,0 +1,117 @@
+//! Progress of a run as the pipeline reports it after every submission, with
+//! the time remaining estimated from the recent throughput.
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Throughput for the ETA is measured over this trailing window
+pub const ETA_WINDOW: Duration = Duration::from_secs(10);
+
+/// Reported to the pipeline's progress callback after every submission
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Steps completed since the callback was set
+    pub completed_steps: usize,
+    pub total_steps: usize,
+    /// Simulated time of the latest state
+    pub time: f64,
+    /// Throughput over `ETA_WINDOW`, `None` until it can be measured
+    pub steps_per_second: Option<f64>,
+    pub eta: Option<Duration>,
+}
+
+pub type ProgressCallback = Box<dyn FnMut(&Progress)>;
+
+/// Estimates the remaining run time from the throughput measured over a
+/// recent window, so that the estimate follows changes in speed without
+/// jumping around on every sample
+#[derive(Debug, Clone)]
+pub struct EtaEstimator {
+    total_steps: usize,
+    window: Duration,
+    samples: VecDeque<(Instant, usize)>,
+}
+
+impl EtaEstimator {
+    pub fn new(total_steps: usize, window: Duration) -> Self {
+        let mut samples = VecDeque::new();
+        samples.push_back((Instant::now(), 0));
+        Self {
+            total_steps,
+            window,
+            samples,
+        }
+    }
+
+    /// Record that `completed_steps` steps have been completed in total
+    pub fn record(&mut self, completed_steps: usize) {
+        self.record_at(Instant::now(), completed_steps);
+    }
+
+    pub fn record_at(&mut self, at: Instant, completed_steps: usize) {
+        self.samples.push_back((at, completed_steps));
+        // Drop samples which have left the window, but always keep two to measure between
+        while self.samples.len() > 2 && at.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn completed_steps(&self) -> usize {
+        self.samples.back().map_or(0, |&(_, steps)| steps)
+    }
+
+    pub fn total_steps(&self) -> usize {
+        self.total_steps
+    }
+
+    /// Throughput over the window, `None` until it can be measured
+    pub fn steps_per_second(&self) -> Option<f64> {
+        let (first_time, first_steps) = *self.samples.front()?;
+        let (last_time, last_steps) = *self.samples.back()?;
+        let elapsed = last_time.duration_since(first_time).as_secs_f64();
+        if elapsed <= 0.0 || last_steps <= first_steps {
+            return None;
+        }
+        Some((last_steps - first_steps) as f64 / elapsed)
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        let remaining_steps = self.total_steps.saturating_sub(self.completed_steps());
+        let rate = self.steps_per_second()?;
+        Some(Duration::from_secs_f64(remaining_steps as f64 / rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_follows_the_windowed_throughput() {
+        let mut estimator = EtaEstimator::new(1000, Duration::from_secs(10));
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        assert_eq!(estimator.steps_per_second(), None);
+        assert_eq!(estimator.remaining(), None);
+
+        // The sample taken on creation is dropped once the one after it is a
+        // window old, leaving the last 10 s at 20 steps per second
+        estimator.record_at(at(20), 100);
+        estimator.record_at(at(25), 200);
+        estimator.record_at(at(30), 300);
+        assert_eq!(estimator.completed_steps(), 300);
+        assert_eq!(estimator.steps_per_second(), Some(20.0));
+        assert_eq!(estimator.remaining(), Some(Duration::from_secs(35)));
+
+        // Slowing down to 2 steps per second shows once the window has passed
+        estimator.record_at(at(35), 310);
+        estimator.record_at(at(40), 320);
+        assert_eq!(estimator.steps_per_second(), Some(2.0));
+        assert_eq!(estimator.remaining(), Some(Duration::from_secs(340)));
+
+        // Done, or past the total
+        estimator.record_at(at(50), 1000);
+        assert_eq!(estimator.remaining(), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,736 @@
+//! Point sprite rendering of the bodies, shared by the viewer window and the
+//! headless frames. The positions are read straight from the simulation's body
+//! buffer as instance data, so drawing never waits on a readback. The bodies
+//! are colored by palette index or by a physical quantity through a color map,
+//! which a color bar in the corner shows.
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingType, BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites,
+    CommandEncoder, CommandEncoderDescriptor, FragmentState, IndexFormat, LoadOp, MultisampleState,
+    Operations, PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor, ShaderStages,
+    TextureFormat, TextureView, VertexAttribute, VertexBufferLayout, VertexFormat, VertexState,
+    VertexStepMode,
+};
+
+use crate::pipeline::Pipeline;
+use crate::structures::{Body, VisualAttributes};
+
+/// Diameter of the sprites in pixels
+const POINT_SIZE: f32 = 4.0;
+/// Camera rotation per pixel dragged, in radians
+const ORBIT_SPEED: f32 = 0.005;
+/// Factor the camera distance changes by per scroll line
+const ZOOM_STEP: f32 = 0.9;
+
+/// Position, mass and velocity of a `Body`, at the shader locations of `points.wgsl`
+const BODY_ATTRIBUTES: [VertexAttribute; 3] = [
+    VertexAttribute {
+        format: VertexFormat::Float32x3,
+        offset: 0,
+        shader_location: 0,
+    },
+    VertexAttribute {
+        format: VertexFormat::Float32,
+        offset: 12,
+        shader_location: 2,
+    },
+    VertexAttribute {
+        format: VertexFormat::Float32x3,
+        offset: 16,
+        shader_location: 3,
+    },
+];
+/// Palette color and group of a body
+const INDEX_ATTRIBUTES: [VertexAttribute; 1] = [VertexAttribute {
+    format: VertexFormat::Uint32x2,
+    offset: 0,
+    shader_location: 1,
+}];
+/// The potential in the `w` of an acceleration
+const POTENTIAL_ATTRIBUTES: [VertexAttribute; 1] = [VertexAttribute {
+    format: VertexFormat::Float32,
+    offset: 12,
+    shader_location: 4,
+}];
+
+/// Must match `Camera` in `points.wgsl`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+struct CameraUniform {
+    view_projection: [[f32; 4]; 4],
+    viewport: [f32; 2],
+    point_size: f32,
+    color_mode: u32,
+    color_range: [f32; 2],
+    /// Index of the highlighted body, `u32::MAX` for none
+    highlight: u32,
+    _pad: u32,
+}
+
+/// What the colors of the bodies show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorQuantity {
+    /// The palette index of the visual attributes
+    Palette,
+    /// The group of the visual attributes, through the palette
+    Group,
+    Mass,
+    Speed,
+    /// Gravitational potential, from `Pipeline::compute_potentials`
+    Potential,
+    /// Specific orbital energy, kinetic plus potential. Negative for bound bodies.
+    Energy,
+}
+
+impl ColorQuantity {
+    pub const ALL: [Self; 6] = [
+        Self::Palette,
+        Self::Group,
+        Self::Mass,
+        Self::Speed,
+        Self::Potential,
+        Self::Energy,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Palette => "palette",
+            Self::Group => "group",
+            Self::Mass => "mass",
+            Self::Speed => "speed",
+            Self::Potential => "potential",
+            Self::Energy => "energy",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|quantity| quantity.name() == name)
+    }
+
+    /// The quantity after this one in `ALL`, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&quantity| quantity == self)
+            .unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Whether the quantity goes through the color map rather than the palette
+    pub fn is_continuous(self) -> bool {
+        !matches!(self, Self::Palette | Self::Group)
+    }
+
+    pub fn uses_potentials(self) -> bool {
+        matches!(self, Self::Potential | Self::Energy)
+    }
+
+    /// Must match `body_color` in `points.wgsl`
+    fn mode(self) -> u32 {
+        match self {
+            Self::Palette => 0,
+            Self::Group => 1,
+            Self::Mass => 2,
+            Self::Speed => 3,
+            Self::Potential => 4,
+            Self::Energy => 5,
+        }
+    }
+
+    /// Value of every body, `potentials` is only read for the quantities which use them
+    fn values(self, bodies: &[Body], potentials: &[f32]) -> Vec<f32> {
+        let speed2 = |body: &Body| Vec3::from(body.velocity).length_squared();
+        match self {
+            Self::Palette | Self::Group => Vec::new(),
+            Self::Mass => bodies.iter().map(|body| body.mass).collect(),
+            Self::Speed => bodies.iter().map(|body| speed2(body).sqrt()).collect(),
+            Self::Potential => potentials.to_vec(),
+            Self::Energy => bodies
+                .iter()
+                .zip(potentials)
+                .map(|(body, potential)| 0.5 * speed2(body) + potential)
+                .collect(),
+        }
+    }
+}
+
+/// A quantity and the values mapped to the ends of the color map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMap {
+    pub quantity: ColorQuantity,
+    pub range: [f32; 2],
+}
+
+impl Default for ColorMap {
+    fn default() -> Self {
+        Self {
+            quantity: ColorQuantity::Palette,
+            range: [0.0, 0.0],
+        }
+    }
+}
+
+impl ColorMap {
+    /// Map `quantity` over its values in the latest state. The energy range is
+    /// symmetric, so that zero, between bound and unbound, sits in the middle.
+    pub fn fit(quantity: ColorQuantity, pipeline: &mut Pipeline) -> Self {
+        if !quantity.is_continuous() {
+            return Self {
+                quantity,
+                ..Self::default()
+            };
+        }
+        let potentials = if quantity.uses_potentials() {
+            pipeline.compute_potentials();
+            pipeline.read_potentials()
+        } else {
+            Vec::new()
+        };
+        let values = quantity.values(&pipeline.read_bodies(), &potentials);
+        let lower = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let upper = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let range = if values.is_empty() {
+            [0.0, 0.0]
+        } else if quantity == ColorQuantity::Energy {
+            let extreme = lower.abs().max(upper.abs());
+            [-extreme, extreme]
+        } else {
+            [lower, upper]
+        };
+        Self { quantity, range }
+    }
+
+    /// A description of the colors for display next to the color bar
+    pub fn legend(&self) -> String {
+        if self.quantity.is_continuous() {
+            format!(
+                "{} {:.3e} to {:.3e}",
+                self.quantity.name(),
+                self.range[0],
+                self.range[1]
+            )
+        } else {
+            self.quantity.name().to_string()
+        }
+    }
+}
+
+/// A camera circling `target` with the z axis up
+#[derive(Debug, Clone, Copy)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    /// Angle around the z axis
+    pub yaw: f32,
+    /// Angle above the xy plane
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl OrbitCamera {
+    /// Look at the center of the bodies from far enough away to see all of them
+    pub fn framing(bodies: &[Body]) -> Self {
+        let (min, max) = bodies.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), body| {
+                let position = Vec3::from(body.position);
+                (min.min(position), max.max(position))
+            },
+        );
+        let (target, radius) = if bodies.is_empty() {
+            (Vec3::ZERO, 1.0)
+        } else {
+            ((min + max) / 2.0, ((max - min).length() / 2.0).max(1e-6))
+        };
+        Self {
+            target,
+            yaw: 0.0,
+            pitch: 0.5,
+            distance: 3.0 * radius,
+        }
+    }
+
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        self.yaw -= dx * ORBIT_SPEED;
+        self.pitch = (self.pitch + dy * ORBIT_SPEED).clamp(-1.5, 1.5);
+    }
+
+    /// Move towards the target for positive `lines`
+    pub fn zoom(&mut self, lines: f32) {
+        self.distance *= ZOOM_STEP.powf(lines);
+    }
+
+    pub fn view_projection(&self, aspect: f32) -> Mat4 {
+        let direction = Vec3::new(
+            self.pitch.cos() * self.yaw.cos(),
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+        );
+        let view = Mat4::look_at_rh(
+            self.target + self.distance * direction,
+            self.target,
+            Vec3::Z,
+        );
+        let projection = Mat4::perspective_rh(
+            45f32.to_radians(),
+            aspect,
+            self.distance * 1e-3,
+            self.distance * 1e3,
+        );
+        projection * view
+    }
+}
+
+/// Must match `Trail` in `points.wgsl`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+struct TrailUniform {
+    length: u32,
+    head: u32,
+    num_bodies: u32,
+    _pad: u32,
+}
+
+/// A ring of the last `length` recorded positions of every body, slot major, so
+/// that recording is a single copy out of the body buffer. The segments are
+/// indexed grouped by the slot they start from, the groups between the oldest
+/// and the newest slot are contiguous apart from the wrap around the ring.
+struct Trails {
+    render_pipeline: wgpu::RenderPipeline,
+    trail_buffer: wgpu::Buffer,
+    bindgroup: wgpu::BindGroup,
+    ring_buffer: wgpu::Buffer,
+    /// The palette colors and groups repeated for every slot
+    color_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    length: u32,
+    num_bodies: u32,
+    /// Slot the next positions are recorded to
+    head: u32,
+    /// Number of slots recorded so far, up to `length`
+    filled: u32,
+}
+
+impl Trails {
+    /// Segment groups from the oldest to the newest recorded slot, as at most
+    /// two contiguous ranges of slots
+    fn segment_ranges(&self) -> [std::ops::Range<u32>; 2] {
+        let count = self.filled.saturating_sub(1);
+        let start = (self.head + self.length - self.filled) % self.length;
+        let end = start + count;
+        if end <= self.length {
+            [start..end, 0..0]
+        } else {
+            [start..self.length, 0..end - self.length]
+        }
+    }
+}
+
+/// The render pipeline drawing one sprite per body, colored through a `ColorMap`
+pub struct PointRenderer {
+    render_pipeline: wgpu::RenderPipeline,
+    legend_pipeline: wgpu::RenderPipeline,
+    camera_buffer: wgpu::Buffer,
+    camera_bindgroup: wgpu::BindGroup,
+    color_buffer: wgpu::Buffer,
+    color_map: ColorMap,
+    highlight: Option<u32>,
+    trails: Option<Trails>,
+}
+
+impl PointRenderer {
+    /// Set up the rendering into targets of `format` on the pipeline's device,
+    /// coloring the bodies by the palette index in `attributes` until another
+    /// color map is set. With a non-zero `trail_length`, the positions passed
+    /// to `record_trails` are drawn as fading polylines behind the bodies.
+    pub fn new(
+        pipeline: &Pipeline,
+        format: TextureFormat,
+        attributes: &[VisualAttributes],
+        trail_length: u32,
+    ) -> Self {
+        let device = pipeline.device();
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/points.wgsl"));
+        let camera_bindgroup_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Camera"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Camera"),
+            size: size_of::<CameraUniform>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_bindgroup = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Camera"),
+            layout: &camera_bindgroup_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        // Color and group per body, padded so that the buffer is never empty
+        let mut colors: Vec<[u32; 2]> = attributes
+            .iter()
+            .map(|attributes| [attributes.color, attributes.group])
+            .collect();
+        colors.resize(pipeline.static_config().max_bodies.max(1) as usize, [0, 0]);
+        let color_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Colors"),
+            contents: bytemuck::cast_slice(&colors),
+            usage: BufferUsages::VERTEX,
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Points"),
+            bind_group_layouts: &[&camera_bindgroup_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Points"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    VertexBufferLayout {
+                        array_stride: size_of::<Body>() as u64,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &BODY_ATTRIBUTES,
+                    },
+                    VertexBufferLayout {
+                        array_stride: size_of::<[u32; 2]>() as u64,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &INDEX_ATTRIBUTES,
+                    },
+                    VertexBufferLayout {
+                        array_stride: size_of::<[f32; 4]>() as u64,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &POTENTIAL_ATTRIBUTES,
+                    },
+                ],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        let legend_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Legend"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_legend",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_legend",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        let num_bodies = pipeline.dynamic_config().num_bodies;
+        let trails = (trail_length > 0 && num_bodies > 0).then(|| {
+            let trail_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Trail"),
+                size: size_of::<TrailUniform>() as u64,
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let uniform_entry = |binding| BindGroupLayoutEntry {
+                binding,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            };
+            let bindgroup_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Trails"),
+                entries: &[uniform_entry(0), uniform_entry(1)],
+            });
+            let bindgroup = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Trails"),
+                layout: &bindgroup_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: camera_buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: trail_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let slot_size = (num_bodies as usize * size_of::<Body>()) as u64;
+            let ring_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Trail ring"),
+                size: trail_length as u64 * slot_size,
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let ring_colors = colors[..num_bodies as usize].repeat(trail_length as usize);
+            let color_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Trail colors"),
+                contents: bytemuck::cast_slice(&ring_colors),
+                usage: BufferUsages::VERTEX,
+            });
+            // Each body's segment from slot `s` to the next, grouped by `s`
+            let indices: Vec<u32> = (0..trail_length)
+                .flat_map(|slot| {
+                    let next = (slot + 1) % trail_length;
+                    (0..num_bodies)
+                        .flat_map(move |body| [slot * num_bodies + body, next * num_bodies + body])
+                })
+                .collect();
+            let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Trail segments"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: BufferUsages::INDEX,
+            });
+
+            let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Trails"),
+                bind_group_layouts: &[&bindgroup_layout],
+                push_constant_ranges: &[],
+            });
+            let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Trails"),
+                layout: Some(&layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: "vs_trail",
+                    buffers: &[
+                        VertexBufferLayout {
+                            array_stride: size_of::<Body>() as u64,
+                            step_mode: VertexStepMode::Vertex,
+                            attributes: &BODY_ATTRIBUTES,
+                        },
+                        VertexBufferLayout {
+                            array_stride: size_of::<[u32; 2]>() as u64,
+                            step_mode: VertexStepMode::Vertex,
+                            attributes: &INDEX_ATTRIBUTES,
+                        },
+                    ],
+                },
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point: "fs_trail",
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            });
+
+            Trails {
+                render_pipeline,
+                trail_buffer,
+                bindgroup,
+                ring_buffer,
+                color_buffer,
+                index_buffer,
+                length: trail_length,
+                num_bodies,
+                head: 0,
+                filled: 0,
+            }
+        });
+
+        Self {
+            render_pipeline,
+            legend_pipeline,
+            camera_buffer,
+            camera_bindgroup,
+            color_buffer,
+            color_map: ColorMap::default(),
+            highlight: None,
+            trails,
+        }
+    }
+
+    pub fn color_map(&self) -> ColorMap {
+        self.color_map
+    }
+
+    pub fn set_color_map(&mut self, color_map: ColorMap) {
+        self.color_map = color_map;
+    }
+
+    pub fn highlight(&self) -> Option<u32> {
+        self.highlight
+    }
+
+    /// Draw the body at `index` enlarged, ringed and on top of the others
+    pub fn set_highlight(&mut self, index: Option<u32>) {
+        self.highlight = index;
+    }
+
+    /// Append the latest body positions to the trails, dropping the oldest
+    pub fn record_trails(&mut self, pipeline: &Pipeline) {
+        let trails = match &mut self.trails {
+            Some(trails) => trails,
+            None => return,
+        };
+        let slot_size = (trails.num_bodies as usize * size_of::<Body>()) as u64;
+        let mut encoder = pipeline
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(
+            pipeline.body_buffer(),
+            0,
+            &trails.ring_buffer,
+            trails.head as u64 * slot_size,
+            slot_size,
+        );
+        pipeline.queue().submit(Some(encoder.finish()));
+        trails.head = (trails.head + 1) % trails.length;
+        trails.filled = (trails.filled + 1).min(trails.length);
+    }
+
+    /// Forget the recorded positions, such as after the bodies jumped
+    pub fn clear_trails(&mut self) {
+        if let Some(trails) = &mut self.trails {
+            trails.head = 0;
+            trails.filled = 0;
+        }
+    }
+
+    /// Record clearing `view` and drawing the latest body state into it as seen
+    /// by `camera`, with a color bar unless the bodies show the palette. The
+    /// camera is uploaded and the potentials the color map needs are computed
+    /// immediately, so the encoder should be submitted before the next draw.
+    pub fn draw(
+        &self,
+        pipeline: &mut Pipeline,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        camera: &OrbitCamera,
+        (width, height): (u32, u32),
+    ) {
+        let (width, height) = (width as f32, height as f32);
+        let uniform = CameraUniform {
+            view_projection: camera.view_projection(width / height).to_cols_array_2d(),
+            viewport: [width, height],
+            point_size: POINT_SIZE,
+            color_mode: self.color_map.quantity.mode(),
+            color_range: self.color_map.range,
+            highlight: self.highlight.unwrap_or(u32::MAX),
+            _pad: 0,
+        };
+        pipeline
+            .queue()
+            .write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&uniform));
+        if self.color_map.quantity.uses_potentials() {
+            pipeline.compute_potentials();
+        }
+
+        let num_bodies = pipeline.dynamic_config().num_bodies;
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Points"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        if let Some(trails) = &self.trails {
+            let uniform = TrailUniform {
+                length: trails.length,
+                head: trails.head,
+                num_bodies: trails.num_bodies,
+                _pad: 0,
+            };
+            pipeline
+                .queue()
+                .write_buffer(&trails.trail_buffer, 0, bytemuck::bytes_of(&uniform));
+            render_pass.set_pipeline(&trails.render_pipeline);
+            render_pass.set_bind_group(0, &trails.bindgroup, &[]);
+            render_pass.set_vertex_buffer(0, trails.ring_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, trails.color_buffer.slice(..));
+            render_pass.set_index_buffer(trails.index_buffer.slice(..), IndexFormat::Uint32);
+            let indices_per_slot = 2 * trails.num_bodies;
+            for slots in trails.segment_ranges() {
+                if !slots.is_empty() {
+                    render_pass.draw_indexed(
+                        slots.start * indices_per_slot..slots.end * indices_per_slot,
+                        0,
+                        0..1,
+                    );
+                }
+            }
+        }
+        if num_bodies > 0 {
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bindgroup, &[]);
+            render_pass.set_vertex_buffer(
+                0,
+                pipeline
+                    .body_buffer()
+                    .slice(..(num_bodies as usize * size_of::<Body>()) as u64),
+            );
+            render_pass.set_vertex_buffer(1, self.color_buffer.slice(..));
+            render_pass.set_vertex_buffer(2, pipeline.acceleration_buffer().slice(..));
+            render_pass.draw(0..6, 0..num_bodies);
+            if let Some(index) = self.highlight.filter(|&index| index < num_bodies) {
+                render_pass.draw(0..6, index..index + 1);
+            }
+        }
+        if self.color_map.quantity != ColorQuantity::Palette {
+            render_pass.set_pipeline(&self.legend_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bindgroup, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Round trips forwards and then backwards in time. Every step is undone exactly
+//! in exact arithmetic, so the distance from the initial state measures the
+//! rounding and any dissipation, and grows quickly where the dynamics are chaotic.
+use crate::analysis;
+use crate::pipeline::{Pipeline, TimeDirection};
+use crate::structures::Body;
+
+/// How far a round trip ended from where it started
+#[derive(Debug, Clone, Copy)]
+pub struct RoundTripError {
+    /// Steps taken in each direction
+    pub steps: usize,
+    pub max_position: f64,
+    pub rms_position: f64,
+    pub max_velocity: f64,
+    pub rms_velocity: f64,
+    /// Change of the total energy relative to the initial, `None` when that is zero
+    pub relative_energy: Option<f64>,
+}
+
+/// Run `steps` steps forwards and as many backwards from the latest state, then
+/// restore the state and the time direction
+pub fn round_trip(pipeline: &mut Pipeline, steps: usize) -> RoundTripError {
+    let initial = pipeline.read_bodies();
+    let direction = pipeline.time_direction();
+    pipeline.set_time_direction(TimeDirection::Forward);
+    pipeline.submit_and_block(steps);
+    pipeline.set_time_direction(TimeDirection::Backward);
+    pipeline.submit_and_block(steps);
+    let returned = pipeline.read_bodies();
+    pipeline.set_time_direction(direction);
+    pipeline
+        .write_bodies(&initial)
+        .expect("Read from the same pipeline");
+
+    let box_size = pipeline.dynamic_config().box_size;
+    let (max_position, rms_position) =
+        deviation(&initial, &returned, box_size, |body| body.position);
+    let (max_velocity, rms_velocity) = deviation(&initial, &returned, 0.0, |body| body.velocity);
+    let energy = analysis::total_energy(&initial);
+    RoundTripError {
+        steps,
+        max_position,
+        rms_position,
+        max_velocity,
+        rms_velocity,
+        relative_energy: (energy != 0.0)
+            .then(|| (analysis::total_energy(&returned) - energy) / energy.abs()),
+    }
+}
+
+/// Round trips of each length in `steps` from the latest state, for how the
+/// error grows with the length of a trip. Leaves the state and the time
+/// direction as they were.
+pub fn round_trips(pipeline: &mut Pipeline, steps: &[usize]) -> Vec<RoundTripError> {
+    steps
+        .iter()
+        .map(|&steps| round_trip(pipeline, steps))
+        .collect()
+}
+
+/// Largest and root mean square distance between the vectors of matching bodies,
+/// across the periodic box for a non-zero `box_size`
+fn deviation(
+    a: &[Body],
+    b: &[Body],
+    box_size: f32,
+    vector: impl Fn(&Body) -> [f32; 3],
+) -> (f64, f64) {
+    let box_size = box_size as f64;
+    let distances: Vec<f64> = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let (a, b) = (vector(a), vector(b));
+            (0..3)
+                .map(|axis| {
+                    let mut offset = a[axis] as f64 - b[axis] as f64;
+                    if box_size > 0.0 {
+                        offset -= box_size * (offset / box_size).round();
+                    }
+                    offset * offset
+                })
+                .sum::<f64>()
+                .sqrt()
+        })
+        .collect();
+    if distances.is_empty() {
+        return (0.0, 0.0);
+    }
+    let max = distances.iter().copied().fold(0.0, f64::max);
+    let mean_square = distances.iter().map(|d| d * d).sum::<f64>() / distances.len() as f64;
+    (max, mean_square.sqrt())
+}
@@ -0,0 +1,715 @@
+//! Scenario files describing a complete run, in TOML or JSON.
+//!
+//! Files are validated against the schema in `validate` before deserialization,
+//! so problems are reported with the path to the offending value.
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::ephemeris::Ephemeris;
+use crate::epoch::Epoch;
+use crate::events::{EventDefinition, EventPredicate};
+use crate::format::invalid_data;
+use crate::ic;
+use crate::maneuver::{Maneuver, ManeuverSchedule};
+use crate::section::SurfaceOfSection;
+use crate::structures::{
+    Body, BodyProperties, ExternalPotential, ForceLaw, ForceSolver, GasState, Hydrodynamics,
+    Integrator, NeighborGrid, PostNewtonian, RadiationPressure, Species, StaticConfig,
+    VisualAttributes, MAX_EPHEMERIS_BODIES,
+};
+use crate::thrust::Thrust;
+
+mod validate;
+pub use validate::Diagnostic;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    pub config: ScenarioConfig,
+    pub external_potential: Option<ExternalPotential>,
+    pub radiation_pressure: Option<RadiationPressure>,
+    pub post_newtonian: Option<PostNewtonian>,
+    pub force_law: ForceLaw,
+    pub force_solver: ForceSolver,
+    pub neighbor_grid: Option<NeighborGrid>,
+    pub hydrodynamics: Option<Hydrodynamics>,
+    /// Lennard-Jones species, indexed by `ScenarioBody::species`
+    pub species: Vec<Species>,
+    /// Listed bodies, followed by those of the `generator` once loaded
+    pub bodies: Vec<ScenarioBody>,
+    pub generator: Option<Generator>,
+    pub ephemeris: Option<EphemerisSource>,
+    /// Impulsive changes of velocity, in any order
+    pub maneuvers: Vec<Maneuver>,
+    /// Continuous thrust, at most one per body
+    pub thrust: Vec<Thrust>,
+    /// Events logged during the run, see `events`
+    pub events: Vec<EventDefinition>,
+    /// Poincaré surface of section recorded during the run, see `section`
+    pub section: Option<SurfaceOfSection>,
+    /// The document the scenario was read from, with its defaults filled in
+    #[serde(skip)]
+    pub document: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScenarioConfig {
+    pub dt: f32,
+    pub steps: usize,
+    pub integrator: Integrator,
+    /// Capacity of the GPU buffers, defaults to the number of bodies
+    pub max_bodies: Option<u32>,
+    pub zonal_harmonics: bool,
+    /// Periodic box edge length, zero for open boundaries
+    pub box_size: f32,
+    /// Plummer softening length of the direct-sum gravity
+    pub softening: f32,
+    /// Threads per workgroup, a tuning knob for the hardware
+    pub workgroup_size: u32,
+    /// Calendar epoch of the simulated time zero, see `epoch::Epoch::parse`
+    pub epoch: Option<String>,
+    /// Seconds in a unit of simulated time, for the epoch
+    pub time_unit: f64,
+    /// Start in the frame of the center of mass
+    pub com_frame: bool,
+    /// Steps between removals of the drift of the center of mass, see
+    /// `Pipeline::set_com_correction`
+    pub com_correction: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScenarioBody {
+    pub position: [f32; 3],
+    pub velocity: [f32; 3],
+    pub mass: f32,
+    pub mu: f32,
+    pub zonal: [f32; 3],
+    pub radius: f32,
+    pub area_to_mass: f32,
+    pub charge: f32,
+    pub species: u32,
+    pub gas: bool,
+    /// Specific internal energy of gas bodies
+    pub internal_energy: f32,
+    pub group: u32,
+    pub color: u32,
+    pub label: u32,
+}
+
+/// Bodies generated from a few parameters instead of being listed. The totals
+/// are shared equally between the bodies.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Generator {
+    /// Bodies spread uniformly over a ball, all moving with the same velocity
+    UniformSphere {
+        count: usize,
+        radius: f32,
+        center: [f32; 3],
+        velocity: [f32; 3],
+        /// Total `mu`
+        mu: f32,
+        /// Total mass
+        mass: f32,
+        group: u32,
+        color: u32,
+    },
+    /// A Plummer sphere in virial equilibrium, see `ic::plummer`. With `G = 1`,
+    /// the `mu` of the bodies is their mass.
+    Plummer {
+        count: usize,
+        scale_radius: f32,
+        /// Total mass
+        mass: f32,
+        seed: u64,
+        center: [f32; 3],
+        velocity: [f32; 3],
+        group: u32,
+        color: u32,
+    },
+    /// A King model truncated at its tidal radius, see `ic::king`
+    King {
+        count: usize,
+        /// Central potential over the velocity dispersion squared
+        w0: f32,
+        core_radius: f32,
+        /// Total mass
+        mass: f32,
+        seed: u64,
+        center: [f32; 3],
+        velocity: [f32; 3],
+        group: u32,
+        color: u32,
+    },
+    /// A Hernquist sphere, see `ic::hernquist`
+    Hernquist {
+        count: usize,
+        scale_radius: f32,
+        /// Total mass
+        mass: f32,
+        seed: u64,
+        center: [f32; 3],
+        velocity: [f32; 3],
+        group: u32,
+        color: u32,
+    },
+    /// A rotating disk galaxy, see `ic::ExponentialDisk`. The external
+    /// potential of the scenario, if any, is taken as its halo.
+    ExponentialDisk {
+        count: usize,
+        /// Mass of the disk
+        mass: f32,
+        scale_length: f32,
+        scale_height: f32,
+        dispersion: f32,
+        bulge: Option<ic::Bulge>,
+        seed: u64,
+        center: [f32; 3],
+        velocity: [f32; 3],
+        group: u32,
+        color: u32,
+    },
+    /// Two disk galaxies on a parabolic encounter, see `ic::merger`. The disk
+    /// parameters are those of the first galaxy, whose bodies get `group` and
+    /// `color` while those of the second get the next ones.
+    Merger {
+        count: usize,
+        mass: f32,
+        scale_length: f32,
+        scale_height: f32,
+        dispersion: f32,
+        bulge: Option<ic::Bulge>,
+        mass_ratio: f32,
+        pericenter: f32,
+        separation: f32,
+        /// Tilt of the second disk in radians
+        inclination: f32,
+        seed: u64,
+        group: u32,
+        color: u32,
+    },
+}
+
+impl Generator {
+    /// Seed of the random numbers drawn, `None` for the quasi-random sphere
+    pub fn seed(&self) -> Option<u64> {
+        match *self {
+            Generator::UniformSphere { .. } => None,
+            Generator::Plummer { seed, .. }
+            | Generator::King { seed, .. }
+            | Generator::Hernquist { seed, .. }
+            | Generator::ExponentialDisk { seed, .. }
+            | Generator::Merger { seed, .. } => Some(seed),
+        }
+    }
+
+    /// The bodies, in the given background potential where the model depends
+    /// on it
+    pub fn generate(&self, external_potential: Option<ExternalPotential>) -> Vec<ScenarioBody> {
+        match *self {
+            Generator::UniformSphere {
+                count,
+                radius,
+                center,
+                velocity,
+                mu,
+                mass,
+                group,
+                color,
+            } => ic::uniform_sphere(count, radius, center)
+                .into_iter()
+                .map(|position| ScenarioBody {
+                    position,
+                    velocity,
+                    mu: mu / count as f32,
+                    mass: mass / count as f32,
+                    group,
+                    color,
+                    ..Default::default()
+                })
+                .collect(),
+            Generator::Plummer {
+                count,
+                scale_radius,
+                mass,
+                seed,
+                center,
+                velocity,
+                group,
+                color,
+            } => ScenarioBody::cluster(
+                &ic::plummer(count, mass, scale_radius, seed),
+                center,
+                velocity,
+                group,
+                color,
+            ),
+            Generator::King {
+                count,
+                w0,
+                core_radius,
+                mass,
+                seed,
+                center,
+                velocity,
+                group,
+                color,
+            } => ScenarioBody::cluster(
+                &ic::king(count, mass, core_radius, w0, seed),
+                center,
+                velocity,
+                group,
+                color,
+            ),
+            Generator::Hernquist {
+                count,
+                scale_radius,
+                mass,
+                seed,
+                center,
+                velocity,
+                group,
+                color,
+            } => ScenarioBody::cluster(
+                &ic::hernquist(count, mass, scale_radius, seed),
+                center,
+                velocity,
+                group,
+                color,
+            ),
+            Generator::ExponentialDisk {
+                count,
+                mass,
+                scale_length,
+                scale_height,
+                dispersion,
+                bulge,
+                seed,
+                center,
+                velocity,
+                group,
+                color,
+            } => {
+                let disk = ic::ExponentialDisk {
+                    count,
+                    mass,
+                    scale_length,
+                    scale_height,
+                    dispersion,
+                    bulge,
+                    halo: external_potential,
+                };
+                ScenarioBody::cluster(&disk.generate(seed), center, velocity, group, color)
+            }
+            Generator::Merger {
+                count,
+                mass,
+                scale_length,
+                scale_height,
+                dispersion,
+                bulge,
+                mass_ratio,
+                pericenter,
+                separation,
+                inclination,
+                seed,
+                group,
+                color,
+            } => {
+                let primary = ic::ExponentialDisk {
+                    count,
+                    mass,
+                    scale_length,
+                    scale_height,
+                    dispersion,
+                    bulge,
+                    halo: None,
+                };
+                let [first, second] = ic::merger(
+                    &primary,
+                    mass_ratio,
+                    pericenter,
+                    separation,
+                    inclination,
+                    seed,
+                );
+                let mut bodies = ScenarioBody::cluster(&first, [0.0; 3], [0.0; 3], group, color);
+                bodies.extend(ScenarioBody::cluster(
+                    &second,
+                    [0.0; 3],
+                    [0.0; 3],
+                    group + 1,
+                    color + 1,
+                ));
+                bodies
+            }
+        }
+    }
+}
+
+impl ScenarioBody {
+    /// The bodies of a cluster moved to `center` and given a bulk `velocity`
+    fn cluster(
+        bodies: &[Body],
+        center: [f32; 3],
+        velocity: [f32; 3],
+        group: u32,
+        color: u32,
+    ) -> Vec<Self> {
+        bodies
+            .iter()
+            .map(|body| Self {
+                group,
+                color,
+                ..Self::moved(body, center, velocity)
+            })
+            .collect()
+    }
+
+    /// `body` moved to `center` and given a bulk `velocity`
+    fn moved(body: &Body, center: [f32; 3], velocity: [f32; 3]) -> Self {
+        Self {
+            position: [0, 1, 2].map(|i| body.position[i] + center[i]),
+            velocity: [0, 1, 2].map(|i| body.velocity[i] + velocity[i]),
+            mass: body.mass,
+            mu: body.mu,
+            ..Default::default()
+        }
+    }
+}
+
+/// Bodies prescribed by an ephemeris file instead of integrated, see `ephemeris`
+#[derive(Debug, Clone, Deserialize)]
+pub struct EphemerisSource {
+    /// An SPK kernel for `.bsp` files, otherwise a CSV table
+    pub path: PathBuf,
+    /// The prescribed bodies, for a CSV table all of its bodies by their ids if empty
+    pub bodies: Vec<EphemerisBody>,
+    /// NAIF id of the origin of the states taken from an SPK kernel
+    pub center: i32,
+    /// Seconds of TDB past J2000 at the simulated time zero, for SPK kernels
+    pub epoch: f64,
+    /// Simulated time between the samples taken from an SPK kernel
+    pub interval: Option<f64>,
+    /// The simulation's unit of length in those of the file, kilometers for SPK kernels
+    pub length_unit: f64,
+    /// The simulation's unit of time in those of the file, seconds for SPK kernels
+    pub time_unit: f64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct EphemerisBody {
+    /// Index of the prescribed body
+    pub body: usize,
+    /// Its id in the file, the `id` column of a CSV table or the NAIF id in a kernel
+    pub id: i64,
+}
+
+impl EphemerisSource {
+    /// Read the file, sampling kernels over `duration` from the simulated time `start`
+    pub fn load(&self, start: f64, duration: f64) -> io::Result<Ephemeris> {
+        let mut ephemeris = match self
+            .path
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("bsp") => self.sample_kernel(start, duration)?,
+            _ => {
+                let ephemeris = Ephemeris::read_csv(&self.path)?;
+                if self.bodies.is_empty() {
+                    ephemeris
+                } else {
+                    let bodies = self
+                        .bodies
+                        .iter()
+                        .map(|body| match usize::try_from(body.id) {
+                            Ok(id) => Ok((body.body, id)),
+                            Err(_) => Err(invalid_data(format!(
+                                "No body {} in the ephemeris",
+                                body.id
+                            ))),
+                        })
+                        .collect::<io::Result<Vec<_>>>()?;
+                    ephemeris.assign(&bodies)?
+                }
+            }
+        };
+        ephemeris.scale(1.0 / self.length_unit, 1.0 / self.time_unit);
+        Ok(ephemeris)
+    }
+
+    /// Samples of an SPK kernel in its units
+    #[cfg(feature = "spk")]
+    fn sample_kernel(&self, start: f64, duration: f64) -> io::Result<Ephemeris> {
+        let kernel = crate::ephemeris::spk::Kernel::open(&self.path)?;
+        let interval = self.interval.ok_or_else(|| {
+            invalid_data("Sampling an SPK kernel requires an interval".to_string())
+        })?;
+        let bodies: Vec<(usize, i32)> = self
+            .bodies
+            .iter()
+            .map(|body| (body.body, body.id as i32))
+            .collect();
+        let mut ephemeris = kernel.ephemeris(
+            &bodies,
+            self.center,
+            self.epoch + start * self.time_unit,
+            duration * self.time_unit,
+            interval * self.time_unit,
+        )?;
+        ephemeris.start = start * self.time_unit;
+        Ok(ephemeris)
+    }
+
+    #[cfg(not(feature = "spk"))]
+    fn sample_kernel(&self, _start: f64, _duration: f64) -> io::Result<Ephemeris> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Reading the SPK kernel {} requires the spk feature",
+                self.path.display()
+            ),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Parse(String),
+    /// The file parsed but did not match the schema
+    Invalid {
+        errors: Vec<Diagnostic>,
+        warnings: Vec<Diagnostic>,
+    },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(error) => write!(f, "Could not read scenario: {}", error),
+            ScenarioError::Parse(error) => write!(f, "Could not parse scenario: {}", error),
+            ScenarioError::Invalid { errors, warnings } => {
+                write!(f, "Invalid scenario:")?;
+                for error in errors {
+                    write!(f, "\n  error: {}", error)?;
+                }
+                for warning in warnings {
+                    write!(f, "\n  warning: {}", warning)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<io::Error> for ScenarioError {
+    fn from(error: io::Error) -> Self {
+        ScenarioError::Io(error)
+    }
+}
+
+/// Replace the value at `path`, e.g. `config.dt` or `bodies.1.mu`, indexing
+/// into the arrays there are and creating the tables on the way
+pub fn set(document: &mut Value, path: &str, value: Value) {
+    let mut target = document;
+    for key in path.split('.') {
+        let index = key
+            .parse::<usize>()
+            .ok()
+            .filter(|&index| target.as_array().is_some_and(|items| index < items.len()));
+        if let Some(index) = index {
+            target = &mut target.as_array_mut().unwrap()[index];
+            continue;
+        }
+        if !target.is_object() {
+            *target = Value::Object(Default::default());
+        }
+        target = target
+            .as_object_mut()
+            .unwrap()
+            .entry(key)
+            .or_insert(Value::Null);
+    }
+    *target = value;
+}
+
+impl Scenario {
+    /// Load a scenario, the format is chosen by the file extension.
+    /// Returns the scenario along with any warnings, such as unknown keys.
+    pub fn load(path: impl AsRef<Path>) -> Result<(Self, Vec<Diagnostic>), ScenarioError> {
+        Self::from_document(Self::read_document(path)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<(Self, Vec<Diagnostic>), ScenarioError> {
+        let value =
+            toml::from_str(text).map_err(|error| ScenarioError::Parse(error.to_string()))?;
+        Self::from_document(value)
+    }
+
+    pub fn from_json(text: &str) -> Result<(Self, Vec<Diagnostic>), ScenarioError> {
+        let value =
+            serde_json::from_str(text).map_err(|error| ScenarioError::Parse(error.to_string()))?;
+        Self::from_document(value)
+    }
+
+    /// Parse a scenario file into its untyped document without validating it,
+    /// so that values can be overridden with `set` first
+    pub fn read_document(path: impl AsRef<Path>) -> Result<Value, ScenarioError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => {
+                serde_json::from_str(&text).map_err(|error| ScenarioError::Parse(error.to_string()))
+            }
+            _ => toml::from_str(&text).map_err(|error| ScenarioError::Parse(error.to_string())),
+        }
+    }
+
+    /// Validate and deserialize an untyped document
+    pub fn from_document(mut value: Value) -> Result<(Self, Vec<Diagnostic>), ScenarioError> {
+        let (errors, warnings) = validate::scenario(&mut value);
+        if !errors.is_empty() {
+            return Err(ScenarioError::Invalid { errors, warnings });
+        }
+        // Validation fills in defaults, so anything that gets here should deserialize
+        let mut scenario: Scenario = match serde_json::from_value(value.clone()) {
+            Ok(scenario) => scenario,
+            Err(error) => {
+                let errors = vec![Diagnostic::new("", error)];
+                return Err(ScenarioError::Invalid { errors, warnings });
+            }
+        };
+        if let Some(generator) = &scenario.generator {
+            scenario
+                .bodies
+                .extend(generator.generate(scenario.external_potential));
+        }
+        scenario.document = value;
+        Ok((scenario, warnings))
+    }
+
+    pub fn static_config(&self) -> StaticConfig {
+        StaticConfig {
+            max_bodies: self.config.max_bodies.unwrap_or(self.bodies.len() as u32),
+            external_potential: self.external_potential,
+            zonal_harmonics: self.config.zonal_harmonics,
+            radiation_pressure: self.radiation_pressure,
+            force_law: self.force_law,
+            force_solver: self.force_solver,
+            neighbor_grid: self.neighbor_grid,
+            hydrodynamics: self.hydrodynamics,
+            ephemeris: self.ephemeris.is_some(),
+            thrust: !self.thrust.is_empty(),
+            com_correction: self.config.com_correction.is_some(),
+            post_newtonian: self.post_newtonian.is_some(),
+            // Up to whoever reads the chaos indicators
+            variational: false,
+            // Scenarios are a single system, ensembles are packed from several
+            ensemble: false,
+            // Up to whoever needs the run repeatable
+            deterministic: false,
+            workgroup_size: self.config.workgroup_size,
+        }
+    }
+
+    /// Load the ephemeris of the prescribed bodies, sampled over the steps of the
+    /// run, which go back from the time zero with a negative `dt`
+    pub fn ephemeris(&self) -> io::Result<Option<Ephemeris>> {
+        let source = match &self.ephemeris {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        let duration = self.config.steps as f64 * self.config.dt.abs() as f64;
+        let start = match self.config.dt < 0.0 {
+            true => -duration,
+            false => 0.0,
+        };
+        let ephemeris = source.load(start, duration)?;
+        if ephemeris.tracks.len() > MAX_EPHEMERIS_BODIES {
+            return Err(invalid_data(format!(
+                "An ephemeris can prescribe at most {} bodies",
+                MAX_EPHEMERIS_BODIES
+            )));
+        }
+        if let Some(track) = ephemeris
+            .tracks
+            .iter()
+            .find(|track| track.body >= self.bodies.len())
+        {
+            return Err(invalid_data(format!(
+                "The ephemeris prescribes body {} of {}",
+                track.body,
+                self.bodies.len()
+            )));
+        }
+        Ok(Some(ephemeris))
+    }
+
+    /// The epoch of the simulated time zero, if the config gives one
+    pub fn epoch(&self) -> Option<Epoch> {
+        self.config.epoch.as_deref().and_then(Epoch::parse)
+    }
+
+    pub fn maneuvers(&self) -> ManeuverSchedule {
+        ManeuverSchedule::new(self.maneuvers.clone())
+    }
+
+    /// The predicates of the events, in their order
+    pub fn event_predicates(&self) -> Vec<EventPredicate> {
+        self.events.iter().map(EventDefinition::predicate).collect()
+    }
+
+    pub fn bodies(&self) -> Vec<Body> {
+        self.bodies
+            .iter()
+            .map(|body| Body {
+                position: body.position,
+                mass: body.mass,
+                velocity: body.velocity,
+                mu: body.mu,
+            })
+            .collect()
+    }
+
+    pub fn properties(&self) -> Vec<BodyProperties> {
+        self.bodies
+            .iter()
+            .map(|body| BodyProperties {
+                zonal: body.zonal,
+                radius: body.radius,
+                area_to_mass: body.area_to_mass,
+                charge: body.charge,
+                species: body.species,
+                gas: body.gas as u32,
+            })
+            .collect()
+    }
+
+    pub fn visual_attributes(&self) -> Vec<VisualAttributes> {
+        self.bodies
+            .iter()
+            .map(|body| VisualAttributes {
+                group: body.group,
+                color: body.color,
+                label: body.label,
+            })
+            .collect()
+    }
+
+    pub fn gas_states(&self) -> Vec<GasState> {
+        self.bodies
+            .iter()
+            .map(|body| GasState {
+                internal_energy: body.internal_energy,
+                ..Default::default()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,1444 @@
+//! Schema checks for scenario files, run on the untyped document so that
+//! errors can name the exact value at fault and defaults can be filled in.
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::epoch::Epoch;
+use crate::structures::{
+    Integrator, DEFAULT_WORKGROUP_SIZE, MAX_EPHEMERIS_BODIES, MAX_PM_GRID_SIZE,
+    MAX_POST_NEWTONIAN_BODIES, MAX_SPECIES,
+};
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Location of the value, e.g. `bodies[3].velocity`
+    pub path: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(path: &str, message: impl ToString) -> Self {
+        Self {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} {}", self.path, self.message)
+        }
+    }
+}
+
+/// Collects diagnostics while walking the document
+#[derive(Default)]
+struct Validator {
+    errors: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Number of single character edits to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+impl Validator {
+    fn error(&mut self, path: &str, message: impl ToString) {
+        self.errors.push(Diagnostic::new(path, message));
+    }
+
+    /// Check that `value` is a table and warn about keys outside of `known`
+    fn table<'a>(
+        &mut self,
+        value: &'a mut Value,
+        path: &str,
+        known: &[&str],
+    ) -> Option<&'a mut Map<String, Value>> {
+        let table = match value {
+            Value::Object(table) => table,
+            _ => {
+                self.error(path, "must be a table");
+                return None;
+            }
+        };
+        for key in table.keys() {
+            if known.contains(&key.as_str()) {
+                continue;
+            }
+            let suggestion = known
+                .iter()
+                .filter(|candidate| edit_distance(key, candidate) <= 2)
+                .min_by_key(|candidate| edit_distance(key, candidate));
+            let message = match suggestion {
+                Some(candidate) => {
+                    format!("is not a recognized key, did you mean `{}`?", candidate)
+                }
+                None => "is not a recognized key and will be ignored".to_string(),
+            };
+            self.warnings
+                .push(Diagnostic::new(&join(path, key), message));
+        }
+        Some(table)
+    }
+
+    /// Fill in `default` if `key` is missing, otherwise check it with `check`
+    fn field(
+        &mut self,
+        table: &mut Map<String, Value>,
+        path: &str,
+        key: &str,
+        default: Option<Value>,
+        check: impl FnOnce(&mut Self, &mut Value, &str),
+    ) {
+        let path = join(path, key);
+        match (table.get_mut(key), default) {
+            (Some(value), _) => check(self, value, &path),
+            (None, Some(default)) => {
+                table.insert(key.to_string(), default);
+            }
+            (None, None) => self.error(&path, "is required"),
+        }
+    }
+
+    fn number(&mut self, value: &mut Value, path: &str) -> Option<f64> {
+        match value.as_f64() {
+            Some(number) if number.is_finite() => Some(number),
+            _ => {
+                self.error(path, "must be a number");
+                None
+            }
+        }
+    }
+
+    fn non_negative(&mut self, value: &mut Value, path: &str) {
+        if let Some(number) = self.number(value, path) {
+            if number < 0.0 {
+                self.error(path, "must not be negative");
+            }
+        }
+    }
+
+    fn non_zero(&mut self, value: &mut Value, path: &str) {
+        if self.number(value, path) == Some(0.0) {
+            self.error(path, "must not be zero");
+        }
+    }
+
+    fn positive(&mut self, value: &mut Value, path: &str) {
+        if let Some(number) = self.number(value, path) {
+            if number <= 0.0 {
+                self.error(path, "must be positive");
+            }
+        }
+    }
+
+    fn integer(&mut self, value: &mut Value, path: &str) {
+        if value.as_u64().is_none() {
+            self.error(path, "must be a non-negative integer");
+        }
+    }
+
+    fn signed_integer(&mut self, value: &mut Value, path: &str) {
+        if value.as_i64().is_none() {
+            self.error(path, "must be an integer");
+        }
+    }
+
+    fn positive_integer(&mut self, value: &mut Value, path: &str) {
+        if value.as_u64().is_none_or(|value| value == 0) {
+            self.error(path, "must be a positive integer");
+        }
+    }
+
+    fn boolean(&mut self, value: &mut Value, path: &str) {
+        if !value.is_boolean() {
+            self.error(path, "must be true or false");
+        }
+    }
+
+    fn vector(&mut self, value: &mut Value, path: &str) {
+        match value.as_array_mut() {
+            Some(elements) if elements.len() == 3 => {
+                for (i, element) in elements.iter_mut().enumerate() {
+                    self.number(element, &format!("{}[{}]", path, i));
+                }
+            }
+            Some(elements) => self.error(
+                path,
+                format!("must have 3 elements, found {}", elements.len()),
+            ),
+            None => self.error(path, "must be an array of 3 numbers"),
+        }
+    }
+
+    fn config(&mut self, value: &mut Value, path: &str) {
+        let known = [
+            "dt",
+            "steps",
+            "integrator",
+            "max_bodies",
+            "zonal_harmonics",
+            "box_size",
+            "softening",
+            "workgroup_size",
+            "epoch",
+            "time_unit",
+            "com_frame",
+            "com_correction",
+        ];
+        if let Some(table) = self.table(value, path, &known) {
+            self.field(table, path, "dt", None, Self::non_zero);
+            self.field(table, path, "steps", None, Self::integer);
+            let leapfrog = Some(Integrator::Leapfrog.name().into());
+            self.field(table, path, "integrator", leapfrog, |v, value, path| {
+                if value.as_str().and_then(Integrator::from_name).is_none() {
+                    let names: Vec<_> = Integrator::ALL.iter().map(|i| i.name()).collect();
+                    v.error(path, format!("must be one of {}", names.join(", ")));
+                }
+            });
+            self.field(
+                table,
+                path,
+                "max_bodies",
+                Some(Value::Null),
+                |v, value, path| {
+                    if !value.is_null() {
+                        v.integer(value, path)
+                    }
+                },
+            );
+            self.field(
+                table,
+                path,
+                "zonal_harmonics",
+                Some(false.into()),
+                Self::boolean,
+            );
+            self.field(
+                table,
+                path,
+                "box_size",
+                Some(0.0.into()),
+                Self::non_negative,
+            );
+            let zero = Some(0.0.into());
+            self.field(table, path, "softening", zero, Self::non_negative);
+            let workgroup_size = Some(DEFAULT_WORKGROUP_SIZE.into());
+            self.field(
+                table,
+                path,
+                "workgroup_size",
+                workgroup_size,
+                Self::positive_integer,
+            );
+            self.field(table, path, "epoch", Some(Value::Null), |v, value, path| {
+                if !value.is_null() && value.as_str().and_then(Epoch::parse).is_none() {
+                    v.error(
+                        path,
+                        "must be a date and time like \"2024-03-01T12:00:00 UTC\" \
+                         or a Julian date like \"JD 2460371.0 TDB\"",
+                    );
+                }
+            });
+            self.field(table, path, "time_unit", Some(1.0.into()), Self::positive);
+            self.field(table, path, "com_frame", Some(false.into()), Self::boolean);
+            self.field(
+                table,
+                path,
+                "com_correction",
+                Some(Value::Null),
+                |v, value, path| {
+                    if !value.is_null() {
+                        v.positive_integer(value, path)
+                    }
+                },
+            );
+        }
+    }
+
+    fn external_potential(&mut self, value: &mut Value, path: &str) {
+        let kinds = ["Kepler", "Harmonic", "MiyamotoNagai", "Nfw", "Cr3bp"];
+        let kind = value.get("kind").and_then(Value::as_str).map(str::to_owned);
+        let (known, parameters): (&[&str], &[&str]) = match kind.as_deref() {
+            Some("Kepler") => (&["kind", "center", "mu"], &["mu"]),
+            Some("Harmonic") => (&["kind", "center", "omega"], &["omega"]),
+            Some("MiyamotoNagai") => (&["kind", "center", "mu", "a", "b"], &["mu", "a", "b"]),
+            Some("Nfw") => (
+                &["kind", "center", "mu", "scale_radius"],
+                &["mu", "scale_radius"],
+            ),
+            Some("Cr3bp") => (&["kind", "center", "mass_parameter"], &[]),
+            _ => {
+                self.error(
+                    &join(path, "kind"),
+                    format!("must be one of {}", kinds.join(", ")),
+                );
+                return;
+            }
+        };
+        if let Some(table) = self.table(value, path, known) {
+            self.field(
+                table,
+                path,
+                "center",
+                Some(serde_json::json!([0.0, 0.0, 0.0])),
+                Self::vector,
+            );
+            for parameter in parameters {
+                self.field(table, path, parameter, None, Self::positive);
+            }
+            if kind.as_deref() == Some("Cr3bp") {
+                self.field(table, path, "mass_parameter", None, |v, value, path| {
+                    if v.number(value, path)
+                        .is_some_and(|mu| mu <= 0.0 || mu > 0.5)
+                    {
+                        v.error(path, "must be in (0, 0.5]");
+                    }
+                });
+            }
+        }
+    }
+
+    fn force_law(&mut self, value: &mut Value, path: &str) {
+        let kinds = ["Newtonian", "Coulomb", "LennardJones", "Mond"];
+        let kind = value.get("kind").and_then(Value::as_str).map(str::to_owned);
+        let known: &[&str] = match kind.as_deref() {
+            Some("Newtonian") => &["kind"],
+            Some("Coulomb") => &["kind", "coulomb_constant", "gravity"],
+            Some("LennardJones") => &["kind", "cutoff", "gravity"],
+            Some("Mond") => &["kind", "a0", "interpolation"],
+            _ => {
+                self.error(
+                    &join(path, "kind"),
+                    format!("must be one of {}", kinds.join(", ")),
+                );
+                return;
+            }
+        };
+        if let Some(table) = self.table(value, path, known) {
+            match kind.as_deref() {
+                Some("Coulomb") => {
+                    self.field(table, path, "coulomb_constant", None, Self::positive);
+                    self.field(table, path, "gravity", Some(false.into()), Self::boolean);
+                }
+                Some("LennardJones") => {
+                    self.field(table, path, "cutoff", None, Self::positive);
+                    self.field(table, path, "gravity", Some(false.into()), Self::boolean);
+                }
+                Some("Mond") => {
+                    self.field(table, path, "a0", None, Self::positive);
+                    let simple = Some("Simple".into());
+                    self.field(table, path, "interpolation", simple, |v, value, path| {
+                        if !matches!(value.as_str(), Some("Simple" | "Standard")) {
+                            v.error(path, "must be one of Simple, Standard");
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn force_solver(&mut self, value: &mut Value, path: &str) {
+        let kinds = ["Direct", "PM"];
+        let kind = value.get("kind").and_then(Value::as_str).map(str::to_owned);
+        let known: &[&str] = match kind.as_deref() {
+            Some("Direct") => &["kind"],
+            Some("PM") => &["kind", "grid_size"],
+            _ => {
+                self.error(
+                    &join(path, "kind"),
+                    format!("must be one of {}", kinds.join(", ")),
+                );
+                return;
+            }
+        };
+        if let Some(table) = self.table(value, path, known) {
+            if kind.as_deref() == Some("PM") {
+                self.field(
+                    table,
+                    path,
+                    "grid_size",
+                    None,
+                    |v, value, path| match value.as_u64() {
+                        Some(size)
+                            if size.is_power_of_two()
+                                && (2..=MAX_PM_GRID_SIZE as u64).contains(&size) => {}
+                        _ => v.error(
+                            path,
+                            format!("must be a power of two between 2 and {}", MAX_PM_GRID_SIZE),
+                        ),
+                    },
+                );
+            }
+        }
+    }
+
+    fn neighbor_grid(&mut self, value: &mut Value, path: &str) {
+        if let Some(table) = self.table(value, path, &["table_size", "cell_size"]) {
+            self.field(table, path, "table_size", None, Self::positive_integer);
+            self.field(table, path, "cell_size", None, Self::positive);
+        }
+    }
+
+    fn hydrodynamics(&mut self, value: &mut Value, path: &str) {
+        let known = ["smoothing_length", "adiabatic_index", "alpha", "beta"];
+        if let Some(table) = self.table(value, path, &known) {
+            self.field(table, path, "smoothing_length", None, Self::positive);
+            self.field(
+                table,
+                path,
+                "adiabatic_index",
+                Some((5.0 / 3.0).into()),
+                |v, value, path| {
+                    if v.number(value, path).is_some_and(|gamma| gamma <= 1.0) {
+                        v.error(path, "must be greater than 1");
+                    }
+                },
+            );
+            self.field(table, path, "alpha", Some(1.0.into()), Self::non_negative);
+            self.field(table, path, "beta", Some(2.0.into()), Self::non_negative);
+        }
+    }
+
+    fn maneuvers(&mut self, value: &mut Value, path: &str) {
+        let maneuvers = match value.as_array_mut() {
+            Some(maneuvers) => maneuvers,
+            None => return self.error(path, "must be an array of maneuvers"),
+        };
+        for (i, entry) in maneuvers.iter_mut().enumerate() {
+            let path = format!("{}[{}]", path, i);
+            if let Some(table) = self.table(entry, &path, &["time", "body", "delta_v"]) {
+                self.field(table, &path, "time", None, |v, value, path| {
+                    v.number(value, path);
+                });
+                self.field(table, &path, "body", None, Self::integer);
+                self.field(table, &path, "delta_v", None, Self::vector);
+            }
+        }
+    }
+
+    fn thrust(&mut self, value: &mut Value, path: &str) {
+        let thrust = match value.as_array_mut() {
+            Some(thrust) => thrust,
+            None => return self.error(path, "must be an array of thrust profiles"),
+        };
+        for (i, entry) in thrust.iter_mut().enumerate() {
+            let path = format!("{}[{}]", path, i);
+            let kind = entry
+                .get("kind")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let known: &[&str] = match kind.as_deref() {
+                Some("Constant" | "Tangential") => &["body", "kind", "acceleration"],
+                Some("Table") => &["body", "kind", "start", "interval", "accelerations"],
+                _ => {
+                    self.error(
+                        &join(&path, "kind"),
+                        "must be one of Constant, Tangential, Table",
+                    );
+                    continue;
+                }
+            };
+            let table = match self.table(entry, &path, known) {
+                Some(table) => table,
+                None => continue,
+            };
+            self.field(table, &path, "body", None, Self::integer);
+            match kind.as_deref() {
+                Some("Constant") => self.field(table, &path, "acceleration", None, Self::vector),
+                Some("Tangential") => {
+                    self.field(table, &path, "acceleration", None, |v, value, path| {
+                        v.number(value, path);
+                    })
+                }
+                _ => {
+                    self.field(table, &path, "start", Some(0.0.into()), |v, value, path| {
+                        v.number(value, path);
+                    });
+                    self.field(table, &path, "interval", None, Self::positive);
+                    self.field(table, &path, "accelerations", None, |v, value, path| {
+                        let samples = match value.as_array_mut() {
+                            Some(samples) => samples,
+                            None => return v.error(path, "must be an array of vectors"),
+                        };
+                        if samples.len() < 2 {
+                            v.error(path, "must have at least 2 entries");
+                        }
+                        for (i, sample) in samples.iter_mut().enumerate() {
+                            v.vector(sample, &format!("{}[{}]", path, i));
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    fn events(&mut self, value: &mut Value, path: &str) {
+        let events = match value.as_array_mut() {
+            Some(events) => events,
+            None => return self.error(path, "must be an array of events"),
+        };
+        for (i, entry) in events.iter_mut().enumerate() {
+            let path = format!("{}[{}]", path, i);
+            let kind = entry
+                .get("kind")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let known: &[&str] = match kind.as_deref() {
+                Some("Plane") => &[
+                    "name",
+                    "kind",
+                    "direction",
+                    "terminal",
+                    "axis",
+                    "offset",
+                    "center",
+                ],
+                Some("Approach") => &["name", "kind", "direction", "terminal", "body", "distance"],
+                Some("Collision") => &["name", "kind", "direction", "terminal", "distance"],
+                Some("Predicate") => &["name", "kind", "direction", "terminal", "predicate"],
+                _ => {
+                    self.error(
+                        &join(&path, "kind"),
+                        "must be one of Plane, Approach, Collision, Predicate",
+                    );
+                    continue;
+                }
+            };
+            let table = match self.table(entry, &path, known) {
+                Some(table) => table,
+                None => continue,
+            };
+            self.field(table, &path, "name", None, |v, value, path| {
+                if !value.is_string() {
+                    v.error(path, "must be a string");
+                }
+            });
+            self.field(
+                table,
+                &path,
+                "direction",
+                Some(Value::Null),
+                |v, value, path| {
+                    if !value.is_null()
+                        && !matches!(value.as_str(), Some("Rising" | "Falling" | "Any"))
+                    {
+                        v.error(path, "must be one of Rising, Falling, Any");
+                    }
+                },
+            );
+            self.field(table, &path, "terminal", Some(false.into()), Self::boolean);
+            match kind.as_deref() {
+                Some("Plane") => self.plane(table, &path),
+                Some("Approach") => {
+                    self.field(table, &path, "body", None, Self::integer);
+                    self.field(table, &path, "distance", None, Self::positive);
+                }
+                Some("Collision") => self.field(table, &path, "distance", None, Self::positive),
+                _ => self.field(table, &path, "predicate", None, |v, value, path| {
+                    if value
+                        .as_str()
+                        .is_none_or(|predicate| predicate.trim().is_empty())
+                    {
+                        v.error(path, "must be a WGSL expression");
+                    }
+                }),
+            }
+        }
+    }
+
+    /// The fields of a plane, of a `Plane` event or a section
+    fn plane(&mut self, table: &mut Map<String, Value>, path: &str) {
+        self.field(table, path, "axis", None, |v, value, path| {
+            if !matches!(value.as_str(), Some("x" | "y" | "z")) {
+                v.error(path, "must be one of x, y, z");
+            }
+        });
+        self.field(table, path, "offset", Some(0.0.into()), |v, value, path| {
+            v.number(value, path);
+        });
+        self.field(
+            table,
+            path,
+            "center",
+            Some(Value::Null),
+            |v, value, path| {
+                if !value.is_null() {
+                    v.integer(value, path);
+                }
+            },
+        );
+    }
+
+    fn section(&mut self, value: &mut Value, path: &str) {
+        let known = ["axis", "offset", "center", "direction", "bodies"];
+        if let Some(table) = self.table(value, path, &known) {
+            self.plane(table, path);
+            let rising = Some("Rising".into());
+            self.field(table, path, "direction", rising, |v, value, path| {
+                if !matches!(value.as_str(), Some("Rising" | "Falling" | "Any")) {
+                    v.error(path, "must be one of Rising, Falling, Any");
+                }
+            });
+            let all = Some(Value::Array(Vec::new()));
+            self.field(table, path, "bodies", all, |v, value, path| {
+                let bodies = match value.as_array_mut() {
+                    Some(bodies) => bodies,
+                    None => return v.error(path, "must be an array of body indices"),
+                };
+                for (i, body) in bodies.iter_mut().enumerate() {
+                    v.integer(body, &format!("{}[{}]", path, i));
+                }
+            });
+        }
+    }
+
+    fn species(&mut self, value: &mut Value, path: &str) {
+        let species = match value.as_array_mut() {
+            Some(species) => species,
+            None => return self.error(path, "must be an array of species"),
+        };
+        if species.len() > MAX_SPECIES {
+            self.error(path, format!("must have at most {} entries", MAX_SPECIES));
+        }
+        for (i, entry) in species.iter_mut().enumerate() {
+            let path = format!("{}[{}]", path, i);
+            if let Some(table) = self.table(entry, &path, &["sigma", "epsilon"]) {
+                self.field(table, &path, "sigma", None, Self::positive);
+                self.field(table, &path, "epsilon", None, Self::non_negative);
+            }
+        }
+    }
+
+    fn ephemeris(&mut self, value: &mut Value, path: &str) {
+        let known = [
+            "path",
+            "bodies",
+            "center",
+            "epoch",
+            "interval",
+            "length_unit",
+            "time_unit",
+        ];
+        let table = match self.table(value, path, &known) {
+            Some(table) => table,
+            None => return,
+        };
+        let mut kernel = false;
+        self.field(table, path, "path", None, |v, value, path| {
+            match value.as_str() {
+                Some(file) => kernel = file.ends_with(".bsp"),
+                None => v.error(path, "must be a path"),
+            }
+        });
+        let none = Some(Value::Array(Vec::new()));
+        self.field(table, path, "bodies", none, |v, value, path| {
+            let bodies = match value.as_array_mut() {
+                Some(bodies) => bodies,
+                None => return v.error(path, "must be an array of bodies"),
+            };
+            if kernel && bodies.is_empty() {
+                v.error(path, "must list the bodies to take from an SPK kernel");
+            }
+            if bodies.len() > MAX_EPHEMERIS_BODIES {
+                v.error(
+                    path,
+                    format!("must have at most {} entries", MAX_EPHEMERIS_BODIES),
+                );
+            }
+            for (i, entry) in bodies.iter_mut().enumerate() {
+                let path = format!("{}[{}]", path, i);
+                if let Some(table) = v.table(entry, &path, &["body", "id"]) {
+                    v.field(table, &path, "body", None, Self::integer);
+                    v.field(table, &path, "id", None, Self::signed_integer);
+                }
+            }
+        });
+        self.field(table, path, "center", Some(0.into()), Self::signed_integer);
+        self.field(table, path, "epoch", Some(0.0.into()), |v, value, path| {
+            v.number(value, path);
+        });
+        let interval = if kernel { None } else { Some(Value::Null) };
+        self.field(table, path, "interval", interval, |v, value, path| {
+            if !value.is_null() {
+                v.positive(value, path)
+            }
+        });
+        self.field(table, path, "length_unit", Some(1.0.into()), Self::positive);
+        self.field(table, path, "time_unit", Some(1.0.into()), Self::positive);
+    }
+
+    fn radiation_pressure(&mut self, value: &mut Value, path: &str) {
+        if let Some(table) = self.table(value, path, &["source", "pressure"]) {
+            self.field(table, path, "source", None, Self::integer);
+            self.field(table, path, "pressure", None, Self::non_negative);
+        }
+    }
+
+    fn post_newtonian(&mut self, value: &mut Value, path: &str) {
+        let known = ["speed_of_light", "bodies", "radiation_reaction"];
+        if let Some(table) = self.table(value, path, &known) {
+            self.field(table, path, "speed_of_light", None, Self::positive);
+            self.field(table, path, "bodies", None, |v, value, path| {
+                let bodies = match value.as_array_mut() {
+                    Some(bodies) => bodies,
+                    None => return v.error(path, "must be an array of body indices"),
+                };
+                if bodies.is_empty() || bodies.len() > MAX_POST_NEWTONIAN_BODIES {
+                    v.error(
+                        path,
+                        format!("must have 1 to {} entries", MAX_POST_NEWTONIAN_BODIES),
+                    );
+                }
+                for (i, body) in bodies.iter_mut().enumerate() {
+                    v.integer(body, &format!("{}[{}]", path, i));
+                }
+            });
+            let off = Some(false.into());
+            self.field(table, path, "radiation_reaction", off, Self::boolean);
+        }
+    }
+
+    fn body(&mut self, value: &mut Value, path: &str) {
+        let known = [
+            "position",
+            "velocity",
+            "mass",
+            "mu",
+            "zonal",
+            "radius",
+            "area_to_mass",
+            "charge",
+            "species",
+            "gas",
+            "internal_energy",
+            "group",
+            "color",
+            "label",
+        ];
+        let zero = serde_json::json!([0.0, 0.0, 0.0]);
+        if let Some(table) = self.table(value, path, &known) {
+            self.field(table, path, "position", None, Self::vector);
+            self.field(table, path, "velocity", Some(zero.clone()), Self::vector);
+            self.field(table, path, "mass", Some(0.0.into()), Self::non_negative);
+            self.field(table, path, "mu", Some(0.0.into()), Self::non_negative);
+            self.field(table, path, "zonal", Some(zero), Self::vector);
+            self.field(table, path, "radius", Some(0.0.into()), Self::non_negative);
+            let zero = Some(0.0.into());
+            self.field(table, path, "area_to_mass", zero, Self::non_negative);
+            self.field(table, path, "charge", Some(0.0.into()), |v, value, path| {
+                v.number(value, path);
+            });
+            self.field(table, path, "species", Some(0.into()), Self::integer);
+            self.field(table, path, "gas", Some(false.into()), Self::boolean);
+            let zero = Some(0.0.into());
+            self.field(table, path, "internal_energy", zero, Self::non_negative);
+            for key in ["group", "color", "label"] {
+                self.field(table, path, key, Some(0.into()), Self::integer);
+            }
+        }
+    }
+
+    fn generator(&mut self, value: &mut Value, path: &str) {
+        let kinds = [
+            "UniformSphere",
+            "Plummer",
+            "King",
+            "Hernquist",
+            "ExponentialDisk",
+            "Merger",
+        ];
+        let kind = value.get("kind").and_then(Value::as_str).map(str::to_owned);
+        let known: &[&str] = match kind.as_deref() {
+            Some("UniformSphere") => &[
+                "kind", "count", "radius", "center", "velocity", "mu", "mass", "group", "color",
+            ],
+            Some("Plummer") | Some("Hernquist") => &[
+                "kind",
+                "count",
+                "scale_radius",
+                "mass",
+                "seed",
+                "center",
+                "velocity",
+                "group",
+                "color",
+            ],
+            Some("King") => &[
+                "kind",
+                "count",
+                "w0",
+                "core_radius",
+                "mass",
+                "seed",
+                "center",
+                "velocity",
+                "group",
+                "color",
+            ],
+            Some("ExponentialDisk") => &[
+                "kind",
+                "count",
+                "mass",
+                "scale_length",
+                "scale_height",
+                "dispersion",
+                "bulge",
+                "seed",
+                "center",
+                "velocity",
+                "group",
+                "color",
+            ],
+            Some("Merger") => &[
+                "kind",
+                "count",
+                "mass",
+                "scale_length",
+                "scale_height",
+                "dispersion",
+                "bulge",
+                "mass_ratio",
+                "pericenter",
+                "separation",
+                "inclination",
+                "seed",
+                "group",
+                "color",
+            ],
+            _ => {
+                self.error(
+                    &join(path, "kind"),
+                    format!("must be one of {}", kinds.join(", ")),
+                );
+                return;
+            }
+        };
+        let zero = serde_json::json!([0.0, 0.0, 0.0]);
+        if let Some(table) = self.table(value, path, known) {
+            self.field(table, path, "count", None, Self::positive_integer);
+            if kind.as_deref() != Some("Merger") {
+                self.field(table, path, "center", Some(zero.clone()), Self::vector);
+                self.field(table, path, "velocity", Some(zero), Self::vector);
+            }
+            match kind.as_deref() {
+                Some("UniformSphere") => {
+                    self.field(table, path, "radius", None, Self::positive);
+                    self.field(table, path, "mu", Some(0.0.into()), Self::non_negative);
+                    self.field(table, path, "mass", Some(0.0.into()), Self::non_negative);
+                }
+                Some("King") => {
+                    self.field(table, path, "w0", None, Self::positive);
+                    self.field(table, path, "core_radius", None, Self::positive);
+                }
+                Some("ExponentialDisk") | Some("Merger") => {
+                    self.field(table, path, "scale_length", None, Self::positive);
+                    self.field(table, path, "scale_height", None, Self::positive);
+                    let zero = Some(0.0.into());
+                    self.field(table, path, "dispersion", zero, Self::non_negative);
+                    if let Some(bulge) = table.get_mut("bulge") {
+                        self.bulge(bulge, &join(path, "bulge"));
+                    }
+                }
+                _ => self.field(table, path, "scale_radius", None, Self::positive),
+            }
+            if kind.as_deref() == Some("Merger") {
+                self.field(table, path, "mass_ratio", Some(1.0.into()), Self::positive);
+                self.field(table, path, "pericenter", None, Self::positive);
+                self.field(table, path, "separation", None, Self::positive);
+                let zero = Some(0.0.into());
+                self.field(table, path, "inclination", zero, |v, value, path| {
+                    v.number(value, path);
+                });
+            }
+            if kind.as_deref() != Some("UniformSphere") {
+                self.field(table, path, "mass", Some(1.0.into()), Self::positive);
+                self.field(table, path, "seed", Some(0.into()), Self::integer);
+            }
+            for key in ["group", "color"] {
+                self.field(table, path, key, Some(0.into()), Self::integer);
+            }
+        }
+    }
+
+    fn bulge(&mut self, value: &mut Value, path: &str) {
+        if let Some(table) = self.table(value, path, &["count", "mass", "scale_radius"]) {
+            self.field(table, path, "count", None, Self::positive_integer);
+            self.field(table, path, "mass", None, Self::positive);
+            self.field(table, path, "scale_radius", None, Self::positive);
+        }
+    }
+
+    fn bodies(&mut self, value: &mut Value, path: &str) {
+        match value.as_array_mut() {
+            Some(bodies) => {
+                for (i, body) in bodies.iter_mut().enumerate() {
+                    self.body(body, &format!("{}[{}]", path, i));
+                }
+            }
+            None => self.error(path, "must be an array of bodies"),
+        }
+    }
+}
+
+impl Validator {
+    /// Checks between sections, once each section is known to be well formed
+    fn references(&mut self, table: &Map<String, Value>) {
+        let bodies = match table.get("bodies").and_then(Value::as_array) {
+            Some(bodies) => bodies,
+            None => return,
+        };
+        let generator = table.get("generator");
+        let generated = generator
+            .and_then(|generator| generator.get("count"))
+            .and_then(Value::as_u64);
+        let bulge = generator
+            .and_then(|generator| generator.get("bulge"))
+            .and_then(|bulge| bulge.get("count"))
+            .and_then(Value::as_u64);
+        let mut num_bodies = bodies.len() as u64 + generated.unwrap_or(0) + bulge.unwrap_or(0);
+        let merger =
+            generator.and_then(|generator| generator.get("kind")) == Some(&"Merger".into());
+        let mass_ratio = generator
+            .and_then(|generator| generator.get("mass_ratio"))
+            .and_then(Value::as_f64);
+        if let (true, Some(mass_ratio)) = (merger, mass_ratio) {
+            // The second galaxy has its bodies in proportion to its mass, as in `ic::merger`
+            let scaled = |count: u64| ((count as f64 * mass_ratio).round() as u64).max(1);
+            num_bodies += generated.map_or(0, scaled) + bulge.map_or(0, scaled);
+        }
+        let max_bodies = table
+            .get("config")
+            .and_then(|config| config.get("max_bodies"))
+            .and_then(Value::as_u64);
+        if max_bodies.is_some_and(|max_bodies| max_bodies < num_bodies) {
+            self.error(
+                "config.max_bodies",
+                format!("must be at least the number of bodies, {}", num_bodies),
+            );
+        }
+        let lennard_jones = table
+            .get("force_law")
+            .and_then(|law| law.get("kind"))
+            .and_then(Value::as_str)
+            == Some("LennardJones");
+        let num_species = table
+            .get("species")
+            .and_then(Value::as_array)
+            .map_or(0, Vec::len) as u64;
+        for (i, body) in bodies.iter().enumerate() {
+            let species = body.get("species").and_then(Value::as_u64);
+            match species {
+                Some(species) if lennard_jones && species >= num_species => self.error(
+                    &format!("bodies[{}].species", i),
+                    format!("must be the index of one of the {} species", num_species),
+                ),
+                _ => {}
+            }
+        }
+        let cutoff = table
+            .get("force_law")
+            .filter(|_| lennard_jones)
+            .and_then(|law| law.get("cutoff"))
+            .and_then(Value::as_f64);
+        let cell_size = table
+            .get("neighbor_grid")
+            .and_then(|grid| grid.get("cell_size"))
+            .and_then(Value::as_f64);
+        if let (Some(cutoff), Some(cell_size)) = (cutoff, cell_size) {
+            if cell_size < cutoff {
+                self.error(
+                    "neighbor_grid.cell_size",
+                    format!("must be at least the Lennard-Jones cutoff of {}", cutoff),
+                );
+            }
+        }
+        if let Some(hydrodynamics) = table.get("hydrodynamics").filter(|h| !h.is_null()) {
+            let support = hydrodynamics
+                .get("smoothing_length")
+                .and_then(Value::as_f64)
+                .map(|h| 2.0 * h);
+            match (support, cell_size) {
+                (_, None) => self.error(
+                    "hydrodynamics",
+                    "requires a neighbor_grid to find the neighboring gas",
+                ),
+                (Some(support), Some(cell_size)) if cell_size < support => self.error(
+                    "neighbor_grid.cell_size",
+                    format!("must be at least twice the smoothing length, {}", support),
+                ),
+                _ => {}
+            }
+            for (i, body) in bodies.iter().enumerate() {
+                let gas = body.get("gas").and_then(Value::as_bool) == Some(true);
+                if gas && body.get("mass").and_then(Value::as_f64) == Some(0.0) {
+                    self.error(
+                        &format!("bodies[{}].mass", i),
+                        "must be positive for gas bodies",
+                    );
+                }
+            }
+        }
+        let particle_mesh = table
+            .get("force_solver")
+            .and_then(|solver| solver.get("kind"))
+            .and_then(Value::as_str)
+            == Some("PM");
+        let box_size = table
+            .get("config")
+            .and_then(|config| config.get("box_size"))
+            .and_then(Value::as_f64);
+        if particle_mesh && box_size == Some(0.0) {
+            self.error(
+                "config.box_size",
+                "must be positive, the particle-mesh solver needs a periodic box",
+            );
+        }
+        if particle_mesh {
+            // The mesh only carries Newtonian gravity, the terms the direct sum adds
+            // per pair or per body, MOND included, would be dropped
+            let law = table
+                .get("force_law")
+                .and_then(|law| law.get("kind"))
+                .and_then(Value::as_str);
+            if law.is_some_and(|law| law != "Newtonian") {
+                self.error(
+                    "force_law.kind",
+                    "must be Newtonian with the particle-mesh solver",
+                );
+            }
+            let zonal_harmonics = table
+                .get("config")
+                .and_then(|config| config.get("zonal_harmonics"))
+                .and_then(Value::as_bool);
+            if zonal_harmonics == Some(true) {
+                self.error(
+                    "config.zonal_harmonics",
+                    "is not supported by the particle-mesh solver",
+                );
+            }
+            for key in [
+                "neighbor_grid",
+                "radiation_pressure",
+                "post_newtonian",
+                "hydrodynamics",
+            ] {
+                if table.get(key).is_some_and(|value| !value.is_null()) {
+                    self.error(key, "is not supported by the particle-mesh solver");
+                }
+            }
+            let thrust = table.get("thrust").and_then(Value::as_array);
+            if thrust.is_some_and(|thrust| !thrust.is_empty()) {
+                self.error("thrust", "is not supported by the particle-mesh solver");
+            }
+        }
+        // The center of mass only stays put when nothing outside the bodies pulls
+        // on them, moves them or wraps them around the box
+        let config = table.get("config");
+        let com_frame = config
+            .and_then(|config| config.get("com_frame"))
+            .and_then(Value::as_bool)
+            == Some(true);
+        let com_correction = config
+            .and_then(|config| config.get("com_correction"))
+            .is_some_and(|value| !value.is_null());
+        for (enabled, path) in [
+            (com_frame, "config.com_frame"),
+            (com_correction, "config.com_correction"),
+        ] {
+            if !enabled {
+                continue;
+            }
+            for key in ["external_potential", "ephemeris"] {
+                if table.get(key).is_some_and(|value| !value.is_null()) {
+                    self.error(path, format!("can't be combined with {}", key));
+                }
+            }
+            if box_size.is_some_and(|size| size > 0.0) {
+                self.error(path, "can't be combined with a periodic box");
+            }
+        }
+        let prescribed = table
+            .get("ephemeris")
+            .and_then(|ephemeris| ephemeris.get("bodies"))
+            .and_then(Value::as_array);
+        for (i, entry) in prescribed.into_iter().flatten().enumerate() {
+            let body = match entry.get("body").and_then(Value::as_u64) {
+                Some(body) => body,
+                None => continue,
+            };
+            let path = format!("ephemeris.bodies[{}].body", i);
+            if body >= num_bodies {
+                self.error(
+                    &path,
+                    format!("must be the index of one of the {} bodies", num_bodies),
+                );
+            } else if prescribed
+                .into_iter()
+                .flatten()
+                .take(i)
+                .any(|other| other.get("body").and_then(Value::as_u64) == Some(body))
+            {
+                self.error(&path, "is already prescribed");
+            }
+        }
+        let maneuvers = table.get("maneuvers").and_then(Value::as_array);
+        for (i, entry) in maneuvers.into_iter().flatten().enumerate() {
+            if entry
+                .get("body")
+                .and_then(Value::as_u64)
+                .is_some_and(|body| body >= num_bodies)
+            {
+                self.error(
+                    &format!("maneuvers[{}].body", i),
+                    format!("must be the index of one of the {} bodies", num_bodies),
+                );
+            }
+        }
+        let thrust = table.get("thrust").and_then(Value::as_array);
+        for (i, entry) in thrust.into_iter().flatten().enumerate() {
+            let body = match entry.get("body").and_then(Value::as_u64) {
+                Some(body) => body,
+                None => continue,
+            };
+            let path = format!("thrust[{}].body", i);
+            if body >= num_bodies {
+                self.error(
+                    &path,
+                    format!("must be the index of one of the {} bodies", num_bodies),
+                );
+            } else if thrust
+                .into_iter()
+                .flatten()
+                .take(i)
+                .any(|other| other.get("body").and_then(Value::as_u64) == Some(body))
+            {
+                self.error(&path, "already has a thrust profile");
+            }
+        }
+        let events = table.get("events").and_then(Value::as_array);
+        for (i, entry) in events.into_iter().flatten().enumerate() {
+            let key = match entry.get("kind").and_then(Value::as_str) {
+                Some("Approach") => "body",
+                Some("Plane") => "center",
+                _ => continue,
+            };
+            if entry
+                .get(key)
+                .and_then(Value::as_u64)
+                .is_some_and(|body| body >= num_bodies)
+            {
+                self.error(
+                    &format!("events[{}].{}", i, key),
+                    format!("must be the index of one of the {} bodies", num_bodies),
+                );
+            }
+        }
+        if let Some(section) = table.get("section") {
+            let center = section.get("center").into_iter();
+            let bodies = section.get("bodies").and_then(Value::as_array);
+            let references = center
+                .map(|body| ("section.center".to_string(), body))
+                .chain(
+                    bodies
+                        .into_iter()
+                        .flatten()
+                        .enumerate()
+                        .map(|(i, body)| (format!("section.bodies[{}]", i), body)),
+                );
+            for (path, body) in references {
+                if body.as_u64().is_some_and(|body| body >= num_bodies) {
+                    self.error(
+                        &path,
+                        format!("must be the index of one of the {} bodies", num_bodies),
+                    );
+                }
+            }
+        }
+        let relativistic = table
+            .get("post_newtonian")
+            .and_then(|post_newtonian| post_newtonian.get("bodies"))
+            .and_then(Value::as_array);
+        for (i, entry) in relativistic.into_iter().flatten().enumerate() {
+            let body = match entry.as_u64() {
+                Some(body) => body,
+                None => continue,
+            };
+            let path = format!("post_newtonian.bodies[{}]", i);
+            if body >= num_bodies {
+                self.error(
+                    &path,
+                    format!("must be the index of one of the {} bodies", num_bodies),
+                );
+            } else if relativistic
+                .into_iter()
+                .flatten()
+                .take(i)
+                .any(|other| other.as_u64() == Some(body))
+            {
+                self.error(&path, "is already listed");
+            }
+        }
+        let source = table
+            .get("radiation_pressure")
+            .and_then(|radiation| radiation.get("source"))
+            .and_then(Value::as_u64);
+        if let Some(source) = source {
+            if source >= num_bodies {
+                self.error(
+                    "radiation_pressure.source",
+                    format!("must be the index of one of the {} bodies", num_bodies),
+                );
+            }
+        }
+    }
+}
+
+/// Validate a whole scenario document, filling in defaults.
+/// Returns the errors and the warnings.
+pub fn scenario(value: &mut Value) -> (Vec<Diagnostic>, Vec<Diagnostic>) {
+    let mut validator = Validator::default();
+    let known = [
+        "config",
+        "external_potential",
+        "radiation_pressure",
+        "post_newtonian",
+        "force_law",
+        "force_solver",
+        "neighbor_grid",
+        "hydrodynamics",
+        "species",
+        "bodies",
+        "generator",
+        "ephemeris",
+        "maneuvers",
+        "thrust",
+        "events",
+        "section",
+    ];
+    if let Some(table) = validator.table(value, "", &known) {
+        validator.field(table, "", "config", None, Validator::config);
+        validator.field(
+            table,
+            "",
+            "external_potential",
+            Some(Value::Null),
+            |v, value, path| {
+                if !value.is_null() {
+                    v.external_potential(value, path)
+                }
+            },
+        );
+        validator.field(
+            table,
+            "",
+            "radiation_pressure",
+            Some(Value::Null),
+            |v, value, path| {
+                if !value.is_null() {
+                    v.radiation_pressure(value, path)
+                }
+            },
+        );
+        validator.field(
+            table,
+            "",
+            "post_newtonian",
+            Some(Value::Null),
+            |v, value, path| {
+                if !value.is_null() {
+                    v.post_newtonian(value, path)
+                }
+            },
+        );
+        let newtonian = serde_json::json!({ "kind": "Newtonian" });
+        validator.field(
+            table,
+            "",
+            "force_law",
+            Some(newtonian),
+            Validator::force_law,
+        );
+        let direct = serde_json::json!({ "kind": "Direct" });
+        validator.field(
+            table,
+            "",
+            "force_solver",
+            Some(direct),
+            Validator::force_solver,
+        );
+        validator.field(
+            table,
+            "",
+            "neighbor_grid",
+            Some(Value::Null),
+            |v, value, path| {
+                if !value.is_null() {
+                    v.neighbor_grid(value, path)
+                }
+            },
+        );
+        validator.field(
+            table,
+            "",
+            "hydrodynamics",
+            Some(Value::Null),
+            |v, value, path| {
+                if !value.is_null() {
+                    v.hydrodynamics(value, path)
+                }
+            },
+        );
+        let none = Some(Value::Array(Vec::new()));
+        validator.field(table, "", "species", none, Validator::species);
+        let generator = table.get("generator").is_some_and(|g| !g.is_null());
+        if !generator && !table.contains_key("bodies") {
+            validator.error("bodies", "is required without a generator");
+        }
+        let none = Some(Value::Array(Vec::new()));
+        validator.field(table, "", "bodies", none, Validator::bodies);
+        validator.field(
+            table,
+            "",
+            "generator",
+            Some(Value::Null),
+            |v, value, path| {
+                if !value.is_null() {
+                    v.generator(value, path)
+                }
+            },
+        );
+        validator.field(
+            table,
+            "",
+            "ephemeris",
+            Some(Value::Null),
+            |v, value, path| {
+                if !value.is_null() {
+                    v.ephemeris(value, path)
+                }
+            },
+        );
+        let none = Some(Value::Array(Vec::new()));
+        validator.field(table, "", "maneuvers", none, Validator::maneuvers);
+        let none = Some(Value::Array(Vec::new()));
+        validator.field(table, "", "thrust", none, Validator::thrust);
+        let none = Some(Value::Array(Vec::new()));
+        validator.field(table, "", "events", none, Validator::events);
+        validator.field(table, "", "section", Some(Value::Null), |v, value, path| {
+            if !value.is_null() {
+                v.section(value, path)
+            }
+        });
+        validator.references(table);
+    }
+    (validator.errors, validator.warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn scenario_with(changes: impl FnOnce(&mut Value)) -> (Vec<Diagnostic>, Vec<Diagnostic>) {
+        let mut document = json!({
+            "config": {"dt": 0.01, "steps": 10},
+            "bodies": [
+                {"position": [0.0, 0.0, 0.0], "velocity": [0.0, 0.0, 0.0], "mu": 1.0},
+                {"position": [1.0, 0.0, 0.0], "velocity": [0.0, 1.0, 0.0], "mu": 0.0},
+            ],
+        });
+        changes(&mut document);
+        scenario(&mut document)
+    }
+
+    #[test]
+    fn valid_scenario() {
+        let (errors, warnings) = scenario_with(|_| {});
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    /// The message of the only diagnostic at `path`
+    fn message_at<'a>(diagnostics: &'a [Diagnostic], path: &str) -> &'a str {
+        let at_path: Vec<_> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.path == path)
+            .collect();
+        assert_eq!(at_path.len(), 1, "{:?}", diagnostics);
+        &at_path[0].message
+    }
+
+    #[test]
+    fn unknown_field() {
+        let (errors, warnings) = scenario_with(|document| {
+            document["config"]["softneing"] = json!(0.1);
+            document["bodies"][1]["colour"] = json!(3);
+        });
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            message_at(&warnings, "config.softneing"),
+            "is not a recognized key, did you mean `softening`?"
+        );
+        assert_eq!(
+            message_at(&warnings, "bodies[1].colour"),
+            "is not a recognized key, did you mean `color`?"
+        );
+    }
+
+    #[test]
+    fn zero_dt() {
+        let (errors, _) = scenario_with(|document| document["config"]["dt"] = json!(0.0));
+        assert_eq!(message_at(&errors, "config.dt"), "must not be zero");
+    }
+
+    #[test]
+    fn negative_dt() {
+        // Integrates backwards in time, the sign is the direction
+        let (errors, _) = scenario_with(|document| document["config"]["dt"] = json!(-0.01));
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (errors, _) = scenario_with(|document| document["config"]["dt"] = json!("-0.01"));
+        assert_eq!(message_at(&errors, "config.dt"), "must be a number");
+    }
+
+    #[test]
+    fn thrust_table_with_one_sample() {
+        let (errors, _) = scenario_with(|document| {
+            document["thrust"] = json!([
+                {"body": 1, "kind": "Constant", "acceleration": [0.0, 0.1, 0.0]},
+                {"body": 0, "kind": "Table", "interval": 0.5, "accelerations": [[0.0, 0.1, 0.0]]},
+            ]);
+        });
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(
+            message_at(&errors, "thrust[1].accelerations"),
+            "must have at least 2 entries"
+        );
+    }
+}
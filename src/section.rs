@@ -0,0 +1,139 @@
+//! Poincaré surfaces of section, the states of the bodies each time they pierce
+//! a plane in one direction, for telling regular orbits from chaotic ones.
+//!
+//! The section is an event of a plane crossing, see `events`, so the points are
+//! found to the precision of the bisection rather than the step. The plane and
+//! the points are relative to the body at index `center` if there is one.
+//!
+//! ```toml
+//! [section]
+//! axis = "y"
+//! center = 0
+//! direction = "Rising"
+//! bodies = [1]
+//! ```
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::events::{Axis, Event, EventCondition, EventDefinition, EventDirection};
+use crate::manifest::RunManifest;
+
+const HEADER: &str = "body,time,x,y,z,vx,vy,vz";
+
+/// The plane where the coordinate along `axis` is `offset`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SurfaceOfSection {
+    pub axis: Axis,
+    pub offset: f64,
+    /// The crossings recorded, `Rising` unless both sides are wanted
+    pub direction: EventDirection,
+    /// Body the frame of the section is centered on
+    pub center: Option<usize>,
+    /// Indices of the bodies recorded, all of them if empty
+    pub bodies: Vec<usize>,
+}
+
+impl SurfaceOfSection {
+    /// The event of a body piercing the section
+    pub fn definition(&self) -> EventDefinition {
+        EventDefinition {
+            name: "section".to_string(),
+            condition: EventCondition::Plane {
+                axis: self.axis,
+                offset: self.offset,
+                center: self.center,
+            },
+            direction: Some(self.direction),
+            terminal: false,
+        }
+    }
+
+    /// Whether the body at index `body` of the scenario is recorded
+    pub fn records(&self, body: usize) -> bool {
+        self.bodies.is_empty() || self.bodies.contains(&body)
+    }
+}
+
+/// A body on the section, relative to the center of its frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SectionPoint {
+    /// Index of the body in the scenario
+    pub body: usize,
+    pub time: f64,
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+}
+
+impl SectionPoint {
+    /// The point of an event of `SurfaceOfSection::definition`
+    pub fn new(event: &Event) -> Self {
+        let relative = |state: [f32; 3], origin: Option<[f32; 3]>| {
+            let origin = origin.unwrap_or_default();
+            [0, 1, 2].map(|i| state[i] as f64 - origin[i] as f64)
+        };
+        let reference = event.reference.as_ref();
+        Self {
+            body: event.body,
+            time: event.time,
+            position: relative(event.state.position, reference.map(|body| body.position)),
+            velocity: relative(event.state.velocity, reference.map(|body| body.velocity)),
+        }
+    }
+}
+
+/// One row per point of a section as it's pierced
+pub struct SectionCsvWriter<W: Write = BufWriter<File>> {
+    writer: W,
+    points: usize,
+}
+
+impl SectionCsvWriter {
+    pub fn create(path: &Path, manifest: &RunManifest) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        manifest.write_comment(&mut writer)?;
+        Self::new(writer)
+    }
+}
+
+impl<W: Write> SectionCsvWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(Self { writer, points: 0 })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn write_point(&mut self, point: &SectionPoint) -> io::Result<()> {
+        let state: Vec<String> = point
+            .position
+            .into_iter()
+            .chain(point.velocity)
+            .map(|value| value.to_string())
+            .collect();
+        writeln!(
+            self.writer,
+            "{},{},{}",
+            point.body,
+            point.time,
+            state.join(",")
+        )?;
+        self.points += 1;
+        Ok(())
+    }
+
+    /// Number of points written so far
+    pub fn points(&self) -> usize {
+        self.points
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,265 @@
+//! Checking the rendered shader templates with naga before wgpu compiles them,
+//! so that errors point back into the template rather than at rendered lines
+use std::fmt;
+
+use naga::{
+    valid::{Capabilities, ValidationFlags, Validator},
+    Span, TypeInner,
+};
+
+use crate::error::describe;
+use crate::structures::SharedLayout;
+
+/// An error in a rendered shader, with the template line it came from
+#[derive(Debug, Clone)]
+pub struct ShaderDiagnostic {
+    /// Name of the template, such as `gather.wgsl`
+    pub template: String,
+    pub message: String,
+    /// 1-based line and column in the rendered source
+    pub line: usize,
+    pub column: usize,
+    /// 1-based line of the template the error line was rendered from, if the
+    /// lines around it could be matched up
+    pub template_line: Option<usize>,
+    /// The error with the rendered lines it points at
+    pub report: String,
+    pub rendered: String,
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.template_line {
+            Some(template_line) => write!(
+                f,
+                "{} line {} (rendered line {}, column {}): {}",
+                self.template, template_line, self.line, self.column, self.message
+            )?,
+            None => write!(
+                f,
+                "{} rendered line {}, column {}: {}",
+                self.template, self.line, self.column, self.message
+            )?,
+        }
+        write!(f, "\n{}", self.report.trim_end())
+    }
+}
+
+/// Parse and validate `rendered`, the source rendered from `template`
+pub fn validate(
+    name: &str,
+    template: &str,
+    rendered: &str,
+) -> Result<naga::Module, Box<ShaderDiagnostic>> {
+    let (message, location, report) = match naga::front::wgsl::parse_str(rendered) {
+        Err(error) => (
+            error.message().to_string(),
+            error.location(rendered),
+            error.emit_to_string(rendered),
+        ),
+        Ok(module) => {
+            // Push constants are declared only where the device supports them
+            match Validator::new(ValidationFlags::all(), Capabilities::PUSH_CONSTANT)
+                .validate(&module)
+            {
+                Ok(_) => return Ok(module),
+                Err(error) => {
+                    // The spans narrow down from the function to the expression
+                    let location = error
+                        .spans()
+                        .filter_map(|(span, _)| span.to_range())
+                        .last()
+                        .map(|range| {
+                            let text = &rendered[range.clone()];
+                            let start = range.end - text.trim_start().len();
+                            Span::from(start..range.end).location(rendered)
+                        });
+                    let report = location.map_or(String::new(), |location| {
+                        excerpt(rendered, location.line_number as usize)
+                    });
+                    (describe(&error), location, report)
+                }
+            }
+        }
+    };
+    let (line, column) = location.map_or((0, 0), |location| {
+        (
+            location.line_number as usize,
+            location.line_position as usize,
+        )
+    });
+    Err(Box::new(ShaderDiagnostic {
+        template: name.to_string(),
+        message,
+        line,
+        column,
+        template_line: trace_line(template, rendered, line),
+        report,
+        rendered: rendered.to_string(),
+    }))
+}
+
+/// Compare the structs of `module`, parsed from the shader `name`, named like
+/// one of `layouts` with the host layout, describing every field at another
+/// offset or missing on either side
+pub fn check_layouts(
+    name: &str,
+    module: &naga::Module,
+    layouts: &[SharedLayout],
+) -> Result<(), String> {
+    for (_, ty) in module.types.iter() {
+        let (members, span) = match &ty.inner {
+            TypeInner::Struct { members, span } => (members, *span as usize),
+            _ => continue,
+        };
+        let layout = ty
+            .name
+            .as_deref()
+            .and_then(|name| layouts.iter().find(|layout| layout.name == name));
+        let layout = match layout {
+            Some(layout) => layout,
+            None => continue,
+        };
+        let mut mismatches = Vec::new();
+        if span != layout.size {
+            mismatches.push(format!(
+                "{} bytes in WGSL but {} in Rust",
+                span, layout.size
+            ));
+        }
+        for member in members {
+            let field = member.name.as_deref().unwrap_or_default();
+            match layout.fields.iter().find(|(name, _)| *name == field) {
+                Some(&(_, offset)) if offset == member.offset as usize => (),
+                Some(&(_, offset)) => mismatches.push(format!(
+                    "{} at offset {} in WGSL but {} in Rust",
+                    field, member.offset, offset
+                )),
+                None => mismatches.push(format!("{} is missing in Rust", field)),
+            }
+        }
+        for (field, _) in layout.fields {
+            if !members
+                .iter()
+                .any(|member| member.name.as_deref() == Some(*field))
+            {
+                mismatches.push(format!("{} is missing in WGSL", field));
+            }
+        }
+        if !mismatches.is_empty() {
+            return Err(format!(
+                "{} of {}: {}",
+                layout.name,
+                name,
+                mismatches.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The rendered lines around `line`, numbered
+fn excerpt(rendered: &str, line: usize) -> String {
+    rendered
+        .lines()
+        .enumerate()
+        .skip(line.saturating_sub(3))
+        .take(5)
+        .map(|(index, text)| format!("{:>5} | {}\n", index + 1, text))
+        .collect()
+}
+
+/// The template line `line` of `rendered` came from. Lines are matched up along
+/// their longest common subsequence. An unmatched line, such as one with a
+/// substituted value, is traced if as many lines separate the matched lines
+/// around it in both, so that no lines were repeated or left out in between.
+fn trace_line(template: &str, rendered: &str, line: usize) -> Option<usize> {
+    let template: Vec<_> = template.lines().map(str::trim_end).collect();
+    let rendered: Vec<_> = rendered.lines().map(str::trim_end).collect();
+    if line == 0 || line > rendered.len() {
+        return None;
+    }
+    // Length of the common subsequence of the tails starting at each pair of lines
+    let width = rendered.len() + 1;
+    let mut common = vec![0u32; (template.len() + 1) * width];
+    for t in (0..template.len()).rev() {
+        for r in (0..rendered.len()).rev() {
+            common[t * width + r] = if template[t] == rendered[r] {
+                common[(t + 1) * width + r + 1] + 1
+            } else {
+                common[(t + 1) * width + r].max(common[t * width + r + 1])
+            };
+        }
+    }
+    let mut matched = vec![None; rendered.len()];
+    let (mut t, mut r) = (0, 0);
+    while t < template.len() && r < rendered.len() {
+        if template[t] == rendered[r] {
+            matched[r] = Some(t);
+            t += 1;
+            r += 1;
+        } else if common[(t + 1) * width + r] >= common[t * width + r + 1] {
+            t += 1;
+        } else {
+            r += 1;
+        }
+    }
+
+    let index = line - 1;
+    if let Some(t) = matched[index] {
+        return Some(t + 1);
+    }
+    let before = (0..index)
+        .rev()
+        .find_map(|r| matched[r].map(|t| (r as isize, t as isize)))
+        .unwrap_or((-1, -1));
+    let after = (index + 1..rendered.len())
+        .find_map(|r| matched[r].map(|t| (r as isize, t as isize)))
+        .unwrap_or((rendered.len() as isize, template.len() as isize));
+    (after.0 - before.0 == after.1 - before.1)
+        .then(|| (before.1 + index as isize - before.0) as usize + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = "\
+fn main() {
+{% if softening %}
+    let eps = {{ softening }};
+{% endif %}
+    let x = 1.0;
+    let y = x * 2.0;
+}";
+
+    #[test]
+    fn rendered_lines_trace_back_to_the_template() {
+        // The tags leave blank lines and the value is substituted
+        let rendered = "\
+fn main() {
+
+    let eps = 0.05;
+
+    let x = 1.0;
+    let y = x * 2.0;
+}";
+        assert_eq!(trace_line(TEMPLATE, rendered, 1), Some(1));
+        assert_eq!(trace_line(TEMPLATE, rendered, 3), Some(3));
+        assert_eq!(trace_line(TEMPLATE, rendered, 6), Some(6));
+        assert_eq!(trace_line(TEMPLATE, rendered, 8), None);
+    }
+
+    #[test]
+    fn substituted_lines_are_not_guessed_across_stripped_tags() {
+        // Fewer lines separate the neighbors of the substituted line than in the template
+        let rendered = "\
+fn main() {
+    let eps = 0.05;
+    let x = 1.0;
+    let y = x * 2.0;
+}";
+        assert_eq!(trace_line(TEMPLATE, rendered, 2), None);
+        assert_eq!(trace_line(TEMPLATE, rendered, 4), Some(6));
+    }
+}
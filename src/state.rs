@@ -0,0 +1,93 @@
+//! Body states with glam vectors, for building initial conditions and analysing
+//! readbacks without the raw arrays of `Body`
+use glam::{DVec3, Vec3};
+
+use crate::error::ParabodyError;
+use crate::pipeline::Pipeline;
+use crate::structures::Body;
+
+/// The state of a body in double precision, rounded to single precision when
+/// uploaded as a `Body`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BodyState {
+    pub position: DVec3,
+    pub velocity: DVec3,
+    pub mass: f64,
+    /// Gravitational parameter, the mass times the gravitational constant
+    pub mu: f64,
+}
+
+impl BodyState {
+    pub fn new(position: DVec3, velocity: DVec3, mass: f64, mu: f64) -> Self {
+        Self {
+            position,
+            velocity,
+            mass,
+            mu,
+        }
+    }
+
+    /// A state from single precision vectors, such as those of `glam::Vec3` math
+    pub fn from_f32(position: Vec3, velocity: Vec3, mass: f32, mu: f32) -> Self {
+        Self::new(
+            position.as_dvec3(),
+            velocity.as_dvec3(),
+            mass as f64,
+            mu as f64,
+        )
+    }
+
+    pub fn position_f32(&self) -> Vec3 {
+        self.position.as_vec3()
+    }
+
+    pub fn velocity_f32(&self) -> Vec3 {
+        self.velocity.as_vec3()
+    }
+
+    pub fn momentum(&self) -> DVec3 {
+        self.mass * self.velocity
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        0.5 * self.mass * self.velocity.length_squared()
+    }
+}
+
+impl From<Body> for BodyState {
+    fn from(body: Body) -> Self {
+        Self::from_f32(
+            Vec3::from(body.position),
+            Vec3::from(body.velocity),
+            body.mass,
+            body.mu,
+        )
+    }
+}
+
+impl From<BodyState> for Body {
+    fn from(state: BodyState) -> Self {
+        Body {
+            position: state.position_f32().to_array(),
+            mass: state.mass as f32,
+            velocity: state.velocity_f32().to_array(),
+            mu: state.mu as f32,
+        }
+    }
+}
+
+impl Pipeline {
+    /// Upload `states` as the bodies, like `write_bodies`
+    pub fn write_states(&mut self, states: &[BodyState]) -> Result<(), ParabodyError> {
+        let bodies: Vec<Body> = states.iter().copied().map(Body::from).collect();
+        self.write_bodies(&bodies)
+    }
+
+    /// The latest state of every body, like `read_bodies`
+    pub fn read_states(&mut self) -> Vec<BodyState> {
+        self.read_bodies()
+            .into_iter()
+            .map(BodyState::from)
+            .collect()
+    }
+}
@@ -0,0 +1,225 @@
+//! Conditions ending a run early, checked on the host between submissions.
+//!
+//! Checking reads the bodies back. The energy is summed over all pairs of bodies
+//! and collisions are looked for on a grid, so large runs should check them
+//! every few thousand steps rather than after every one.
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    analysis,
+    escape::{Ejection, EscapeCriteria},
+    pipeline::Pipeline,
+    structures::{Body, BodyProperties},
+};
+
+/// Fraction of a step by which the simulated time may fall short of its limit
+/// and still count as reaching it, for the rounding of the step size
+const TIME_TOLERANCE: f64 = 1e-3;
+
+/// When a run becomes meaningless or reaches its goal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopCondition {
+    /// The total energy drifts from its initial value by more than this fraction
+    /// of it
+    EnergyError(f64),
+    /// Two bodies come within `distance` plus the sum of their radii of each other,
+    /// without wrapping around a periodic box
+    Collision { distance: f64 },
+    /// A body escapes the system
+    Escape(EscapeCriteria),
+    /// The run has taken this long
+    WallClock(Duration),
+    /// The simulated time reaches this, in the direction of integration
+    SimulatedTime(f64),
+}
+
+/// The condition a run stopped on, with what met it. Events are those of a
+/// scenario by their index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    EnergyError { relative: f64 },
+    Collision { bodies: [usize; 2], distance: f64 },
+    Escape(Ejection),
+    WallClock(Duration),
+    SimulatedTime(f64),
+    Event { event: usize, body: usize },
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::EnergyError { relative } => {
+                write!(f, "relative energy error {:.3e}", relative)
+            }
+            StopReason::Collision { bodies, distance } => write!(
+                f,
+                "bodies {} and {} collided {:.3e} apart",
+                bodies[0], bodies[1], distance
+            ),
+            StopReason::Escape(ejection) => write!(
+                f,
+                "body {} escaped {:.3e} from the barycenter",
+                ejection.body, ejection.distance
+            ),
+            StopReason::WallClock(elapsed) => {
+                write!(f, "ran for {:.3} s", elapsed.as_secs_f64())
+            }
+            StopReason::SimulatedTime(time) => write!(f, "reached t = {:.6e}", time),
+            StopReason::Event { event, body } => {
+                write!(f, "terminal event {} of body {}", event, body)
+            }
+        }
+    }
+}
+
+/// Stop conditions with what they're measured from, the state and the moment
+/// the monitor was created
+pub struct StopMonitor {
+    conditions: Vec<StopCondition>,
+    started: Instant,
+    initial_energy: f64,
+}
+
+impl StopMonitor {
+    pub fn new(conditions: Vec<StopCondition>, pipeline: &mut Pipeline) -> Self {
+        let energy_error = conditions
+            .iter()
+            .any(|condition| matches!(condition, StopCondition::EnergyError(_)));
+        let initial_energy = match energy_error {
+            true => analysis::total_energy(&pipeline.read_bodies()),
+            false => 0.0,
+        };
+        Self {
+            conditions,
+            started: Instant::now(),
+            initial_energy,
+        }
+    }
+
+    pub fn conditions(&self) -> &[StopCondition] {
+        &self.conditions
+    }
+
+    /// Steps of `pipeline` up to the simulated time limit, at most `max_steps`,
+    /// so that the submissions end on it
+    pub fn steps_until(&self, pipeline: &Pipeline, max_steps: usize) -> usize {
+        let dt = pipeline.dynamic_config().dt.abs() as f64;
+        let sign = pipeline.time_direction().sign() as f64;
+        let mut steps = max_steps;
+        for condition in &self.conditions {
+            if let StopCondition::SimulatedTime(limit) = condition {
+                let remaining = sign * (limit - pipeline.time()) / dt;
+                if remaining > 0.0 {
+                    steps = steps.min(((remaining - TIME_TOLERANCE).ceil() as usize).max(1));
+                }
+            }
+        }
+        steps
+    }
+
+    /// The first condition met by the current state of `pipeline`, in the order
+    /// they were given
+    pub fn check(&self, pipeline: &mut Pipeline) -> Option<StopReason> {
+        let needs_bodies = self.conditions.iter().any(|condition| {
+            matches!(
+                condition,
+                StopCondition::EnergyError(_)
+                    | StopCondition::Collision { .. }
+                    | StopCondition::Escape(_)
+            )
+        });
+        let bodies = match needs_bodies {
+            true => pipeline.read_bodies(),
+            false => Vec::new(),
+        };
+        for condition in &self.conditions {
+            let reason = match *condition {
+                StopCondition::EnergyError(max) => {
+                    let energy = analysis::total_energy(&bodies);
+                    let relative = match self.initial_energy != 0.0 {
+                        true => (energy - self.initial_energy).abs() / self.initial_energy.abs(),
+                        false => 0.0,
+                    };
+                    (relative > max).then_some(StopReason::EnergyError { relative })
+                }
+                StopCondition::Collision { distance } => {
+                    first_collision(&bodies, &pipeline.read_properties(), distance)
+                        .map(|(bodies, distance)| StopReason::Collision { bodies, distance })
+                }
+                StopCondition::Escape(criteria) => criteria
+                    .escapers(&bodies)
+                    .first()
+                    .map(|&ejection| StopReason::Escape(ejection)),
+                StopCondition::WallClock(limit) => {
+                    let elapsed = self.started.elapsed();
+                    (elapsed >= limit).then_some(StopReason::WallClock(elapsed))
+                }
+                StopCondition::SimulatedTime(limit) => {
+                    let dt = pipeline.dynamic_config().dt.abs() as f64;
+                    let sign = pipeline.time_direction().sign() as f64;
+                    let time = pipeline.time();
+                    (sign * (time - limit) >= -TIME_TOLERANCE * dt)
+                        .then_some(StopReason::SimulatedTime(time))
+                }
+            };
+            if reason.is_some() {
+                return reason;
+            }
+        }
+        None
+    }
+}
+
+/// The pair of bodies with the lowest indices within `distance` plus the sum of
+/// their radii of each other, and how far apart they are. The bodies are binned
+/// into cells as wide as the farthest reach, so only neighboring cells are compared.
+fn first_collision(
+    bodies: &[Body],
+    properties: &[BodyProperties],
+    distance: f64,
+) -> Option<([usize; 2], f64)> {
+    let radius = |i: usize| properties.get(i).map_or(0.0, |p| p.radius as f64);
+    let max_radius = (0..bodies.len()).map(radius).fold(0.0, f64::max);
+    let cell_size = distance + 2.0 * max_radius;
+    if cell_size <= 0.0 {
+        return None;
+    }
+    let cell = |body: &Body| body.position.map(|x| (x as f64 / cell_size).floor() as i64);
+    let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    for (i, body) in bodies.iter().enumerate() {
+        cells.entry(cell(body)).or_default().push(i);
+    }
+    let mut first: Option<([usize; 2], f64)> = None;
+    for (i, body) in bodies.iter().enumerate() {
+        let [x, y, z] = cell(body);
+        for offset in 0..27 {
+            let neighbor = [
+                x + offset % 3 - 1,
+                y + offset / 3 % 3 - 1,
+                z + offset / 9 - 1,
+            ];
+            for &j in cells.get(&neighbor).into_iter().flatten() {
+                if j <= i || first.is_some_and(|(pair, _)| pair <= [i, j]) {
+                    continue;
+                }
+                let separation = (0..3)
+                    .map(|axis| {
+                        (body.position[axis] as f64 - bodies[j].position[axis] as f64).powi(2)
+                    })
+                    .sum::<f64>()
+                    .sqrt();
+                if separation <= distance + radius(i) + radius(j) {
+                    first = Some(([i, j], separation));
+                }
+            }
+        }
+        if first.is_some() {
+            break;
+        }
+    }
+    first
+}
@@ -1,18 +1,431 @@
+use std::mem::{offset_of, size_of};
+
 use bytemuck::{Pod, Zeroable};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct StaticConfig {
     pub max_bodies: u32,
+    pub external_potential: Option<ExternalPotential>,
+    /// Apply the zonal harmonics in `BodyProperties` to bodies orbiting oblate bodies
+    pub zonal_harmonics: bool,
+    pub radiation_pressure: Option<RadiationPressure>,
+    pub force_law: ForceLaw,
+    pub force_solver: ForceSolver,
+    /// Bin bodies into cells so that short-range forces only visit neighboring cells
+    pub neighbor_grid: Option<NeighborGrid>,
+    /// Smoothed-particle hydrodynamics between the bodies flagged as gas
+    pub hydrodynamics: Option<Hydrodynamics>,
+    /// Overwrite the bodies prescribed by an ephemeris with their interpolated
+    /// state after every step, see `Pipeline::write_ephemeris`
+    #[serde(default)]
+    pub ephemeris: bool,
+    /// Add the continuous thrust of the bodies to their accelerations, see
+    /// `Pipeline::write_thrust`
+    #[serde(default)]
+    pub thrust: bool,
+    /// Compile the kernels removing the drift of the center of mass, see
+    /// `Pipeline::set_com_correction`
+    #[serde(default)]
+    pub com_correction: bool,
+    /// Add the first post-Newtonian corrections of the bodies set with
+    /// `Pipeline::set_post_newtonian`
+    #[serde(default)]
+    pub post_newtonian: bool,
+    /// Integrate a tangent vector of every body for the chaos indicators, see
+    /// `Pipeline::read_chaos_indicators`
+    #[serde(default)]
+    pub variational: bool,
+    /// Keep the direct-sum forces within the systems written with
+    /// `Pipeline::write_ensemble`
+    #[serde(default)]
+    pub ensemble: bool,
+    /// Sort the bodies of every entry of the neighbor grid by index, so that
+    /// the sums over neighbors run in a fixed order and a run restarted from a
+    /// checkpoint repeats it bit for bit, see `archive`
+    #[serde(default)]
+    pub deterministic: bool,
+    /// Threads per workgroup of every kernel, reduced to what the adapter allows
+    pub workgroup_size: u32,
+}
+
+/// Threads per workgroup unless configured otherwise
+pub const DEFAULT_WORKGROUP_SIZE: u32 = 64;
+
+impl Default for StaticConfig {
+    fn default() -> Self {
+        Self {
+            max_bodies: 1024,
+            force_law: ForceLaw::Newtonian,
+            external_potential: None,
+            zonal_harmonics: false,
+            radiation_pressure: None,
+            force_solver: ForceSolver::Direct,
+            neighbor_grid: None,
+            hydrodynamics: None,
+            ephemeris: false,
+            thrust: false,
+            com_correction: false,
+            post_newtonian: false,
+            variational: false,
+            ensemble: false,
+            deterministic: false,
+            workgroup_size: DEFAULT_WORKGROUP_SIZE,
+        }
+    }
+}
+
+impl StaticConfig {
+    /// Whether any enabled feature reads `BodyProperties`
+    pub fn uses_properties(&self) -> bool {
+        self.zonal_harmonics
+            || self.radiation_pressure.is_some()
+            || !matches!(self.force_law, ForceLaw::Newtonian | ForceLaw::Mond { .. })
+            || self.hydrodynamics.is_some()
+    }
+}
+
+/// The pairwise interaction between bodies. The variant is selected when the
+/// shader is rendered, the parameters are packed into `DynamicConfig::force_params`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum ForceLaw {
+    /// Newtonian gravity from each body's `mu`
+    Newtonian,
+    /// Electrostatic `k * q_i * q_j / r^2` from `BodyProperties::charge`, divided by
+    /// `Body::mass` to get the acceleration. Massless bodies feel no electrostatic force.
+    Coulomb {
+        coulomb_constant: f32,
+        /// Also apply Newtonian gravity
+        gravity: bool,
+    },
+    /// Lennard-Jones 12-6 between the species in `BodyProperties::species`, using
+    /// the Lorentz-Berthelot rules to mix the parameters set with `Pipeline::set_species`.
+    /// Divided by `Body::mass` to get the acceleration.
+    LennardJones {
+        /// Separation beyond which the interaction is ignored
+        cutoff: f32,
+        /// Also apply Newtonian gravity
+        gravity: bool,
+    },
+    /// Modified Newtonian dynamics, the gravity of the other bodies strengthened
+    /// where it falls below `a0`. The summed Newtonian acceleration `g` becomes
+    /// `nu(|g| / a0) * g`, which tends to `sqrt(a0 * |g|)` in the deep MOND regime
+    /// and flattens the rotation curves. The potential and the energies stay
+    /// Newtonian.
+    Mond {
+        a0: f32,
+        #[serde(default)]
+        interpolation: MondInterpolation,
+    },
+}
+
+/// The interpolating function of MOND, as `mu(x)` with `mu(x) * a = g` for
+/// `x = |a| / a0`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MondInterpolation {
+    /// `mu(x) = x / (1 + x)`
+    #[default]
+    Simple,
+    /// `mu(x) = x / sqrt(1 + x^2)`
+    Standard,
+}
+
+impl ForceLaw {
+    pub fn params(&self) -> [f32; 4] {
+        match *self {
+            ForceLaw::Newtonian => [0.0; 4],
+            ForceLaw::Coulomb {
+                coulomb_constant, ..
+            } => [coulomb_constant, 0.0, 0.0, 0.0],
+            ForceLaw::LennardJones { cutoff, .. } => [cutoff, 0.0, 0.0, 0.0],
+            ForceLaw::Mond { a0, .. } => [a0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// The time stepping scheme. Every scheme integrates backwards with a negative
+/// step, the time-symmetric ones retrace their forward steps doing so.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// Kick-drift-kick leapfrog, second order, symplectic and time-symmetric
+    #[default]
+    Leapfrog,
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 1] = [Integrator::Leapfrog];
+
+    /// Name in scenario files and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Integrator::Leapfrog => "leapfrog",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|integrator| integrator.name() == name)
+    }
+
+    /// Whether a step backwards undoes a step forwards in exact arithmetic
+    pub fn is_time_symmetric(self) -> bool {
+        match self {
+            Integrator::Leapfrog => true,
+        }
+    }
+}
+
+/// How the gravitational accelerations are computed
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum ForceSolver {
+    /// Sum over every pair of bodies, exact but `O(n^2)`
+    #[default]
+    Direct,
+    /// Particle-mesh: deposit the mass onto a periodic grid of `grid_size^3` cells
+    /// with cloud-in-cell weights, solve Poisson's equation with an FFT and
+    /// interpolate the forces back. Requires a periodic box and the split pass graph.
+    /// Only gravity and the external potential are applied.
+    PM {
+        /// Cells along each axis, a power of two no larger than `MAX_PM_GRID_SIZE`
+        grid_size: u32,
+    },
+}
+
+/// Largest supported particle-mesh grid, so that a dispatch over every cell
+/// stays within the workgroup count limit
+pub const MAX_PM_GRID_SIZE: u32 = 128;
+
+/// A uniform grid hashed into a fixed size table, rebuilt before the forces of
+/// every step. Cells that collide in the table are visited together, which costs
+/// time but not correctness. Currently used by the Lennard-Jones force law.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct NeighborGrid {
+    /// Number of entries in the hash table
+    pub table_size: u32,
+    /// Edge length of a cell, at least the interaction range. In a periodic box
+    /// the cells are enlarged so that a whole number of them fit.
+    pub cell_size: f32,
+}
+
+/// Parameters of the smoothed-particle hydrodynamics, an ideal gas with a cubic
+/// spline kernel and Monaghan artificial viscosity. Gas bodies are flagged with
+/// `BodyProperties::gas` and carry a `GasState`. Requires the neighbor grid, with
+/// cells of at least twice the smoothing length.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Hydrodynamics {
+    /// Kernel smoothing length `h`, the kernel vanishes beyond `2 * h`
+    pub smoothing_length: f32,
+    /// Ratio of specific heats
+    pub adiabatic_index: f32,
+    /// Linear artificial viscosity coefficient
+    pub alpha: f32,
+    /// Quadratic artificial viscosity coefficient
+    pub beta: f32,
+}
+
+impl Hydrodynamics {
+    pub fn params(&self) -> [f32; 4] {
+        [
+            self.smoothing_length,
+            self.adiabatic_index,
+            self.alpha,
+            self.beta,
+        ]
+    }
+}
+
+/// Maximum number of Lennard-Jones species, the shader's species table has this size
+pub const MAX_SPECIES: usize = 8;
+
+/// Lennard-Jones parameters for one species
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Species {
+    /// Separation at which the potential is zero
+    pub sigma: f32,
+    /// Depth of the potential well
+    pub epsilon: f32,
+}
+
+/// Maximum number of bodies with post-Newtonian corrections, the shader's table
+/// of their indices has this size
+pub const MAX_POST_NEWTONIAN_BODIES: usize = 4;
+
+/// First-order post-Newtonian corrections to the gravity of a few massive bodies,
+/// taking the bodies orbiting them as test particles. Gives the relativistic
+/// precession of the orbits, `6 * pi * mu / (c^2 * a * (1 - e^2))` per orbit.
+///
+/// The corrections are added to the Newtonian acceleration in `f32`, so they are
+/// lost when smaller than its resolution. At the real speed of light, 63241 AU
+/// per year, Mercury's are about 1e-8 of its acceleration and its 43 arcseconds
+/// per century don't come out. Scaled down to 632 AU per year the precession
+/// matches the formula to about 2%, for studying the effect rather than Mercury.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostNewtonian {
+    /// Speed of light in simulation units
+    pub speed_of_light: f32,
+    /// Indices of the massive bodies, which feel no corrections from each other
+    /// unless both are listed
+    pub bodies: Vec<usize>,
+    /// Add the 2.5PN radiation reaction between pairs of the listed bodies, the
+    /// loss of orbital energy to gravitational waves which makes compact binaries
+    /// inspiral
+    #[serde(default)]
+    pub radiation_reaction: bool,
+}
+
+/// Radiation pressure from a single luminous body, acting on bodies with a
+/// non-zero `BodyProperties::area_to_mass`. Bodies with a radius cast cylindrical shadows.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RadiationPressure {
+    /// Index of the luminous body
+    pub source: u32,
+    /// `L / (4 * pi * c)` in simulation units, so that the acceleration is
+    /// `pressure * area_to_mass / d^2` directed away from the source
+    pub pressure: f32,
+}
+
+/// A fixed analytic background potential felt by every body.
+/// The variant is selected when the shader is rendered, the parameters
+/// are packed into `DynamicConfig::external_params` and can be changed between runs.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum ExternalPotential {
+    /// Point mass, `a = -mu * r / |r|^3`
+    Kepler { center: [f32; 3], mu: f32 },
+    /// Isotropic harmonic trap, `a = -omega^2 * r`
+    Harmonic { center: [f32; 3], omega: f32 },
+    /// Miyamoto-Nagai disk with scale length `a` and scale height `b`, symmetric about z
+    MiyamotoNagai {
+        center: [f32; 3],
+        mu: f32,
+        a: f32,
+        b: f32,
+    },
+    /// Navarro-Frenk-White halo, `mu = 4 * pi * G * rho_0 * r_s^3`
+    Nfw {
+        center: [f32; 3],
+        mu: f32,
+        scale_radius: f32,
+    },
+    /// The circular restricted three-body problem in the frame rotating with the
+    /// primaries about `center`, see `ic::cr3bp` for the units. Adds the
+    /// centrifugal term, and the kicks rotate the velocities for the Coriolis term.
+    Cr3bp {
+        center: [f32; 3],
+        /// Mass of the secondary over the total mass of the primaries
+        mass_parameter: f32,
+    },
+}
+
+impl ExternalPotential {
+    /// Pack the parameters into the layout expected by the shader,
+    /// the center always occupies `xyz` of the first vector
+    pub fn params(&self) -> [[f32; 4]; 2] {
+        match *self {
+            ExternalPotential::Kepler { center, mu } => {
+                [[center[0], center[1], center[2], mu], [0.0; 4]]
+            }
+            ExternalPotential::Harmonic { center, omega } => {
+                [[center[0], center[1], center[2], omega], [0.0; 4]]
+            }
+            ExternalPotential::MiyamotoNagai { center, mu, a, b } => {
+                [[center[0], center[1], center[2], mu], [a, b, 0.0, 0.0]]
+            }
+            ExternalPotential::Nfw {
+                center,
+                mu,
+                scale_radius,
+            } => [
+                [center[0], center[1], center[2], mu],
+                [scale_radius, 0.0, 0.0, 0.0],
+            ],
+            ExternalPotential::Cr3bp {
+                center,
+                mass_parameter,
+            } => [[center[0], center[1], center[2], mass_parameter], [0.0; 4]],
+        }
+    }
+
+    /// Acceleration at `r` from the center of the potential, as applied by the
+    /// shader
+    pub fn acceleration(&self, r: [f64; 3]) -> [f64; 3] {
+        let distance = r.iter().map(|x| x * x).sum::<f64>().sqrt();
+        match *self {
+            ExternalPotential::Kepler { mu, .. } => r.map(|x| -mu as f64 / distance.powi(3) * x),
+            ExternalPotential::Harmonic { omega, .. } => r.map(|x| -(omega as f64).powi(2) * x),
+            ExternalPotential::MiyamotoNagai { mu, a, b, .. } => {
+                let (a, b) = (a as f64, b as f64);
+                let zeta = (r[2] * r[2] + b * b).sqrt();
+                let d = (r[0] * r[0] + r[1] * r[1] + (a + zeta).powi(2)).sqrt();
+                let factor = -mu as f64 / d.powi(3);
+                [
+                    factor * r[0],
+                    factor * r[1],
+                    factor * r[2] * (a + zeta) / zeta,
+                ]
+            }
+            ExternalPotential::Nfw {
+                mu, scale_radius, ..
+            } => {
+                let x = distance / scale_radius as f64;
+                let enclosed = (1.0 + x).ln() - x / (1.0 + x);
+                r.map(|r| -mu as f64 * enclosed / distance.powi(3) * r)
+            }
+            ExternalPotential::Cr3bp { mass_parameter, .. } => {
+                crate::ic::cr3bp::acceleration(mass_parameter as f64, r)
+            }
+        }
+    }
+}
+
+/// Parameters pushed with every dispatch where the adapter supports push constants,
+/// in place of the same fields of `DynamicConfig`. The shader reads them as one
+/// `vec2<i32>` and reinterprets the step size.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Zeroable, Pod)]
+pub struct StepParams {
+    /// Signed, the GL backend can't push unsigned integers
+    pub num_bodies: i32,
+    pub dt: f32,
 }
 
-// TODO: Check alignment
+/// Stored by field name in checkpoints and archives, so that a file from a build
+/// with different fields fails to load instead of loading shifted values
 #[repr(C, align(16))]
-#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[derive(Debug, Clone, Copy, Zeroable, Pod, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct DynamicConfig {
     pub num_bodies: u32,
     pub dt: f32,
-    _pad: [u32; 2],
+    pub luminous_body: u32,
+    pub radiation_pressure: f32,
+    pub external_params: [[f32; 4]; 2],
+    pub force_params: [f32; 4],
+    /// `sigma` and `epsilon` in `xy` of each entry
+    pub species: [[f32; 4]; MAX_SPECIES],
+    /// `Hydrodynamics::params`
+    pub hydro_params: [f32; 4],
+    /// Edge length of the periodic box `[0, box_size)^3`, zero for open boundaries
+    pub box_size: f32,
+    /// Fixed-point scale of the particle-mesh mass deposit
+    pub pm_mass_scale: f32,
+    /// Edge length of the neighbor grid cells
+    pub cell_size: f32,
+    /// Plummer softening length of the direct-sum gravity
+    pub softening: f32,
+    /// Indices of the bodies with post-Newtonian corrections, `u32::MAX` in the
+    /// unused entries
+    pub post_newtonian_bodies: [u32; MAX_POST_NEWTONIAN_BODIES],
+    /// Speed of light in `x`, one in `y` to add the radiation reaction
+    pub post_newtonian_params: [f32; 4],
+    /// Number of systems of the ensemble, zero if the bodies form one system
+    pub num_systems: u32,
+    #[serde(skip)]
+    pub _padding: [u32; 3],
 }
 
 impl Default for DynamicConfig {
@@ -20,7 +433,20 @@ impl Default for DynamicConfig {
         Self {
             num_bodies: 0,
             dt: 3600.0,
-            _pad: [0; 2],
+            luminous_body: 0,
+            radiation_pressure: 0.0,
+            external_params: [[0.0; 4]; 2],
+            force_params: [0.0; 4],
+            species: [[0.0; 4]; MAX_SPECIES],
+            hydro_params: [0.0; 4],
+            box_size: 0.0,
+            pm_mass_scale: 1.0,
+            cell_size: 0.0,
+            softening: 0.0,
+            post_newtonian_bodies: [u32::MAX; MAX_POST_NEWTONIAN_BODIES],
+            post_newtonian_params: [0.0; 4],
+            num_systems: 0,
+            _padding: [0; 3],
         }
     }
 }
@@ -33,3 +459,347 @@ pub struct Body {
     pub velocity: [f32; 3],
     pub mu: f32,
 }
+
+/// A field of `Body` which can be read back on its own with `Pipeline::read_field`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyField {
+    Position,
+    Velocity,
+    Mass,
+    Mu,
+}
+
+impl BodyField {
+    pub const ALL: [Self; 4] = [Self::Position, Self::Velocity, Self::Mass, Self::Mu];
+
+    /// Number of `f32` components
+    pub fn components(self) -> usize {
+        match self {
+            Self::Position | Self::Velocity => 3,
+            Self::Mass | Self::Mu => 1,
+        }
+    }
+
+    /// The kernel in `gather.wgsl` copying out the field
+    pub fn entry_point(self) -> &'static str {
+        match self {
+            Self::Position => "gather_position",
+            Self::Velocity => "gather_velocity",
+            Self::Mass => "gather_mass",
+            Self::Mu => "gather_mu",
+        }
+    }
+
+    /// The components of the field of `body`
+    pub fn of(self, body: &Body) -> &[f32] {
+        match self {
+            Self::Position => &body.position,
+            Self::Velocity => &body.velocity,
+            Self::Mass => std::slice::from_ref(&body.mass),
+            Self::Mu => std::slice::from_ref(&body.mu),
+        }
+    }
+}
+
+/// Layout of a struct the shaders share with the host, which every shader module
+/// declaring a struct of the same name must match, see `shader::check_layouts`
+#[derive(Debug, Clone, Copy)]
+pub struct SharedLayout {
+    /// Name of the struct in WGSL
+    pub name: &'static str,
+    pub size: usize,
+    /// Name and offset of every field
+    pub fields: &'static [(&'static str, usize)],
+}
+
+macro_rules! shared_layout {
+    ($name:literal, $ty:ty, [$($field:ident),* $(,)?]) => {
+        SharedLayout {
+            name: $name,
+            size: size_of::<$ty>(),
+            fields: &[$((stringify!($field), offset_of!($ty, $field))),*],
+        }
+    };
+}
+
+/// The structs uploaded to the shaders or read back from them
+pub const SHARED_LAYOUTS: [SharedLayout; 11] = [
+    shared_layout!(
+        "Config",
+        DynamicConfig,
+        [
+            num_bodies,
+            dt,
+            luminous_body,
+            radiation_pressure,
+            external_params,
+            force_params,
+            species,
+            hydro_params,
+            box_size,
+            pm_mass_scale,
+            cell_size,
+            softening,
+            post_newtonian_bodies,
+            post_newtonian_params,
+            num_systems,
+        ]
+    ),
+    shared_layout!("Body", Body, [position, mass, velocity, mu]),
+    shared_layout!(
+        "BodyProperties",
+        BodyProperties,
+        [zonal, radius, area_to_mass, charge, species, gas]
+    ),
+    shared_layout!(
+        "GasState",
+        GasState,
+        [internal_energy, density, pressure, heating_rate]
+    ),
+    shared_layout!(
+        "EphemerisState",
+        EphemerisState,
+        [
+            bodies,
+            num_bodies,
+            num_samples,
+            interval,
+            sample_index,
+            fraction
+        ]
+    ),
+    shared_layout!(
+        "ThrustState",
+        ThrustState,
+        [
+            acceleration,
+            kind,
+            first,
+            num_samples,
+            sample_index,
+            fraction
+        ]
+    ),
+    shared_layout!(
+        "Tangent",
+        Tangent,
+        [
+            position,
+            log_growth,
+            velocity,
+            weighted_growth,
+            megno_integral,
+            time
+        ]
+    ),
+    shared_layout!("System", System, [first, count, softening]),
+    shared_layout!(
+        "EventRecord",
+        EventRecord,
+        [step, body, predicate, before, after]
+    ),
+    shared_layout!(
+        "NeighborQuery",
+        NeighborQuery,
+        [
+            num_bodies,
+            k,
+            cell_size,
+            box_size,
+            cells_per_axis,
+            rings,
+            table_size
+        ]
+    ),
+    shared_layout!("Neighbor", Neighbor, [index, distance]),
+];
+
+/// Per-body parameters which are not evolved by the integrator
+#[repr(C, align(16))]
+#[derive(Debug, Default, Clone, Copy, Zeroable, Pod)]
+pub struct BodyProperties {
+    /// Zonal harmonic coefficients J2, J3 and J4, the symmetry axis is the frame's z axis
+    pub zonal: [f32; 3],
+    /// Equatorial reference radius the zonal harmonics are normalized to,
+    /// also the radius of the shadow cast by the body
+    pub radius: f32,
+    /// Effective area to mass ratio for radiation pressure, including the reflectivity
+    pub area_to_mass: f32,
+    /// Electric charge for the Coulomb force law
+    pub charge: f32,
+    /// Index into the species table for the Lennard-Jones force law
+    pub species: u32,
+    /// Non-zero for gas bodies in the hydrodynamics
+    pub gas: u32,
+}
+
+/// How external viewers should style a body, carried alongside the body state
+/// in exported files but never uploaded to the GPU
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VisualAttributes {
+    /// Bodies in a group are usually shown or hidden together, and their
+    /// statistics are taken together, see `groups`
+    pub group: u32,
+    /// Index into the viewer's palette
+    pub color: u32,
+    /// Identifier the viewer can resolve to a display name
+    pub label: u32,
+}
+
+/// RGB colors indexed by `VisualAttributes::color` modulo the length, must match
+/// the palette of `points.wgsl`
+pub const PALETTE: [[f32; 3]; 8] = [
+    [1.0, 1.0, 1.0],
+    [1.0, 0.8, 0.3],
+    [0.3, 0.6, 1.0],
+    [1.0, 0.35, 0.3],
+    [0.4, 0.9, 0.4],
+    [0.8, 0.5, 1.0],
+    [0.3, 0.9, 0.9],
+    [0.6, 0.6, 0.6],
+];
+
+/// Thermodynamic state of a gas body, the internal energy is integrated
+/// alongside the velocity while the density and pressure are recomputed every step
+#[repr(C, align(16))]
+#[derive(Debug, Default, Clone, Copy, Zeroable, Pod)]
+pub struct GasState {
+    /// Specific internal energy
+    pub internal_energy: f32,
+    pub density: f32,
+    pub pressure: f32,
+    /// Rate of change of the internal energy from the last force evaluation
+    pub heating_rate: f32,
+}
+
+/// A body's tangent vector, the displacement of a shadow body in phase space, and
+/// the integrals of its growth behind the chaos indicators, see `chaos`
+#[repr(C, align(16))]
+#[derive(Debug, Default, Clone, Copy, Zeroable, Pod)]
+pub struct Tangent {
+    pub position: [f32; 3],
+    /// Logarithm of the norm taken out at every renormalization, summed
+    pub log_growth: f32,
+    pub velocity: [f32; 3],
+    /// Integral of the growth rate times the time
+    pub weighted_growth: f32,
+    /// Integral of the MEGNO over the time
+    pub megno_integral: f32,
+    /// Time since the tangent vectors were reset
+    pub time: f32,
+    pub _padding: [f32; 2],
+}
+
+/// One system of an ensemble as the kernels read it, see `ensemble::Ensemble`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Zeroable, Pod)]
+pub struct System {
+    /// Index of the first body of the system, and the number of bodies
+    pub first: u32,
+    pub count: u32,
+    /// Plummer softening length of the gravity within the system
+    pub softening: f32,
+}
+
+/// Crossings the event log holds between readings, see `Pipeline::read_events`
+pub const MAX_EVENT_RECORDS: usize = 4096;
+
+/// A crossing of an event predicate as the kernels log it, see `events`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Zeroable, Pod)]
+pub struct EventRecord {
+    /// Step since the log was last read, counting from zero
+    pub step: u32,
+    pub body: u32,
+    /// Index of the predicate among those the pipeline was built with
+    pub predicate: u32,
+    /// Values of the predicate at the start and the end of the step
+    pub before: f32,
+    pub after: f32,
+}
+
+/// Maximum number of bodies an ephemeris can prescribe, the shader's table of
+/// their indices has this size
+pub const MAX_EPHEMERIS_BODIES: usize = 32;
+
+/// The bodies prescribed by an ephemeris and the clock its samples are read at.
+/// The clock is advanced by the steps on the GPU and set from the simulated
+/// time before every submission.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+pub struct EphemerisState {
+    /// Index of every prescribed body, in the order of their samples
+    pub bodies: [u32; MAX_EPHEMERIS_BODIES],
+    pub num_bodies: u32,
+    /// Samples per body
+    pub num_samples: u32,
+    /// Time between samples
+    pub interval: f32,
+    /// Index of the sample at or before the current time
+    pub sample_index: i32,
+    /// Position between that sample and the next, in `[0, 1)`
+    pub fraction: f32,
+}
+
+impl Default for EphemerisState {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+/// Kinds of `ThrustState`, bodies without thrust are zeroed
+pub const THRUST_CONSTANT: u32 = 1;
+pub const THRUST_TANGENTIAL: u32 = 2;
+pub const THRUST_TABLE: u32 = 3;
+
+/// A body's thrust as the kernels read it, see `thrust::ThrustProfile`. Tables
+/// keep a clock like `EphemerisState`'s, the interval between their samples is
+/// in the `w` of every sample.
+#[repr(C, align(16))]
+#[derive(Debug, Default, Clone, Copy, Zeroable, Pod)]
+pub struct ThrustState {
+    /// Constant acceleration, or in `x` the acceleration along the velocity
+    pub acceleration: [f32; 3],
+    /// One of the `THRUST_*` kinds
+    pub kind: u32,
+    /// Index of the first sample of a table, and their number
+    pub first: u32,
+    pub num_samples: u32,
+    /// Index of the sample at or before the current time
+    pub sample_index: i32,
+    /// Position between that sample and the next, in `[0, 1)`
+    pub fraction: f32,
+}
+
+/// Most neighbors `Pipeline::search_neighbors` finds per body, the size of the
+/// list each thread keeps
+pub const MAX_NEIGHBORS: usize = 64;
+
+/// The parameters of a neighbor search, see `neighbors::NeighborSearch`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Zeroable, Pod)]
+pub struct NeighborQuery {
+    pub num_bodies: u32,
+    /// Neighbors found per body, at most `MAX_NEIGHBORS`
+    pub k: u32,
+    /// Edge length of the cells the bodies are hashed by
+    pub cell_size: f32,
+    /// Periodic box edge length, zero for open boundaries
+    pub box_size: f32,
+    /// Cells along each axis of the periodic box, zero for open boundaries
+    pub cells_per_axis: u32,
+    /// Shells of cells searched around a body's own, beyond which a body can
+    /// end up with fewer than `k` neighbors
+    pub rings: u32,
+    /// Entries of the hash table
+    pub table_size: u32,
+}
+
+/// A neighbor of a body as the search finds it
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Zeroable, Pod)]
+pub struct Neighbor {
+    /// Index of the neighbor, `u32::MAX` where fewer than `k` were found
+    pub index: u32,
+    pub distance: f32,
+}
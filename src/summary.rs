@@ -0,0 +1,145 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{analysis, manifest::RunManifest, structures::Body};
+
+/// A self-describing record of a finished run
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub adapter: String,
+    pub wall_time: f64,
+    pub steps: usize,
+    pub simulated_time: f64,
+    /// Simulated time at the end, which runs continued from a checkpoint start
+    /// past zero
+    pub final_time: f64,
+    /// Calendar epochs of the start and the end, if the run has one
+    pub start_epoch: Option<String>,
+    pub final_epoch: Option<String>,
+    pub bodies: usize,
+    /// Pairwise interactions evaluated over the run
+    pub interactions: u64,
+    pub interactions_per_second: f64,
+    pub initial_energy: f64,
+    pub final_energy: f64,
+    /// `(final - initial) / |initial|`, zero if the initial energy is zero
+    pub relative_energy_drift: f64,
+    pub final_momentum: [f64; 3],
+    pub final_center_of_mass: Option<[f64; 3]>,
+    /// Number of events of each kind raised during the run
+    pub events: BTreeMap<String, u64>,
+    /// The stop condition which ended the run early, if one did
+    pub stop_reason: Option<String>,
+    /// Files written by the run
+    pub outputs: Vec<PathBuf>,
+    /// Provenance of the run, when known
+    pub manifest: Option<RunManifest>,
+}
+
+impl RunSummary {
+    pub fn new(
+        adapter: String,
+        wall_time: Duration,
+        steps: usize,
+        dt: f32,
+        initial: &[Body],
+        last: &[Body],
+    ) -> Self {
+        let wall_time = wall_time.as_secs_f64();
+        let num_bodies = initial.len() as u64;
+        let interactions = steps as u64 * num_bodies * num_bodies.saturating_sub(1);
+        let initial_energy = analysis::total_energy(initial);
+        let final_energy = analysis::total_energy(last);
+        let relative_energy_drift = if initial_energy != 0.0 {
+            (final_energy - initial_energy) / initial_energy.abs()
+        } else {
+            0.0
+        };
+        Self {
+            adapter,
+            wall_time,
+            steps,
+            simulated_time: steps as f64 * dt as f64,
+            final_time: steps as f64 * dt as f64,
+            start_epoch: None,
+            final_epoch: None,
+            bodies: initial.len(),
+            interactions,
+            interactions_per_second: if wall_time > 0.0 {
+                interactions as f64 / wall_time
+            } else {
+                0.0
+            },
+            initial_energy,
+            final_energy,
+            relative_energy_drift,
+            final_momentum: analysis::momentum(last),
+            final_center_of_mass: analysis::center_of_mass(last),
+            events: BTreeMap::new(),
+            stop_reason: None,
+            outputs: Vec::new(),
+            manifest: None,
+        }
+    }
+
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let vector = |v: [f64; 3]| format!("[{:.6e}, {:.6e}, {:.6e}]", v[0], v[1], v[2]);
+        let mut rows = vec![
+            ("Adapter", self.adapter.clone()),
+            ("Wall time", format!("{:.3} s", self.wall_time)),
+            ("Steps", self.steps.to_string()),
+            ("Simulated time", format!("{:.6}", self.simulated_time)),
+            ("Final time", format!("{:.6}", self.final_time)),
+            ("Bodies", self.bodies.to_string()),
+            ("Interactions", self.interactions.to_string()),
+            (
+                "Interactions/s",
+                format!("{:.3e}", self.interactions_per_second),
+            ),
+            ("Initial energy", format!("{:.9e}", self.initial_energy)),
+            ("Final energy", format!("{:.9e}", self.final_energy)),
+            (
+                "Energy drift",
+                format!("{:.3e}", self.relative_energy_drift),
+            ),
+            ("Final momentum", vector(self.final_momentum)),
+            (
+                "Final COM",
+                self.final_center_of_mass.map_or("-".to_string(), vector),
+            ),
+        ];
+        if let (Some(start), Some(end)) = (&self.start_epoch, &self.final_epoch) {
+            rows.push(("Start epoch", start.clone()));
+            rows.push(("Final epoch", end.clone()));
+        }
+        if let Some(reason) = &self.stop_reason {
+            rows.push(("Stopped", reason.clone()));
+        }
+        for (kind, count) in &self.events {
+            rows.push(("Events", format!("{}: {}", kind, count)));
+        }
+        for output in &self.outputs {
+            rows.push(("Output", output.display().to_string()));
+        }
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let rule = "-".repeat(width + 3 + rows.iter().map(|(_, v)| v.len()).max().unwrap_or(0));
+        writeln!(f, "{}", rule)?;
+        for (name, value) in rows {
+            writeln!(f, "{:<width$} | {}", name, value, width = width)?;
+        }
+        write!(f, "{}", rule)
+    }
+}
@@ -0,0 +1,331 @@
+//! Parameter sweeps, many runs of a scenario with some of its values replaced.
+//!
+//! A sweep file names the scenario and the parameters, each a dotted path into
+//! the scenario as for `scenario::set` with the values it takes, a list or a
+//! range. The runs are every combination of the values, with the ranges evenly
+//! spaced over `count` values, or `samples` runs drawn at random, each value
+//! uniformly from its list or range.
+//!
+//! ```toml
+//! scenario = "binary.toml"
+//! samples = 1000
+//!
+//! [[parameters]]
+//! path = "bodies.1.velocity.1"
+//! range = [0.8, 1.2]
+//! ```
+//!
+//! Runs which only differ in their bodies and softening can share a pipeline
+//! as the systems of an ensemble, see `batch_key`.
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::analysis;
+use crate::format::invalid_data;
+use crate::ic::Rng;
+use crate::scenario::{self, Scenario};
+use crate::structures::{Body, ForceSolver, StaticConfig};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sweep {
+    /// Scenario file the runs start from, relative to the sweep file
+    pub scenario: PathBuf,
+    pub parameters: Vec<Parameter>,
+    /// Runs drawn at random instead of the grid of every combination
+    #[serde(default)]
+    pub samples: Option<usize>,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// A value of the scenario the sweep varies
+#[derive(Debug, Clone, Deserialize)]
+pub struct Parameter {
+    /// Dotted path to the value, e.g. `config.softening` or `bodies.1.mu`
+    pub path: String,
+    #[serde(flatten)]
+    pub values: ParameterValues,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ParameterValues {
+    List {
+        values: Vec<Value>,
+    },
+    /// Between the two ends, `count` values on a grid including both
+    Range {
+        range: [f64; 2],
+        #[serde(default)]
+        count: Option<usize>,
+    },
+}
+
+impl Sweep {
+    /// Load a sweep file in TOML or JSON, chosen by the extension
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let document = Scenario::read_document(path).map_err(|error| match error {
+            scenario::ScenarioError::Io(error) => error,
+            error => invalid_data(error.to_string()),
+        })?;
+        let mut sweep: Sweep =
+            serde_json::from_value(document).map_err(|error| invalid_data(error.to_string()))?;
+        if let Some(directory) = path.parent() {
+            sweep.scenario = directory.join(&sweep.scenario);
+        }
+        sweep.check()?;
+        Ok(sweep)
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.parameters.is_empty() {
+            return Err(invalid_data(
+                "A sweep needs at least one parameter".to_string(),
+            ));
+        }
+        if self.samples == Some(0) {
+            return Err(invalid_data("samples must be positive".to_string()));
+        }
+        for parameter in &self.parameters {
+            let problem = match &parameter.values {
+                ParameterValues::List { values } if values.is_empty() => Some("has no values"),
+                ParameterValues::List { .. } => None,
+                ParameterValues::Range { range, .. }
+                    if !range.iter().all(|end| end.is_finite()) =>
+                {
+                    Some("must range between finite numbers")
+                }
+                ParameterValues::Range { count, .. } if self.samples.is_none() => match count {
+                    None => Some("needs a count of values for the grid"),
+                    Some(0) => Some("needs a positive count"),
+                    Some(_) => None,
+                },
+                ParameterValues::Range { .. } => None,
+            };
+            if let Some(problem) = problem {
+                return Err(invalid_data(format!("{} {}", parameter.path, problem)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Paths of the parameters, in the order of the values of every run
+    pub fn paths(&self) -> Vec<&str> {
+        self.parameters
+            .iter()
+            .map(|parameter| parameter.path.as_str())
+            .collect()
+    }
+
+    /// The values of the parameters of every run
+    pub fn runs(&self) -> Vec<Vec<Value>> {
+        match self.samples {
+            Some(samples) => {
+                let mut rng = Rng::new(self.seed);
+                (0..samples)
+                    .map(|_| {
+                        self.parameters
+                            .iter()
+                            .map(|parameter| match &parameter.values {
+                                ParameterValues::List { values } => {
+                                    let index = (rng.uniform() * values.len() as f64) as usize;
+                                    values[index.min(values.len() - 1)].clone()
+                                }
+                                ParameterValues::Range { range, .. } => {
+                                    (range[0] + rng.uniform() * (range[1] - range[0])).into()
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+            None => {
+                let mut runs = vec![Vec::new()];
+                for parameter in &self.parameters {
+                    let values = parameter.grid();
+                    runs = runs
+                        .into_iter()
+                        .flat_map(|run| {
+                            values.iter().map(move |value| {
+                                let mut run = run.clone();
+                                run.push(value.clone());
+                                run
+                            })
+                        })
+                        .collect();
+                }
+                runs
+            }
+        }
+    }
+
+    /// Set the `values` of a run in the scenario's `document`
+    pub fn apply(&self, document: &mut Value, values: &[Value]) {
+        for (parameter, value) in self.parameters.iter().zip(values) {
+            scenario::set(document, &parameter.path, value.clone());
+        }
+    }
+}
+
+impl Parameter {
+    fn grid(&self) -> Vec<Value> {
+        match &self.values {
+            ParameterValues::List { values } => values.clone(),
+            ParameterValues::Range { range, count } => {
+                let count = count.unwrap_or(1);
+                (0..count)
+                    .map(|i| match count {
+                        1 => range[0],
+                        _ => range[0] + (range[1] - range[0]) * i as f64 / (count - 1) as f64,
+                    })
+                    .map(Value::from)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Equal for scenarios which can run together as the systems of an ensemble,
+/// `None` for those which need a pipeline of their own. Ensembles share
+/// everything but the bodies and the softening, and leave out the features
+/// which address bodies by their index or solve for all of them at once.
+pub fn batch_key(scenario: &Scenario) -> Option<String> {
+    let batchable = matches!(scenario.force_solver, ForceSolver::Direct)
+        && scenario.neighbor_grid.is_none()
+        && scenario.hydrodynamics.is_none()
+        && scenario.radiation_pressure.is_none()
+        && scenario.post_newtonian.is_none()
+        && scenario.ephemeris.is_none()
+        && scenario.thrust.is_empty()
+        && scenario.maneuvers.is_empty()
+        && !scenario.config.com_frame
+        && scenario.config.com_correction.is_none();
+    if !batchable {
+        return None;
+    }
+    let static_config = StaticConfig {
+        max_bodies: 0,
+        ..scenario.static_config()
+    };
+    let config = &scenario.config;
+    serde_json::to_string(&(
+        static_config,
+        config.dt,
+        config.steps,
+        config.integrator,
+        config.box_size,
+        &config.epoch,
+        config.time_unit,
+        &scenario.species,
+    ))
+    .ok()
+}
+
+/// Diagnostics of a finished run of a sweep
+#[derive(Debug, Clone, Copy)]
+pub struct RunOutcome {
+    pub bodies: usize,
+    pub final_time: f64,
+    pub initial_energy: f64,
+    pub final_energy: f64,
+    /// `(final - initial) / |initial|`, zero if the initial energy is zero
+    pub relative_energy_drift: f64,
+    /// Length of the change of the total momentum
+    pub momentum_drift: f64,
+}
+
+impl RunOutcome {
+    pub fn new(initial: &[Body], last: &[Body], final_time: f64) -> Self {
+        let initial_energy = analysis::total_energy(initial);
+        let final_energy = analysis::total_energy(last);
+        let momentum = analysis::momentum(initial);
+        let change = analysis::momentum(last)
+            .iter()
+            .zip(momentum)
+            .map(|(last, initial)| (last - initial).powi(2))
+            .sum::<f64>();
+        Self {
+            bodies: last.len(),
+            final_time,
+            initial_energy,
+            final_energy,
+            relative_energy_drift: if initial_energy != 0.0 {
+                (final_energy - initial_energy) / initial_energy.abs()
+            } else {
+                0.0
+            },
+            momentum_drift: change.sqrt(),
+        }
+    }
+}
+
+/// A run of a sweep, with the pipeline it ran on and how it ended
+#[derive(Debug, Clone)]
+pub struct RunResult {
+    pub values: Vec<Value>,
+    /// Runs with the same batch shared a pipeline, `None` for those which were
+    /// never run
+    pub batch: Option<usize>,
+    /// Why the run failed, if it did
+    pub outcome: Result<RunOutcome, String>,
+}
+
+/// Write one row per run with the values of the parameters and the diagnostics,
+/// left empty for the runs which failed
+pub fn write_results(path: &Path, sweep: &Sweep, results: &[RunResult]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let paths: Vec<String> = sweep.paths().into_iter().map(csv_field).collect();
+    writeln!(
+        writer,
+        "run,{},batch,bodies,final_time,initial_energy,final_energy,relative_energy_drift,\
+         momentum_drift,error",
+        paths.join(",")
+    )?;
+    for (run, result) in results.iter().enumerate() {
+        let values: Vec<String> = result
+            .values
+            .iter()
+            .map(|value| match value {
+                Value::String(text) => csv_field(text),
+                value => csv_field(&value.to_string()),
+            })
+            .collect();
+        let diagnostics = match &result.outcome {
+            Ok(outcome) => format!(
+                "{},{},{},{},{},{},",
+                outcome.bodies,
+                outcome.final_time,
+                outcome.initial_energy,
+                outcome.final_energy,
+                outcome.relative_energy_drift,
+                outcome.momentum_drift
+            ),
+            Err(error) => format!(",,,,,,{}", csv_field(error)),
+        };
+        writeln!(
+            writer,
+            "{},{},{},{}",
+            run,
+            values.join(","),
+            result
+                .batch
+                .map_or(String::new(), |batch| batch.to_string()),
+            diagnostics
+        )?;
+    }
+    writer.flush()
+}
+
+/// `text` quoted if it has a separator, quote or line break
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
@@ -0,0 +1,113 @@
+//! Pausing between submissions to keep a long run from saturating the GPU.
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Keeps the fraction of wall-clock time the GPU spends busy below a limit,
+/// measured over a trailing window, by asking for pauses between submissions.
+/// Long runs on shared workstations then stay clear of thermal throttling.
+#[derive(Debug, Clone)]
+pub struct DutyCycleGuard {
+    limit: f64,
+    window: Duration,
+    /// End of each busy period and its length
+    busy: VecDeque<(Instant, Duration)>,
+}
+
+impl DutyCycleGuard {
+    /// `limit` is the largest busy fraction, in `(0, 1]`
+    pub fn new(limit: f64, window: Duration) -> Self {
+        assert!(limit > 0.0 && limit <= 1.0);
+        Self {
+            limit,
+            window,
+            busy: VecDeque::new(),
+        }
+    }
+
+    pub fn limit(&self) -> f64 {
+        self.limit
+    }
+
+    /// Record a busy period which has just ended
+    pub fn record(&mut self, busy: Duration) {
+        self.record_at(Instant::now(), busy);
+    }
+
+    pub fn record_at(&mut self, at: Instant, busy: Duration) {
+        self.busy.push_back((at, busy));
+        while self
+            .busy
+            .front()
+            .is_some_and(|&(end, _)| at.duration_since(end) >= self.window)
+        {
+            self.busy.pop_front();
+        }
+    }
+
+    /// Busy fraction over the window so far, `None` before anything was recorded
+    pub fn duty_cycle(&self) -> Option<f64> {
+        self.duty_cycle_at(Instant::now())
+    }
+
+    pub fn duty_cycle_at(&self, at: Instant) -> Option<f64> {
+        let elapsed = self.elapsed_at(at)?.as_secs_f64();
+        (elapsed > 0.0).then(|| self.busy_time().as_secs_f64() / elapsed)
+    }
+
+    /// The pause needed to bring the duty cycle back down to the limit
+    pub fn backoff(&self) -> Duration {
+        self.backoff_at(Instant::now())
+    }
+
+    pub fn backoff_at(&self, at: Instant) -> Duration {
+        let elapsed = match self.elapsed_at(at) {
+            Some(elapsed) => elapsed,
+            None => return Duration::ZERO,
+        };
+        self.busy_time().div_f64(self.limit).saturating_sub(elapsed)
+    }
+
+    fn busy_time(&self) -> Duration {
+        self.busy.iter().map(|&(_, busy)| busy).sum()
+    }
+
+    /// Wall-clock time since the start of the oldest recorded busy period
+    fn elapsed_at(&self, at: Instant) -> Option<Duration> {
+        let &(end, busy) = self.busy.front()?;
+        Some(at.saturating_duration_since(end.checked_sub(busy).unwrap_or(end)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_keeps_a_half_duty_cycle() {
+        let mut guard = DutyCycleGuard::new(0.5, Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        let seconds = Duration::from_secs;
+        assert_eq!(guard.backoff_at(at(0)), Duration::ZERO);
+        assert_eq!(guard.duty_cycle_at(at(0)), None);
+
+        // Busy for the first 2 s, so as long again idle
+        guard.record_at(at(2), seconds(2));
+        assert_eq!(guard.duty_cycle_at(at(2)), Some(1.0));
+        assert_eq!(guard.backoff_at(at(2)), seconds(2));
+        assert_eq!(guard.backoff_at(at(3)), seconds(1));
+        assert_eq!(guard.backoff_at(at(4)), Duration::ZERO);
+
+        // Busy from 4 to 6 s, 4 s out of 6
+        guard.record_at(at(6), seconds(2));
+        assert_eq!(guard.backoff_at(at(6)), seconds(2));
+        assert_eq!(guard.duty_cycle_at(at(8)), Some(0.5));
+        assert_eq!(guard.backoff_at(at(8)), Duration::ZERO);
+
+        // Busy periods a window old no longer count
+        guard.record_at(at(70), seconds(1));
+        assert_eq!(guard.backoff_at(at(70)), seconds(1));
+    }
+}
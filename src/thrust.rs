@@ -0,0 +1,97 @@
+//! Continuous low thrust, an acceleration of its own for a body on top of the
+//! forces, for electric propulsion and solar sails.
+//!
+//! The kernels add the thrust of a body to its acceleration at every force
+//! evaluation. Tables are evenly spaced in time and interpolated linearly, held
+//! at their first and last sample outside of them. Their clock is advanced by
+//! the steps on the GPU and set from the simulated time before every submission.
+use serde::{Deserialize, Serialize};
+
+use crate::structures::{ThrustState, THRUST_CONSTANT, THRUST_TABLE, THRUST_TANGENTIAL};
+
+/// How a body's thrust changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum ThrustProfile {
+    /// A fixed acceleration in the frame of the simulation
+    Constant { acceleration: [f64; 3] },
+    /// An acceleration of fixed magnitude along the velocity, against it if
+    /// negative
+    Tangential { acceleration: f64 },
+    /// Accelerations `interval` apart in time, the first at `start`
+    Table {
+        start: f64,
+        interval: f64,
+        accelerations: Vec<[f64; 3]>,
+    },
+}
+
+/// The thrust of the body at index `body`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Thrust {
+    pub body: usize,
+    #[serde(flatten)]
+    pub profile: ThrustProfile,
+}
+
+impl ThrustProfile {
+    /// Acceleration at `time` of a body moving at `velocity`, as the kernels apply it
+    pub fn acceleration(&self, time: f64, velocity: [f64; 3]) -> [f64; 3] {
+        match self {
+            ThrustProfile::Constant { acceleration } => *acceleration,
+            ThrustProfile::Tangential { acceleration } => {
+                let speed = velocity.iter().map(|v| v * v).sum::<f64>().sqrt();
+                match speed > 0.0 {
+                    true => velocity.map(|v| acceleration * v / speed),
+                    false => [0.0; 3],
+                }
+            }
+            ThrustProfile::Table {
+                start,
+                interval,
+                accelerations,
+            } => {
+                let last = accelerations.len() - 1;
+                let u = ((time - start) / interval).clamp(0.0, last as f64);
+                let sample = (u.floor() as usize).min(last.saturating_sub(1));
+                let s = u - sample as f64;
+                let next = (sample + 1).min(last);
+                [0, 1, 2].map(|axis| {
+                    (1.0 - s) * accelerations[sample][axis] + s * accelerations[next][axis]
+                })
+            }
+        }
+    }
+
+    /// The state the kernels read at `time`, with the table's samples from `first`
+    pub(crate) fn state(&self, time: f64, first: usize) -> ThrustState {
+        match self {
+            ThrustProfile::Constant { acceleration } => ThrustState {
+                acceleration: acceleration.map(|a| a as f32),
+                kind: THRUST_CONSTANT,
+                ..Default::default()
+            },
+            ThrustProfile::Tangential { acceleration } => ThrustState {
+                acceleration: [*acceleration as f32, 0.0, 0.0],
+                kind: THRUST_TANGENTIAL,
+                ..Default::default()
+            },
+            ThrustProfile::Table {
+                start,
+                interval,
+                accelerations,
+            } => {
+                let position = (time - start) / interval;
+                let sample = position.floor().clamp(i32::MIN as f64, i32::MAX as f64);
+                ThrustState {
+                    kind: THRUST_TABLE,
+                    first: first as u32,
+                    num_samples: accelerations.len() as u32,
+                    sample_index: sample as i32,
+                    fraction: (position - sample) as f32,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Trajectories kept in memory for analysis, indexed by time so that the state
+//! of a body or a region can be queried at any time between the recorded frames.
+//!
+//! Frames recorded with the accelerations of their bodies are dense output:
+//! positions between them are quintic Hermite interpolations of the positions,
+//! velocities and accelerations at both ends, and velocities the derivative of
+//! those. Without accelerations at both ends positions are cubic Hermite
+//! interpolations of the positions and velocities and velocities are
+//! interpolated linearly. In a periodic box the bodies are assumed to move less
+//! than half the box between frames.
+use crate::pipeline::{Pipeline, TimeDirection};
+use crate::structures::Body;
+
+/// Frames of body state at increasing times
+#[derive(Debug, Clone, Default)]
+pub struct Trajectory {
+    times: Vec<f64>,
+    frames: Vec<Vec<Body>>,
+    /// Accelerations of the bodies of each frame, if recorded
+    accelerations: Vec<Option<Vec<[f32; 3]>>>,
+    /// Edge length of the periodic box, zero for open boundaries
+    box_size: f32,
+}
+
+/// Where a time falls between two frames
+#[derive(Debug, Clone, Copy)]
+struct Bracket {
+    before: usize,
+    after: usize,
+    /// Fraction of the way from `before` to `after`
+    fraction: f32,
+    /// Time between the frames
+    interval: f32,
+}
+
+impl Trajectory {
+    pub fn new(box_size: f32) -> Self {
+        Self {
+            box_size,
+            ..Self::default()
+        }
+    }
+
+    /// Run `steps` steps forwards in time on `pipeline`, recording the state
+    /// with its accelerations before the first and after every `interval` steps.
+    /// The steps leave the velocities half a kick behind the positions, the
+    /// frames have them brought level.
+    pub fn record(pipeline: &mut Pipeline, steps: usize, interval: usize) -> Self {
+        assert!(interval > 0, "The recording interval must be positive");
+        assert_eq!(pipeline.time_direction(), TimeDirection::Forward);
+        let config = *pipeline.dynamic_config();
+        let mut trajectory = Self::new(config.box_size);
+        let mut completed = 0;
+        loop {
+            let (bodies, accelerations) = dense_state(pipeline);
+            trajectory.push_dense(completed as f64 * config.dt as f64, &bodies, &accelerations);
+            if completed == steps {
+                return trajectory;
+            }
+            let passes = interval.min(steps - completed);
+            pipeline.submit_and_block(passes);
+            completed += passes;
+        }
+    }
+
+    /// Append the state at `time`, which must be after the last frame. All
+    /// frames hold the same bodies in the same order.
+    pub fn push(&mut self, time: f64, bodies: &[Body]) {
+        if let Some(&last) = self.times.last() {
+            assert!(time > last, "Frames must be pushed in order of time");
+            assert_eq!(
+                bodies.len(),
+                self.num_bodies(),
+                "The number of bodies changed"
+            );
+        }
+        self.times.push(time);
+        self.frames.push(bodies.to_vec());
+        self.accelerations.push(None);
+    }
+
+    /// Append the state at `time` like `push`, with the `accelerations` of
+    /// its bodies such as from `Pipeline::evaluate_accelerations`
+    pub fn push_dense(&mut self, time: f64, bodies: &[Body], accelerations: &[[f32; 3]]) {
+        assert_eq!(
+            accelerations.len(),
+            bodies.len(),
+            "Every body needs an acceleration"
+        );
+        self.push(time, bodies);
+        *self.accelerations.last_mut().expect("Just pushed") = Some(accelerations.to_vec());
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn num_bodies(&self) -> usize {
+        self.frames.first().map_or(0, Vec::len)
+    }
+
+    pub fn times(&self) -> &[f64] {
+        &self.times
+    }
+
+    pub fn frame(&self, index: usize) -> &[Body] {
+        &self.frames[index]
+    }
+
+    /// The recorded time span, `None` while empty
+    pub fn span(&self) -> Option<(f64, f64)> {
+        Some((*self.times.first()?, *self.times.last()?))
+    }
+
+    /// Queries on body `id`, the index into every frame
+    pub fn body(&self, id: usize) -> BodyTrajectory<'_> {
+        assert!(id < self.num_bodies(), "No body {} in the trajectory", id);
+        BodyTrajectory {
+            trajectory: self,
+            id,
+        }
+    }
+
+    /// All bodies at `time`, between the frames or on one, `None` outside the
+    /// recorded span
+    pub fn sample(&self, time: f64) -> Option<Vec<Body>> {
+        let bracket = self.bracket(time)?;
+        Some(
+            (0..self.num_bodies())
+                .map(|id| self.interpolate(id, bracket))
+                .collect(),
+        )
+    }
+
+    /// Ids of the bodies within `radius` of `center` at `time`, in order. Empty
+    /// outside the recorded span.
+    pub fn bodies_in_sphere(&self, center: [f32; 3], radius: f32, time: f64) -> Vec<usize> {
+        let bracket = match self.bracket(time) {
+            Some(bracket) => bracket,
+            None => return Vec::new(),
+        };
+        (0..self.num_bodies())
+            .filter(|&id| {
+                let position = self.interpolate(id, bracket).position;
+                let offset = self.minimum_image(sub(position, center));
+                dot(offset, offset) <= radius * radius
+            })
+            .collect()
+    }
+
+    /// The frames around `time` by binary search
+    fn bracket(&self, time: f64) -> Option<Bracket> {
+        let (first, last) = self.span()?;
+        if !(first..=last).contains(&time) {
+            return None;
+        }
+        let after = self.times.partition_point(|&t| t < time);
+        if self.times[after] == time {
+            return Some(Bracket {
+                before: after,
+                after,
+                fraction: 0.0,
+                interval: 0.0,
+            });
+        }
+        let before = after - 1;
+        let interval = self.times[after] - self.times[before];
+        Some(Bracket {
+            before,
+            after,
+            fraction: ((time - self.times[before]) / interval) as f32,
+            interval: interval as f32,
+        })
+    }
+
+    fn interpolate(&self, id: usize, bracket: Bracket) -> Body {
+        let a = self.frames[bracket.before][id];
+        if bracket.before == bracket.after {
+            return a;
+        }
+        let b = self.frames[bracket.after][id];
+        let (s, h) = (bracket.fraction, bracket.interval);
+        if let (Some(before), Some(after)) = (
+            &self.accelerations[bracket.before],
+            &self.accelerations[bracket.after],
+        ) {
+            return self.interpolate_quintic(a, b, before[id], after[id], s, h);
+        }
+        // Hermite basis for the displacement from `a`, the start term drops out
+        let h10 = s * (1.0 - s) * (1.0 - s);
+        let h01 = s * s * (3.0 - 2.0 * s);
+        let h11 = s * s * (s - 1.0);
+        let displacement = self.minimum_image(sub(b.position, a.position));
+        let mut position = [0.0; 3];
+        let mut velocity = [0.0; 3];
+        for axis in 0..3 {
+            position[axis] = a.position[axis]
+                + h * (h10 * a.velocity[axis] + h11 * b.velocity[axis])
+                + h01 * displacement[axis];
+            velocity[axis] = a.velocity[axis] + s * (b.velocity[axis] - a.velocity[axis]);
+        }
+        Body {
+            position: self.wrap(position),
+            velocity,
+            ..a
+        }
+    }
+
+    /// Between `a` and `b` with accelerations `a_acceleration` and
+    /// `b_acceleration`, a fraction `s` of the interval `h`
+    fn interpolate_quintic(
+        &self,
+        a: Body,
+        b: Body,
+        a_acceleration: [f32; 3],
+        b_acceleration: [f32; 3],
+        s: f32,
+        h: f32,
+    ) -> Body {
+        let (s2, s3) = (s * s, s * s * s);
+        let (s4, s5) = (s3 * s, s3 * s2);
+        // Quintic Hermite basis for the displacement from `a` and its derivatives
+        let h1 = s - 6.0 * s3 + 8.0 * s4 - 3.0 * s5;
+        let h2 = 0.5 * (s2 - 3.0 * s3 + 3.0 * s4 - s5);
+        let h3 = 10.0 * s3 - 15.0 * s4 + 6.0 * s5;
+        let h4 = -4.0 * s3 + 7.0 * s4 - 3.0 * s5;
+        let h5 = 0.5 * (s3 - 2.0 * s4 + s5);
+        let d1 = 1.0 - 18.0 * s2 + 32.0 * s3 - 15.0 * s4;
+        let d2 = 0.5 * (2.0 * s - 9.0 * s2 + 12.0 * s3 - 5.0 * s4);
+        let d3 = 30.0 * s2 - 60.0 * s3 + 30.0 * s4;
+        let d4 = -12.0 * s2 + 28.0 * s3 - 15.0 * s4;
+        let d5 = 0.5 * (3.0 * s2 - 8.0 * s3 + 5.0 * s4);
+        let displacement = self.minimum_image(sub(b.position, a.position));
+        let mut position = [0.0; 3];
+        let mut velocity = [0.0; 3];
+        for axis in 0..3 {
+            let (v0, v1) = (a.velocity[axis], b.velocity[axis]);
+            let (a0, a1) = (a_acceleration[axis], b_acceleration[axis]);
+            position[axis] = a.position[axis]
+                + h3 * displacement[axis]
+                + h * (h1 * v0 + h4 * v1)
+                + h * h * (h2 * a0 + h5 * a1);
+            velocity[axis] =
+                d3 * displacement[axis] / h + d1 * v0 + d4 * v1 + h * (d2 * a0 + d5 * a1);
+        }
+        Body {
+            position: self.wrap(position),
+            velocity,
+            ..a
+        }
+    }
+
+    fn minimum_image(&self, mut offset: [f32; 3]) -> [f32; 3] {
+        if self.box_size > 0.0 {
+            for component in &mut offset {
+                *component -= self.box_size * (*component / self.box_size).round();
+            }
+        }
+        offset
+    }
+
+    fn wrap(&self, mut position: [f32; 3]) -> [f32; 3] {
+        if self.box_size > 0.0 {
+            for component in &mut position {
+                *component = component.rem_euclid(self.box_size);
+            }
+        }
+        position
+    }
+}
+
+/// The latest bodies of `pipeline` with their accelerations, as `Trajectory::record`
+/// takes its frames. The velocities are brought level with the positions by
+/// half a kick.
+pub fn dense_state(pipeline: &mut Pipeline) -> (Vec<Body>, Vec<[f32; 3]>) {
+    let dt = pipeline.dynamic_config().dt;
+    let accelerations = pipeline.evaluate_accelerations();
+    let mut bodies = pipeline.read_bodies();
+    for (body, acceleration) in bodies.iter_mut().zip(&accelerations) {
+        for (velocity, acceleration) in body.velocity.iter_mut().zip(acceleration) {
+            *velocity += 0.5 * dt * acceleration;
+        }
+    }
+    (bodies, accelerations)
+}
+
+/// One body of a `Trajectory`
+#[derive(Debug, Clone, Copy)]
+pub struct BodyTrajectory<'a> {
+    trajectory: &'a Trajectory,
+    id: usize,
+}
+
+impl BodyTrajectory<'_> {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The body at `time`, `None` outside the recorded span
+    pub fn at(&self, time: f64) -> Option<Body> {
+        let bracket = self.trajectory.bracket(time)?;
+        Some(self.trajectory.interpolate(self.id, bracket))
+    }
+
+    pub fn position_at(&self, time: f64) -> Option<[f32; 3]> {
+        self.at(time).map(|body| body.position)
+    }
+
+    pub fn velocity_at(&self, time: f64) -> Option<[f32; 3]> {
+        self.at(time).map(|body| body.velocity)
+    }
+
+    /// The recorded states with their times
+    pub fn frames(&self) -> impl Iterator<Item = (f64, &Body)> + '_ {
+        let id = self.id;
+        self.trajectory
+            .times
+            .iter()
+            .copied()
+            .zip(self.trajectory.frames.iter().map(move |frame| &frame[id]))
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
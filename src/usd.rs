@@ -0,0 +1,168 @@
+//! Export of a recorded trajectory as an animated USD point cloud, written as a
+//! `.usda` text layer which DCC tools and USD pipelines open directly. The
+//! positions are time sampled once per frame, the colors, groups and labels are
+//! constant primvars.
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::manifest::RunManifest;
+use crate::structures::{Body, VisualAttributes, PALETTE};
+
+/// Time codes per second of the layer, one time code per frame
+const TIME_CODES_PER_SECOND: u32 = 24;
+/// Point width as a fraction of the extent of the bodies in the first frame
+const WIDTH_FRACTION: f32 = 0.005;
+
+/// Streams the frames of a trajectory into a time samples block, which `finish`
+/// wraps in the layer once the number of frames is known
+pub struct UsdExport {
+    path: PathBuf,
+    samples_path: PathBuf,
+    samples: BufWriter<File>,
+    attributes: Vec<VisualAttributes>,
+    /// Manifest of the run as JSON, kept in the custom layer data
+    manifest: String,
+    /// Extent of the first frame
+    extent: Option<f32>,
+    frames: usize,
+}
+
+impl UsdExport {
+    /// Start an export to `path`, the samples are staged next to it until `finish`
+    pub fn create(
+        path: &Path,
+        attributes: &[VisualAttributes],
+        manifest: &RunManifest,
+    ) -> io::Result<Self> {
+        let mut samples_path = path.as_os_str().to_owned();
+        samples_path.push(".samples");
+        let samples_path = PathBuf::from(samples_path);
+        Ok(Self {
+            path: path.to_path_buf(),
+            samples: BufWriter::new(File::create(&samples_path)?),
+            samples_path,
+            attributes: attributes.to_vec(),
+            manifest: manifest.to_json()?,
+            extent: None,
+            frames: 0,
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Append the next frame
+    pub fn write_frame(&mut self, bodies: &[Body]) -> io::Result<()> {
+        assert_eq!(bodies.len(), self.attributes.len());
+        if self.extent.is_none() {
+            self.extent = Some(extent(bodies));
+        }
+        write!(self.samples, "        {}: [", self.frames)?;
+        for (i, body) in bodies.iter().enumerate() {
+            let [x, y, z] = body.position;
+            let separator = if i == 0 { "" } else { ", " };
+            write!(self.samples, "{}({}, {}, {})", separator, x, y, z)?;
+        }
+        writeln!(self.samples, "],")?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Write the layer for the frames so far, `frame_interval` steps of `dt`
+    /// apart, and return its path
+    pub fn finish(self, frame_interval: usize, dt: f32) -> io::Result<PathBuf> {
+        let mut samples = self.samples;
+        samples.flush()?;
+        drop(samples);
+        let mut layer = BufWriter::new(File::create(&self.path)?);
+        writeln!(layer, "#usda 1.0")?;
+        writeln!(layer, "(")?;
+        writeln!(layer, "    customLayerData = {{")?;
+        writeln!(layer, "        double dt = {}", dt)?;
+        writeln!(layer, "        int frameInterval = {}", frame_interval)?;
+        // A JSON string is escaped as USD expects
+        writeln!(
+            layer,
+            "        string manifest = {}",
+            serde_json::to_string(&self.manifest)?
+        )?;
+        writeln!(layer, "    }}")?;
+        writeln!(layer, "    defaultPrim = \"parabody\"")?;
+        writeln!(layer, "    startTimeCode = 0")?;
+        writeln!(layer, "    endTimeCode = {}", self.frames.saturating_sub(1))?;
+        writeln!(layer, "    timeCodesPerSecond = {}", TIME_CODES_PER_SECOND)?;
+        writeln!(layer, "    upAxis = \"Z\"")?;
+        writeln!(layer, ")")?;
+        writeln!(layer)?;
+        writeln!(layer, "def Points \"parabody\"")?;
+        writeln!(layer, "{{")?;
+
+        let width = WIDTH_FRACTION * self.extent.unwrap_or(1.0);
+        writeln!(layer, "    float[] widths = [{}] (", width)?;
+        writeln!(layer, "        interpolation = \"constant\"")?;
+        writeln!(layer, "    )")?;
+        let colors: Vec<String> = self
+            .attributes
+            .iter()
+            .map(|attributes| {
+                let [r, g, b] = PALETTE[attributes.color as usize % PALETTE.len()];
+                format!("({}, {}, {})", r, g, b)
+            })
+            .collect();
+        write_primvar(&mut layer, "color3f[]", "displayColor", &colors)?;
+        let groups: Vec<String> = self
+            .attributes
+            .iter()
+            .map(|attributes| attributes.group.to_string())
+            .collect();
+        write_primvar(&mut layer, "uint[]", "group", &groups)?;
+        let labels: Vec<String> = self
+            .attributes
+            .iter()
+            .map(|attributes| attributes.label.to_string())
+            .collect();
+        write_primvar(&mut layer, "uint[]", "label", &labels)?;
+
+        writeln!(layer, "    point3f[] points.timeSamples = {{")?;
+        io::copy(&mut File::open(&self.samples_path)?, &mut layer)?;
+        writeln!(layer, "    }}")?;
+        writeln!(layer, "}}")?;
+        layer.flush()?;
+        fs::remove_file(&self.samples_path)?;
+        Ok(self.path)
+    }
+}
+
+fn write_primvar<W: Write>(
+    writer: &mut W,
+    ty: &str,
+    name: &str,
+    values: &[String],
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "    {} primvars:{} = [{}] (",
+        ty,
+        name,
+        values.join(", ")
+    )?;
+    writeln!(writer, "        interpolation = \"vertex\"")?;
+    writeln!(writer, "    )")
+}
+
+/// Largest edge of the bounding box of the bodies
+fn extent(bodies: &[Body]) -> f32 {
+    (0..3)
+        .map(|axis| {
+            let coordinates = bodies.iter().map(|body| body.position[axis]);
+            let lower = coordinates.clone().fold(f32::INFINITY, f32::min);
+            let upper = coordinates.fold(f32::NEG_INFINITY, f32::max);
+            upper - lower
+        })
+        .fold(0.0, f32::max)
+        .max(1e-6)
+}
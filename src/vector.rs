@@ -0,0 +1,26 @@
+//! Arithmetic on the `[f64; 3]` vectors of the orbits and diagnostics computed
+//! on the host
+
+pub(crate) fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn scale(v: [f64; 3], factor: f64) -> [f64; 3] {
+    v.map(|x| x * factor)
+}
+
+pub(crate) fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn norm(v: [f64; 3]) -> f64 {
+    dot(v, v).sqrt()
+}
@@ -0,0 +1,377 @@
+//! Real-time window drawing the bodies with the `PointRenderer`, updated as the
+//! compute passes run, with a control panel for changing the run as it goes.
+use wgpu::{
+    CommandEncoderDescriptor, Instance, PresentMode, Surface, SurfaceConfiguration, SurfaceError,
+    TextureUsages, TextureViewDescriptor,
+};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{
+        ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+        WindowEvent,
+    },
+    event_loop::{ControlFlow, EventLoop},
+    platform::run_return::EventLoopExtRunReturn,
+    window::{Window, WindowBuilder},
+};
+
+use crate::gui::Gui;
+use crate::pipeline::Pipeline;
+use crate::render::{ColorMap, ColorQuantity, OrbitCamera, PointRenderer};
+use crate::structures::{Body, VisualAttributes};
+
+/// Number of recorded positions in each body's trail, one is recorded per redraw
+const TRAIL_LENGTH: u32 = 64;
+/// Pixels of a precise scroll counted as one line
+const PIXELS_PER_LINE: f32 = 40.0;
+/// Upper limit of the steps per frame in the control panel
+const MAX_STEPS_PER_FRAME: usize = 10_000;
+
+/// The window and its surface, opened before the pipeline so that it can pick
+/// an adapter which presents to the surface
+pub struct ViewerWindow {
+    // Dropped before the window it was created from
+    surface: Surface,
+    window: Window,
+    event_loop: Option<EventLoop<()>>,
+}
+
+impl ViewerWindow {
+    pub fn open(instance: &Instance, title: &str) -> Self {
+        let event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_title(title)
+            .build(&event_loop)
+            .expect("Could not open window");
+        // Safety: the window outlives the surface, see the field order
+        let surface = unsafe { instance.create_surface(&window) };
+        Self {
+            surface,
+            window,
+            event_loop: Some(event_loop),
+        }
+    }
+
+    pub fn surface(&self) -> &Surface {
+        &self.surface
+    }
+}
+
+/// Settings edited in the control panel and with the keyboard, applied to the
+/// pipeline and the renderer between dispatches
+#[derive(Debug, Clone)]
+struct Controls {
+    paused: bool,
+    dt: f32,
+    softening: f32,
+    /// Steps between redraws, each of which records the positions into the trails
+    steps_per_frame: usize,
+    color_quantity: ColorQuantity,
+    /// Fit the color map to the latest state when the controls are next applied
+    fit_colors: bool,
+    highlight: bool,
+    highlighted_body: u32,
+}
+
+pub struct Viewer {
+    window: ViewerWindow,
+    surface_config: SurfaceConfiguration,
+    renderer: PointRenderer,
+    camera: OrbitCamera,
+    gui: Gui,
+    controls: Controls,
+    completed: usize,
+    steps: usize,
+}
+
+impl Viewer {
+    /// Set up the rendering on the pipeline's device, colored through
+    /// `color_map` with the palette indices and groups in `attributes` and
+    /// framed around `bodies`
+    pub fn new(
+        window: ViewerWindow,
+        pipeline: &Pipeline,
+        bodies: &[Body],
+        attributes: &[VisualAttributes],
+        color_map: ColorMap,
+    ) -> Self {
+        let size = window.window.inner_size();
+        let surface_config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: window.surface.get_supported_formats(pipeline.adapter())[0],
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: PresentMode::Fifo,
+        };
+        window.surface.configure(pipeline.device(), &surface_config);
+        let mut renderer =
+            PointRenderer::new(pipeline, surface_config.format, attributes, TRAIL_LENGTH);
+        renderer.record_trails(pipeline);
+        renderer.set_color_map(color_map);
+        let gui = Gui::new(
+            pipeline,
+            surface_config.format,
+            window.window.scale_factor() as f32,
+        );
+        let config = pipeline.dynamic_config();
+        let controls = Controls {
+            paused: false,
+            dt: config.dt.abs(),
+            softening: config.softening,
+            steps_per_frame: 1,
+            color_quantity: color_map.quantity,
+            fit_colors: false,
+            highlight: false,
+            highlighted_body: 0,
+        };
+        Self {
+            renderer,
+            window,
+            surface_config,
+            camera: OrbitCamera::framing(bodies),
+            gui,
+            controls,
+            completed: 0,
+            steps: 0,
+        }
+    }
+
+    /// Run `steps` steps, `steps_per_frame` between each redraw unless changed in
+    /// the control panel, which also sets the step size, the softening, the colors
+    /// and a highlighted body. Dragging with the left button orbits the camera,
+    /// scrolling zooms and space pauses. C cycles the quantity the colors show and
+    /// R fits the color map to the latest state. The window stays open after the
+    /// run until it is closed or escape is pressed. Returns the number of steps
+    /// completed.
+    pub fn run(mut self, pipeline: &mut Pipeline, steps: usize, steps_per_frame: usize) -> usize {
+        let mut event_loop = self.window.event_loop.take().expect("Viewer already ran");
+        self.steps = steps;
+        self.controls.steps_per_frame = steps_per_frame.clamp(1, MAX_STEPS_PER_FRAME);
+        let mut dragging = false;
+        let mut cursor: Option<PhysicalPosition<f64>> = None;
+        event_loop.run_return(|event, _, control_flow| {
+            let running = !self.controls.paused && self.completed < steps;
+            *control_flow = if running {
+                ControlFlow::Poll
+            } else {
+                ControlFlow::Wait
+            };
+            match event {
+                Event::WindowEvent { event, .. } => {
+                    let used_by_gui = self.gui.handle_event(&event);
+                    match event {
+                        WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                        WindowEvent::Resized(size) => {
+                            self.resize(pipeline, size.width, size.height)
+                        }
+                        // Released even when the press went to the control panel
+                        WindowEvent::MouseInput {
+                            button: MouseButton::Left,
+                            state: ElementState::Released,
+                            ..
+                        } => dragging = false,
+                        WindowEvent::CursorMoved { position, .. } => {
+                            if let (true, Some(last)) = (dragging, cursor) {
+                                self.camera.orbit(
+                                    (position.x - last.x) as f32,
+                                    (position.y - last.y) as f32,
+                                );
+                            }
+                            cursor = Some(position);
+                        }
+                        _ if used_by_gui => (),
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(key),
+                                    ..
+                                },
+                            ..
+                        } => match key {
+                            VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
+                            VirtualKeyCode::Space => self.controls.paused = !self.controls.paused,
+                            VirtualKeyCode::C => {
+                                self.controls.color_quantity = self.controls.color_quantity.next()
+                            }
+                            VirtualKeyCode::R => self.controls.fit_colors = true,
+                            _ => (),
+                        },
+                        WindowEvent::MouseInput {
+                            button: MouseButton::Left,
+                            state: ElementState::Pressed,
+                            ..
+                        } => dragging = true,
+                        WindowEvent::MouseWheel { delta, .. } => self.camera.zoom(match delta {
+                            MouseScrollDelta::LineDelta(_, lines) => lines,
+                            MouseScrollDelta::PixelDelta(position) => {
+                                position.y as f32 / PIXELS_PER_LINE
+                            }
+                        }),
+                        _ => (),
+                    }
+                    self.window.window.request_redraw();
+                }
+                Event::MainEventsCleared if running => {
+                    let passes = self.controls.steps_per_frame.min(steps - self.completed);
+                    pipeline.submit_and_block(passes);
+                    self.completed += passes;
+                    self.renderer.record_trails(pipeline);
+                    self.update_title();
+                    self.window.window.request_redraw();
+                }
+                Event::RedrawRequested(_) => self.render(pipeline),
+                _ => (),
+            }
+        });
+        self.completed
+    }
+
+    /// Bring the pipeline and the renderer in line with `controls`
+    fn apply_controls(&mut self, pipeline: &mut Pipeline, mut controls: Controls) {
+        if controls.dt != self.controls.dt {
+            pipeline.set_dt(controls.dt);
+        }
+        if controls.softening != self.controls.softening {
+            pipeline.set_softening(controls.softening);
+        }
+        self.renderer
+            .set_highlight(controls.highlight.then_some(controls.highlighted_body));
+        if controls.fit_colors || controls.color_quantity != self.renderer.color_map().quantity {
+            self.renderer
+                .set_color_map(ColorMap::fit(controls.color_quantity, pipeline));
+            controls.fit_colors = false;
+        }
+        self.controls = controls;
+        self.update_title();
+    }
+
+    fn update_title(&self) {
+        self.window.window.set_title(&format!(
+            "parabody, step {}/{}, {}",
+            self.completed,
+            self.steps,
+            self.renderer.color_map().legend()
+        ));
+    }
+
+    fn resize(&mut self, pipeline: &Pipeline, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.surface_config.width = width;
+        self.surface_config.height = height;
+        self.window
+            .surface
+            .configure(pipeline.device(), &self.surface_config);
+    }
+
+    fn render(&mut self, pipeline: &mut Pipeline) {
+        let size = (self.surface_config.width, self.surface_config.height);
+        let mut controls = self.controls.clone();
+        let num_bodies = pipeline.dynamic_config().num_bodies;
+        let progress = (self.completed, self.steps);
+        let legend = self.renderer.color_map().legend();
+        let repaint = self.gui.run(size, |context| {
+            control_panel(context, &mut controls, num_bodies, progress, &legend)
+        });
+        self.apply_controls(pipeline, controls);
+
+        let frame = match self.window.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                self.resize(pipeline, size.0, size.1);
+                return;
+            }
+            Err(error) => {
+                log::warn!("Could not get the next frame: {}", error);
+                return;
+            }
+        };
+        let view = frame.texture.create_view(&TextureViewDescriptor::default());
+        let mut encoder = pipeline
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        self.renderer
+            .draw(pipeline, &mut encoder, &view, &self.camera, size);
+        self.gui.paint(pipeline, &mut encoder, &view, size);
+        pipeline.queue().submit(Some(encoder.finish()));
+        frame.present();
+        if repaint {
+            self.window.window.request_redraw();
+        }
+    }
+}
+
+/// The panel editing `controls`, `progress` is the completed and total steps
+fn control_panel(
+    context: &egui::Context,
+    controls: &mut Controls,
+    num_bodies: u32,
+    progress: (usize, usize),
+    legend: &str,
+) {
+    egui::Window::new("Controls")
+        .resizable(false)
+        .show(context, |ui| {
+            ui.horizontal(|ui| {
+                let label = if controls.paused { "Resume" } else { "Pause" };
+                if ui.button(label).clicked() {
+                    controls.paused = !controls.paused;
+                }
+                ui.label(format!("Step {}/{}", progress.0, progress.1));
+            });
+            egui::Grid::new("settings").num_columns(2).show(ui, |ui| {
+                // Dragging changes the values by a fraction of themselves
+                ui.label("dt");
+                let speed = controls.dt as f64 * 0.01;
+                ui.add(
+                    egui::DragValue::new(&mut controls.dt)
+                        .speed(speed)
+                        .clamp_range(f32::MIN_POSITIVE..=f32::MAX),
+                );
+                ui.end_row();
+                ui.label("Softening");
+                let speed = (controls.softening as f64 * 0.01).max(1e-4);
+                ui.add(
+                    egui::DragValue::new(&mut controls.softening)
+                        .speed(speed)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+                ui.end_row();
+                ui.label("Steps per frame");
+                ui.add(
+                    egui::DragValue::new(&mut controls.steps_per_frame)
+                        .clamp_range(1..=MAX_STEPS_PER_FRAME),
+                );
+                ui.end_row();
+                ui.label("Colors");
+                egui::ComboBox::from_id_source("colors")
+                    .selected_text(controls.color_quantity.name())
+                    .show_ui(ui, |ui| {
+                        for quantity in ColorQuantity::ALL {
+                            ui.selectable_value(
+                                &mut controls.color_quantity,
+                                quantity,
+                                quantity.name(),
+                            );
+                        }
+                    });
+                ui.end_row();
+                ui.checkbox(&mut controls.highlight, "Highlight");
+                ui.add_enabled(
+                    controls.highlight,
+                    egui::DragValue::new(&mut controls.highlighted_body)
+                        .clamp_range(0..=num_bodies.saturating_sub(1))
+                        .prefix("body "),
+                );
+                ui.end_row();
+            });
+            ui.horizontal(|ui| {
+                ui.label(legend);
+                if ui.button("Fit").clicked() {
+                    controls.fit_colors = true;
+                }
+            });
+        });
+}
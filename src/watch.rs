@@ -0,0 +1,78 @@
+//! Reloading the shaders from disk while a pipeline runs, see `Pipeline::set_shader_watch`
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Shader files checked for changes between submissions
+#[derive(Debug, Default)]
+pub struct ShaderWatch {
+    shader: Option<WatchedFile>,
+    custom_force: Option<WatchedFile>,
+}
+
+impl ShaderWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reload the shader template from `path`
+    pub fn shader(mut self, path: impl Into<PathBuf>) -> Self {
+        self.shader = Some(WatchedFile::new(path.into()));
+        self
+    }
+
+    /// Reload the custom force snippet, see `PipelineBuilder::custom_force`, from `path`
+    pub fn custom_force(mut self, path: impl Into<PathBuf>) -> Self {
+        self.custom_force = Some(WatchedFile::new(path.into()));
+        self
+    }
+
+    /// The new shader template and custom force of the files modified since the
+    /// last poll
+    pub fn poll(&mut self) -> (Option<String>, Option<String>) {
+        let poll = |file: &mut Option<WatchedFile>| file.as_mut().and_then(WatchedFile::poll);
+        (poll(&mut self.shader), poll(&mut self.custom_force))
+    }
+}
+
+#[derive(Debug)]
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            modified: modified(&path),
+            path,
+        }
+    }
+
+    /// The contents if the file was modified since the last poll. A file which
+    /// can't be read, such as one an editor is replacing, is retried next time.
+    fn poll(&mut self) -> Option<String> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        match fs::read_to_string(&self.path) {
+            Ok(src) => {
+                self.modified = modified;
+                Some(src)
+            }
+            Err(error) => {
+                log::debug!("Could not read {}: {}", self.path.display(), error);
+                None
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
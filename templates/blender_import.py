@@ -0,0 +1,122 @@
+# Imports a parabody trajectory into Blender as animated point clouds, one per
+# palette color, each instancing a small sphere at every body.
+#
+#     blender --python import.py                       # open interactively
+#     blender --background --python import.py -a       # render the animation
+#
+# The frames are read from the `frames` directory next to this script whenever
+# the current frame changes, so nothing is baked into the .blend file.
+import os
+import struct
+
+import bpy
+
+FRAME_COUNT = {{ frame_count }}
+# Simulation steps between frames and the step size, for reference
+FRAME_INTERVAL = {{ frame_interval }}
+DT = {{ dt }}
+# Provenance of the run as JSON, see `manifest::RunManifest`
+MANIFEST = {{ manifest }}
+# Scene units per simulation unit
+SCALE = 1.0
+# Sphere radius as a fraction of the initial extent of the bodies
+SPHERE_FRACTION = 0.005
+# Must match `PALETTE` in structures.rs
+PALETTE = [
+    (1.0, 1.0, 1.0),
+    (1.0, 0.8, 0.3),
+    (0.3, 0.6, 1.0),
+    (1.0, 0.35, 0.3),
+    (0.4, 0.9, 0.4),
+    (0.8, 0.5, 1.0),
+    (0.3, 0.9, 0.9),
+    (0.6, 0.6, 0.6),
+]
+
+DIRECTORY = os.path.dirname(os.path.abspath(__file__))
+
+
+def read_frame(index):
+    """Positions and visual attributes from a PBDY snapshot"""
+    path = os.path.join(DIRECTORY, "frames", "frame_%05d.pbdy" % index)
+    with open(path, "rb") as file:
+        data = file.read()
+    if data[:4] != b"PBDY":
+        raise ValueError("%s is not a parabody snapshot" % path)
+    order = "<" if data[4:5] == b"L" else ">"
+    version, record_size, count = struct.unpack_from(order + "HIQ", data, 6)
+    positions = []
+    attributes = []
+    for i in range(count):
+        offset = 20 + i * record_size
+        x, y, z = struct.unpack_from(order + "3f", data, offset)
+        positions.append((x * SCALE, y * SCALE, z * SCALE))
+        if version >= 2:
+            attributes.append(struct.unpack_from(order + "3I", data, offset + 32))
+        else:
+            attributes.append((0, 0, 0))
+    return positions, attributes
+
+
+def extent(positions):
+    if not positions:
+        return 1.0
+    lower = [min(p[axis] for p in positions) for axis in range(3)]
+    upper = [max(p[axis] for p in positions) for axis in range(3)]
+    return max(max(u - l for l, u in zip(lower, upper)), 1e-6)
+
+
+positions, attributes = read_frame(0)
+radius = SPHERE_FRACTION * extent(positions)
+
+# Bodies are split by color, `members` maps each color to its body indices
+members = {}
+for index, (group, color, label) in enumerate(attributes):
+    members.setdefault(color % len(PALETTE), []).append(index)
+
+collection = bpy.data.collections.new("parabody")
+bpy.context.scene.collection.children.link(collection)
+clouds = []
+for color, indices in sorted(members.items()):
+    material = bpy.data.materials.new("parabody color %d" % color)
+    material.diffuse_color = PALETTE[color] + (1.0,)
+    material.use_nodes = True
+    shader = material.node_tree.nodes["Principled BSDF"]
+    shader.inputs["Base Color"].default_value = PALETTE[color] + (1.0,)
+
+    bpy.ops.mesh.primitive_ico_sphere_add(subdivisions=2, radius=radius)
+    sphere = bpy.context.object
+    sphere.name = "parabody sphere %d" % color
+    sphere.data.materials.append(material)
+    for other in sphere.users_collection:
+        other.objects.unlink(sphere)
+    collection.objects.link(sphere)
+
+    mesh = bpy.data.meshes.new("parabody color %d" % color)
+    mesh.from_pydata([positions[i] for i in indices], [], [])
+    cloud = bpy.data.objects.new(mesh.name, mesh)
+    cloud.instance_type = "VERTS"
+    sphere.parent = cloud
+    collection.objects.link(cloud)
+    clouds.append((cloud, indices))
+
+cache = {}
+
+
+def update_positions(scene, *args):
+    index = min(max(scene.frame_current - scene.frame_start, 0), FRAME_COUNT - 1)
+    if index not in cache:
+        cache.clear()
+        cache[index] = read_frame(index)[0]
+    frame = cache[index]
+    for cloud, indices in clouds:
+        coordinates = [value for i in indices for value in frame[i]]
+        cloud.data.vertices.foreach_set("co", coordinates)
+        cloud.data.update()
+
+
+scene = bpy.context.scene
+scene.frame_start = 1
+scene.frame_end = FRAME_COUNT
+bpy.app.handlers.frame_change_pre.append(update_positions)
+update_positions(scene)